@@ -1,15 +1,19 @@
 use crate::pages::{
     UiLogin, UiPage, change_password::UiChangePassword, chat::UiChat,
-    egui_settings::UiEguiSettings, uac::UiUAC,
+    egui_settings::UiEguiSettings, roles::UiRoles, uac::UiUAC,
 };
 use crate::shortcuts::Shortcuts;
 pub use data_shared::DataShared;
+use egui_helpers::UiHelpers as _;
 use egui_pages::{PageContainer as _, do_organize_pages};
+use plugin_chat::consts::CHAT_PLUGIN_ID;
+use reqwest_cross::{Awaiting, DataState};
 use tracing::{info, warn};
-use wykies_shared::uac::init_permissions_to_defaults;
+use wykies_shared::{uac::init_permissions_to_defaults, version::CompatibilityOutcome};
 use wykies_time::Timestamp;
 
 const VERSION_STR: &str = concat!("ver: ", env!("CARGO_PKG_VERSION"));
+const REQUIRED_PLUGINS: &[&str] = &[CHAT_PLUGIN_ID];
 
 mod data_shared;
 
@@ -22,6 +26,8 @@ pub struct ChatApp {
     data_shared: DataShared,
     active_pages: Vec<UiPage>,
     shortcuts: Shortcuts,
+    #[serde(skip)]
+    compatibility: DataState<CompatibilityOutcome>,
 }
 
 impl eframe::App for ChatApp {
@@ -37,8 +43,15 @@ impl eframe::App for ChatApp {
     fn ui(&mut self, ui: &mut egui::Ui, _frame: &mut eframe::Frame) {
         self.data_shared.screen_lock_info_tick();
         self.top_panel(ui);
+        let is_refused = self.compatibility_banner(ui);
         self.bottom_panel(ui);
-        self.show_pages(ui);
+        if is_refused {
+            egui::CentralPanel::default().show(ui, |ui| {
+                ui.vertical_centered(|ui| ui.heading("This client cannot be used with the server"));
+            });
+        } else {
+            self.show_pages(ui);
+        }
 
         // Request repaint after 1 second
         ui.request_repaint_after(std::time::Duration::from_secs(1));
@@ -101,6 +114,8 @@ impl ChatApp {
                 .expect("type is correct and defined at compile time");
             UiPage::ui_menu_page_btn::<UiUAC>(ui, &self.data_shared, &mut self.active_pages)
                 .expect("type is correct and defined at compile time");
+            UiPage::ui_menu_page_btn::<UiRoles>(ui, &self.data_shared, &mut self.active_pages)
+                .expect("type is correct and defined at compile time");
             UiPage::ui_menu_page_btn::<UiEguiSettings>(
                 ui,
                 &self.data_shared,
@@ -131,6 +146,53 @@ impl ChatApp {
         });
     }
 
+    /// Checks compatibility with the server before login and shows a banner if
+    /// there is a problem. Returns true if the server was refused and the rest of
+    /// the UI should be blocked
+    fn compatibility_banner(&mut self, ui: &mut egui::Ui) -> bool {
+        let message = match &mut self.compatibility {
+            DataState::None => {
+                let rx = self
+                    .data_shared
+                    .client
+                    .check_compatibility(env!("CARGO_PKG_VERSION"), REQUIRED_PLUGINS);
+                self.compatibility = DataState::AwaitingResponse(Awaiting(rx));
+                return false;
+            }
+            DataState::AwaitingResponse(rx) => {
+                if let Some(new_state) = DataState::await_data(rx) {
+                    self.compatibility = new_state;
+                    ui.request_repaint();
+                }
+                return false;
+            }
+            DataState::Present(CompatibilityOutcome::Compatible) => return false,
+            DataState::Present(CompatibilityOutcome::Warn(msg)) => {
+                format!("Client may not work correctly with this server: {msg}")
+            }
+            DataState::Present(CompatibilityOutcome::Refuse(msg)) => {
+                format!("Client is not compatible with this server, please update: {msg}")
+            }
+            DataState::Failed(e) => format!("Failed to check server version: {e}"),
+        };
+        let is_refused = matches!(
+            self.compatibility,
+            DataState::Present(CompatibilityOutcome::Refuse(_))
+        );
+        let is_failed = matches!(self.compatibility, DataState::Failed(_));
+
+        // Single instance of global panel thus unique
+        egui::Panel::top("compatibility_banner").show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.error_label(message);
+                if is_failed && ui.button("Retry").clicked() {
+                    self.compatibility = DataState::None;
+                }
+            });
+        });
+        is_refused
+    }
+
     fn bottom_panel(&mut self, ui: &mut egui::Ui) {
         // Single instance of global panel thus unique
         egui::Panel::bottom("bottom_panel").show(ui, |ui| {
@@ -146,6 +208,11 @@ impl ChatApp {
                     if let Some(user_info) = self.data_shared.client.user_info() {
                         ui.label(format!("Logged in as {}", user_info.username));
                     }
+                    if let Some(days) = self.data_shared.client.password_expires_in_days() {
+                        ui.warn_label(format!(
+                            "Your password expires in {days} day(s), please change it"
+                        ));
+                    }
                 }
                 egui::warn_if_debug_build(ui);
             });
@@ -249,6 +316,7 @@ impl Default for ChatApp {
                     .new_page_with_unique_number(0),
             ],
             shortcuts: Default::default(),
+            compatibility: Default::default(),
         }
     }
 }
@@ -85,7 +85,8 @@ impl Default for DataShared {
         Self {
             username: Default::default(),
             is_login_completed: Default::default(),
-            client: Default::default(),
+            client: wykies_client_core::Client::default()
+                .with_retry_policy(wykies_client_core::RetryPolicy::default()),
             screen_lock_info: ScreenLockInfo::new(
                 CLIENT_IDLE_TIMEOUT,
                 CLIENT_TICKS_PER_SECOND_FOR_ACTIVE,
@@ -3,6 +3,7 @@ pub mod change_password;
 pub mod chat;
 pub mod egui_settings;
 pub mod login;
+pub mod roles;
 pub mod uac;
 
 mod private {
@@ -18,6 +19,7 @@ use egui_helpers::RemovableItem;
 use egui_pages::{DisplayablePage, PageContainer, show_page};
 use egui_settings::UiEguiSettings;
 pub use login::UiLogin;
+use roles::UiRoles;
 use strum::{EnumIter, IntoEnumIterator};
 use uac::UiUAC;
 use wykies_shared::uac::Permission;
@@ -25,8 +27,9 @@ use wykies_shared::uac::Permission;
 #[derive(Debug, serde::Serialize, serde::Deserialize, EnumIter)]
 pub enum UiPage {
     ChangePassword(UiChangePassword),
-    Chat(UiChat),
+    Chat(Box<UiChat>),
     EguiSetting(UiEguiSettings),
+    Roles(UiRoles),
     Uac(UiUAC),
 }
 
@@ -48,12 +51,23 @@ impl RemovableItem for UiPage {
     }
 }
 
+/// Boxed pages are unboxed so that `$page` is always a reference to the page.
+/// Use `mut` before `$on` if a mutable reference is needed
 macro_rules! do_on_ui_page {
     ($on:ident, $page:ident, $body:tt) => {
+        do_on_ui_page!($on, $page, $body, as_ref)
+    };
+    (mut $on:ident, $page:ident, $body:tt) => {
+        do_on_ui_page!($on, $page, $body, as_mut)
+    };
+    ($on:ident, $page:ident, $body:tt, $unbox:ident) => {
         match $on {
             UiPage::ChangePassword($page) => $body,
-            UiPage::Chat($page) => $body,
+            UiPage::Chat(boxed) => match Box::$unbox(boxed) {
+                $page => $body,
+            },
             UiPage::EguiSetting($page) => $body,
+            UiPage::Roles($page) => $body,
             UiPage::Uac($page) => $body,
         }
     };
@@ -63,13 +77,16 @@ impl PageContainer<DataShared, Permission, private::Token> for UiPage {
     #[tracing::instrument(ret)]
     fn new_page_with_unique_number(&self, page_unique_number: usize) -> Self {
         match self {
-            UiPage::Chat(_) => Self::Chat(UiChat::new_page(page_unique_number).and_open_page()),
+            UiPage::Chat(_) => Self::Chat(Box::new(
+                UiChat::new_page(page_unique_number).and_open_page(),
+            )),
             UiPage::ChangePassword(_) => {
                 Self::ChangePassword(UiChangePassword::new_page(page_unique_number).and_open_page())
             }
             UiPage::EguiSetting(_) => {
                 Self::EguiSetting(UiEguiSettings::new_page(page_unique_number).and_open_page())
             }
+            UiPage::Roles(_) => Self::Roles(UiRoles::new_page(page_unique_number).and_open_page()),
             UiPage::Uac(_) => Self::Uac(UiUAC::new_page(page_unique_number).and_open_page()),
         }
     }
@@ -79,9 +96,10 @@ impl PageContainer<DataShared, Permission, private::Token> for UiPage {
         for page in Self::iter() {
             if page.title_base() == T::title_base() {
                 return Ok(match page {
-                    UiPage::Chat(_) => Self::Chat(UiChat::default()),
+                    UiPage::Chat(_) => Self::Chat(Box::default()),
                     UiPage::ChangePassword(_) => Self::ChangePassword(UiChangePassword::default()),
                     UiPage::EguiSetting(_) => Self::EguiSetting(UiEguiSettings::default()),
+                    UiPage::Roles(_) => Self::Roles(UiRoles::default()),
                     UiPage::Uac(_) => Self::Uac(UiUAC::default()),
                 });
             }
@@ -109,7 +127,7 @@ impl PageContainer<DataShared, Permission, private::Token> for UiPage {
     }
 
     fn display_page(&mut self, ui: &mut egui::Ui, data_shared: &mut DataShared) {
-        do_on_ui_page!(self, page, { show_page(page, ui, data_shared) })
+        do_on_ui_page!(mut self, page, { show_page(page, ui, data_shared) })
     }
 
     fn title_base(&self) -> &'static str {
@@ -129,11 +147,11 @@ impl PageContainer<DataShared, Permission, private::Token> for UiPage {
     }
 
     fn open_page(&mut self) {
-        do_on_ui_page!(self, page, { page.open_page() })
+        do_on_ui_page!(mut self, page, { page.open_page() })
     }
 
     fn close_page(&mut self) {
-        do_on_ui_page!(self, page, { page.close_page() })
+        do_on_ui_page!(mut self, page, { page.close_page() })
     }
 }
 
@@ -4,10 +4,11 @@ use egui_helpers::{ResponseHelpers, UiHelpers as _};
 use egui_pages::{DisplayablePage, displayable_page_common};
 use reqwest_cross::{Awaiting, DataState};
 use secrecy::{ExposeSecret as _, SecretString};
+use wykies_client_core::api_error;
 use wykies_shared::{
     const_config::path::PATH_API_CHANGE_PASSWORD,
     req_args::api::ChangePasswordReqArgs,
-    uac::{Permission, get_required_permissions},
+    uac::{PasswordComplexity, PasswordPolicy, Permission, Username, get_required_permissions},
 };
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -27,6 +28,8 @@ pub struct UiChangePassword {
     new_password: SecretString,
     #[serde(skip)]
     confirmation_password: SecretString,
+    #[serde(skip)]
+    password_policy: DataState<PasswordPolicy>,
 }
 impl UiChangePassword {
     fn is_ready_to_send(&self) -> bool {
@@ -35,6 +38,39 @@ impl UiChangePassword {
             && !self.confirmation_password.expose_secret().is_empty()
     }
 
+    /// Checks the new password against the same policy the server uses so
+    /// problems are shown while typing. Returns true if the policy is not
+    /// available as the server still does the check
+    fn check_new_password(&mut self, ui: &mut egui::Ui, data_shared: &mut DataShared) -> bool {
+        match &mut self.password_policy {
+            DataState::None => {
+                let rx = data_shared.client.password_policy();
+                self.password_policy = DataState::AwaitingResponse(Awaiting(rx));
+                true
+            }
+            DataState::AwaitingResponse(rx) => {
+                if let Some(new_state) = DataState::await_data(rx) {
+                    self.password_policy = new_state;
+                }
+                true
+            }
+            DataState::Present(policy) => {
+                let Ok(username) = Username::try_from(data_shared.username.clone()) else {
+                    return true;
+                };
+                if self.new_password.expose_secret().is_empty() {
+                    return true;
+                }
+                let complexity = PasswordComplexity::new(policy, &username, &self.new_password);
+                for error in complexity.error_list() {
+                    ui.error_label(format!("{}: {error}", field_label("new_password")));
+                }
+                complexity.does_meet_requirements()
+            }
+            DataState::Failed(_) => true,
+        }
+    }
+
     fn send_request(&mut self, data_shared: &mut crate::DataShared) {
         let rx = data_shared.client.change_password(&ChangePasswordReqArgs {
             current_password: self.current_password.clone(),
@@ -55,12 +91,13 @@ impl UiChangePassword {
             .password_edit(&mut self.new_password, "New Password")
             .enter_pressed(ui)
             || was_enter_pressed;
+        let is_new_password_valid = self.check_new_password(ui, data_shared);
         was_enter_pressed = ui
             .password_edit(&mut self.confirmation_password, "Confirm New Password")
             .enter_pressed(ui)
             || was_enter_pressed;
 
-        let is_ready_to_send = self.is_ready_to_send();
+        let is_ready_to_send = self.is_ready_to_send() && is_new_password_valid;
         if was_enter_pressed && is_ready_to_send {
             should_send = true;
         }
@@ -111,7 +148,14 @@ impl DisplayablePage<DataShared, Permission, private::Token> for UiChangePasswor
                     ui.label("Password Successfully Changed");
                 }
                 DataState::Failed(e) => {
-                    ui.error_label(format!("Failed {e}"));
+                    match api_error(e).and_then(|x| x.field_errors.first()) {
+                        Some(field_error) => ui.error_label(format!(
+                            "{}: {}",
+                            field_label(&field_error.field),
+                            field_error.message
+                        )),
+                        None => ui.error_label(format!("Failed {e}")),
+                    };
                     if ui.button("Try Again").clicked() {
                         self.data_state = DataState::default();
                     }
@@ -121,6 +165,16 @@ impl DisplayablePage<DataShared, Permission, private::Token> for UiChangePasswor
     }
 }
 
+/// Maps the field names used in [`ChangePasswordReqArgs`] to the labels shown
+fn field_label(field: &str) -> &str {
+    match field {
+        "current_password" => "Current Password",
+        "new_password" => "New Password",
+        "new_password_check" => "Confirm New Password",
+        other => other,
+    }
+}
+
 impl Default for UiChangePassword {
     fn default() -> Self {
         Self {
@@ -129,6 +183,7 @@ impl Default for UiChangePassword {
             current_password: SecretString::from(""),
             new_password: SecretString::from(""),
             confirmation_password: SecretString::from(""),
+            password_policy: Default::default(),
             data_state: Default::default(),
             should_send: false,
             heading_text: Default::default(),
@@ -33,10 +33,15 @@ impl DisplayablePage<DataShared, Permission, private::Token> for UiChat {
     fn show(&mut self, ui: &mut egui::Ui, data_shared: &mut crate::DataShared) {
         let title = self.title(); // Needed to allocate it to not capture self
         let frontend_init = || {
+            let user_info = data_shared.client.user_info();
             FrontEnd::new(
                 data_shared.username.clone().try_into().expect(
                     "at this point the user should be logged in so the username should be valid",
                 ),
+                user_info.as_ref().map(|user_info| user_info.branch_id),
+                user_info.is_some_and(|user_info| {
+                    user_info.permissions.0.contains(&Permission::ManChat)
+                }),
                 title,
             )
         };
@@ -1,21 +1,39 @@
 use anyhow::Context;
-use connected_users::ConnectedUsers;
+use direct_chat::DirectChat;
 use egui::{
     Align, KeyboardShortcut, Layout, Modifiers, ScrollArea, scroll_area::ScrollBarVisibility,
 };
 use egui_helpers::UiHelpers as _;
 use ewebsock::{WsEvent, WsMessage};
+use joined_room::JoinedRoom;
 use plugin_chat::{
-    ChatIM, ChatImText, ChatMsg, ChatMsgsHistory, ReqHistoryBody,
+    ChatIM, ChatImId, ChatImText, ChatMsg, ChatMsgsHistory, ChatRoomInfo, ChatRoomName,
+    ChatRoomUser, DirectErrorBody, DirectIM, EditErrorBody, EditImBody, HistoryIm, RateLimitedBody,
+    ReqDirectHistoryBody, ReqHistoryBody, RespDirectHistoryBody, RespHistoryBody, RoomErrorBody,
+    RoomStateBody, RoomVisibility, SearchErrorBody, SearchMatch, SearchScope,
     consts::{
         CHAT_HISTORY_REQUEST_SIZE, CHAT_MIN_TIME_BETWEEN_HISTORY_REQUESTS, CHAT_SYSTEM_USERNAME,
     },
 };
-use tracing::{error, info};
-use wykies_shared::{internal_error_msg, uac::Username, websockets::WsConnTxRx};
+use search::{SearchAction, SearchPanel};
+use std::{
+    collections::BTreeMap,
+    fmt::{Debug, Display},
+};
+use strum::IntoEnumIterator as _;
+use tracing::{error, info, warn};
+use wykies_shared::{
+    branch::BranchId,
+    internal_error_msg,
+    uac::{Permission, Username},
+    websockets::WsConnTxRx,
+};
 use wykies_time::Timestamp;
 
 mod connected_users;
+mod direct_chat;
+mod joined_room;
+mod search;
 
 /// Scrolling to the bottom once doesn't seem to always get you there especially
 /// if messages are still coming in
@@ -25,13 +43,65 @@ const SCROLLS_TO_GET_TO_BOTTOM: u8 = 2;
 pub struct FrontEnd {
     username: Username,
     system_username: Username,
+    /// Used to offer restricting new rooms to the user's branch
+    branch_id: Option<BranchId>,
+    /// Able to delete other users' IMs in rooms
+    is_moderator: bool,
     unique_id_prefix: String,
-    history: ChatMsgsHistory,
+    /// The rooms the user is able to see
+    rooms: Vec<ChatRoomInfo>,
+    joined_rooms: BTreeMap<ChatRoomName, JoinedRoom>,
+    /// The open direct message tabs by the username of the other user
+    direct_chats: BTreeMap<Username, DirectChat>,
+    /// The joined room or direct message tab being shown
+    active: Option<ActiveChat>,
     text_to_send: String,
+    /// The IM being edited (`text_to_send` is saved as its new content)
+    editing: Option<ChatImId>,
     error_status: Option<ChatUiError>,
     scroll_to_bottom: Option<u8>,
-    connected_users: ConnectedUsers,
-    last_history_request: Timestamp,
+    new_room: NewRoom,
+    search: SearchPanel,
+    /// The search match to scroll to once it is loaded in the history
+    jump_to: Option<JumpTarget>,
+    /// The IM shown as the last search match selected
+    highlighted: Option<ChatImId>,
+}
+
+#[derive(Debug, Default)]
+struct NewRoom {
+    name: String,
+    visibility: RoomVisibility,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ActiveChat {
+    Room(ChatRoomName),
+    Direct(Username),
+}
+
+enum ImAction {
+    Edit(ChatImId, ChatImText),
+    Delete(ChatImId),
+}
+
+enum PanelAction {
+    ShowRoom(ChatRoomName),
+    JoinRoom(ChatRoomName),
+    LeaveRoom(ChatRoomName),
+    ShowDirect(Username),
+    CloseDirect(Username),
+}
+
+/// A search match to show in the history of its conversation
+#[derive(Debug)]
+struct JumpTarget {
+    chat: ActiveChat,
+    id: ChatImId,
+    timestamp: Timestamp,
+    /// The earliest timestamp in the history when more history was last
+    /// requested to reach the match (Used to detect when there is no more)
+    requested_from: Option<Timestamp>,
 }
 
 #[derive(Debug)]
@@ -41,24 +111,38 @@ struct ChatUiError {
 }
 
 impl FrontEnd {
-    pub fn new(username: Username, page_unique_name: String) -> Self {
+    pub fn new(
+        username: Username,
+        branch_id: Option<BranchId>,
+        is_moderator: bool,
+        page_unique_name: String,
+    ) -> Self {
         Self {
             username,
+            is_moderator,
             system_username: Username::try_from(CHAT_SYSTEM_USERNAME)
                 .expect("username is from a constant should either always work or always fail"),
+            branch_id,
             unique_id_prefix: page_unique_name,
-            history: Default::default(),
+            rooms: Default::default(),
+            joined_rooms: Default::default(),
+            direct_chats: Default::default(),
+            active: Default::default(),
             text_to_send: Default::default(),
+            editing: Default::default(),
             error_status: Default::default(),
             scroll_to_bottom: Default::default(),
-            connected_users: Default::default(),
-            last_history_request: Timestamp::now(),
+            new_room: Default::default(),
+            search: Default::default(),
+            jump_to: Default::default(),
+            highlighted: Default::default(),
         }
     }
 
     pub fn show(&mut self, ui: &mut egui::Ui, connection: &mut WsConnTxRx) {
         if self.error_status.is_none() {
             self.check_for_server_msgs(connection);
+            self.request_pending_direct_history(connection);
         }
         let half_height = ui.available_height() / 2.;
         egui::Panel::bottom(format!("{}bottom", self.unique_id_prefix))
@@ -72,10 +156,24 @@ impl FrontEnd {
                 }
             });
 
+        egui::Panel::left(format!("{}rooms", self.unique_id_prefix))
+            .min_size(20.)
+            .show(ui, |ui| {
+                self.ui_rooms(ui, connection);
+                ui.separator();
+                self.ui_direct_chats(ui);
+            });
+
         egui::Panel::right(format!("{}connected users", self.unique_id_prefix))
             .min_size(20.)
             .show(ui, |ui| self.ui_connected_users(ui));
 
+        if self.search.is_open {
+            egui::Panel::right(format!("{}search", self.unique_id_prefix))
+                .resizable(true)
+                .show(ui, |ui| self.ui_search(ui, connection));
+        }
+
         egui::CentralPanel::default().show(ui, |ui| self.ui_messages(ui, connection));
     }
 
@@ -142,56 +240,162 @@ impl FrontEnd {
 
     fn process_chat_msg(&mut self, chat_msg: ChatMsg) -> Result<(), ()> {
         match chat_msg {
-            ChatMsg::UserJoined(user) => {
-                let sys_msg = self.system_msg(format!("{user} connected"))?;
-                self.history.push(sys_msg);
-                self.connected_users.user_joined(user);
-            }
-            ChatMsg::UserLeft(user) => {
-                let sys_msg = self.system_msg(format!("{user} disconnected"))?;
-                self.history.push(sys_msg);
-                if let Err(err) = self
-                    .connected_users
-                    .user_left(user)
-                    .context("removing user failed")
-                {
-                    error!(?err);
-                    self.set_error_unrecoverable("error occurred trying to disconnect user")
+            ChatMsg::UserJoined(ChatRoomUser { room, user }) => {
+                let sys_msg = self.system_msg(&room, format!("{user} joined"))?;
+                if let Some(joined_room) = self.joined_room_mut(&room) {
+                    joined_room.history.push(sys_msg);
+                    joined_room.connected_users.user_joined(user);
+                }
+            }
+            ChatMsg::UserLeft(ChatRoomUser { room, user }) => {
+                let sys_msg = self.system_msg(&room, format!("{user} left"))?;
+                if let Some(joined_room) = self.joined_room_mut(&room) {
+                    joined_room.history.push(sys_msg);
+                    if let Err(err) = joined_room
+                        .connected_users
+                        .user_left(user)
+                        .context("removing user failed")
+                    {
+                        error!(?err);
+                        self.set_error_unrecoverable("error occurred trying to disconnect user")
+                    }
                 }
             }
             ChatMsg::IM(im) => {
-                self.history.push(im);
+                if let Some(joined_room) = self.joined_room_mut(&im.room) {
+                    joined_room.history.push(im);
+                }
             }
             ChatMsg::InitialState(initial_state) => {
-                self.connected_users
-                    .merge_initial_users(initial_state.connected_users);
-                if let Err(e) = self.history.prepend_other(initial_state.history) {
-                    self.error_status = Some(ChatUiError {
-                        err_msg: e.to_string(),
-                        is_transient: true,
-                    })
-                };
+                self.rooms = initial_state.rooms;
+                self.active = Some(ActiveChat::Room(initial_state.default_room.room.clone()));
+                self.merge_room_state(initial_state.default_room);
             }
-            ChatMsg::ReqHistory(req_history_body) => {
-                error!("Received a request for history: {req_history_body:?}");
+            ChatMsg::ReqHistory(_)
+            | ChatMsg::CreateRoom(_)
+            | ChatMsg::JoinRoom(_)
+            | ChatMsg::LeaveRoom(_)
+            | ChatMsg::ReqDirectHistory(_)
+            | ChatMsg::Search(_) => {
+                error!("Received a message only expected from clients: {chat_msg:?}");
                 self.set_error_transient(internal_error_msg!(
-                    "unexpected request for history received from the server"
+                    "unexpected client message received from the server"
                 ));
                 return Err(());
             }
-            ChatMsg::RespHistory(incoming_history) => {
-                if let Err(e) = self.history.prepend_other(incoming_history) {
-                    self.error_status = Some(ChatUiError {
-                        err_msg: e.to_string(),
-                        is_transient: true,
-                    })
+            ChatMsg::RespHistory(RespHistoryBody { room, history }) => {
+                if let Some(joined_room) = self.joined_room_mut(&room)
+                    && let Err(e) = joined_room.history.prepend_other(history)
+                {
+                    self.set_error_transient(e.to_string());
+                };
+            }
+            ChatMsg::RoomCreated(room_info) => {
+                if !self.rooms.iter().any(|x| x.name == room_info.name) {
+                    self.rooms.push(room_info);
+                    self.rooms.sort_by(|a, b| a.name.cmp(&b.name));
+                }
+            }
+            ChatMsg::RoomJoined(room_state) => {
+                self.active = Some(ActiveChat::Room(room_state.room.clone()));
+                self.merge_room_state(room_state);
+                self.request_scroll_to_bottom();
+            }
+            ChatMsg::RoomLeft(room) => {
+                self.joined_rooms.remove(&room);
+                if self.active == Some(ActiveChat::Room(room)) {
+                    self.active = self
+                        .joined_rooms
+                        .keys()
+                        .next()
+                        .cloned()
+                        .map(ActiveChat::Room);
+                }
+            }
+            ChatMsg::RoomError(RoomErrorBody { room, message }) => {
+                self.set_error_transient(format!("{room}: {message}"));
+            }
+            ChatMsg::DirectIM(im) => {
+                // Incoming messages open a tab if one is not already open
+                self.direct_chats
+                    .entry(im.other_user(&self.username).clone())
+                    .or_insert_with(DirectChat::new)
+                    .history
+                    .push(im);
+            }
+            ChatMsg::RespDirectHistory(RespDirectHistoryBody {
+                other_user,
+                history,
+            }) => {
+                if let Some(direct_chat) = self.direct_chats.get_mut(&other_user)
+                    && let Err(e) = direct_chat.history.prepend_other(history)
+                {
+                    self.set_error_transient(e.to_string());
                 };
             }
+            ChatMsg::DirectError(DirectErrorBody {
+                other_user,
+                message,
+            }) => {
+                self.set_error_transient(format!("{other_user}: {message}"));
+            }
+            ChatMsg::Edit(EditImBody { id, content }) => {
+                // IMs that are not loaded are ignored as they will be up to date when loaded
+                if let Some(im) = self.history_im_mut(id) {
+                    im.edit(content);
+                }
+            }
+            ChatMsg::Delete(id) => {
+                if let Some(im) = self.history_im_mut(id) {
+                    im.delete();
+                }
+            }
+            ChatMsg::EditError(EditErrorBody { id: _, message }) => {
+                self.set_error_transient(message);
+            }
+            ChatMsg::RateLimited(RateLimitedBody { retry_after }) => {
+                self.search.search_failed();
+                self.set_error_transient(format!(
+                    "Sending too fast, try again in {retry_after} seconds"
+                ));
+            }
+            ChatMsg::SearchResults(results) => self.search.add_results(results),
+            ChatMsg::SearchError(SearchErrorBody { scope: _, message }) => {
+                self.search.search_failed();
+                self.set_error_transient(format!("Search failed: {message}"));
+            }
         }
         Ok(())
     }
 
+    fn merge_room_state(&mut self, room_state: RoomStateBody) {
+        let joined_room = self
+            .joined_rooms
+            .entry(room_state.room)
+            .or_insert_with(JoinedRoom::new);
+        joined_room
+            .connected_users
+            .merge_initial_users(room_state.connected_users);
+        if let Err(e) = joined_room.history.prepend_other(room_state.history) {
+            self.set_error_transient(e.to_string());
+        };
+    }
+
+    /// Messages for rooms that are not joined are expected if they were sent
+    /// before the server processed a request to leave
+    fn joined_room_mut(&mut self, room: &ChatRoomName) -> Option<&mut JoinedRoom> {
+        let result = self.joined_rooms.get_mut(room);
+        if result.is_none() {
+            warn!(?room, "ignoring message for a room that is not joined");
+        }
+        result
+    }
+
     fn ui_send_area(&mut self, ui: &mut egui::Ui, connection: &mut WsConnTxRx) {
+        if self.active.is_none() {
+            ui.label("Join a room to send messages");
+            return;
+        }
         ui.with_layout(egui::Layout::right_to_left(egui::Align::BOTTOM), |ui| {
             let bytes_left = ChatImText::MAX_LENGTH as i32 - self.text_to_send.len() as i32;
             if bytes_left <= ChatImText::MAX_LENGTH as i32 / 10 {
@@ -208,9 +412,18 @@ impl FrontEnd {
             }
 
             ui.horizontal_centered(|ui| {
-                if ui.button("Send").clicked() {
+                let send_label = if self.editing.is_some() {
+                    "Save"
+                } else {
+                    "Send"
+                };
+                if ui.button(send_label).clicked() {
                     self.send_msg(connection);
                 }
+                if self.editing.is_some() && ui.button("Cancel").clicked() {
+                    self.editing = None;
+                    self.text_to_send.clear();
+                }
             });
 
             let key_combination_for_new_line = KeyboardShortcut {
@@ -237,6 +450,9 @@ impl FrontEnd {
     }
 
     fn send_msg(&mut self, connection: &mut WsConnTxRx) {
+        let Some(active) = self.active.clone() else {
+            return;
+        };
         if self.text_to_send.is_empty() {
             return;
         }
@@ -260,18 +476,55 @@ NB: Number of bytes is not equal the number of characters, eg. emojis use multip
                 return;
             }
         };
-        let chat_msg = ChatMsg::IM(ChatIM {
-            author: self.username.clone(),
-            timestamp: Timestamp::now(),
-            content,
-        });
-        connection.send(WsMessage::Text(
-            serde_json::to_string(&chat_msg).expect("failed to serialize chat msg for IM"),
-        ));
+        if let Some(id) = self.editing.take() {
+            send_chat_msg(connection, &ChatMsg::Edit(EditImBody { id, content }));
+            return;
+        }
+        let author = self.username.clone();
+        let timestamp = Timestamp::now();
+        let chat_msg = match active {
+            ActiveChat::Room(room) => ChatMsg::IM(ChatIM {
+                id: Default::default(),
+                room,
+                author,
+                timestamp,
+                content,
+                state: Default::default(),
+            }),
+            ActiveChat::Direct(recipient) => ChatMsg::DirectIM(DirectIM {
+                id: Default::default(),
+                recipient,
+                author,
+                timestamp,
+                content,
+                state: Default::default(),
+            }),
+        };
+        send_chat_msg(connection, &chat_msg);
         self.request_scroll_to_bottom();
     }
 
     fn ui_messages(&mut self, ui: &mut egui::Ui, connection: &mut WsConnTxRx) {
+        let Some(active) = self.active.clone() else {
+            ui.label("No room selected");
+            return;
+        };
+        let last_history_request = match &active {
+            ActiveChat::Room(room) => self.joined_rooms.get(room).map(|x| x.last_history_request),
+            ActiveChat::Direct(other_user) => self
+                .direct_chats
+                .get(other_user)
+                .map(|x| x.last_history_request),
+        };
+        let Some(last_history_request) = last_history_request else {
+            self.set_error_transient(internal_error_msg!("active chat is not open"));
+            return;
+        };
+        ui.horizontal(|ui| {
+            ui.heading(active.to_string());
+            ui.toggle_value(&mut self.search.is_open, "Search");
+        });
+        let scroll_to_match = self.progress_jump(&active, connection);
         ScrollArea::vertical()
             .auto_shrink(false)
             .stick_to_bottom(true)
@@ -280,10 +533,10 @@ NB: Number of bytes is not equal the number of characters, eg. emojis use multip
                 ui.vertical_centered(|ui| {
                     let now = Timestamp::now();
                     let min_time_stamp_for_request =
-                        self.last_history_request + CHAT_MIN_TIME_BETWEEN_HISTORY_REQUESTS;
+                        last_history_request + CHAT_MIN_TIME_BETWEEN_HISTORY_REQUESTS;
                     if min_time_stamp_for_request < now {
                         if ui.button("Load more history").clicked() {
-                            self.request_more_history(connection);
+                            self.request_more_history(&active, connection);
                         }
                     } else {
                         let time_left = now.abs_diff(min_time_stamp_for_request);
@@ -293,30 +546,30 @@ NB: Number of bytes is not equal the number of characters, eg. emojis use multip
                         );
                     }
                 });
-                for im in self.history.iter() {
-                    let mut frame = egui::Frame::default().inner_margin(4.0).begin(ui);
-                    {
-                        let ui = &mut frame.content_ui;
-                        ui.with_layout(
-                            Layout::top_down(Align::LEFT).with_cross_justify(true),
-                            |ui| {
-                                let color = match &im.author {
-                                    x if x == &self.username => ui.visuals().strong_text_color(),
-                                    x if x == &self.system_username => {
-                                        ui.visuals().weak_text_color()
-                                    }
-                                    _ => ui.visuals().text_color(),
-                                };
-                                ui.colored_label(color, format!("{im}"))
-                                    .on_hover_text(im.timestamp.display_as_utc_datetime_long());
-                            },
-                        );
+                let action = match &active {
+                    ActiveChat::Room(room) => self.joined_rooms.get(room).and_then(|joined_room| {
+                        self.ui_ims(
+                            ui,
+                            joined_room.history.iter(),
+                            self.is_moderator,
+                            scroll_to_match,
+                        )
+                    }),
+                    ActiveChat::Direct(other_user) => {
+                        self.direct_chats.get(other_user).and_then(|direct_chat| {
+                            self.ui_ims(ui, direct_chat.history.iter(), false, scroll_to_match)
+                        })
                     }
-                    let response = frame.allocate_space(ui);
-                    if response.hovered() {
-                        frame.frame.fill = ui.visuals().faint_bg_color;
+                };
+                match action {
+                    Some(ImAction::Edit(id, content)) => {
+                        self.editing = Some(id);
+                        self.text_to_send = content.into();
+                    }
+                    Some(ImAction::Delete(id)) => {
+                        send_chat_msg(connection, &ChatMsg::Delete(id));
                     }
-                    frame.paint(ui);
+                    None => {}
                 }
                 if let Some(left) = self.scroll_to_bottom.as_mut() {
                     if *left == 0 {
@@ -327,12 +580,332 @@ NB: Number of bytes is not equal the number of characters, eg. emojis use multip
                     *left -= 1;
                 }
             });
+        if scroll_to_match {
+            self.jump_to = None;
+        }
+    }
+
+    /// Requests more history until the search match being jumped to is loaded.
+    /// Returns true once it is loaded so it can be scrolled to
+    fn progress_jump(&mut self, active: &ActiveChat, connection: &mut WsConnTxRx) -> bool {
+        let Some(jump) = self.jump_to.as_ref().filter(|jump| &jump.chat == active) else {
+            return false;
+        };
+        let history_state = match active {
+            ActiveChat::Room(room) => self.joined_rooms.get(room).map(|joined_room| {
+                (
+                    is_in_history(&joined_room.history, jump.id),
+                    joined_room.history.earliest_timestamp_or_now(),
+                    joined_room.last_history_request,
+                )
+            }),
+            ActiveChat::Direct(other_user) => {
+                self.direct_chats.get(other_user).map(|direct_chat| {
+                    (
+                        is_in_history(&direct_chat.history, jump.id),
+                        direct_chat.history.earliest_timestamp_or_now(),
+                        direct_chat.last_history_request,
+                    )
+                })
+            }
+        };
+        let Some((is_loaded, earliest, last_history_request)) = history_state else {
+            return false;
+        };
+        if is_loaded {
+            return true;
+        }
+        let is_request_allowed =
+            last_history_request + CHAT_MIN_TIME_BETWEEN_HISTORY_REQUESTS < Timestamp::now();
+        if jump.timestamp > earliest
+            || (is_request_allowed && jump.requested_from == Some(earliest))
+        {
+            // Either it should already be loaded or the last request did not return any
+            // older history (eg. it was deleted after the search)
+            self.jump_to = None;
+            self.set_error_transient("Unable to find the search match in the history");
+            return false;
+        }
+        if is_request_allowed {
+            if let Some(jump) = self.jump_to.as_mut() {
+                jump.requested_from = Some(earliest);
+            }
+            self.request_more_history(active, connection);
+        }
+        false
+    }
+
+    fn ui_search(&mut self, ui: &mut egui::Ui, connection: &mut WsConnTxRx) {
+        let scope = self.active.as_ref().map(ActiveChat::search_scope);
+        match self.search.ui(ui, scope) {
+            Ok(Some(SearchAction::Send(req))) => send_chat_msg(connection, &ChatMsg::Search(req)),
+            Ok(Some(SearchAction::Show(scope, search_match))) => {
+                self.show_search_match(scope, search_match)
+            }
+            Ok(None) => {}
+            Err(err) => self.set_error_transient(format!("{err:#}")),
+        }
+    }
+
+    /// Switches to the conversation of the match and scrolls to it once it is
+    /// loaded
+    fn show_search_match(&mut self, scope: SearchScope, search_match: SearchMatch) {
+        let chat = match scope {
+            SearchScope::Room(room) => {
+                if !self.joined_rooms.contains_key(&room) {
+                    self.set_error_transient(format!("Join {room} to see the search match"));
+                    return;
+                }
+                self.active = Some(ActiveChat::Room(room.clone()));
+                ActiveChat::Room(room)
+            }
+            SearchScope::Direct(other_user) => {
+                self.open_direct_chat(other_user.clone());
+                ActiveChat::Direct(other_user)
+            }
+        };
+        self.scroll_to_bottom = None;
+        self.highlighted = Some(search_match.id);
+        self.jump_to = Some(JumpTarget {
+            chat,
+            id: search_match.id,
+            timestamp: search_match.timestamp,
+            requested_from: None,
+        });
+    }
+
+    /// Shows the IMs and returns the action selected from the context menu of
+    /// an IM if any
+    fn ui_ims<'a, T: HistoryIm + Display + 'a>(
+        &self,
+        ui: &mut egui::Ui,
+        ims: impl Iterator<Item = &'a T>,
+        can_moderate: bool,
+        scroll_to_highlighted: bool,
+    ) -> Option<ImAction> {
+        let mut result = None;
+        for im in ims {
+            let is_own = im.author() == &self.username;
+            // IMs without an ID have not been assigned one by the server
+            let is_changeable = im.id() != ChatImId::default()
+                && !im.state().is_deleted()
+                && (is_own || can_moderate);
+            let mut frame = egui::Frame::default().inner_margin(4.0).begin(ui);
+            {
+                let ui = &mut frame.content_ui;
+                ui.with_layout(
+                    Layout::top_down(Align::LEFT).with_cross_justify(true),
+                    |ui| {
+                        let color = match im.author() {
+                            x if x == &self.username => ui.visuals().strong_text_color(),
+                            x if x == &self.system_username => ui.visuals().weak_text_color(),
+                            _ => ui.visuals().text_color(),
+                        };
+                        let response = ui
+                            .colored_label(color, format!("{im}"))
+                            .on_hover_text(im.timestamp().display_as_utc_datetime_long());
+                        if is_changeable {
+                            response.interact(egui::Sense::click()).context_menu(|ui| {
+                                if is_own && ui.button("Edit").clicked() {
+                                    result = Some(ImAction::Edit(im.id(), im.content().clone()));
+                                    ui.close();
+                                }
+                                if ui.button("Delete").clicked() {
+                                    result = Some(ImAction::Delete(im.id()));
+                                    ui.close();
+                                }
+                            });
+                        }
+                    },
+                );
+            }
+            let response = frame.allocate_space(ui);
+            if self.highlighted.is_some_and(|id| id == im.id()) {
+                frame.frame.fill = ui.visuals().selection.bg_fill;
+                if scroll_to_highlighted {
+                    response.scroll_to_me(Some(Align::Center));
+                }
+            } else if response.hovered() {
+                frame.frame.fill = ui.visuals().faint_bg_color;
+            }
+            frame.paint(ui);
+        }
+        result
+    }
+
+    /// Finds the IM in the history of any joined room or open direct message
+    /// tab
+    fn history_im_mut(&mut self, id: ChatImId) -> Option<&mut dyn HistoryIm> {
+        if let Some(im) = self
+            .joined_rooms
+            .values_mut()
+            .find_map(|joined_room| joined_room.history.get_mut(id))
+        {
+            return Some(im);
+        }
+        self.direct_chats
+            .values_mut()
+            .find_map(|direct_chat| direct_chat.history.get_mut(id))
+            .map(|im| im as &mut dyn HistoryIm)
     }
 
     fn ui_connected_users(&mut self, ui: &mut egui::Ui) {
         ui.heading("Connected Users");
-        for (user, qty) in self.connected_users.iter() {
-            ui.label(format!("{user} ({qty})"));
+        let joined_room = match &self.active {
+            Some(ActiveChat::Room(room)) => self.joined_rooms.get(room),
+            Some(ActiveChat::Direct(other_user)) => {
+                ui.label(format!("Direct messages with {other_user}"));
+                None
+            }
+            None => None,
+        };
+        let Some(joined_room) = joined_room else {
+            return;
+        };
+        if let Some(other_user) = joined_room.connected_users.ui(ui, &self.username) {
+            self.open_direct_chat(other_user);
+        }
+    }
+
+    fn open_direct_chat(&mut self, other_user: Username) {
+        self.direct_chats
+            .entry(other_user.clone())
+            .or_insert_with(DirectChat::new);
+        self.active = Some(ActiveChat::Direct(other_user));
+        self.request_scroll_to_bottom();
+    }
+
+    fn ui_direct_chats(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Direct Messages");
+        if self.direct_chats.is_empty() {
+            ui.label("Open from the connected users list");
+        }
+        let mut action = None;
+        for other_user in self.direct_chats.keys() {
+            let is_active = self.active.as_ref() == Some(&ActiveChat::Direct(other_user.clone()));
+            ui.horizontal(|ui| {
+                if ui.selectable_label(is_active, other_user).clicked() {
+                    action = Some(PanelAction::ShowDirect(other_user.clone()));
+                }
+                if ui.small_button("Close").clicked() {
+                    action = Some(PanelAction::CloseDirect(other_user.clone()));
+                }
+            });
+        }
+        match action {
+            Some(PanelAction::ShowDirect(other_user)) => {
+                self.active = Some(ActiveChat::Direct(other_user));
+                self.request_scroll_to_bottom();
+            }
+            Some(PanelAction::CloseDirect(other_user)) => {
+                self.direct_chats.remove(&other_user);
+                if self.active == Some(ActiveChat::Direct(other_user)) {
+                    self.active = self
+                        .joined_rooms
+                        .keys()
+                        .next()
+                        .cloned()
+                        .map(ActiveChat::Room);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ui_rooms(&mut self, ui: &mut egui::Ui, connection: &mut WsConnTxRx) {
+        ui.heading("Rooms");
+        let mut action = None;
+        for room_info in self.rooms.iter() {
+            let is_joined = self.joined_rooms.contains_key(&room_info.name);
+            let is_active = self.active == Some(ActiveChat::Room(room_info.name.clone()));
+            ui.horizontal(|ui| {
+                if is_joined {
+                    if ui
+                        .selectable_label(is_active, &room_info.name)
+                        .on_hover_text(room_info.visibility.to_string())
+                        .clicked()
+                    {
+                        action = Some(PanelAction::ShowRoom(room_info.name.clone()));
+                    }
+                    if ui.small_button("Leave").clicked() {
+                        action = Some(PanelAction::LeaveRoom(room_info.name.clone()));
+                    }
+                } else {
+                    ui.label(&room_info.name)
+                        .on_hover_text(room_info.visibility.to_string());
+                    if ui.small_button("Join").clicked() {
+                        action = Some(PanelAction::JoinRoom(room_info.name.clone()));
+                    }
+                }
+            });
+        }
+        match action {
+            Some(PanelAction::ShowRoom(room)) => {
+                self.active = Some(ActiveChat::Room(room));
+                self.request_scroll_to_bottom();
+            }
+            Some(PanelAction::JoinRoom(room)) => {
+                send_chat_msg(connection, &ChatMsg::JoinRoom(room))
+            }
+            Some(PanelAction::LeaveRoom(room)) => {
+                send_chat_msg(connection, &ChatMsg::LeaveRoom(room))
+            }
+            _ => {}
+        }
+
+        ui.separator();
+        egui::CollapsingHeader::new("New Room").show(ui, |ui| self.ui_new_room(ui, connection));
+    }
+
+    fn ui_new_room(&mut self, ui: &mut egui::Ui, connection: &mut WsConnTxRx) {
+        ui.add(
+            egui::TextEdit::singleline(&mut self.new_room.name)
+                .hint_text("Room name")
+                .char_limit(ChatRoomName::MAX_LENGTH),
+        );
+        let visibility = &mut self.new_room.visibility;
+        egui::ComboBox::from_id_salt(format!("{}visibility", self.unique_id_prefix))
+            .selected_text(match visibility {
+                RoomVisibility::Everyone => "Everyone",
+                RoomVisibility::Permission(_) => "Permission",
+                RoomVisibility::Branch(_) => "My Branch",
+            })
+            .show_ui(ui, |ui| {
+                ui.selectable_value(visibility, RoomVisibility::Everyone, "Everyone");
+                ui.selectable_value(
+                    visibility,
+                    RoomVisibility::Permission(Permission::iter().next().expect(
+                        "there should always be at least one permission as they are hard coded",
+                    )),
+                    "Permission",
+                );
+                if let Some(branch_id) = self.branch_id {
+                    ui.selectable_value(visibility, RoomVisibility::Branch(branch_id), "My Branch");
+                }
+            })
+            .response
+            .on_hover_text("Who is able to see the room");
+        if let RoomVisibility::Permission(permission) = visibility {
+            egui::ComboBox::from_id_salt(format!("{}permission", self.unique_id_prefix))
+                .selected_text(permission.to_string())
+                .show_ui(ui, |ui| {
+                    for option in Permission::iter() {
+                        let text = option.to_string();
+                        ui.selectable_value(permission, option, text);
+                    }
+                });
+        }
+        if ui.button("Create").clicked() {
+            match ChatRoomName::try_from(self.new_room.name.trim()) {
+                Ok(name) => {
+                    let room_info = ChatRoomInfo {
+                        name,
+                        visibility: std::mem::take(&mut self.new_room).visibility,
+                    };
+                    send_chat_msg(connection, &ChatMsg::CreateRoom(room_info));
+                }
+                Err(err) => self.set_error_transient(err.to_string()),
+            }
         }
     }
 
@@ -379,7 +952,7 @@ NB: Number of bytes is not equal the number of characters, eg. emojis use multip
         });
     }
 
-    fn system_msg(&mut self, content: String) -> Result<ChatIM, ()> {
+    fn system_msg(&mut self, room: &ChatRoomName, content: String) -> Result<ChatIM, ()> {
         let content = match content.try_into() {
             Ok(x) => x,
             Err(err_msg) => {
@@ -389,23 +962,83 @@ NB: Number of bytes is not equal the number of characters, eg. emojis use multip
             }
         };
         Ok(ChatIM {
+            id: Default::default(),
+            room: room.clone(),
             author: self.system_username.clone(),
             timestamp: Timestamp::now(),
             content,
+            state: Default::default(),
         })
     }
 
-    fn request_more_history(&mut self, connection: &mut WsConnTxRx) {
-        self.last_history_request = Timestamp::now();
+    fn request_more_history(&mut self, active: &ActiveChat, connection: &mut WsConnTxRx) {
         let qty = CHAT_HISTORY_REQUEST_SIZE;
-        let current_earliest_timestamp = self.history.earliest_timestamp_or_now();
-        let chat_msg = ChatMsg::ReqHistory(ReqHistoryBody {
-            qty,
-            latest_timestamp: current_earliest_timestamp,
-        });
-        connection.send(WsMessage::Text(
-            serde_json::to_string(&chat_msg)
-                .expect("failed to serialize chat msg for history request"),
-        ));
+        let chat_msg = match active {
+            ActiveChat::Room(room) => {
+                let Some(joined_room) = self.joined_rooms.get_mut(room) else {
+                    return;
+                };
+                joined_room.last_history_request = Timestamp::now();
+                ChatMsg::ReqHistory(ReqHistoryBody {
+                    room: room.clone(),
+                    qty,
+                    latest_timestamp: joined_room.history.earliest_timestamp_or_now(),
+                })
+            }
+            ActiveChat::Direct(other_user) => {
+                let Some(direct_chat) = self.direct_chats.get_mut(other_user) else {
+                    return;
+                };
+                direct_chat.last_history_request = Timestamp::now();
+                direct_chat.is_history_pending = false;
+                ChatMsg::ReqDirectHistory(ReqDirectHistoryBody {
+                    other_user: other_user.clone(),
+                    qty,
+                    latest_timestamp: direct_chat.history.earliest_timestamp_or_now(),
+                })
+            }
+        };
+        send_chat_msg(connection, &chat_msg);
+    }
+
+    /// Requests the initial history of newly opened direct message tabs
+    fn request_pending_direct_history(&mut self, connection: &mut WsConnTxRx) {
+        let pending: Vec<Username> = self
+            .direct_chats
+            .iter()
+            .filter(|(_, direct_chat)| direct_chat.is_history_pending)
+            .map(|(other_user, _)| other_user.clone())
+            .collect();
+        for other_user in pending {
+            self.request_more_history(&ActiveChat::Direct(other_user), connection);
+        }
+    }
+}
+
+impl ActiveChat {
+    fn search_scope(&self) -> SearchScope {
+        match self {
+            ActiveChat::Room(room) => SearchScope::Room(room.clone()),
+            ActiveChat::Direct(other_user) => SearchScope::Direct(other_user.clone()),
+        }
     }
 }
+
+impl Display for ActiveChat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ActiveChat::Room(room) => write!(f, "{room}"),
+            ActiveChat::Direct(other_user) => write!(f, "Direct: {other_user}"),
+        }
+    }
+}
+
+fn is_in_history<T: HistoryIm + Debug>(history: &ChatMsgsHistory<T>, id: ChatImId) -> bool {
+    history.iter().any(|im| im.id() == id)
+}
+
+fn send_chat_msg(connection: &mut WsConnTxRx, chat_msg: &ChatMsg) {
+    connection.send(WsMessage::Text(
+        serde_json::to_string(chat_msg).expect("failed to serialize chat msg"),
+    ));
+}
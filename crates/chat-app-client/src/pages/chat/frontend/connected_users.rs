@@ -2,6 +2,7 @@ use std::collections::BTreeMap;
 
 use anyhow::{Ok, bail};
 use plugin_chat::ChatUser;
+use wykies_shared::uac::Username;
 
 #[derive(Debug, Default)]
 pub struct ConnectedUsers {
@@ -34,7 +35,18 @@ impl ConnectedUsers {
         }
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = (&ChatUser, &u8)> {
-        self.users.iter()
+    /// Lists the users and returns the user to open a direct message tab for if
+    /// one was clicked
+    pub fn ui(&self, ui: &mut egui::Ui, own_username: &Username) -> Option<Username> {
+        let mut result = None;
+        for (user, qty) in self.users.iter() {
+            ui.horizontal(|ui| {
+                ui.label(format!("{user} ({qty})"));
+                if user.username() != own_username && ui.small_button("DM").clicked() {
+                    result = Some(user.username().clone());
+                }
+            });
+        }
+        result
     }
 }
@@ -0,0 +1,22 @@
+use plugin_chat::DirectMsgsHistory;
+use wykies_time::Timestamp;
+
+/// The state kept for each open direct message tab
+#[derive(Debug)]
+pub struct DirectChat {
+    pub history: DirectMsgsHistory,
+    pub last_history_request: Timestamp,
+    /// Set when the tab is opened so the history is requested once the
+    /// connection is available
+    pub is_history_pending: bool,
+}
+
+impl DirectChat {
+    pub fn new() -> Self {
+        Self {
+            history: Default::default(),
+            last_history_request: Timestamp::now(),
+            is_history_pending: true,
+        }
+    }
+}
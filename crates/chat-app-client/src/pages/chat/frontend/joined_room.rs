@@ -0,0 +1,21 @@
+use super::connected_users::ConnectedUsers;
+use plugin_chat::ChatMsgsHistory;
+use wykies_time::Timestamp;
+
+/// The state kept for each room the user is in
+#[derive(Debug)]
+pub struct JoinedRoom {
+    pub history: ChatMsgsHistory,
+    pub connected_users: ConnectedUsers,
+    pub last_history_request: Timestamp,
+}
+
+impl JoinedRoom {
+    pub fn new() -> Self {
+        Self {
+            history: Default::default(),
+            connected_users: Default::default(),
+            last_history_request: Timestamp::now(),
+        }
+    }
+}
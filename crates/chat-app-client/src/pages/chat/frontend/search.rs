@@ -0,0 +1,154 @@
+use anyhow::{Context as _, bail};
+use plugin_chat::{
+    SearchBody, SearchMatch, SearchResultsBody, SearchScope, consts::CHAT_HISTORY_REQUEST_SIZE,
+};
+use wykies_shared::uac::Username;
+use wykies_time::{Seconds, Timestamp};
+
+/// Added to the start of the last day of the range so the whole day is included
+const END_OF_DAY_OFFSET: Seconds = Seconds::new(24 * 60 * 60 - 1);
+
+/// The state of the search panel
+#[derive(Debug, Default)]
+pub struct SearchPanel {
+    pub is_open: bool,
+    query: String,
+    /// Empty matches any author
+    author: String,
+    /// First day of the range (YYYY-MM-DD). Empty for no limit
+    from: String,
+    /// Last day of the range (YYYY-MM-DD). Empty for no limit
+    to: String,
+    /// The last search sent (Only results for this search are shown)
+    last_req: Option<SearchBody>,
+    matches: Vec<SearchMatch>,
+    has_more: bool,
+    is_pending: bool,
+}
+
+pub enum SearchAction {
+    /// Send the search to the server
+    Send(SearchBody),
+    /// Show the match in the history of the conversation it was found in
+    Show(SearchScope, SearchMatch),
+}
+
+impl SearchPanel {
+    /// Shows the search inputs and results. `scope` is the conversation
+    /// searched if a new search is started
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        scope: Option<SearchScope>,
+    ) -> anyhow::Result<Option<SearchAction>> {
+        let mut result = None;
+        ui.heading("Search");
+        let Some(scope) = scope else {
+            ui.label("Select a room to search");
+            return Ok(None);
+        };
+        let query_response = ui.add(
+            egui::TextEdit::singleline(&mut self.query).hint_text("Words to find (all required)"),
+        );
+        ui.add(egui::TextEdit::singleline(&mut self.author).hint_text("Author (optional)"));
+        ui.add(egui::TextEdit::singleline(&mut self.from).hint_text("From YYYY-MM-DD (optional)"));
+        ui.add(egui::TextEdit::singleline(&mut self.to).hint_text("To YYYY-MM-DD (optional)"));
+        ui.horizontal(|ui| {
+            let is_enter_pressed =
+                query_response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if ui.button("Search").clicked() || is_enter_pressed {
+                result = Some(self.new_search(scope));
+            }
+            if self.is_pending {
+                ui.spinner();
+            }
+        });
+        let mut action = result.transpose()?;
+
+        ui.separator();
+        if self.last_req.is_some() && self.matches.is_empty() && !self.is_pending {
+            ui.label("No matches found");
+        }
+        egui::ScrollArea::vertical()
+            .auto_shrink(false)
+            .show(ui, |ui| {
+                for search_match in self.matches.iter() {
+                    let response = ui
+                        .selectable_label(false, search_match.to_string())
+                        .on_hover_text("Show in history");
+                    if response.clicked()
+                        && let Some(req) = self.last_req.as_ref()
+                    {
+                        action = Some(SearchAction::Show(req.scope.clone(), search_match.clone()));
+                    }
+                }
+                if self.has_more && !self.is_pending && ui.button("Load more results").clicked() {
+                    action = self.next_page().map(SearchAction::Send);
+                }
+            });
+        Ok(action)
+    }
+
+    /// Results that are not for the last search sent are ignored
+    pub fn add_results(&mut self, results: SearchResultsBody) {
+        let Some(last_req) = self.last_req.as_ref() else {
+            return;
+        };
+        if last_req != &results.req {
+            return;
+        }
+        self.matches.extend(results.matches);
+        self.has_more = results.has_more;
+        self.is_pending = false;
+    }
+
+    pub fn search_failed(&mut self) {
+        self.is_pending = false;
+    }
+
+    fn new_search(&mut self, scope: SearchScope) -> anyhow::Result<SearchAction> {
+        let author = match self.author.trim() {
+            "" => None,
+            author => Some(Username::try_from(author).context("invalid author")?),
+        };
+        let from = parse_date(&self.from).context("invalid start date")?;
+        let to = parse_date(&self.to)
+            .context("invalid end date")?
+            .map(|to| to + END_OF_DAY_OFFSET);
+        let req = SearchBody {
+            scope,
+            query: self.query.clone(),
+            author,
+            from,
+            to,
+            qty: CHAT_HISTORY_REQUEST_SIZE,
+            before: None,
+        };
+        if req.terms().is_empty() {
+            bail!("Enter at least one word to search for");
+        }
+        self.matches.clear();
+        self.has_more = false;
+        Ok(SearchAction::Send(self.send(req)))
+    }
+
+    /// Requests the matches older than the ones already received
+    fn next_page(&mut self) -> Option<SearchBody> {
+        let mut req = self.last_req.clone()?;
+        req.before = Some(self.matches.last()?.id);
+        Some(self.send(req))
+    }
+
+    fn send(&mut self, req: SearchBody) -> SearchBody {
+        self.last_req = Some(req.clone());
+        self.is_pending = true;
+        req
+    }
+}
+
+fn parse_date(value: &str) -> anyhow::Result<Option<Timestamp>> {
+    match value.trim() {
+        "" => Ok(None),
+        date => Ok(Some(Timestamp::from_utc_date_str(date)?)),
+    }
+}
@@ -1,10 +1,15 @@
+mod password_reset;
+mod totp_enrolment;
+
 use super::change_password::UiChangePassword;
 use crate::DataShared;
 use egui_helpers::{ResponseHelpers, UiHelpers as _};
 use egui_pages::DisplayablePage as _;
+use password_reset::UiPasswordReset;
 use reqwest_cross::{Awaiting, DataState};
 use secrecy::{ExposeSecret, SecretString};
 use std::fmt::Debug;
+use totp_enrolment::UiTotpEnrolment;
 use tracing::info;
 use wykies_client_core::LoginOutcome;
 use wykies_shared::req_args::LoginReqArgs;
@@ -12,8 +17,11 @@ use wykies_shared::req_args::LoginReqArgs;
 #[derive(Debug)]
 pub struct UiLogin {
     password: SecretString,
+    totp_code: SecretString,
     login_attempt_status: DataState<LoginOutcome>,
     password_change_page: Option<UiChangePassword>,
+    totp_enrolment_page: Option<UiTotpEnrolment>,
+    password_reset_page: Option<UiPasswordReset>,
 }
 
 impl UiLogin {
@@ -21,13 +29,24 @@ impl UiLogin {
         !self.password.expose_secret().is_empty()
     }
 
+    fn is_totp_code_required(&self) -> bool {
+        matches!(
+            self.login_attempt_status,
+            DataState::Present(LoginOutcome::TotpRequired)
+        )
+    }
+
     fn is_login_state_allowed_to_login(&self) -> bool {
         match self.login_attempt_status.as_ref() {
             DataState::None
             | DataState::Failed(_)
             | DataState::Present(LoginOutcome::RetryWithBranchSet) => true,
+            DataState::Present(LoginOutcome::TotpRequired) => {
+                !self.totp_code.expose_secret().is_empty()
+            }
             DataState::AwaitingResponse(_)
             | DataState::Present(LoginOutcome::ForcePasswordChange)
+            | DataState::Present(LoginOutcome::TotpEnrolmentRequired)
             | DataState::Present(LoginOutcome::Success) => false,
         }
     }
@@ -49,6 +68,13 @@ impl UiLogin {
             .enter_pressed(ui)
             || was_enter_pressed;
 
+        if self.is_totp_code_required() {
+            was_enter_pressed = ui
+                .password_edit(&mut self.totp_code, "Authenticator or recovery code")
+                .enter_pressed(ui)
+                || was_enter_pressed;
+        }
+
         if was_enter_pressed && is_allowed_to_login(self, &data_shared.username) {
             self.send_login_attempt(data_shared)
         }
@@ -59,9 +85,13 @@ impl UiLogin {
             DataState::None => {
                 // No special UI needed
             }
-            DataState::Present(LoginOutcome::ForcePasswordChange) => {
+            DataState::Present(LoginOutcome::ForcePasswordChange)
+            | DataState::Present(LoginOutcome::TotpEnrolmentRequired) => {
                 // Handled at the start of the update loop
             }
+            DataState::Present(LoginOutcome::TotpRequired) => {
+                ui.label("Please enter the code from your authenticator app");
+            }
             DataState::Present(LoginOutcome::Success) => {
                 if data_shared.is_logged_in() {
                     debug_assert!(
@@ -118,6 +148,20 @@ impl UiLogin {
                 if password_page.data_state.is_present() {
                     self.login_attempt_status = DataState::Present(LoginOutcome::Success);
                 }
+            } else if matches!(
+                self.login_attempt_status,
+                DataState::Present(LoginOutcome::TotpEnrolmentRequired),
+            ) {
+                let totp_enrolment_page = self.totp_enrolment_page.get_or_insert_default();
+                totp_enrolment_page.show(ui, data_shared);
+                if totp_enrolment_page.is_complete {
+                    self.login_attempt_status = DataState::Present(LoginOutcome::Success);
+                }
+            } else if let Some(password_reset_page) = self.password_reset_page.as_mut() {
+                password_reset_page.show(ui, data_shared);
+                if password_reset_page.is_closed {
+                    self.password_reset_page = None;
+                }
             } else {
                 ui.vertical_centered(|ui| {
                     ui.heading("Login");
@@ -127,6 +171,10 @@ impl UiLogin {
                     self.check_login_attempt_status(ui, data_shared);
 
                     self.login_button(ui, data_shared);
+
+                    if !data_shared.is_logged_in() && ui.button("Forgot password?").clicked() {
+                        self.password_reset_page = Some(UiPasswordReset::default());
+                    }
                 });
             }
         });
@@ -145,11 +193,14 @@ impl UiLogin {
     }
 
     fn send_login_attempt(&mut self, data_shared: &mut DataShared) {
+        let totp_code =
+            (!self.totp_code.expose_secret().is_empty()).then(|| self.totp_code.clone());
         let args = LoginReqArgs::new_with_branch(
             data_shared.username.clone(),
             self.password.clone(),
             1.into(), // Branches are not needed by the demo
-        );
+        )
+        .totp_code(totp_code);
 
         let rx = data_shared.client.login(args);
         self.login_attempt_status = DataState::AwaitingResponse(Awaiting(rx));
@@ -160,8 +211,11 @@ impl Default for UiLogin {
     fn default() -> Self {
         Self {
             password: SecretString::from(""),
+            totp_code: SecretString::from(""),
             login_attempt_status: Default::default(),
             password_change_page: Default::default(),
+            totp_enrolment_page: Default::default(),
+            password_reset_page: Default::default(),
         }
     }
 }
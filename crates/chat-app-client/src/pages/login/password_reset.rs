@@ -0,0 +1,135 @@
+use crate::DataShared;
+use egui_helpers::UiHelpers as _;
+use reqwest_cross::{Awaiting, DataState};
+use secrecy::{ExposeSecret as _, SecretString};
+use wykies_shared::{
+    req_args::password_reset::{RedeemReqArgs, RequestReqArgs},
+    uac::Username,
+};
+
+/// Lets users that forgot their password request a reset code be sent to
+/// their email address and use it to set a new password
+#[derive(Debug)]
+pub struct UiPasswordReset {
+    request_status: DataState<()>,
+    token: SecretString,
+    new_password: SecretString,
+    new_password_check: SecretString,
+    redeem_status: DataState<()>,
+    pub is_closed: bool,
+}
+
+impl UiPasswordReset {
+    pub fn show(&mut self, ui: &mut egui::Ui, data_shared: &mut DataShared) {
+        ui.vertical_centered(|ui| {
+            ui.heading("Reset Password");
+            let username = match Username::try_from(data_shared.username.clone()) {
+                Ok(username) => username,
+                Err(e) => {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut data_shared.username).hint_text("Username"),
+                    );
+                    ui.error_label(e.to_string());
+                    self.back_button(ui);
+                    return;
+                }
+            };
+            match &mut self.request_status {
+                DataState::None => {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut data_shared.username).hint_text("Username"),
+                    );
+                    if ui.button("Send Reset Code").clicked() {
+                        let rx = data_shared
+                            .client
+                            .request_password_reset(RequestReqArgs { username });
+                        self.request_status = DataState::AwaitingResponse(Awaiting(rx));
+                    }
+                }
+                DataState::AwaitingResponse(rx) => {
+                    if let Some(new_state) = DataState::await_data(rx) {
+                        self.request_status = new_state;
+                    } else {
+                        ui.spinner();
+                    }
+                }
+                DataState::Present(()) => self.show_redeem(ui, data_shared, username),
+                DataState::Failed(e) => {
+                    ui.error_label(format!("Failed to request reset code {e}"));
+                    if ui.button("Try Again").clicked() {
+                        self.request_status = DataState::default();
+                    }
+                }
+            }
+            self.back_button(ui);
+        });
+    }
+
+    fn show_redeem(&mut self, ui: &mut egui::Ui, data_shared: &mut DataShared, username: Username) {
+        match &mut self.redeem_status {
+            DataState::None => {
+                ui.label(
+                    "If the user has an email address a reset code has been sent to it. Enter \
+                    the code and your new password below.",
+                );
+                ui.password_edit(&mut self.token, "Reset code");
+                ui.password_edit(&mut self.new_password, "New Password");
+                ui.password_edit(&mut self.new_password_check, "Confirm New Password");
+                let do_passwords_match =
+                    self.new_password.expose_secret() == self.new_password_check.expose_secret();
+                if !do_passwords_match {
+                    ui.error_label("Passwords do not match");
+                }
+                let is_ready_to_send = do_passwords_match
+                    && !self.token.expose_secret().is_empty()
+                    && !self.new_password.expose_secret().is_empty();
+                if ui
+                    .add_enabled(is_ready_to_send, egui::Button::new("Set Password"))
+                    .clicked()
+                {
+                    let rx = data_shared.client.redeem_password_reset(RedeemReqArgs {
+                        username,
+                        token: self.token.clone(),
+                        new_password: self.new_password.clone(),
+                    });
+                    self.redeem_status = DataState::AwaitingResponse(Awaiting(rx));
+                }
+            }
+            DataState::AwaitingResponse(rx) => {
+                if let Some(new_state) = DataState::await_data(rx) {
+                    self.redeem_status = new_state;
+                } else {
+                    ui.spinner();
+                }
+            }
+            DataState::Present(()) => {
+                ui.label("Password changed. You can now login with your new password.");
+            }
+            DataState::Failed(e) => {
+                ui.error_label(format!("Failed to set password {e}"));
+                if ui.button("Try Again").clicked() {
+                    self.redeem_status = DataState::default();
+                }
+            }
+        }
+    }
+
+    fn back_button(&mut self, ui: &mut egui::Ui) {
+        if ui.button("Back to Login").clicked() {
+            self.is_closed = true;
+        }
+    }
+}
+
+impl Default for UiPasswordReset {
+    fn default() -> Self {
+        Self {
+            request_status: Default::default(),
+            token: SecretString::from(""),
+            new_password: SecretString::from(""),
+            new_password_check: SecretString::from(""),
+            redeem_status: Default::default(),
+            is_closed: false,
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use crate::DataShared;
+use egui_helpers::{ResponseHelpers, UiHelpers as _};
+use reqwest_cross::{Awaiting, DataState};
+use secrecy::{ExposeSecret as _, SecretString};
+use wykies_shared::uac::{TotpEnrolment, TotpRecoveryCodes};
+
+/// Walks the user through setting up two-factor authentication when it is
+/// required before they can continue after logging in
+#[derive(Debug)]
+pub struct UiTotpEnrolment {
+    enrolment: DataState<TotpEnrolment>,
+    code: SecretString,
+    recovery_codes: DataState<TotpRecoveryCodes>,
+    pub is_complete: bool,
+}
+
+impl UiTotpEnrolment {
+    pub fn show(&mut self, ui: &mut egui::Ui, data_shared: &mut DataShared) {
+        ui.vertical_centered(|ui| {
+            ui.heading("You are required to set up two-factor authentication to logon");
+            match &mut self.recovery_codes {
+                DataState::None => self.show_enrolment(ui, data_shared),
+                DataState::AwaitingResponse(rx) => {
+                    if let Some(new_state) = DataState::await_data(rx) {
+                        self.recovery_codes = new_state;
+                    } else {
+                        ui.spinner();
+                    }
+                }
+                DataState::Present(recovery_codes) => {
+                    ui.label(
+                        "Two-factor authentication enabled. Store these recovery codes somewhere \
+                        safe, each can be used once instead of a code if you lose access to your \
+                        authenticator app. They will not be shown again.",
+                    );
+                    for code in recovery_codes.codes.iter() {
+                        ui.monospace(code);
+                    }
+                    if ui.button("Done").clicked() {
+                        self.is_complete = true;
+                    }
+                }
+                DataState::Failed(e) => {
+                    ui.error_label(format!("Failed {e}"));
+                    if ui.button("Try Again").clicked() {
+                        self.recovery_codes = DataState::default();
+                    }
+                }
+            }
+        });
+    }
+
+    fn show_enrolment(&mut self, ui: &mut egui::Ui, data_shared: &mut DataShared) {
+        match &mut self.enrolment {
+            DataState::None => {
+                let rx = data_shared.client.totp_enrol();
+                self.enrolment = DataState::AwaitingResponse(Awaiting(rx));
+            }
+            DataState::AwaitingResponse(rx) => {
+                if let Some(new_state) = DataState::await_data(rx) {
+                    self.enrolment = new_state;
+                } else {
+                    ui.spinner();
+                }
+            }
+            DataState::Present(enrolment) => {
+                ui.label("Add this account to your authenticator app using the link or secret");
+                ui.hyperlink(&enrolment.provisioning_uri);
+                ui.monospace(&enrolment.secret);
+                let was_enter_pressed = ui
+                    .password_edit(&mut self.code, "Code from authenticator app")
+                    .enter_pressed(ui);
+                let is_ready_to_send = !self.code.expose_secret().is_empty();
+                if ui
+                    .add_enabled(is_ready_to_send, egui::Button::new("Confirm"))
+                    .clicked()
+                    || (was_enter_pressed && is_ready_to_send)
+                {
+                    let rx = data_shared.client.totp_confirm(self.code.clone());
+                    self.recovery_codes = DataState::AwaitingResponse(Awaiting(rx));
+                }
+            }
+            DataState::Failed(e) => {
+                ui.error_label(format!("Failed to start enrolment {e}"));
+                if ui.button("Try Again").clicked() {
+                    self.enrolment = DataState::default();
+                }
+            }
+        }
+    }
+}
+
+impl Default for UiTotpEnrolment {
+    fn default() -> Self {
+        Self {
+            enrolment: Default::default(),
+            code: SecretString::from(""),
+            recovery_codes: Default::default(),
+            is_complete: false,
+        }
+    }
+}
@@ -0,0 +1,589 @@
+use crate::DataShared;
+
+use super::private;
+use egui::Button;
+use egui_extras::{Column, TableBuilder};
+use egui_helpers::UiHelpers;
+use egui_pages::{DisplayablePage, displayable_page_common};
+use reqwest_cross::{Awaiting, DataState};
+use std::{collections::BTreeSet, ops::ControlFlow};
+use strum::IntoEnumIterator as _;
+use wykies_client_core::Client;
+use wykies_shared::{
+    const_config::path::PATH_API_ROLE_LIST,
+    internal_error_msg,
+    uac::{
+        Permission, PermissionCategory, Permissions, Role, RoleDescription, RoleDiff, RoleDraft,
+        RoleId, RoleName, get_required_permissions,
+    },
+};
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct UiRoles {
+    is_open: bool,
+    page_unique_number: usize,
+    #[serde(skip)]
+    should_refresh: bool,
+    #[serde(skip)]
+    data_state: DataState<Vec<Role>>,
+    #[serde(skip)]
+    role_op: RoleOp,
+}
+
+#[derive(Debug, Default)]
+enum RoleOp {
+    #[default]
+    None,
+    Selected(Role),
+    New(RoleInfo),
+    Edit(RoleInfo),
+    Delete(DeleteRoleInfo),
+}
+
+#[must_use]
+#[derive(Debug, PartialEq, Eq)]
+enum OpResult {
+    NoAction,
+    ResetPage,
+}
+
+#[must_use]
+enum SaveState {
+    Completed,
+    Ongoing,
+    Failed(String),
+}
+
+/// Holds the values being edited for a new or existing role. The text fields
+/// are stored as strings so invalid values can be shown with an error
+#[derive(Debug)]
+struct RoleInfo {
+    /// `None` for a new role
+    original: Option<Role>,
+    name: String,
+    description: String,
+    permissions: Permissions,
+    parent_roles: BTreeSet<RoleId>,
+    save_status: DataState<()>,
+    save_new_status: DataState<RoleId>,
+}
+
+#[derive(Debug)]
+struct DeleteRoleInfo {
+    role: Role,
+    save_status: DataState<()>,
+}
+
+impl RoleOp {
+    // Serves as a way to check if there are changes to be lost
+    fn has_changes(&self) -> bool {
+        match self {
+            RoleOp::None => false,
+            RoleOp::Selected(_) => false,
+            RoleOp::New(_) => true,
+            RoleOp::Edit(role_info) => role_info.has_changes(),
+            RoleOp::Delete(_) => true,
+        }
+    }
+
+    fn set_selected_role(&mut self, role: Option<Role>) {
+        *self = match role {
+            Some(role) => Self::Selected(role),
+            None => Self::None,
+        };
+    }
+
+    fn selected_role(&self) -> Option<&Role> {
+        match self {
+            RoleOp::Selected(role) => Some(role),
+            RoleOp::Edit(role_info) => role_info.original.as_ref(),
+            RoleOp::Delete(delete_role_info) => Some(&delete_role_info.role),
+            _ => None,
+        }
+    }
+}
+
+impl RoleInfo {
+    fn new() -> Self {
+        Self {
+            original: None,
+            name: Default::default(),
+            description: Default::default(),
+            permissions: Default::default(),
+            parent_roles: Default::default(),
+            save_status: Default::default(),
+            save_new_status: Default::default(),
+        }
+    }
+
+    fn edit(role: &Role) -> Self {
+        Self {
+            original: Some(role.clone()),
+            name: role.name.to_string(),
+            description: role.description.to_string(),
+            permissions: role.permissions.clone(),
+            parent_roles: role.parent_roles.clone(),
+            save_status: Default::default(),
+            save_new_status: Default::default(),
+        }
+    }
+
+    /// Returns if the edited role is different from the original one (Always
+    /// true for new roles)
+    fn has_changes(&self) -> bool {
+        let Some(original) = self.original.as_ref() else {
+            return true;
+        };
+        original.name.as_ref() != self.name
+            || original.description.as_ref() != self.description
+            || original.permissions != self.permissions
+            || original.parent_roles != self.parent_roles
+    }
+
+    fn try_into_draft(&self) -> anyhow::Result<RoleDraft> {
+        Ok(RoleDraft {
+            name: self.name.clone().try_into()?,
+            description: self.description.clone().try_into()?,
+            permissions: self.permissions.clone(),
+            parent_roles: self.parent_roles.clone(),
+        })
+    }
+
+    /// Initiates the save of the role to the database
+    fn save(&mut self, client_core: &Client) {
+        let draft = match self.try_into_draft() {
+            Ok(draft) => draft,
+            Err(e) => {
+                self.save_status = DataState::Failed(e.into());
+                return;
+            }
+        };
+        let Some(original) = self.original.as_ref() else {
+            self.save_new_status =
+                DataState::AwaitingResponse(Awaiting(client_core.role_new(&draft)));
+            return;
+        };
+        let edited = Role {
+            id: original.id,
+            name: draft.name,
+            description: draft.description,
+            permissions: draft.permissions,
+            parent_roles: draft.parent_roles,
+        };
+        match RoleDiff::from_diff(original, &edited) {
+            Ok(Some(diff)) => {
+                self.save_status =
+                    DataState::AwaitingResponse(Awaiting(client_core.role_update(&diff)))
+            }
+            Ok(None) => {
+                self.save_status = DataState::Failed(internal_error_msg!("No changes found").into())
+            }
+            Err(e) => self.save_status = DataState::Failed(internal_error_msg!("{e}").into()),
+        }
+    }
+
+    /// Returns None if no save is ongoing
+    fn save_outcome(&mut self) -> Option<SaveState> {
+        get_save_outcome(&mut self.save_status)
+            .or_else(|| get_save_outcome(&mut self.save_new_status))
+    }
+}
+
+impl DeleteRoleInfo {
+    fn new(role: &Role) -> Self {
+        Self {
+            role: role.clone(),
+            save_status: Default::default(),
+        }
+    }
+
+    fn save(&mut self, client_core: &Client) {
+        self.save_status =
+            DataState::AwaitingResponse(Awaiting(client_core.role_delete(self.role.id)))
+    }
+
+    /// Returns None if no save is ongoing
+    fn save_outcome(&mut self) -> Option<SaveState> {
+        get_save_outcome(&mut self.save_status)
+    }
+}
+
+impl DisplayablePage<DataShared, Permission, private::Token> for UiRoles {
+    displayable_page_common!(
+        "Roles",
+        get_required_permissions(PATH_API_ROLE_LIST.path).expect("failed to get permissions"),
+        private::Token
+    );
+
+    fn show(&mut self, ui: &mut egui::Ui, data_shared: &mut crate::DataShared) {
+        if self.should_refresh {
+            self.reset_to_default();
+        }
+        if self.data_state.is_none() {
+            self.data_state
+                .egui_start_task(ui, || data_shared.client.role_list());
+        }
+        let bottom_panel_id = self.unique_prefix_for_id("bottom");
+        if let Some(roles) = self.data_state.egui_poll_mut(ui, None) {
+            egui::Panel::bottom(bottom_panel_id).show(ui, |ui| {
+                ui.vertical_centered(|ui| {
+                    if ui_show_role_op(ui, &data_shared.client, roles, &mut self.role_op)
+                        == OpResult::ResetPage
+                    {
+                        self.should_refresh = true;
+                    };
+                });
+            });
+
+            egui::CentralPanel::default().show(ui, |ui| {
+                if self.role_op.has_changes() {
+                    // Reduce risk of accidental data loss by changing role
+                    ui.disable();
+                }
+                ui.horizontal_wrapped(|ui| {
+                    if ui.button("Refresh Page").clicked() {
+                        self.should_refresh = true;
+                        return;
+                    }
+                    if ui.button("Add New Role").clicked() {
+                        self.role_op = RoleOp::New(RoleInfo::new());
+                    }
+                });
+                ui.separator();
+                egui::ScrollArea::horizontal()
+                    .show(ui, |ui| ui_show_role_list(ui, roles, &mut self.role_op));
+            });
+        }
+    }
+}
+
+fn ui_show_role_op(
+    ui: &mut egui::Ui,
+    client_core: &Client,
+    roles: &[Role],
+    role_op: &mut RoleOp,
+) -> OpResult {
+    match role_op {
+        RoleOp::None => {
+            ui.label("[NO ROLE SELECTED]");
+            OpResult::NoAction
+        }
+        RoleOp::Selected(role) => {
+            if ui.button("Edit Role").clicked() {
+                *role_op = RoleOp::Edit(RoleInfo::edit(role));
+                return OpResult::NoAction;
+            }
+            if ui.button("Delete Role").clicked() {
+                *role_op = RoleOp::Delete(DeleteRoleInfo::new(role));
+                return OpResult::NoAction;
+            }
+            OpResult::NoAction
+        }
+        RoleOp::New(role_info) | RoleOp::Edit(role_info) => {
+            ui_show_edit_role(ui, client_core, roles, role_info)
+        }
+        RoleOp::Delete(delete_role_info) => ui_show_delete_role(ui, client_core, delete_role_info),
+    }
+}
+
+fn ui_show_delete_role(
+    ui: &mut egui::Ui,
+    client_core: &Client,
+    delete_role_info: &mut DeleteRoleInfo,
+) -> OpResult {
+    match poll_save_outcome(delete_role_info.save_outcome(), ui) {
+        ControlFlow::Continue(()) => {} // Do nothing just continue
+        ControlFlow::Break(action) => return action,
+    }
+
+    ui.label(format!(
+        "Delete the role \"{}\"? Roles still assigned to users or inherited by other roles cannot be deleted",
+        delete_role_info.role.name
+    ));
+
+    if ui.button("Delete Role").clicked() {
+        delete_role_info.save(client_core);
+    }
+
+    if ui.cancel_button() {
+        return OpResult::ResetPage;
+    }
+
+    OpResult::NoAction
+}
+
+fn ui_show_edit_role(
+    ui: &mut egui::Ui,
+    client_core: &Client,
+    roles: &[Role],
+    role_info: &mut RoleInfo,
+) -> OpResult {
+    let mut has_errors = false;
+    match poll_save_outcome(role_info.save_outcome(), ui) {
+        ControlFlow::Continue(()) => {} // Do nothing just continue
+        ControlFlow::Break(action) => return action,
+    }
+
+    let org = role_info.original.clone();
+    egui::ScrollArea::vertical()
+        .max_height(400.)
+        .show(ui, |ui| {
+            egui::Grid::new("Edit Role Grid")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Name");
+                        if let Some(org) = org.as_ref() {
+                            ui_change_indicator(ui, org.name.as_ref() != role_info.name);
+                        }
+                    });
+                    ui.text_edit_singleline(&mut role_info.name);
+                    if let Err(e) = RoleName::try_from(role_info.name.clone()) {
+                        has_errors = true;
+                        ui.error_label(e.to_string());
+                    }
+                    ui.end_row();
+
+                    //----------------------------------------------------------------------
+                    ui.horizontal(|ui| {
+                        ui.label("Description");
+                        if let Some(org) = org.as_ref() {
+                            ui_change_indicator(
+                                ui,
+                                org.description.as_ref() != role_info.description,
+                            );
+                        }
+                    });
+                    ui.text_edit_singleline(&mut role_info.description);
+                    if let Err(e) = RoleDescription::try_from(role_info.description.clone()) {
+                        has_errors = true;
+                        ui.error_label(e.to_string());
+                    }
+                    ui.end_row();
+
+                    //----------------------------------------------------------------------
+                    ui.horizontal(|ui| {
+                        ui.label("Inherits From");
+                        if let Some(org) = org.as_ref() {
+                            ui_change_indicator(ui, org.parent_roles != role_info.parent_roles);
+                        }
+                    });
+                    ui_parent_roles(
+                        ui,
+                        org.as_ref().map(|x| x.id),
+                        roles,
+                        &mut role_info.parent_roles,
+                    );
+                    ui.end_row();
+
+                    //----------------------------------------------------------------------
+                    ui.horizontal(|ui| {
+                        ui.label("Permissions");
+                        if let Some(org) = org.as_ref() {
+                            ui_change_indicator(ui, org.permissions != role_info.permissions);
+                        }
+                    });
+                    ui_permissions_checklist(
+                        ui,
+                        org.as_ref().map(|x| &x.permissions),
+                        &mut role_info.permissions,
+                    );
+                    ui.end_row();
+                });
+        });
+
+    if ui
+        .add_enabled(!has_errors && role_info.has_changes(), Button::new("Save"))
+        .clicked()
+    {
+        role_info.save(client_core);
+    }
+
+    if ui.cancel_button() {
+        return OpResult::ResetPage;
+    }
+
+    OpResult::NoAction
+}
+
+/// Shows every other role as a possible parent. Cycles are rejected by the
+/// server on save
+fn ui_parent_roles(
+    ui: &mut egui::Ui,
+    role_id: Option<RoleId>,
+    roles: &[Role],
+    edit: &mut BTreeSet<RoleId>,
+) {
+    ui.vertical(|ui| {
+        for role in roles.iter().filter(|x| Some(x.id) != role_id) {
+            let mut is_parent = edit.contains(&role.id);
+            if ui.checkbox(&mut is_parent, &role.name).changed() {
+                if is_parent {
+                    edit.insert(role.id);
+                } else {
+                    edit.remove(&role.id);
+                }
+            }
+        }
+    });
+}
+
+/// Shows a checkbox for every [`Permission`] grouped by [`PermissionCategory`]
+fn ui_permissions_checklist(ui: &mut egui::Ui, org: Option<&Permissions>, edit: &mut Permissions) {
+    ui.vertical(|ui| {
+        for category in PermissionCategory::iter() {
+            egui::CollapsingHeader::new(category.to_string())
+                .default_open(true)
+                .show(ui, |ui| {
+                    for permission in category.permissions() {
+                        let mut is_granted = edit.0.contains(&permission);
+                        ui.horizontal(|ui| {
+                            if ui
+                                .checkbox(&mut is_granted, permission.to_string())
+                                .changed()
+                            {
+                                if is_granted {
+                                    edit.0.insert(permission.clone());
+                                } else {
+                                    edit.0.remove(&permission);
+                                }
+                            }
+                            if let Some(org) = org {
+                                ui_change_indicator(ui, org.0.contains(&permission) != is_granted);
+                            }
+                        });
+                    }
+                });
+        }
+    });
+}
+
+fn ui_change_indicator(ui: &mut egui::Ui, is_changed: bool) {
+    if is_changed {
+        ui.label("*");
+    } else {
+        // Add placeholder space for indicator to avoid resizing
+        ui.label("  ");
+    }
+}
+
+fn ui_show_role_list(ui: &mut egui::Ui, roles: &[Role], role_op: &mut RoleOp) {
+    let text_height = ui.text_height();
+    let table_builder = TableBuilder::new(ui)
+        .striped(true)
+        .resizable(true)
+        .cell_layout(egui::Layout::left_to_right(egui::Align::LEFT))
+        .column(Column::auto())
+        .column(Column::auto())
+        .column(Column::auto())
+        .column(Column::auto())
+        .column(Column::remainder())
+        .min_scrolled_height(0.0)
+        .sense(egui::Sense::click());
+
+    let table = table_builder.header(text_height, |mut header| {
+        header.col(|ui| {
+            ui.strong("Selected");
+        });
+        header.col(|ui| {
+            ui.strong("Name");
+        });
+        header.col(|ui| {
+            ui.strong("Description");
+        });
+        header.col(|ui| {
+            ui.strong("Permissions");
+        });
+        header.col(|ui| {
+            ui.strong("Inherits From");
+        });
+    });
+
+    table.body(|body| {
+        body.rows(text_height, roles.len(), |mut row| {
+            let role = &roles[row.index()];
+            let is_selected_at_start = if let Some(selected) = role_op.selected_role() {
+                let is_selected = selected.id == role.id;
+                row.set_selected(is_selected);
+                is_selected
+            } else {
+                false
+            };
+            let mut is_selected_at_end = is_selected_at_start;
+            row.col(|ui| {
+                ui.vertical_centered(|ui| {
+                    ui.checkbox(&mut is_selected_at_end, "");
+                });
+            });
+            row.col(|ui| {
+                ui.label(&role.name);
+            });
+            row.col(|ui| {
+                ui.label(&role.description);
+            });
+            row.col(|ui| {
+                ui.label(role.permissions.0.len().to_string());
+            });
+            row.col(|ui| {
+                let parent_names: Vec<&str> = roles
+                    .iter()
+                    .filter(|x| role.parent_roles.contains(&x.id))
+                    .map(|x| x.name.as_ref())
+                    .collect();
+                ui.label(parent_names.join(", "));
+            });
+
+            // Check for click of a row
+            if row.response().clicked() {
+                is_selected_at_end = !is_selected_at_end;
+            }
+            match (is_selected_at_start, is_selected_at_end) {
+                (true, true) | (false, false) => {} // No change
+                (true, false) => role_op.set_selected_role(None),
+                (false, true) => role_op.set_selected_role(Some(role.clone())),
+            }
+        });
+    });
+}
+
+fn poll_save_outcome(outcome: Option<SaveState>, ui: &mut egui::Ui) -> ControlFlow<OpResult> {
+    if let Some(save_status) = outcome {
+        // Save in progress
+        match save_status {
+            SaveState::Completed => return ControlFlow::Break(OpResult::ResetPage),
+            SaveState::Ongoing => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Saving...");
+                });
+            }
+            SaveState::Failed(e) => {
+                ui.error_label(e);
+                if ui.button("Clear Error").clicked() {
+                    return ControlFlow::Break(OpResult::ResetPage);
+                }
+            }
+        }
+        ControlFlow::Break(OpResult::NoAction)
+    } else {
+        ControlFlow::Continue(())
+    }
+}
+
+fn get_save_outcome<T>(save_status: &mut DataState<T>) -> Option<SaveState> {
+    match save_status {
+        DataState::None => {
+            // No action no save ongoing
+            None
+        }
+        DataState::AwaitingResponse(rx) => {
+            if let Some(new_state) = DataState::await_data(rx) {
+                *save_status = new_state;
+            }
+            Some(SaveState::Ongoing)
+        }
+        DataState::Present(_data) => Some(SaveState::Completed),
+        DataState::Failed(e) => Some(SaveState::Failed(format!("Save failed. {e}"))),
+    }
+}
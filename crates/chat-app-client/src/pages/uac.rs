@@ -8,16 +8,18 @@ use egui_helpers::UiHelpers;
 use egui_pages::{DisplayablePage, displayable_page_common};
 use new_user_info::NewUserInfo;
 use pass_reset_user_info::PassResetUserInfo;
+use permissions_user_info::PermissionsUserInfo;
 use reqwest_cross::DataState;
 use secrecy::ExposeSecret;
-use std::ops::ControlFlow;
+use std::{collections::BTreeSet, ops::ControlFlow};
+use totp_reset_user_info::TotpResetUserInfo;
 use wykies_client_core::Client;
 use wykies_shared::{
     const_config::{error::err_role_name, path::PATH_API_USERS_LIST_AND_ROLES},
     debug_panic,
     uac::{
-        DisplayName, ListUsersRoles, Permission, RoleId, RoleName, UserMetadata, Username,
-        get_required_permissions,
+        DisplayName, EmailAddress, ListUsersRoles, Permission, RoleId, RoleName, UserMetadata,
+        Username, get_required_permissions,
     },
 };
 use wykies_time::Seconds;
@@ -25,6 +27,8 @@ use wykies_time::Seconds;
 mod edit_user_info;
 mod new_user_info;
 mod pass_reset_user_info;
+mod permissions_user_info;
+mod totp_reset_user_info;
 
 #[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
 #[serde(default)]
@@ -47,6 +51,8 @@ enum UserOp {
     New(NewUserInfo),
     Edit(EditUserInfo),
     PasswordReset(PassResetUserInfo),
+    TotpReset(TotpResetUserInfo),
+    Permissions(PermissionsUserInfo),
 }
 
 #[must_use]
@@ -72,6 +78,8 @@ impl UserOp {
             UserOp::New(_) => true,
             UserOp::Edit(edit_user_info) => edit_user_info.has_changes(),
             UserOp::PasswordReset(_) => true,
+            UserOp::TotpReset(_) => true,
+            UserOp::Permissions(_) => false,
         }
     }
 
@@ -160,16 +168,23 @@ fn ui_show_user_op(
                 *user_op = UserOp::Edit(edit_user_info);
                 return OpResult::NoAction;
             }
-            if client_core
+            if ui.button("Show Permissions").clicked() {
+                *user_op = UserOp::Permissions(PermissionsUserInfo::new(user_metadata));
+                return OpResult::NoAction;
+            }
+            let is_other_user = client_core
                 .user_info()
                 .expect("unable to get user info")
                 .username
-                != user_metadata.username
-                && ui.button("Reset Password").clicked()
-            {
+                != user_metadata.username;
+            if is_other_user && ui.button("Reset Password").clicked() {
                 *user_op = UserOp::PasswordReset(PassResetUserInfo::new(user_metadata));
                 return OpResult::NoAction;
             }
+            if is_other_user && ui.button("Reset Two-Factor Authentication").clicked() {
+                *user_op = UserOp::TotpReset(TotpResetUserInfo::new(user_metadata));
+                return OpResult::NoAction;
+            }
             OpResult::NoAction
         }
         UserOp::New(new_user_info) => ui_show_new_user(ui, client_core, data, new_user_info),
@@ -177,9 +192,54 @@ fn ui_show_user_op(
         UserOp::PasswordReset(pass_reset_user_info) => {
             ui_show_reset_password(ui, client_core, pass_reset_user_info)
         }
+        UserOp::TotpReset(totp_reset_user_info) => {
+            ui_show_reset_totp(ui, client_core, totp_reset_user_info)
+        }
+        UserOp::Permissions(permissions_user_info) => {
+            ui_show_permissions(ui, client_core, permissions_user_info)
+        }
     }
 }
 
+fn ui_show_permissions(
+    ui: &mut egui::Ui,
+    client_core: &Client,
+    permissions_user_info: &mut PermissionsUserInfo,
+) -> OpResult {
+    ui.horizontal(|ui| ui_user_username_read_only(ui, &permissions_user_info.username));
+    permissions_user_info.show(ui, client_core);
+
+    if ui.button("Close").clicked() {
+        return OpResult::ResetPage;
+    }
+
+    OpResult::NoAction
+}
+
+fn ui_show_reset_totp(
+    ui: &mut egui::Ui,
+    client_core: &Client,
+    totp_reset_user_info: &mut TotpResetUserInfo,
+) -> OpResult {
+    match poll_save_outcome(totp_reset_user_info.save_outcome(), ui) {
+        ControlFlow::Continue(()) => {} // Do nothing just continue
+        ControlFlow::Break(action) => return action,
+    }
+
+    ui.horizontal(|ui| ui_user_username_read_only(ui, &totp_reset_user_info.username));
+    ui.label("The user will need to set up two-factor authentication again if they use it");
+
+    if ui.button("Reset Two-Factor Authentication").clicked() {
+        totp_reset_user_info.save(client_core);
+    }
+
+    if ui.cancel_button() {
+        return OpResult::ResetPage;
+    }
+
+    OpResult::NoAction
+}
+
 fn ui_show_reset_password(
     ui: &mut egui::Ui,
     client_core: &Client,
@@ -264,7 +324,18 @@ fn ui_show_new_user(
             ui.end_row();
 
             //----------------------------------------------------------------------
-            ui_user_role(ui, None, &mut new_user_info.assigned_role, data);
+            ui.label("Email");
+            ui.text_edit_singleline(&mut new_user_info.email);
+            if !new_user_info.email.is_empty()
+                && let Err(e) = EmailAddress::try_from(new_user_info.email.clone())
+            {
+                has_errors = true;
+                ui.error_label(e.to_string());
+            }
+            ui.end_row();
+
+            //----------------------------------------------------------------------
+            ui_user_roles(ui, None, &mut new_user_info.assigned_roles, data);
         });
 
     if ui
@@ -323,6 +394,9 @@ fn ui_show_edit_user(
             );
             ui.end_row();
 
+            ui_user_email(ui, Some(&org_user.email), &mut edit_user.email);
+            ui.end_row();
+
             ui_user_force_pass_change(
                 ui,
                 Some(&org_user.force_pass_change),
@@ -330,10 +404,10 @@ fn ui_show_edit_user(
             );
             ui.end_row();
 
-            ui_user_role(
+            ui_user_roles(
                 ui,
-                Some(&org_user.assigned_role),
-                &mut edit_user.assigned_role,
+                Some(&org_user.assigned_roles),
+                &mut edit_user.assigned_roles,
                 data,
             );
             ui.end_row();
@@ -442,34 +516,35 @@ fn ui_user_enabled(ui: &mut egui::Ui, org: Option<&bool>, edit: &mut bool) {
     ui.checkbox(edit, "");
 }
 
-fn ui_user_role(
+/// The user gets the permissions of each selected role and of the roles those
+/// inherit from
+fn ui_user_roles(
     ui: &mut egui::Ui,
-    org: Option<&Option<RoleId>>,
-    edit: &mut Option<RoleId>,
+    org: Option<&BTreeSet<RoleId>>,
+    edit: &mut BTreeSet<RoleId>,
     data: &ListUsersRoles,
 ) {
     ui.horizontal(|ui| {
-        ui.label("Role");
+        ui.label("Roles");
         if let Some(org) = org {
             ui_change_indicator(ui, org != edit);
         }
     });
-    egui::ComboBox::from_label("")
-        .selected_text(
-            edit.map(|id| {
-                data.role_id_to_name(id).unwrap_or_else(|e| {
-                    debug_panic!("unable to find Role ID {:?}. {e:?}", edit);
-                    err_role_name()
-                })
-            })
-            .unwrap_or(RoleName::no_role_set()),
-        )
-        .show_ui(ui, |ui| {
-            ui.selectable_value(edit, None, RoleName::no_role_set());
-            for x in data.roles.iter() {
-                ui.selectable_value(edit, Some(x.id), &x.name);
+    ui.vertical(|ui| {
+        if data.roles.is_empty() {
+            ui.label(RoleName::no_role_set());
+        }
+        for role in data.roles.iter() {
+            let mut is_assigned = edit.contains(&role.id);
+            if ui.checkbox(&mut is_assigned, &role.name).changed() {
+                if is_assigned {
+                    edit.insert(role.id);
+                } else {
+                    edit.remove(&role.id);
+                }
             }
-        });
+        }
+    });
 }
 
 fn ui_user_force_pass_change(ui: &mut egui::Ui, org: Option<&bool>, edit: &mut bool) {
@@ -510,6 +585,27 @@ fn ui_user_display_name(ui: &mut egui::Ui, org: Option<&DisplayName>, edit: &mut
     }
 }
 
+/// An empty value removes the email address
+fn ui_user_email(
+    ui: &mut egui::Ui,
+    org: Option<&Option<EmailAddress>>,
+    edit: &mut Option<EmailAddress>,
+) {
+    ui.horizontal(|ui| {
+        ui.label("Email");
+        if let Some(org) = org {
+            ui_change_indicator(ui, org != edit);
+        }
+    });
+    let mut temp: String = edit.as_ref().map(|x| x.to_string()).unwrap_or_default();
+    ui.text_edit_singleline(&mut temp);
+    if temp.is_empty() {
+        *edit = None;
+    } else if let Ok(x) = temp.try_into() {
+        *edit = Some(x);
+    }
+}
+
 fn ui_show_user_list(ui: &mut egui::Ui, data: &mut ListUsersRoles, user_op: &mut UserOp) {
     let text_height = ui.text_height();
     let mut table_builder = TableBuilder::new(ui)
@@ -543,7 +639,7 @@ fn ui_show_user_list(ui: &mut egui::Ui, data: &mut ListUsersRoles, user_op: &mut
             ui.strong("Force Pass Change");
         });
         header.col(|ui| {
-            ui.strong("Role");
+            ui.strong("Roles");
         });
         header.col(|ui| {
             ui.strong("Enabled");
@@ -588,19 +684,23 @@ fn ui_show_user_list(ui: &mut egui::Ui, data: &mut ListUsersRoles, user_op: &mut
                 });
             });
             row.col(|ui| {
-                ui.label(
-                    user.assigned_role
-                        .map(|id| {
-                            data.role_id_to_name(id).unwrap_or_else(|e| {
-                                debug_panic!(
-                                    "unable to find Role ID {:?}. {e:?}",
-                                    user.assigned_role
-                                );
-                                err_role_name()
-                            })
+                if user.assigned_roles.is_empty() {
+                    ui.label(RoleName::no_role_set());
+                } else {
+                    let role_names: Vec<String> = user
+                        .assigned_roles
+                        .iter()
+                        .map(|&id| {
+                            data.role_id_to_name(id)
+                                .unwrap_or_else(|e| {
+                                    debug_panic!("unable to find Role ID {id:?}. {e:?}");
+                                    err_role_name()
+                                })
+                                .to_string()
                         })
-                        .unwrap_or(RoleName::no_role_set()),
-                );
+                        .collect();
+                    ui.label(role_names.join(", "));
+                }
             });
             row.col(|ui| {
                 ui.vertical_centered(|ui| {
@@ -1,7 +1,11 @@
 use reqwest_cross::{Awaiting, DataState};
 use secrecy::SecretString;
+use std::collections::BTreeSet;
 use wykies_client_core::Client;
-use wykies_shared::{req_args::api::user::NewUserReqArgs, uac::RoleId};
+use wykies_shared::{
+    req_args::api::user::NewUserReqArgs,
+    uac::{EmailAddress, RoleId},
+};
 
 use super::SaveState;
 
@@ -10,7 +14,9 @@ pub struct NewUserInfo {
     pub username: String,
     pub display_name: String,
     pub password: SecretString,
-    pub assigned_role: Option<RoleId>,
+    pub assigned_roles: BTreeSet<RoleId>,
+    /// Empty if the user should not have an email address
+    pub email: String,
     save_status: DataState<()>,
 }
 
@@ -20,7 +26,8 @@ impl NewUserInfo {
             username: Default::default(),
             display_name: Default::default(),
             password: "".to_string().into(),
-            assigned_role: Default::default(),
+            assigned_roles: Default::default(),
+            email: Default::default(),
             save_status: Default::default(),
         }
     }
@@ -58,13 +65,19 @@ impl NewUserInfo {
         let username = self.username.clone().try_into()?;
         let display_name = self.display_name.clone().try_into()?;
         let password = self.password.clone();
-        let assigned_role = self.assigned_role;
+        let assigned_roles = self.assigned_roles.clone();
+        let email = if self.email.is_empty() {
+            None
+        } else {
+            Some(EmailAddress::try_from(self.email.clone())?)
+        };
 
         Ok(NewUserReqArgs {
             username,
             display_name,
             password,
-            assigned_role,
+            assigned_roles,
+            email,
         })
     }
 }
@@ -0,0 +1,58 @@
+use reqwest_cross::DataState;
+use strum::IntoEnumIterator as _;
+use wykies_client_core::Client;
+use wykies_shared::uac::{Permission, UserMetadata, UserPermissions, Username};
+
+/// Shows which permissions a user has directly from their assigned roles and
+/// which they only have through inheritance
+#[derive(Debug)]
+pub struct PermissionsUserInfo {
+    pub username: Username,
+    permissions: DataState<UserPermissions>,
+}
+
+impl PermissionsUserInfo {
+    pub fn new(user: &UserMetadata) -> Self {
+        Self {
+            username: user.username.clone(),
+            permissions: Default::default(),
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, client_core: &Client) {
+        if self.permissions.is_none() {
+            self.permissions
+                .egui_start_task(ui, || client_core.user_permissions(self.username.clone()));
+        }
+        let Some(permissions) = self.permissions.egui_poll(ui, Some("Retry")) else {
+            return;
+        };
+        if permissions.effective.0.is_empty() {
+            ui.label("User has no permissions");
+            return;
+        }
+        egui::ScrollArea::vertical()
+            .max_height(200.)
+            .show(ui, |ui| {
+                egui::Grid::new("User permissions grid")
+                    .num_columns(2)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Permission");
+                        ui.strong("Source");
+                        ui.end_row();
+                        for permission in
+                            Permission::iter().filter(|x| permissions.effective.0.contains(x))
+                        {
+                            ui.label(permission.to_string());
+                            ui.label(if permissions.direct.0.contains(&permission) {
+                                "Direct"
+                            } else {
+                                "Inherited"
+                            });
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+}
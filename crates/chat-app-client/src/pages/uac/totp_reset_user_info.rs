@@ -0,0 +1,31 @@
+use reqwest_cross::{Awaiting, DataState};
+use wykies_client_core::Client;
+use wykies_shared::uac::{UserMetadata, Username};
+
+use super::{SaveState, get_save_outcome};
+
+#[derive(Debug)]
+pub struct TotpResetUserInfo {
+    pub username: Username,
+    save_status: DataState<()>,
+}
+
+impl TotpResetUserInfo {
+    pub fn new(user: &UserMetadata) -> Self {
+        Self {
+            username: user.username.clone(),
+            save_status: Default::default(),
+        }
+    }
+
+    /// Returns None if no save is ongoing
+    pub fn save_outcome(&mut self) -> Option<SaveState> {
+        get_save_outcome(&mut self.save_status)
+    }
+
+    pub fn save(&mut self, client_core: &Client) {
+        self.save_status = DataState::AwaitingResponse(Awaiting(
+            client_core.user_totp_reset(self.username.clone()),
+        ))
+    }
+}
@@ -0,0 +1,33 @@
+START TRANSACTION;
+-- --------------------------------------------------------
+--
+-- Table structure for table `sessions`
+--
+
+CREATE TABLE `sessions` (
+    `SessionID` int(11) NOT NULL,
+    `SessionKey` char(64) NOT NULL,
+    `UserName` varchar(16) DEFAULT NULL,
+    `SessionState` TEXT NOT NULL,
+    `Created` INT(11) UNSIGNED NOT NULL,
+    `Expires` INT(11) UNSIGNED NOT NULL
+) ENGINE = InnoDB DEFAULT CHARSET = latin1;
+--
+-- Indexes for table `sessions`
+--
+ALTER TABLE `sessions`
+ADD PRIMARY KEY (`SessionID`),
+    ADD UNIQUE KEY `SessionKey` (`SessionKey`),
+    ADD KEY `UserName` (`UserName`),
+    ADD KEY `Expires` (`Expires`);
+--
+-- AUTO_INCREMENT for table `sessions`
+--
+ALTER TABLE `sessions`
+MODIFY `SessionID` int(11) NOT NULL AUTO_INCREMENT;
+--
+-- Constraints for table `sessions`
+--
+ALTER TABLE `sessions`
+ADD CONSTRAINT `sessions_ibfk_1` FOREIGN KEY (`UserName`) REFERENCES `user` (`UserName`) ON DELETE CASCADE;
+COMMIT;
\ No newline at end of file
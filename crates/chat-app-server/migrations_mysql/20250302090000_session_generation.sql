@@ -0,0 +1,3 @@
+ALTER TABLE `user`
+ADD `SessionGeneration` INT(11) NOT NULL DEFAULT 0
+AFTER `LockedOut`;
\ No newline at end of file
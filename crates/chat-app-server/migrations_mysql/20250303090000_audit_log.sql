@@ -0,0 +1,30 @@
+START TRANSACTION;
+-- --------------------------------------------------------
+--
+-- Table structure for table `audit_log`
+--
+-- No foreign keys so that entries are kept even if the user is removed
+
+CREATE TABLE `audit_log` (
+    `AuditID` int(11) NOT NULL,
+    `EventTime` INT(11) UNSIGNED NOT NULL,
+    `Actor` varchar(16) NOT NULL,
+    `Action` varchar(32) NOT NULL,
+    `Target` varchar(255) NOT NULL,
+    `Details` TEXT NOT NULL,
+    `HostID` varchar(50) NOT NULL
+) ENGINE = InnoDB DEFAULT CHARSET = latin1;
+--
+-- Indexes for table `audit_log`
+--
+ALTER TABLE `audit_log`
+ADD PRIMARY KEY (`AuditID`),
+    ADD KEY `EventTime` (`EventTime`),
+    ADD KEY `Actor` (`Actor`),
+    ADD KEY `Target` (`Target`);
+--
+-- AUTO_INCREMENT for table `audit_log`
+--
+ALTER TABLE `audit_log`
+MODIFY `AuditID` int(11) NOT NULL AUTO_INCREMENT;
+COMMIT;
\ No newline at end of file
@@ -0,0 +1,36 @@
+-- Adds the ViewAnalytics permission (appended so existing positions are unchanged)
+UPDATE `roles`
+SET `Permissions` = CONCAT(
+        `Permissions`,
+        CASE
+            WHEN `Name` = 'SeedAdmin' THEN '1'
+            ELSE '0'
+        END
+    )
+WHERE CHAR_LENGTH(`Permissions`) = 35;
+-- --------------------------------------------------------
+--
+-- Table structure for table `analytics`
+--
+
+CREATE TABLE `analytics` (
+    `RequestID` bigint(20) NOT NULL,
+    `EventTime` INT(11) UNSIGNED NOT NULL,
+    `Path` varchar(255) NOT NULL,
+    `Method` varchar(10) NOT NULL,
+    `Status` SMALLINT(5) UNSIGNED NOT NULL,
+    `LatencyMs` INT(11) UNSIGNED NOT NULL,
+    `UserName` varchar(16) DEFAULT NULL,
+    `BranchID` int(11) DEFAULT NULL
+) ENGINE = InnoDB DEFAULT CHARSET = latin1;
+--
+-- Indexes for table `analytics`
+--
+ALTER TABLE `analytics`
+ADD PRIMARY KEY (`RequestID`),
+    ADD KEY `EventTime` (`EventTime`);
+--
+-- AUTO_INCREMENT for table `analytics`
+--
+ALTER TABLE `analytics`
+MODIFY `RequestID` bigint(20) NOT NULL AUTO_INCREMENT;
\ No newline at end of file
@@ -0,0 +1,31 @@
+START TRANSACTION;
+ALTER TABLE `user`
+ADD `TotpSecret` varchar(64) DEFAULT NULL AFTER `SessionGeneration`,
+    ADD `TotpEnabled` tinyint(1) NOT NULL DEFAULT 0 AFTER `TotpSecret`;
+-- --------------------------------------------------------
+--
+-- Table structure for table `totp_recovery_codes`
+--
+
+CREATE TABLE `totp_recovery_codes` (
+    `RecoveryCodeID` int(11) NOT NULL,
+    `UserName` varchar(16) NOT NULL,
+    `CodeHash` varchar(255) NOT NULL
+) ENGINE = InnoDB DEFAULT CHARSET = latin1;
+--
+-- Indexes for table `totp_recovery_codes`
+--
+ALTER TABLE `totp_recovery_codes`
+ADD PRIMARY KEY (`RecoveryCodeID`),
+    ADD KEY `UserName` (`UserName`);
+--
+-- AUTO_INCREMENT for table `totp_recovery_codes`
+--
+ALTER TABLE `totp_recovery_codes`
+MODIFY `RecoveryCodeID` int(11) NOT NULL AUTO_INCREMENT;
+--
+-- Constraints for table `totp_recovery_codes`
+--
+ALTER TABLE `totp_recovery_codes`
+ADD CONSTRAINT `totp_recovery_codes_ibfk_1` FOREIGN KEY (`UserName`) REFERENCES `user` (`UserName`) ON DELETE CASCADE;
+COMMIT;
\ No newline at end of file
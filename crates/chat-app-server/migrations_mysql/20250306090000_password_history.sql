@@ -0,0 +1,28 @@
+START TRANSACTION;
+-- --------------------------------------------------------
+--
+-- Table structure for table `password_history`
+--
+
+CREATE TABLE `password_history` (
+    `PasswordHistoryID` int(11) NOT NULL,
+    `UserName` varchar(16) NOT NULL,
+    `PasswordHash` TEXT NOT NULL
+) ENGINE = InnoDB DEFAULT CHARSET = latin1;
+--
+-- Indexes for table `password_history`
+--
+ALTER TABLE `password_history`
+ADD PRIMARY KEY (`PasswordHistoryID`),
+    ADD KEY `UserName` (`UserName`);
+--
+-- AUTO_INCREMENT for table `password_history`
+--
+ALTER TABLE `password_history`
+MODIFY `PasswordHistoryID` int(11) NOT NULL AUTO_INCREMENT;
+--
+-- Constraints for table `password_history`
+--
+ALTER TABLE `password_history`
+ADD CONSTRAINT `password_history_ibfk_1` FOREIGN KEY (`UserName`) REFERENCES `user` (`UserName`) ON DELETE CASCADE;
+COMMIT;
\ No newline at end of file
@@ -0,0 +1,5 @@
+START TRANSACTION;
+ALTER TABLE `user`
+ADD `LockedOutAt` int(11) UNSIGNED DEFAULT NULL AFTER `LockedOut`,
+    ADD `LastFailedAttempt` int(11) UNSIGNED DEFAULT NULL AFTER `FailedAttempts`;
+COMMIT;
\ No newline at end of file
@@ -0,0 +1,31 @@
+START TRANSACTION;
+ALTER TABLE `user`
+ADD `Email` varchar(254) DEFAULT NULL AFTER `DisplayName`;
+-- --------------------------------------------------------
+--
+-- Table structure for table `password_reset_tokens`
+--
+
+CREATE TABLE `password_reset_tokens` (
+    `PasswordResetTokenID` int(11) NOT NULL,
+    `UserName` varchar(16) NOT NULL,
+    `TokenHash` varchar(255) NOT NULL,
+    `Expires` int(11) UNSIGNED NOT NULL
+) ENGINE = InnoDB DEFAULT CHARSET = latin1;
+--
+-- Indexes for table `password_reset_tokens`
+--
+ALTER TABLE `password_reset_tokens`
+ADD PRIMARY KEY (`PasswordResetTokenID`),
+    ADD KEY `UserName` (`UserName`);
+--
+-- AUTO_INCREMENT for table `password_reset_tokens`
+--
+ALTER TABLE `password_reset_tokens`
+MODIFY `PasswordResetTokenID` int(11) NOT NULL AUTO_INCREMENT;
+--
+-- Constraints for table `password_reset_tokens`
+--
+ALTER TABLE `password_reset_tokens`
+ADD CONSTRAINT `password_reset_tokens_ibfk_1` FOREIGN KEY (`UserName`) REFERENCES `user` (`UserName`) ON DELETE CASCADE;
+COMMIT;
\ No newline at end of file
@@ -0,0 +1,34 @@
+START TRANSACTION;
+-- --------------------------------------------------------
+--
+-- Table structure for table `api_keys`
+--
+
+CREATE TABLE `api_keys` (
+    `ApiKeyID` int(11) NOT NULL,
+    `Name` varchar(16) NOT NULL,
+    `KeyHash` varchar(255) NOT NULL,
+    `Permissions` varchar(256) NOT NULL,
+    `BranchID` int(11) NOT NULL,
+    `Created` int(11) UNSIGNED NOT NULL,
+    `Expires` int(11) UNSIGNED DEFAULT NULL,
+    `LastUsed` int(11) UNSIGNED DEFAULT NULL,
+    `Revoked` tinyint(1) NOT NULL DEFAULT '0'
+) ENGINE = InnoDB DEFAULT CHARSET = latin1;
+--
+-- Indexes for table `api_keys`
+--
+ALTER TABLE `api_keys`
+ADD PRIMARY KEY (`ApiKeyID`),
+    ADD KEY `BranchID` (`BranchID`);
+--
+-- AUTO_INCREMENT for table `api_keys`
+--
+ALTER TABLE `api_keys`
+MODIFY `ApiKeyID` int(11) NOT NULL AUTO_INCREMENT;
+--
+-- Constraints for table `api_keys`
+--
+ALTER TABLE `api_keys`
+ADD CONSTRAINT `api_keys_ibfk_1` FOREIGN KEY (`BranchID`) REFERENCES `branch` (`BranchID`);
+COMMIT;
\ No newline at end of file
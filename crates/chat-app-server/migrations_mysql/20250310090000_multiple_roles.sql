@@ -0,0 +1,54 @@
+START TRANSACTION;
+-- --------------------------------------------------------
+--
+-- Table structure for table `user_roles`
+--
+
+CREATE TABLE `user_roles` (
+    `UserName` varchar(16) NOT NULL,
+    `RoleID` int(11) NOT NULL
+) ENGINE = InnoDB DEFAULT CHARSET = latin1;
+-- --------------------------------------------------------
+--
+-- Table structure for table `role_parents`
+--
+
+CREATE TABLE `role_parents` (
+    `RoleID` int(11) NOT NULL,
+    `ParentRoleID` int(11) NOT NULL
+) ENGINE = InnoDB DEFAULT CHARSET = latin1;
+--
+-- Indexes for table `user_roles`
+--
+ALTER TABLE `user_roles`
+ADD PRIMARY KEY (`UserName`, `RoleID`),
+    ADD KEY `RoleID` (`RoleID`);
+--
+-- Indexes for table `role_parents`
+--
+ALTER TABLE `role_parents`
+ADD PRIMARY KEY (`RoleID`, `ParentRoleID`),
+    ADD KEY `ParentRoleID` (`ParentRoleID`);
+--
+-- Constraints for table `user_roles`
+--
+ALTER TABLE `user_roles`
+ADD CONSTRAINT `user_roles_ibfk_1` FOREIGN KEY (`UserName`) REFERENCES `user` (`UserName`) ON DELETE CASCADE,
+    ADD CONSTRAINT `user_roles_ibfk_2` FOREIGN KEY (`RoleID`) REFERENCES `roles` (`RoleID`);
+--
+-- Constraints for table `role_parents`
+--
+ALTER TABLE `role_parents`
+ADD CONSTRAINT `role_parents_ibfk_1` FOREIGN KEY (`RoleID`) REFERENCES `roles` (`RoleID`) ON DELETE CASCADE,
+    ADD CONSTRAINT `role_parents_ibfk_2` FOREIGN KEY (`ParentRoleID`) REFERENCES `roles` (`RoleID`);
+--
+-- Convert the single role previously assigned to each user
+--
+INSERT INTO `user_roles` (`UserName`, `RoleID`)
+SELECT `UserName`,
+    `AssignedRole`
+FROM `user`
+WHERE `AssignedRole` IS NOT NULL;
+ALTER TABLE `user` DROP FOREIGN KEY `user_ibfk_1`;
+ALTER TABLE `user` DROP COLUMN `AssignedRole`;
+COMMIT;
\ No newline at end of file
@@ -0,0 +1,99 @@
+START TRANSACTION;
+-- Permissions are stored as a JSON list of the names of the permissions
+-- instead of one '0' or '1' per permission in enum order. The server can still
+-- read the old format so values that are not converted keep working
+ALTER TABLE `roles`
+MODIFY `Permissions` text NOT NULL;
+ALTER TABLE `api_keys`
+MODIFY `Permissions` text NOT NULL;
+UPDATE `roles`
+SET `Permissions` = CONCAT(
+        '[',
+        CONCAT_WS(
+            ',',
+            IF(SUBSTRING(`Permissions`, 1, 1) = '1', '"RecordManualTransaction"', NULL),
+            IF(SUBSTRING(`Permissions`, 2, 1) = '1', '"RecordDiscrepancy"', NULL),
+            IF(SUBSTRING(`Permissions`, 3, 1) = '1', '"TransferRequest"', NULL),
+            IF(SUBSTRING(`Permissions`, 4, 1) = '1', '"TransferTo"', NULL),
+            IF(SUBSTRING(`Permissions`, 5, 1) = '1', '"TransferFrom"', NULL),
+            IF(SUBSTRING(`Permissions`, 6, 1) = '1', '"TransferView"', NULL),
+            IF(SUBSTRING(`Permissions`, 7, 1) = '1', '"TransferAny"', NULL),
+            IF(SUBSTRING(`Permissions`, 8, 1) = '1', '"TransferRemove"', NULL),
+            IF(SUBSTRING(`Permissions`, 9, 1) = '1', '"CustomsEntries"', NULL),
+            IF(SUBSTRING(`Permissions`, 10, 1) = '1', '"ViewShipmentManifest"', NULL),
+            IF(SUBSTRING(`Permissions`, 11, 1) = '1', '"ChangePass"', NULL),
+            IF(SUBSTRING(`Permissions`, 12, 1) = '1', '"ImportData"', NULL),
+            IF(SUBSTRING(`Permissions`, 13, 1) = '1', '"ViewLog"', NULL),
+            IF(SUBSTRING(`Permissions`, 14, 1) = '1', '"ViewStockInfo"', NULL),
+            IF(SUBSTRING(`Permissions`, 15, 1) = '1', '"RunReports"', NULL),
+            IF(SUBSTRING(`Permissions`, 16, 1) = '1', '"Settings"', NULL),
+            IF(SUBSTRING(`Permissions`, 17, 1) = '1', '"NonCurrentDate"', NULL),
+            IF(SUBSTRING(`Permissions`, 18, 1) = '1', '"GrantOverrideLocal"', NULL),
+            IF(SUBSTRING(`Permissions`, 19, 1) = '1', '"GrantOverrideRemote"', NULL),
+            IF(SUBSTRING(`Permissions`, 20, 1) = '1', '"ManBranches"', NULL),
+            IF(SUBSTRING(`Permissions`, 21, 1) = '1', '"ManClasses"', NULL),
+            IF(SUBSTRING(`Permissions`, 22, 1) = '1', '"ManHostBranchAssignment"', NULL),
+            IF(SUBSTRING(`Permissions`, 23, 1) = '1', '"ManLines"', NULL),
+            IF(SUBSTRING(`Permissions`, 24, 1) = '1', '"ManMenu"', NULL),
+            IF(SUBSTRING(`Permissions`, 25, 1) = '1', '"ManMinMax"', NULL),
+            IF(SUBSTRING(`Permissions`, 26, 1) = '1', '"ManResetLocks"', NULL),
+            IF(SUBSTRING(`Permissions`, 27, 1) = '1', '"ManRoles"', NULL),
+            IF(SUBSTRING(`Permissions`, 28, 1) = '1', '"ManSpareParts"', NULL),
+            IF(SUBSTRING(`Permissions`, 29, 1) = '1', '"ManSuppliers"', NULL),
+            IF(SUBSTRING(`Permissions`, 30, 1) = '1', '"ManSupplierInvoices"', NULL),
+            IF(SUBSTRING(`Permissions`, 31, 1) = '1', '"ManUAC"', NULL),
+            IF(SUBSTRING(`Permissions`, 32, 1) = '1', '"ImportStockLevelCountBatch"', NULL),
+            IF(SUBSTRING(`Permissions`, 33, 1) = '1', '"ImportTamsSync"', NULL),
+            IF(SUBSTRING(`Permissions`, 34, 1) = '1', '"ImportSales"', NULL),
+            IF(SUBSTRING(`Permissions`, 35, 1) = '1', '"ImportReceiving"', NULL),
+            IF(SUBSTRING(`Permissions`, 36, 1) = '1', '"ViewAnalytics"', NULL)
+        ),
+        ']'
+    )
+WHERE CHAR_LENGTH(`Permissions`) = 36;
+UPDATE `api_keys`
+SET `Permissions` = CONCAT(
+        '[',
+        CONCAT_WS(
+            ',',
+            IF(SUBSTRING(`Permissions`, 1, 1) = '1', '"RecordManualTransaction"', NULL),
+            IF(SUBSTRING(`Permissions`, 2, 1) = '1', '"RecordDiscrepancy"', NULL),
+            IF(SUBSTRING(`Permissions`, 3, 1) = '1', '"TransferRequest"', NULL),
+            IF(SUBSTRING(`Permissions`, 4, 1) = '1', '"TransferTo"', NULL),
+            IF(SUBSTRING(`Permissions`, 5, 1) = '1', '"TransferFrom"', NULL),
+            IF(SUBSTRING(`Permissions`, 6, 1) = '1', '"TransferView"', NULL),
+            IF(SUBSTRING(`Permissions`, 7, 1) = '1', '"TransferAny"', NULL),
+            IF(SUBSTRING(`Permissions`, 8, 1) = '1', '"TransferRemove"', NULL),
+            IF(SUBSTRING(`Permissions`, 9, 1) = '1', '"CustomsEntries"', NULL),
+            IF(SUBSTRING(`Permissions`, 10, 1) = '1', '"ViewShipmentManifest"', NULL),
+            IF(SUBSTRING(`Permissions`, 11, 1) = '1', '"ChangePass"', NULL),
+            IF(SUBSTRING(`Permissions`, 12, 1) = '1', '"ImportData"', NULL),
+            IF(SUBSTRING(`Permissions`, 13, 1) = '1', '"ViewLog"', NULL),
+            IF(SUBSTRING(`Permissions`, 14, 1) = '1', '"ViewStockInfo"', NULL),
+            IF(SUBSTRING(`Permissions`, 15, 1) = '1', '"RunReports"', NULL),
+            IF(SUBSTRING(`Permissions`, 16, 1) = '1', '"Settings"', NULL),
+            IF(SUBSTRING(`Permissions`, 17, 1) = '1', '"NonCurrentDate"', NULL),
+            IF(SUBSTRING(`Permissions`, 18, 1) = '1', '"GrantOverrideLocal"', NULL),
+            IF(SUBSTRING(`Permissions`, 19, 1) = '1', '"GrantOverrideRemote"', NULL),
+            IF(SUBSTRING(`Permissions`, 20, 1) = '1', '"ManBranches"', NULL),
+            IF(SUBSTRING(`Permissions`, 21, 1) = '1', '"ManClasses"', NULL),
+            IF(SUBSTRING(`Permissions`, 22, 1) = '1', '"ManHostBranchAssignment"', NULL),
+            IF(SUBSTRING(`Permissions`, 23, 1) = '1', '"ManLines"', NULL),
+            IF(SUBSTRING(`Permissions`, 24, 1) = '1', '"ManMenu"', NULL),
+            IF(SUBSTRING(`Permissions`, 25, 1) = '1', '"ManMinMax"', NULL),
+            IF(SUBSTRING(`Permissions`, 26, 1) = '1', '"ManResetLocks"', NULL),
+            IF(SUBSTRING(`Permissions`, 27, 1) = '1', '"ManRoles"', NULL),
+            IF(SUBSTRING(`Permissions`, 28, 1) = '1', '"ManSpareParts"', NULL),
+            IF(SUBSTRING(`Permissions`, 29, 1) = '1', '"ManSuppliers"', NULL),
+            IF(SUBSTRING(`Permissions`, 30, 1) = '1', '"ManSupplierInvoices"', NULL),
+            IF(SUBSTRING(`Permissions`, 31, 1) = '1', '"ManUAC"', NULL),
+            IF(SUBSTRING(`Permissions`, 32, 1) = '1', '"ImportStockLevelCountBatch"', NULL),
+            IF(SUBSTRING(`Permissions`, 33, 1) = '1', '"ImportTamsSync"', NULL),
+            IF(SUBSTRING(`Permissions`, 34, 1) = '1', '"ImportSales"', NULL),
+            IF(SUBSTRING(`Permissions`, 35, 1) = '1', '"ImportReceiving"', NULL),
+            IF(SUBSTRING(`Permissions`, 36, 1) = '1', '"ViewAnalytics"', NULL)
+        ),
+        ']'
+    )
+WHERE CHAR_LENGTH(`Permissions`) = 36;
+COMMIT;
\ No newline at end of file
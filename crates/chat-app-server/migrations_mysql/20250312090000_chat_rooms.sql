@@ -0,0 +1,45 @@
+START TRANSACTION;
+-- --------------------------------------------------------
+--
+-- Table structure for table `chat_room`
+--
+
+CREATE TABLE `chat_room` (
+    `RoomName` varchar(32) NOT NULL,
+    `VisiblePermission` varchar(50) DEFAULT NULL,
+    `VisibleBranch` int(11) DEFAULT NULL
+) ENGINE = InnoDB DEFAULT CHARSET = latin1;
+--
+-- Create the default room
+--
+INSERT INTO `chat_room` (`RoomName`)
+VALUES ('general');
+--
+-- Indexes for table `chat_room`
+--
+ALTER TABLE `chat_room`
+ADD PRIMARY KEY (`RoomName`),
+    ADD KEY `VisibleBranch` (`VisibleBranch`);
+--
+-- Constraints for table `chat_room`
+--
+ALTER TABLE `chat_room`
+ADD CONSTRAINT `chat_room_ibfk_1` FOREIGN KEY (`VisibleBranch`) REFERENCES `branch` (`BranchID`);
+-- --------------------------------------------------------
+--
+-- Add room to table `chat` (existing messages are moved into the default room)
+--
+ALTER TABLE `chat`
+ADD `Room` varchar(32) NOT NULL DEFAULT 'general'
+AFTER `ChatID`;
+--
+-- Indexes for table `chat`
+--
+ALTER TABLE `chat`
+ADD KEY `RoomTimestamp` (`Room`, `Timestamp`);
+--
+-- Constraints for table `chat`
+--
+ALTER TABLE `chat`
+ADD CONSTRAINT `chat_ibfk_2` FOREIGN KEY (`Room`) REFERENCES `chat_room` (`RoomName`);
+COMMIT;
\ No newline at end of file
@@ -0,0 +1,21 @@
+START TRANSACTION;
+-- --------------------------------------------------------
+--
+-- Add recipient to table `chat` (Room is NULL for direct messages and
+-- Recipient is NULL for messages sent to a room)
+--
+ALTER TABLE `chat`
+MODIFY `Room` varchar(32) DEFAULT 'general',
+    ADD `Recipient` varchar(16) DEFAULT NULL
+AFTER `Author`;
+--
+-- Indexes for table `chat`
+--
+ALTER TABLE `chat`
+ADD KEY `RecipientTimestamp` (`Recipient`, `Timestamp`);
+--
+-- Constraints for table `chat`
+--
+ALTER TABLE `chat`
+ADD CONSTRAINT `chat_ibfk_3` FOREIGN KEY (`Recipient`) REFERENCES `user` (`UserName`);
+COMMIT;
\ No newline at end of file
@@ -0,0 +1,10 @@
+START TRANSACTION;
+-- --------------------------------------------------------
+--
+-- Add edit and delete flags to table `chat` (IDs are now assigned by the chat
+-- server when the IM is sent so `ChatID` is always provided on insert)
+--
+ALTER TABLE `chat`
+ADD `Edited` tinyint(1) NOT NULL DEFAULT '0',
+    ADD `Deleted` tinyint(1) NOT NULL DEFAULT '0';
+COMMIT;
\ No newline at end of file
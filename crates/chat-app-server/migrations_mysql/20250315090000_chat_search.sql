@@ -0,0 +1,16 @@
+START TRANSACTION;
+-- --------------------------------------------------------
+--
+-- Store `Content` of table `chat` as text so it can be full-text indexed
+-- (It was stored as zero padded binary)
+--
+ALTER TABLE `chat`
+MODIFY `Content` varchar(255) CHARACTER SET utf8mb4 COLLATE utf8mb4_unicode_ci NOT NULL;
+UPDATE `chat`
+SET `Content` = TRIM(TRAILING '\0' FROM `Content`);
+--
+-- Indexes for table `chat`
+--
+ALTER TABLE `chat`
+ADD FULLTEXT KEY `ContentSearch` (`Content`);
+COMMIT;
\ No newline at end of file
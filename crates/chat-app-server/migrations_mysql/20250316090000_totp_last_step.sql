@@ -0,0 +1,8 @@
+START TRANSACTION;
+--
+-- Time step of the last two-factor authentication code accepted for the user
+-- so that codes cannot be used again
+--
+ALTER TABLE `user`
+ADD `TotpLastStep` bigint(20) DEFAULT NULL AFTER `TotpEnabled`;
+COMMIT;
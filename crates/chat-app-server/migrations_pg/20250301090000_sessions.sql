@@ -0,0 +1,27 @@
+-- --------------------------------------------------------
+--
+-- Table structure for table sessions
+--
+
+CREATE TABLE sessions (
+    session_id serial NOT NULL,
+    session_key char(64) NOT NULL,
+    user_name varchar(16) DEFAULT NULL,
+    session_state text NOT NULL,
+    created bigint NOT NULL,
+    expires bigint NOT NULL
+);
+--
+-- Indexes for table sessions
+--
+ALTER TABLE sessions
+ADD PRIMARY KEY (session_id);
+ALTER TABLE sessions
+ADD UNIQUE (session_key);
+CREATE INDEX ON sessions (user_name);
+CREATE INDEX ON sessions (expires);
+--
+-- Constraints for table sessions
+--
+ALTER TABLE sessions
+ADD CONSTRAINT sessions_ibfk_1 FOREIGN KEY (user_name) REFERENCES users (user_name) ON DELETE CASCADE;
@@ -0,0 +1,2 @@
+ALTER TABLE users
+ADD session_generation INTEGER NOT NULL DEFAULT 0;
\ No newline at end of file
@@ -0,0 +1,23 @@
+-- --------------------------------------------------------
+--
+-- Table structure for table audit_log
+--
+-- No foreign keys so that entries are kept even if the user is removed
+
+CREATE TABLE audit_log (
+    audit_id serial NOT NULL,
+    event_time bigint NOT NULL,
+    actor varchar(16) NOT NULL,
+    action varchar(32) NOT NULL,
+    target varchar(255) NOT NULL,
+    details text NOT NULL,
+    host_id varchar(50) NOT NULL
+);
+--
+-- Indexes for table audit_log
+--
+ALTER TABLE audit_log
+ADD PRIMARY KEY (audit_id);
+CREATE INDEX ON audit_log (event_time);
+CREATE INDEX ON audit_log (actor);
+CREATE INDEX ON audit_log (target);
@@ -0,0 +1,28 @@
+-- Adds the ViewAnalytics permission (appended so existing positions are unchanged)
+UPDATE roles
+SET permissions = permissions || CASE
+        WHEN role_name = 'SeedAdmin' THEN '1'
+        ELSE '0'
+    END
+WHERE CHAR_LENGTH(permissions) = 35;
+-- --------------------------------------------------------
+--
+-- Table structure for table analytics
+--
+
+CREATE TABLE analytics (
+    request_id bigserial NOT NULL,
+    event_time bigint NOT NULL,
+    path varchar(255) NOT NULL,
+    method varchar(10) NOT NULL,
+    status integer NOT NULL,
+    latency_ms integer NOT NULL,
+    user_name varchar(16) DEFAULT NULL,
+    branch_id integer DEFAULT NULL
+);
+--
+-- Indexes for table analytics
+--
+ALTER TABLE analytics
+ADD PRIMARY KEY (request_id);
+CREATE INDEX ON analytics (event_time);
@@ -0,0 +1,24 @@
+ALTER TABLE users
+ADD totp_secret varchar(64) DEFAULT NULL,
+ADD totp_enabled boolean NOT NULL DEFAULT false;
+-- --------------------------------------------------------
+--
+-- Table structure for table totp_recovery_codes
+--
+
+CREATE TABLE totp_recovery_codes (
+    recovery_code_id serial NOT NULL,
+    user_name varchar(16) NOT NULL,
+    code_hash varchar(255) NOT NULL
+);
+--
+-- Indexes for table totp_recovery_codes
+--
+ALTER TABLE totp_recovery_codes
+ADD PRIMARY KEY (recovery_code_id);
+CREATE INDEX ON totp_recovery_codes (user_name);
+--
+-- Constraints for table totp_recovery_codes
+--
+ALTER TABLE totp_recovery_codes
+ADD CONSTRAINT totp_recovery_codes_ibfk_1 FOREIGN KEY (user_name) REFERENCES users (user_name) ON DELETE CASCADE;
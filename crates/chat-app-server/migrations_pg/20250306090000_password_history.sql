@@ -0,0 +1,21 @@
+-- --------------------------------------------------------
+--
+-- Table structure for table password_history
+--
+
+CREATE TABLE password_history (
+    password_history_id serial NOT NULL,
+    user_name varchar(16) NOT NULL,
+    password_hash text NOT NULL
+);
+--
+-- Indexes for table password_history
+--
+ALTER TABLE password_history
+ADD PRIMARY KEY (password_history_id);
+CREATE INDEX ON password_history (user_name);
+--
+-- Constraints for table password_history
+--
+ALTER TABLE password_history
+ADD CONSTRAINT password_history_ibfk_1 FOREIGN KEY (user_name) REFERENCES users (user_name) ON DELETE CASCADE;
@@ -0,0 +1,3 @@
+ALTER TABLE users
+ADD locked_out_at bigint DEFAULT NULL,
+ADD last_failed_attempt bigint DEFAULT NULL;
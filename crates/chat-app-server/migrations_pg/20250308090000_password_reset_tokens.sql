@@ -0,0 +1,24 @@
+ALTER TABLE users
+ADD email varchar(254) DEFAULT NULL;
+-- --------------------------------------------------------
+--
+-- Table structure for table password_reset_tokens
+--
+
+CREATE TABLE password_reset_tokens (
+    password_reset_token_id serial NOT NULL,
+    user_name varchar(16) NOT NULL,
+    token_hash varchar(255) NOT NULL,
+    expires bigint NOT NULL
+);
+--
+-- Indexes for table password_reset_tokens
+--
+ALTER TABLE password_reset_tokens
+ADD PRIMARY KEY (password_reset_token_id);
+CREATE INDEX ON password_reset_tokens (user_name);
+--
+-- Constraints for table password_reset_tokens
+--
+ALTER TABLE password_reset_tokens
+ADD CONSTRAINT password_reset_tokens_ibfk_1 FOREIGN KEY (user_name) REFERENCES users (user_name) ON DELETE CASCADE;
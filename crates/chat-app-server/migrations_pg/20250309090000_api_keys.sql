@@ -0,0 +1,27 @@
+-- --------------------------------------------------------
+--
+-- Table structure for table api_keys
+--
+
+CREATE TABLE api_keys (
+    api_key_id serial NOT NULL,
+    key_name varchar(16) NOT NULL,
+    key_hash varchar(255) NOT NULL,
+    permissions varchar(256) NOT NULL,
+    branch_id int NOT NULL,
+    created bigint NOT NULL,
+    expires bigint DEFAULT NULL,
+    last_used bigint DEFAULT NULL,
+    revoked boolean NOT NULL DEFAULT false
+);
+--
+-- Indexes for table api_keys
+--
+ALTER TABLE api_keys
+ADD PRIMARY KEY (api_key_id);
+CREATE INDEX ON api_keys (branch_id);
+--
+-- Constraints for table api_keys
+--
+ALTER TABLE api_keys
+ADD CONSTRAINT api_keys_ibfk_1 FOREIGN KEY (branch_id) REFERENCES branch (branch_id);
@@ -0,0 +1,51 @@
+-- --------------------------------------------------------
+--
+-- Table structure for table user_roles
+--
+
+CREATE TABLE user_roles (
+    user_name varchar(16) NOT NULL,
+    role_id int NOT NULL
+);
+-- --------------------------------------------------------
+--
+-- Table structure for table role_parents
+--
+
+CREATE TABLE role_parents (
+    role_id int NOT NULL,
+    parent_role_id int NOT NULL
+);
+--
+-- Indexes for table user_roles
+--
+ALTER TABLE user_roles
+ADD PRIMARY KEY (user_name, role_id);
+CREATE INDEX ON user_roles (role_id);
+--
+-- Indexes for table role_parents
+--
+ALTER TABLE role_parents
+ADD PRIMARY KEY (role_id, parent_role_id);
+CREATE INDEX ON role_parents (parent_role_id);
+--
+-- Constraints for table user_roles
+--
+ALTER TABLE user_roles
+ADD CONSTRAINT user_roles_ibfk_1 FOREIGN KEY (user_name) REFERENCES users (user_name) ON DELETE CASCADE,
+    ADD CONSTRAINT user_roles_ibfk_2 FOREIGN KEY (role_id) REFERENCES roles (role_id);
+--
+-- Constraints for table role_parents
+--
+ALTER TABLE role_parents
+ADD CONSTRAINT role_parents_ibfk_1 FOREIGN KEY (role_id) REFERENCES roles (role_id) ON DELETE CASCADE,
+    ADD CONSTRAINT role_parents_ibfk_2 FOREIGN KEY (parent_role_id) REFERENCES roles (role_id);
+--
+-- Convert the single role previously assigned to each user
+--
+INSERT INTO user_roles (user_name, role_id)
+SELECT user_name,
+    assigned_role
+FROM users
+WHERE assigned_role IS NOT NULL;
+ALTER TABLE users DROP COLUMN assigned_role;
@@ -0,0 +1,89 @@
+-- Permissions are stored as a JSON list of the names of the permissions
+-- instead of one '0' or '1' per permission in enum order. The server can still
+-- read the old format so values that are not converted keep working
+ALTER TABLE roles
+ALTER COLUMN permissions TYPE text;
+ALTER TABLE api_keys
+ALTER COLUMN permissions TYPE text;
+UPDATE roles
+SET permissions = '[' || concat_ws(
+            ',',
+            CASE WHEN substr(permissions, 1, 1) = '1' THEN '"RecordManualTransaction"' END,
+            CASE WHEN substr(permissions, 2, 1) = '1' THEN '"RecordDiscrepancy"' END,
+            CASE WHEN substr(permissions, 3, 1) = '1' THEN '"TransferRequest"' END,
+            CASE WHEN substr(permissions, 4, 1) = '1' THEN '"TransferTo"' END,
+            CASE WHEN substr(permissions, 5, 1) = '1' THEN '"TransferFrom"' END,
+            CASE WHEN substr(permissions, 6, 1) = '1' THEN '"TransferView"' END,
+            CASE WHEN substr(permissions, 7, 1) = '1' THEN '"TransferAny"' END,
+            CASE WHEN substr(permissions, 8, 1) = '1' THEN '"TransferRemove"' END,
+            CASE WHEN substr(permissions, 9, 1) = '1' THEN '"CustomsEntries"' END,
+            CASE WHEN substr(permissions, 10, 1) = '1' THEN '"ViewShipmentManifest"' END,
+            CASE WHEN substr(permissions, 11, 1) = '1' THEN '"ChangePass"' END,
+            CASE WHEN substr(permissions, 12, 1) = '1' THEN '"ImportData"' END,
+            CASE WHEN substr(permissions, 13, 1) = '1' THEN '"ViewLog"' END,
+            CASE WHEN substr(permissions, 14, 1) = '1' THEN '"ViewStockInfo"' END,
+            CASE WHEN substr(permissions, 15, 1) = '1' THEN '"RunReports"' END,
+            CASE WHEN substr(permissions, 16, 1) = '1' THEN '"Settings"' END,
+            CASE WHEN substr(permissions, 17, 1) = '1' THEN '"NonCurrentDate"' END,
+            CASE WHEN substr(permissions, 18, 1) = '1' THEN '"GrantOverrideLocal"' END,
+            CASE WHEN substr(permissions, 19, 1) = '1' THEN '"GrantOverrideRemote"' END,
+            CASE WHEN substr(permissions, 20, 1) = '1' THEN '"ManBranches"' END,
+            CASE WHEN substr(permissions, 21, 1) = '1' THEN '"ManClasses"' END,
+            CASE WHEN substr(permissions, 22, 1) = '1' THEN '"ManHostBranchAssignment"' END,
+            CASE WHEN substr(permissions, 23, 1) = '1' THEN '"ManLines"' END,
+            CASE WHEN substr(permissions, 24, 1) = '1' THEN '"ManMenu"' END,
+            CASE WHEN substr(permissions, 25, 1) = '1' THEN '"ManMinMax"' END,
+            CASE WHEN substr(permissions, 26, 1) = '1' THEN '"ManResetLocks"' END,
+            CASE WHEN substr(permissions, 27, 1) = '1' THEN '"ManRoles"' END,
+            CASE WHEN substr(permissions, 28, 1) = '1' THEN '"ManSpareParts"' END,
+            CASE WHEN substr(permissions, 29, 1) = '1' THEN '"ManSuppliers"' END,
+            CASE WHEN substr(permissions, 30, 1) = '1' THEN '"ManSupplierInvoices"' END,
+            CASE WHEN substr(permissions, 31, 1) = '1' THEN '"ManUAC"' END,
+            CASE WHEN substr(permissions, 32, 1) = '1' THEN '"ImportStockLevelCountBatch"' END,
+            CASE WHEN substr(permissions, 33, 1) = '1' THEN '"ImportTamsSync"' END,
+            CASE WHEN substr(permissions, 34, 1) = '1' THEN '"ImportSales"' END,
+            CASE WHEN substr(permissions, 35, 1) = '1' THEN '"ImportReceiving"' END,
+            CASE WHEN substr(permissions, 36, 1) = '1' THEN '"ViewAnalytics"' END
+        ) || ']'
+WHERE CHAR_LENGTH(permissions) = 36;
+UPDATE api_keys
+SET permissions = '[' || concat_ws(
+            ',',
+            CASE WHEN substr(permissions, 1, 1) = '1' THEN '"RecordManualTransaction"' END,
+            CASE WHEN substr(permissions, 2, 1) = '1' THEN '"RecordDiscrepancy"' END,
+            CASE WHEN substr(permissions, 3, 1) = '1' THEN '"TransferRequest"' END,
+            CASE WHEN substr(permissions, 4, 1) = '1' THEN '"TransferTo"' END,
+            CASE WHEN substr(permissions, 5, 1) = '1' THEN '"TransferFrom"' END,
+            CASE WHEN substr(permissions, 6, 1) = '1' THEN '"TransferView"' END,
+            CASE WHEN substr(permissions, 7, 1) = '1' THEN '"TransferAny"' END,
+            CASE WHEN substr(permissions, 8, 1) = '1' THEN '"TransferRemove"' END,
+            CASE WHEN substr(permissions, 9, 1) = '1' THEN '"CustomsEntries"' END,
+            CASE WHEN substr(permissions, 10, 1) = '1' THEN '"ViewShipmentManifest"' END,
+            CASE WHEN substr(permissions, 11, 1) = '1' THEN '"ChangePass"' END,
+            CASE WHEN substr(permissions, 12, 1) = '1' THEN '"ImportData"' END,
+            CASE WHEN substr(permissions, 13, 1) = '1' THEN '"ViewLog"' END,
+            CASE WHEN substr(permissions, 14, 1) = '1' THEN '"ViewStockInfo"' END,
+            CASE WHEN substr(permissions, 15, 1) = '1' THEN '"RunReports"' END,
+            CASE WHEN substr(permissions, 16, 1) = '1' THEN '"Settings"' END,
+            CASE WHEN substr(permissions, 17, 1) = '1' THEN '"NonCurrentDate"' END,
+            CASE WHEN substr(permissions, 18, 1) = '1' THEN '"GrantOverrideLocal"' END,
+            CASE WHEN substr(permissions, 19, 1) = '1' THEN '"GrantOverrideRemote"' END,
+            CASE WHEN substr(permissions, 20, 1) = '1' THEN '"ManBranches"' END,
+            CASE WHEN substr(permissions, 21, 1) = '1' THEN '"ManClasses"' END,
+            CASE WHEN substr(permissions, 22, 1) = '1' THEN '"ManHostBranchAssignment"' END,
+            CASE WHEN substr(permissions, 23, 1) = '1' THEN '"ManLines"' END,
+            CASE WHEN substr(permissions, 24, 1) = '1' THEN '"ManMenu"' END,
+            CASE WHEN substr(permissions, 25, 1) = '1' THEN '"ManMinMax"' END,
+            CASE WHEN substr(permissions, 26, 1) = '1' THEN '"ManResetLocks"' END,
+            CASE WHEN substr(permissions, 27, 1) = '1' THEN '"ManRoles"' END,
+            CASE WHEN substr(permissions, 28, 1) = '1' THEN '"ManSpareParts"' END,
+            CASE WHEN substr(permissions, 29, 1) = '1' THEN '"ManSuppliers"' END,
+            CASE WHEN substr(permissions, 30, 1) = '1' THEN '"ManSupplierInvoices"' END,
+            CASE WHEN substr(permissions, 31, 1) = '1' THEN '"ManUAC"' END,
+            CASE WHEN substr(permissions, 32, 1) = '1' THEN '"ImportStockLevelCountBatch"' END,
+            CASE WHEN substr(permissions, 33, 1) = '1' THEN '"ImportTamsSync"' END,
+            CASE WHEN substr(permissions, 34, 1) = '1' THEN '"ImportSales"' END,
+            CASE WHEN substr(permissions, 35, 1) = '1' THEN '"ImportReceiving"' END,
+            CASE WHEN substr(permissions, 36, 1) = '1' THEN '"ViewAnalytics"' END
+        ) || ']'
+WHERE CHAR_LENGTH(permissions) = 36;
@@ -0,0 +1,41 @@
+-- --------------------------------------------------------
+--
+-- Table structure for table chat_room
+--
+
+CREATE TABLE chat_room (
+    room_name varchar(32) NOT NULL,
+    visible_permission varchar(50) DEFAULT NULL,
+    visible_branch int DEFAULT NULL
+);
+--
+-- Create the default room
+--
+INSERT INTO chat_room (room_name)
+VALUES ('general');
+--
+-- Indexes for table chat_room
+--
+ALTER TABLE chat_room
+ADD PRIMARY KEY (room_name);
+CREATE INDEX ON chat_room (visible_branch);
+--
+-- Constraints for table chat_room
+--
+ALTER TABLE chat_room
+ADD CONSTRAINT chat_room_ibfk_1 FOREIGN KEY (visible_branch) REFERENCES branch (branch_id);
+-- --------------------------------------------------------
+--
+-- Add room to table chat (existing messages are moved into the default room)
+--
+ALTER TABLE chat
+ADD room varchar(32) NOT NULL DEFAULT 'general';
+--
+-- Indexes for table chat
+--
+CREATE INDEX ON chat (room, unix_timestamp);
+--
+-- Constraints for table chat
+--
+ALTER TABLE chat
+ADD CONSTRAINT chat_ibfk_2 FOREIGN KEY (room) REFERENCES chat_room (room_name);
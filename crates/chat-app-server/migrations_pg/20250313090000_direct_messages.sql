@@ -0,0 +1,18 @@
+-- --------------------------------------------------------
+--
+-- Add recipient to table chat (room is NULL for direct messages and
+-- recipient is NULL for messages sent to a room)
+--
+ALTER TABLE chat
+ALTER COLUMN room DROP NOT NULL;
+ALTER TABLE chat
+ADD recipient varchar(16) DEFAULT NULL;
+--
+-- Indexes for table chat
+--
+CREATE INDEX ON chat (recipient, unix_timestamp);
+--
+-- Constraints for table chat
+--
+ALTER TABLE chat
+ADD CONSTRAINT chat_ibfk_3 FOREIGN KEY (recipient) REFERENCES users (user_name);
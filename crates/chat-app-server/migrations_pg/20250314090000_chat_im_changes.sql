@@ -0,0 +1,9 @@
+-- --------------------------------------------------------
+--
+-- Add edit and delete flags to table chat (IDs are now assigned by the chat
+-- server when the IM is sent so chat_id is always provided on insert)
+--
+ALTER TABLE chat
+ADD edited boolean NOT NULL DEFAULT false;
+ALTER TABLE chat
+ADD deleted boolean NOT NULL DEFAULT false;
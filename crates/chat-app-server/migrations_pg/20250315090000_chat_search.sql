@@ -0,0 +1,6 @@
+-- --------------------------------------------------------
+--
+-- Indexes for table chat (Searches must use the same text search configuration
+-- for the index to be used)
+--
+CREATE INDEX chat_content_search ON chat USING GIN (to_tsvector('simple', content));
@@ -0,0 +1,6 @@
+--
+-- Time step of the last two-factor authentication code accepted for the user
+-- so that codes cannot be used again
+--
+ALTER TABLE users
+ADD totp_last_step bigint DEFAULT NULL;
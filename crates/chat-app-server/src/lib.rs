@@ -48,6 +48,7 @@ mod warning_suppress_test {
     use secrecy as _;
     use serde_json as _;
     use sqlx as _;
+    use totp_rs as _;
     use uuid as _;
     use wykies_client_core as _;
     use wykies_server_test_helper as _;
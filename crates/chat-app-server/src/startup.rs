@@ -9,10 +9,14 @@ use tracked_cancellations::CancellationTracker;
 use ws_auth::ws_get_route_add_closures;
 use wykies_server::{
     ApiServerBuilder, ServerTask as _,
+    analytics::AnalyticsWriter,
+    openapi::ApiOperation,
     plugin::{ServerPlugin, ServerPluginArtifacts},
 };
 use wykies_shared::{
-    const_config::web_socket::WS_INITIAL_MSG_TIMEOUT, uac::init_permissions_to_defaults,
+    const_config::{path::PATH_WS_TOKEN_CHAT, web_socket::WS_INITIAL_MSG_TIMEOUT},
+    token::AuthToken,
+    uac::init_permissions_to_defaults,
 };
 
 #[derive(Clone, serde::Deserialize)]
@@ -30,7 +34,15 @@ pub async fn start_servers(
 ) {
     init_permissions_to_defaults();
 
+    let (analytics_writer, analytics_recorder) =
+        AnalyticsWriter::new(api_server_builder.db_pool.clone());
+    let api_server_builder = api_server_builder.analytics_recorder(analytics_recorder);
     let configuration = &api_server_builder.api_server_init_bundle.configuration;
+    #[cfg(feature = "db-session")]
+    let session_purge_task = wykies_server::SessionPurgeTask::new(
+        api_server_builder.db_pool.clone(),
+        wykies_shared::const_config::server::SESSION_PURGE_INTERVAL,
+    );
     let cancellation_token = api_server_builder
         .api_server_init_bundle
         .cancellation_token
@@ -40,6 +52,7 @@ pub async fn start_servers(
     let ServerPluginArtifacts {
         task: chat_server,
         handle: chat_server_handle,
+        health_checks: chat_health_checks,
     } = ChatPlugin::setup(
         &ChatPluginConfig {
             settings: configuration.custom.chat.clone(),
@@ -49,6 +62,14 @@ pub async fn start_servers(
         &configuration.websockets,
     )
     .expect("failed to start Chat Server");
+    let api_server_builder = api_server_builder
+        .plugin::<ChatPlugin>()
+        .health_checks(chat_health_checks)
+        .api_operations([ApiOperation::new(
+            PATH_WS_TOKEN_CHAT,
+            "Get a token to open the chat websocket",
+        )
+        .response::<AuthToken>()]);
 
     // Setup Routes / Server Resources
     let (chat_open_add, chat_protected_add) = ws_get_route_add_closures(
@@ -81,6 +102,25 @@ pub async fn start_servers(
             tokio::spawn(api_server.run(cancellation_token1)).await,
         )
     });
+    let cancellation_token2 = cancellation_token.clone();
+    result.spawn(async move {
+        let name = analytics_writer.name();
+        (
+            name,
+            tokio::spawn(analytics_writer.run(cancellation_token2)).await,
+        )
+    });
+    #[cfg(feature = "db-session")]
+    {
+        let cancellation_token = cancellation_token.clone();
+        result.spawn(async move {
+            let name = session_purge_task.name();
+            (
+                name,
+                tokio::spawn(session_purge_task.run(cancellation_token)).await,
+            )
+        });
+    }
     result.spawn(async move {
         let name = chat_server.name();
         (
@@ -0,0 +1,62 @@
+use wykies_server_test_helper::expect_ok;
+use wykies_shared::{
+    req_args::api::analytics::SummaryReqArgs,
+    uac::{Permission, PermissionsError},
+};
+use wykies_time::{Seconds, Timestamp};
+
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn summary_available_to_admin() {
+    // Arrange
+    let app = spawn_app().await.create_admin_user().await;
+    app.login_assert().await;
+    let args = SummaryReqArgs {
+        since: Timestamp::now(),
+        until: Some(Timestamp::now() + Seconds::new(60)),
+    };
+
+    // Act
+    let actual = expect_ok!(app.core_client.analytics_summary(&args));
+
+    // Assert - Records are saved in batches so newly made requests are not
+    // expected to be included yet
+    assert!(actual.iter().all(|x| x.p50_latency_ms <= x.p95_latency_ms));
+}
+
+#[tokio::test]
+async fn summary_rejects_empty_window() {
+    // Arrange
+    let app = spawn_app().await.create_admin_user().await;
+    app.login_assert().await;
+    let now = Timestamp::now();
+    let args = SummaryReqArgs {
+        since: now,
+        until: Some(now),
+    };
+
+    // Act
+    let actual = app.core_client.analytics_summary(&args).await.unwrap();
+
+    // Assert
+    assert!(actual.is_err());
+}
+
+#[tokio::test]
+async fn unprivileged_user_cannot_view_summary() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_assert().await;
+    let args = SummaryReqArgs {
+        since: Timestamp::now(),
+        until: None,
+    };
+
+    // Act
+    let actual = app.core_client.analytics_summary(&args).await.unwrap();
+
+    // Assert
+    let expected_error = PermissionsError::MissingPermissions(vec![Permission::ViewAnalytics]);
+    assert_eq!(actual.unwrap_err().to_string(), expected_error.to_string());
+}
@@ -0,0 +1,202 @@
+use std::collections::BTreeSet;
+
+use secrecy::SecretString;
+use wykies_client_core::Client;
+use wykies_server_test_helper::expect_ok;
+use wykies_shared::{
+    api_error::{ApiError, ApiErrorCode},
+    req_args::api::{api_key::NewReqArgs, audit::ListReqArgs, user::NewUserReqArgs},
+    uac::{ApiKeyError, ApiKeySecret, Permission, Permissions, PermissionsError},
+};
+
+use crate::helpers::{TestApp, spawn_app};
+
+fn new_key_args(app: &TestApp) -> NewReqArgs {
+    NewReqArgs {
+        name: "svc_reports".to_string().try_into().unwrap(),
+        permissions: Permissions(BTreeSet::from([Permission::ViewLog])),
+        branch_id: app.host_branch_pair.branch_id,
+        valid_for_days: Some(30),
+    }
+}
+
+fn key_client(app: &TestApp, api_key: &ApiKeySecret) -> Client {
+    Client::new(app.address.clone()).with_api_key(SecretString::from(api_key.key.clone()))
+}
+
+#[tokio::test]
+async fn api_key_can_access_permitted_endpoint() {
+    // Arrange
+    let app = spawn_app().await;
+    let admin = app.create_admin_user().await;
+    admin.login_assert().await;
+    let api_key = expect_ok!(admin.core_client.api_key_new(new_key_args(&app)));
+    let client = key_client(&app, &api_key);
+
+    // Act
+    let actual = client.audit_list(&ListReqArgs::default()).await.unwrap();
+
+    // Assert
+    assert!(actual.is_ok());
+    let keys = expect_ok!(admin.core_client.api_key_list());
+    let key_info = keys.iter().find(|x| x.id == api_key.id).unwrap();
+    assert!(key_info.last_used.is_some());
+    assert!(key_info.expires.is_some());
+    assert!(!key_info.revoked);
+}
+
+#[tokio::test]
+async fn api_key_limited_to_its_permissions() {
+    // Arrange
+    let app = spawn_app().await;
+    let admin = app.create_admin_user().await;
+    admin.login_assert().await;
+    let api_key = expect_ok!(admin.core_client.api_key_new(new_key_args(&app)));
+    let client = key_client(&app, &api_key);
+
+    // Act
+    let actual = client.list_users_and_roles().await.unwrap();
+
+    // Assert
+    let expected_error = PermissionsError::MissingPermissions(vec![Permission::ManUAC]);
+    assert_eq!(actual.unwrap_err().to_string(), expected_error.to_string());
+}
+
+#[tokio::test]
+async fn revoked_api_key_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let admin = app.create_admin_user().await;
+    admin.login_assert().await;
+    let api_key = expect_ok!(admin.core_client.api_key_new(new_key_args(&app)));
+    let client = key_client(&app, &api_key);
+    expect_ok!(admin.core_client.api_key_revoke(api_key.id));
+
+    // Act
+    let actual = client
+        .audit_list(&ListReqArgs::default())
+        .await
+        .unwrap()
+        .unwrap_err();
+
+    // Assert
+    assert_eq!(actual.to_string(), ApiKeyError::Invalid.to_string());
+    let actual = actual
+        .downcast_ref::<ApiError>()
+        .expect("failed to decode error");
+    assert_eq!(actual.code, ApiErrorCode::InvalidApiKey);
+}
+
+#[tokio::test]
+async fn rotated_api_key_replaces_old_key() {
+    // Arrange
+    let app = spawn_app().await;
+    let admin = app.create_admin_user().await;
+    admin.login_assert().await;
+    let old_key = expect_ok!(admin.core_client.api_key_new(new_key_args(&app)));
+
+    // Act
+    let new_key = expect_ok!(admin.core_client.api_key_rotate(old_key.id));
+
+    // Assert
+    assert_eq!(new_key.id, old_key.id);
+    let old_client = key_client(&app, &old_key);
+    let actual = old_client
+        .audit_list(&ListReqArgs::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        actual.unwrap_err().to_string(),
+        ApiKeyError::Invalid.to_string()
+    );
+    let new_client = key_client(&app, &new_key);
+    expect_ok!(new_client.audit_list(&ListReqArgs::default()));
+}
+
+#[tokio::test]
+async fn unprivileged_user_cannot_create_api_key() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_assert().await;
+
+    // Act
+    let actual = app
+        .core_client
+        .api_key_new(new_key_args(&app))
+        .await
+        .unwrap();
+
+    // Assert
+    let expected_error = PermissionsError::MissingPermissions(vec![Permission::ManUAC]);
+    assert_eq!(actual.unwrap_err().to_string(), expected_error.to_string());
+}
+
+#[tokio::test]
+async fn api_key_cannot_use_name_of_existing_user() {
+    // Arrange
+    let app = spawn_app().await;
+    let admin = app.create_admin_user().await;
+    admin.login_assert().await;
+    let args = NewReqArgs {
+        name: admin.test_user.username.clone().try_into().unwrap(),
+        ..new_key_args(&app)
+    };
+
+    // Act
+    let actual = admin.core_client.api_key_new(args).await.unwrap();
+
+    // Assert
+    let actual = actual.unwrap_err();
+    assert_eq!(actual.to_string(), "name is already used by a user");
+    let actual = actual
+        .downcast_ref::<ApiError>()
+        .expect("failed to decode error");
+    assert_eq!(actual.code, ApiErrorCode::BadRequest);
+}
+
+#[tokio::test]
+async fn user_cannot_use_name_of_api_key() {
+    // Arrange
+    let app = spawn_app().await;
+    let admin = app.create_admin_user().await;
+    admin.login_assert().await;
+    let key_args = new_key_args(&app);
+    expect_ok!(admin.core_client.api_key_new(key_args.clone()));
+    let user_args = NewUserReqArgs {
+        username: key_args.name,
+        display_name: "Service Reports".to_string().try_into().unwrap(),
+        password: "a test password".to_string().into(),
+        assigned_roles: BTreeSet::new(),
+        email: None,
+    };
+
+    // Act
+    let actual = admin.core_client.user_new(user_args).await.unwrap();
+
+    // Assert
+    assert_eq!(
+        actual.unwrap_err().to_string(),
+        "username is already used by an api key"
+    );
+}
+
+#[tokio::test]
+async fn api_key_refused_on_session_only_endpoints() {
+    // Arrange
+    let app = spawn_app().await;
+    let admin = app.create_admin_user().await;
+    admin.login_assert().await;
+    let api_key = expect_ok!(admin.core_client.api_key_new(new_key_args(&app)));
+    let client = key_client(&app, &api_key);
+
+    // Act
+    let actual = client.totp_enrol().await.unwrap();
+
+    // Assert
+    let actual = actual.unwrap_err();
+    assert_eq!(actual.to_string(), ApiKeyError::SessionRequired.to_string());
+    let actual = actual
+        .downcast_ref::<ApiError>()
+        .expect("failed to decode error");
+    assert_eq!(actual.code, ApiErrorCode::BadRequest);
+}
@@ -0,0 +1,80 @@
+use wykies_server_test_helper::expect_ok;
+use wykies_shared::{
+    audit::AuditAction,
+    req_args::api::{audit::ListReqArgs, user::PasswordResetReqArgs},
+    uac::{PasswordComplexity, Permission, PermissionsError, Username},
+};
+
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn admin_action_is_recorded() {
+    // Arrange
+    let app = spawn_app().await;
+    let admin = app.create_admin_user().await;
+    admin.login_assert().await;
+    let target: Username = app.test_user.username.clone().try_into().unwrap();
+
+    // Act
+    expect_ok!(admin.core_client.user_sessions_revoke(target.clone(), None));
+
+    // Assert
+    let actual = expect_ok!(admin.core_client.audit_list(&ListReqArgs {
+        action: Some(AuditAction::SessionsRevoked),
+        ..Default::default()
+    }));
+    assert_eq!(actual.len(), 1);
+    assert_eq!(actual[0].actor.as_ref(), admin.test_user.username);
+    assert_eq!(actual[0].target, target.to_string());
+}
+
+#[tokio::test]
+async fn list_filters_and_pages() {
+    // Arrange
+    let app = spawn_app().await;
+    let admin = app.create_admin_user().await;
+    admin.login_assert().await;
+    let target: Username = app.test_user.username.clone().try_into().unwrap();
+    expect_ok!(admin.core_client.reset_password(PasswordResetReqArgs {
+        username: target.clone(),
+        new_password: PasswordComplexity::generate_random_password(),
+    }));
+    expect_ok!(admin.core_client.user_sessions_revoke(target.clone(), None));
+    let mut args = ListReqArgs {
+        target: Some(target.to_string()),
+        page_size: Some(1),
+        ..Default::default()
+    };
+
+    // Act
+    let first_page = expect_ok!(admin.core_client.audit_list(&args));
+    args.before_id = Some(first_page[0].id);
+    let second_page = expect_ok!(admin.core_client.audit_list(&args));
+    args.before_id = Some(second_page[0].id);
+    let third_page = expect_ok!(admin.core_client.audit_list(&args));
+
+    // Assert - Newest first
+    assert_eq!(first_page.len(), 1);
+    assert_eq!(first_page[0].action, AuditAction::SessionsRevoked);
+    assert_eq!(second_page.len(), 1);
+    assert_eq!(second_page[0].action, AuditAction::PasswordReset);
+    assert!(third_page.is_empty());
+}
+
+#[tokio::test]
+async fn unprivileged_user_cannot_list() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_assert().await;
+
+    // Act
+    let actual = app
+        .core_client
+        .audit_list(&ListReqArgs::default())
+        .await
+        .unwrap();
+
+    // Assert
+    let expected_error = PermissionsError::MissingPermissions(vec![Permission::ViewLog]);
+    assert_eq!(actual.unwrap_err().to_string(), expected_error.to_string());
+}
@@ -1,11 +1,29 @@
-use crate::helpers::spawn_app;
+use crate::helpers::{TestApp, spawn_app, spawn_app_with_configuration};
+use secrecy::SecretString;
 use uuid::Uuid;
+use wykies_server_test_helper::expect_ok;
 use wykies_shared::{
+    api_error::{ApiError, ApiErrorCode},
     errors::NotLoggedInError,
     req_args::api::ChangePasswordReqArgs,
     uac::{ChangePasswordError, PasswordComplexity},
 };
 
+async fn change_password(
+    app: &TestApp,
+    current_password: SecretString,
+    new_password: SecretString,
+) -> anyhow::Result<()> {
+    app.core_client
+        .change_password(&ChangePasswordReqArgs {
+            current_password,
+            new_password: new_password.clone(),
+            new_password_check: new_password,
+        })
+        .await
+        .expect("failed to receive on rx")
+}
+
 #[tokio::test]
 async fn you_must_be_logged_in_to_change_your_password() {
     // Arrange
@@ -49,10 +67,16 @@ async fn new_password_fields_must_match() {
         .unwrap();
 
     // Assert - Password mismatch rejected
+    let actual = actual.unwrap_err();
     assert_eq!(
-        actual.unwrap_err().to_string(),
+        actual.to_string(),
         ChangePasswordError::PasswordsDoNotMatch.to_string()
     );
+    let actual = actual
+        .downcast_ref::<ApiError>()
+        .expect("failed to decode error");
+    assert_eq!(actual.code, ApiErrorCode::ValidationFailed);
+    assert!(actual.field_error("new_password_check").is_some());
 }
 
 #[tokio::test]
@@ -146,3 +170,124 @@ async fn password_complexity_checks_are_done() {
     // Assert - Password fails for validation
     assert!(actual.unwrap_err().to_string().contains("complexity"),);
 }
+
+#[tokio::test]
+async fn configured_password_policy_is_served() {
+    // Arrange
+    let app = spawn_app_with_configuration(|c| c.user_auth.password_policy.min_length = 42).await;
+    app.login_assert().await;
+
+    // Act
+    let actual = expect_ok!(app.core_client.password_policy());
+
+    // Assert
+    assert_eq!(actual.min_length, 42);
+}
+
+#[tokio::test]
+async fn configured_password_policy_is_enforced() {
+    // Arrange
+    let app = spawn_app_with_configuration(|c| c.user_auth.password_policy.min_length = 100).await;
+    app.login_assert().await;
+
+    // Act
+    let actual = change_password(
+        &app,
+        app.test_user.password.clone().into(),
+        PasswordComplexity::generate_random_password(),
+    )
+    .await
+    .expect_err("password shorter than the configured minimum should be rejected");
+
+    // Assert
+    let actual = actual
+        .downcast_ref::<ApiError>()
+        .expect("failed to decode error");
+    let field_error = actual
+        .field_error("new_password")
+        .expect("error should be for the new password");
+    assert!(field_error.message.contains("minimum length of 100"));
+}
+
+#[tokio::test]
+async fn blocklisted_passwords_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_assert().await;
+
+    // Act
+    let actual = change_password(
+        &app,
+        app.test_user.password.clone().into(),
+        "pASSWORD123".to_string().into(),
+    )
+    .await
+    .expect_err("blocklisted password should be rejected");
+
+    // Assert
+    let actual = actual
+        .downcast_ref::<ApiError>()
+        .expect("failed to decode error");
+    assert!(actual.field_error("new_password").is_some());
+}
+
+#[tokio::test]
+async fn recent_passwords_cannot_be_reused() {
+    // Arrange
+    let app = spawn_app().await;
+    let first_password = PasswordComplexity::generate_random_password();
+    let second_password = PasswordComplexity::generate_random_password();
+    app.login_assert().await;
+    change_password(
+        &app,
+        app.test_user.password.clone().into(),
+        first_password.clone(),
+    )
+    .await
+    .unwrap();
+    change_password(&app, first_password.clone(), second_password.clone())
+        .await
+        .unwrap();
+
+    // Act
+    let actual = change_password(&app, second_password, first_password)
+        .await
+        .expect_err("recently used password should be rejected");
+
+    // Assert
+    let actual = actual
+        .downcast_ref::<ApiError>()
+        .expect("failed to decode error");
+    let field_error = actual
+        .field_error("new_password")
+        .expect("error should be for the new password");
+    assert_eq!(
+        field_error.message,
+        ChangePasswordError::ReusedPassword(5).to_string()
+    );
+}
+
+#[tokio::test]
+async fn only_needed_password_history_is_kept() {
+    // Arrange
+    let app = spawn_app_with_configuration(|c| {
+        c.user_auth.password_policy.history_count = 3;
+        // Random passwords can have repeated characters
+        c.user_auth.password_policy.max_consecutive_same_char = 0;
+    })
+    .await;
+    app.login_assert().await;
+    let mut current_password: SecretString = app.test_user.password.clone().into();
+
+    // Act
+    for _ in 0..4 {
+        let new_password = PasswordComplexity::generate_random_password();
+        change_password(&app, current_password, new_password.clone())
+            .await
+            .unwrap();
+        current_password = new_password;
+    }
+
+    // Assert - The current password is one of the three but is not stored in the history
+    assert_eq!(app.test_user.password_history_count_in_db(&app).await, 2);
+}
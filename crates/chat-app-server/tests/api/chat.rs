@@ -1,14 +1,24 @@
-use crate::helpers::{no_cb, spawn_app};
+use crate::helpers::{no_cb, spawn_app, spawn_app_with_configuration};
 use ewebsock::{WsEvent, WsMessage};
 use plugin_chat::{
-    ChatIM, ChatImText, ChatMsg, ChatMsgsHistory, ChatUser, InitialStateBody, ReqHistoryBody,
+    ChatIM, ChatImId, ChatImText, ChatMsg, ChatMsgsHistory, ChatRoomInfo, ChatRoomName,
+    ChatRoomUser, ChatUser, DirectErrorBody, DirectIM, EditErrorBody, EditImBody, ImState,
+    InitialStateBody, RateLimitedBody, ReqDirectHistoryBody, ReqHistoryBody, RespHistoryBody,
+    RoomErrorBody, RoomStateBody, RoomVisibility, SearchBody, SearchErrorBody, SearchResultsBody,
+    SearchScope,
     consts::{CHAT_HISTORY_RECENT_CAPACITY, CHAT_HISTORY_REQUEST_SIZE},
+    server_only::{ChatRateLimitSettings, TokenBucketSettings},
 };
 use pretty_assertions::{assert_eq, assert_ne};
 use std::time::Duration;
 use tokio::time::sleep;
 use wykies_server_test_helper::{TEST_MSG_WAIT_TIMEOUT, expect_ok};
-use wykies_shared::{const_config::path::PATH_WS_TOKEN_CHAT, uac::Username};
+use wykies_shared::{
+    branch::BranchId,
+    const_config::path::PATH_WS_TOKEN_CHAT,
+    uac::{Permission, Username},
+    websockets::WsConnTxRx,
+};
 use wykies_time::Timestamp;
 
 #[tokio::test]
@@ -28,16 +38,26 @@ async fn sent_messages_received() {
     ));
     let author: Username = app.test_user.username.clone().try_into().unwrap();
     let expected_im = ChatMsg::IM(ChatIM {
+        id: Default::default(),
+        room: ChatRoomName::default_room(),
         author: author.clone(),
         timestamp: Timestamp::now(),
         content: "test message".try_into().unwrap(),
+        state: Default::default(),
     });
     let msg = WsMessage::Text(serde_json::to_string(&expected_im).unwrap());
     let chat_user = ChatUser::new(author);
     let expected_initial_state = WsEvent::Message(WsMessage::Text(
         serde_json::to_string(&ChatMsg::InitialState(InitialStateBody {
-            connected_users: vec![(chat_user, 2)],
-            history: ChatMsgsHistory { ims: Vec::new() },
+            rooms: vec![ChatRoomInfo {
+                name: ChatRoomName::default_room(),
+                visibility: RoomVisibility::Everyone,
+            }],
+            default_room: RoomStateBody {
+                room: ChatRoomName::default_room(),
+                connected_users: vec![(chat_user, 2)],
+                history: ChatMsgsHistory { ims: Vec::new() },
+            },
         }))
         .unwrap(),
     ));
@@ -67,9 +87,12 @@ async fn sent_messages_received() {
         other => panic!("Actual:   {other:?}\nExpected: {:?}", WsEvent::Message(msg)),
     };
 
-    // Prevent test from being flakey as server might change the time stamp
+    // Prevent test from being flakey as server might change the time stamp (and
+    // assigns the ID)
     if let (ChatMsg::IM(actual), ChatMsg::IM(expected)) = (&mut actual, &expected_im) {
-        actual.timestamp = expected.timestamp
+        assert_ne!(actual.id, ChatImId::default());
+        actual.timestamp = expected.timestamp;
+        actual.id = expected.id;
     }
 
     // Assert
@@ -109,9 +132,12 @@ async fn chat_initial_buffered_history() {
     // Act - Send messages
     for im in expected_ims_texts.iter() {
         let msg = ChatMsg::IM(ChatIM {
+            id: Default::default(),
+            room: ChatRoomName::default_room(),
             author: author.clone(),
             timestamp: Timestamp::now(),
             content: im.clone(),
+            state: Default::default(),
         });
         let msg = WsMessage::Text(serde_json::to_string(&msg).unwrap());
         conn.send(msg);
@@ -139,7 +165,11 @@ async fn chat_initial_buffered_history() {
             let msg: ChatMsg = serde_json::from_str(&text).unwrap();
             let ims = match msg {
                 ChatMsg::InitialState(InitialStateBody {
-                    history: ChatMsgsHistory { ims, .. },
+                    default_room:
+                        RoomStateBody {
+                            history: ChatMsgsHistory { ims, .. },
+                            ..
+                        },
                     ..
                 }) => ims,
                 other => panic!("expected initial state but got: {other:?}"),
@@ -158,7 +188,10 @@ async fn chat_initial_buffered_history() {
 #[tokio::test]
 async fn chat_overflowing_server_history_buffer() {
     // Arrange
-    let app = spawn_app().await;
+    let app = spawn_app_with_configuration(|c| {
+        c.custom.chat.rate_limit = ChatRateLimitSettings::disabled()
+    })
+    .await;
     app.login_assert().await;
     let author: Username = app.test_user.username.clone().try_into().unwrap();
     const MSGS_SENT: u64 = 2 * CHAT_HISTORY_RECENT_CAPACITY as u64;
@@ -177,9 +210,12 @@ async fn chat_overflowing_server_history_buffer() {
     let sleep_interval = CHAT_HISTORY_REQUEST_SIZE as usize / 2;
     for (count, im) in expected_ims_texts.iter().enumerate() {
         let msg = ChatMsg::IM(ChatIM {
+            id: Default::default(),
+            room: ChatRoomName::default_room(),
             author: author.clone(),
             timestamp: Timestamp::now(),
             content: im.clone(),
+            state: Default::default(),
         });
         let msg = WsMessage::Text(serde_json::to_string(&msg).unwrap());
         conn.send(msg);
@@ -214,7 +250,10 @@ async fn chat_overflowing_server_history_buffer() {
         WsEvent::Message(WsMessage::Text(text)) => {
             let msg: ChatMsg = serde_json::from_str(&text).unwrap();
             match msg {
-                ChatMsg::InitialState(InitialStateBody { history, .. }) => history,
+                ChatMsg::InitialState(InitialStateBody {
+                    default_room: RoomStateBody { history, .. },
+                    ..
+                }) => history,
                 other => panic!("expected initial state but got: {other:?}"),
             }
         }
@@ -230,6 +269,7 @@ async fn chat_overflowing_server_history_buffer() {
     for i in 0..request_count {
         let current_earliest_timestamp = history.earliest_timestamp_or_now();
         let chat_msg = ChatMsg::ReqHistory(ReqHistoryBody {
+            room: ChatRoomName::default_room(),
             qty,
             latest_timestamp: current_earliest_timestamp,
         });
@@ -242,7 +282,7 @@ async fn chat_overflowing_server_history_buffer() {
             WsEvent::Message(WsMessage::Text(text)) => {
                 let msg: ChatMsg = serde_json::from_str(&text).unwrap();
                 match msg {
-                    ChatMsg::RespHistory(history) => history,
+                    ChatMsg::RespHistory(RespHistoryBody { history, .. }) => history,
                     other => panic!("expected Response to History Request but got: {other:?}"),
                 }
             }
@@ -275,3 +315,974 @@ async fn chat_overflowing_server_history_buffer() {
     );
     assert_eq!(actual, expected_ims_texts);
 }
+
+fn send_chat_msg(conn: &mut WsConnTxRx, chat_msg: &ChatMsg) {
+    conn.send(WsMessage::Text(serde_json::to_string(chat_msg).unwrap()));
+}
+
+async fn recv_chat_msg(conn: &mut WsConnTxRx) -> ChatMsg {
+    let incoming = conn
+        .recv_with_timeout_ignoring_ping(TEST_MSG_WAIT_TIMEOUT)
+        .await
+        .expect("failed to receive message");
+    match incoming {
+        WsEvent::Message(WsMessage::Text(text)) => serde_json::from_str(&text).unwrap(),
+        other => panic!("unexpected event: {other:?}"),
+    }
+}
+
+/// Returns the room and content of the IM or panics if the message is not an IM
+fn unwrap_im(chat_msg: ChatMsg) -> (ChatRoomName, ChatImText) {
+    match chat_msg {
+        ChatMsg::IM(im) => (im.room, im.content),
+        other => panic!("expected IM but got: {other:?}"),
+    }
+}
+
+fn im_msg(room: &ChatRoomName, author: &Username, content: &str) -> ChatMsg {
+    ChatMsg::IM(ChatIM {
+        id: Default::default(),
+        room: room.clone(),
+        author: author.clone(),
+        timestamp: Timestamp::now(),
+        content: content.try_into().unwrap(),
+        state: Default::default(),
+    })
+}
+
+fn direct_im_msg(recipient: &Username, author: &Username, content: &str) -> ChatMsg {
+    ChatMsg::DirectIM(DirectIM {
+        id: Default::default(),
+        recipient: recipient.clone(),
+        author: author.clone(),
+        timestamp: Timestamp::now(),
+        content: content.try_into().unwrap(),
+        state: Default::default(),
+    })
+}
+
+/// Returns the author, recipient and content of the direct IM or panics if the
+/// message is not a direct IM
+fn unwrap_direct_im(chat_msg: ChatMsg) -> (Username, Username, ChatImText) {
+    match chat_msg {
+        ChatMsg::DirectIM(im) => (im.author, im.recipient, im.content),
+        other => panic!("expected direct IM but got: {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn ims_only_sent_to_room_members() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_assert().await;
+    let author: Username = app.test_user.username.clone().try_into().unwrap();
+    let default_room = ChatRoomName::default_room();
+    let side_room: ChatRoomName = "side room".try_into().unwrap();
+    let side_room_info = ChatRoomInfo {
+        name: side_room.clone(),
+        visibility: RoomVisibility::Everyone,
+    };
+    let mut conn1 = expect_ok!(app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    assert!(matches!(
+        recv_chat_msg(&mut conn1).await,
+        ChatMsg::InitialState(_)
+    ));
+    let mut conn2 = expect_ok!(app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    assert!(matches!(
+        recv_chat_msg(&mut conn2).await,
+        ChatMsg::InitialState(_)
+    ));
+    assert!(matches!(
+        recv_chat_msg(&mut conn1).await,
+        ChatMsg::UserJoined(_)
+    ));
+
+    // Act - Create room
+    send_chat_msg(&mut conn1, &ChatMsg::CreateRoom(side_room_info.clone()));
+
+    // Assert - Both learn about the room but only the creator joins
+    assert_eq!(
+        recv_chat_msg(&mut conn1).await,
+        ChatMsg::RoomCreated(side_room_info.clone())
+    );
+    assert_eq!(
+        recv_chat_msg(&mut conn1).await,
+        ChatMsg::RoomJoined(RoomStateBody {
+            room: side_room.clone(),
+            connected_users: vec![(ChatUser::new(author.clone()), 1)],
+            history: Default::default(),
+        })
+    );
+    assert_eq!(
+        recv_chat_msg(&mut conn2).await,
+        ChatMsg::RoomCreated(side_room_info)
+    );
+
+    // Act - Send one IM to each room
+    send_chat_msg(&mut conn1, &im_msg(&side_room, &author, "side room msg"));
+    send_chat_msg(
+        &mut conn1,
+        &im_msg(&default_room, &author, "default room msg"),
+    );
+
+    // Assert - Sender gets both but the other connection only gets the default room
+    assert_eq!(
+        unwrap_im(recv_chat_msg(&mut conn1).await),
+        (side_room.clone(), "side room msg".try_into().unwrap())
+    );
+    assert_eq!(
+        unwrap_im(recv_chat_msg(&mut conn1).await),
+        (default_room.clone(), "default room msg".try_into().unwrap())
+    );
+    assert_eq!(
+        unwrap_im(recv_chat_msg(&mut conn2).await),
+        (default_room, "default room msg".try_into().unwrap())
+    );
+
+    // Act - Send to a room the connection is not in
+    send_chat_msg(&mut conn2, &im_msg(&side_room, &author, "not a member"));
+
+    // Assert
+    assert_eq!(
+        recv_chat_msg(&mut conn2).await,
+        ChatMsg::RoomError(RoomErrorBody {
+            room: side_room,
+            message: "You are not in this room".to_string()
+        })
+    );
+}
+
+#[tokio::test]
+async fn join_room_gets_history_and_leave_notifies_room() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_assert().await;
+    let author: Username = app.test_user.username.clone().try_into().unwrap();
+    let chat_user = ChatUser::new(author.clone());
+    let side_room: ChatRoomName = "side room".try_into().unwrap();
+    let mut conn1 = expect_ok!(app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    recv_chat_msg(&mut conn1).await; // Initial State
+    send_chat_msg(
+        &mut conn1,
+        &ChatMsg::CreateRoom(ChatRoomInfo {
+            name: side_room.clone(),
+            visibility: RoomVisibility::Everyone,
+        }),
+    );
+    recv_chat_msg(&mut conn1).await; // Room Created
+    recv_chat_msg(&mut conn1).await; // Room Joined
+    send_chat_msg(&mut conn1, &im_msg(&side_room, &author, "before join"));
+    recv_chat_msg(&mut conn1).await; // Own IM
+    let mut conn2 = expect_ok!(app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    recv_chat_msg(&mut conn2).await; // Initial State
+    recv_chat_msg(&mut conn1).await; // User joined default room
+
+    // Act - Join
+    send_chat_msg(&mut conn2, &ChatMsg::JoinRoom(side_room.clone()));
+
+    // Assert
+    let room_state = match recv_chat_msg(&mut conn2).await {
+        ChatMsg::RoomJoined(room_state) => room_state,
+        other => panic!("expected room joined but got: {other:?}"),
+    };
+    assert_eq!(room_state.room, side_room);
+    assert_eq!(room_state.connected_users, vec![(chat_user.clone(), 2)]);
+    let history: Vec<ChatImText> = room_state
+        .history
+        .ims
+        .into_iter()
+        .map(|x| x.content)
+        .collect();
+    assert_eq!(history, vec!["before join".try_into().unwrap()]);
+    assert_eq!(
+        recv_chat_msg(&mut conn1).await,
+        ChatMsg::UserJoined(ChatRoomUser {
+            room: side_room.clone(),
+            user: chat_user.clone()
+        })
+    );
+
+    // Act - Join again
+    send_chat_msg(&mut conn2, &ChatMsg::JoinRoom(side_room.clone()));
+
+    // Assert
+    assert!(matches!(
+        recv_chat_msg(&mut conn2).await,
+        ChatMsg::RoomError(_)
+    ));
+
+    // Act - Leave
+    send_chat_msg(&mut conn2, &ChatMsg::LeaveRoom(side_room.clone()));
+
+    // Assert
+    assert_eq!(
+        recv_chat_msg(&mut conn2).await,
+        ChatMsg::RoomLeft(side_room.clone())
+    );
+    assert_eq!(
+        recv_chat_msg(&mut conn1).await,
+        ChatMsg::UserLeft(ChatRoomUser {
+            room: side_room,
+            user: chat_user
+        })
+    );
+}
+
+#[tokio::test]
+async fn room_hidden_from_users_without_permission() {
+    // Arrange
+    let app = spawn_app().await;
+    let admin_app = app.create_admin_user().await;
+    app.login_assert().await;
+    admin_app.login_assert().await;
+    let restricted_room = ChatRoomInfo {
+        name: "managers".try_into().unwrap(),
+        visibility: RoomVisibility::Permission(Permission::ManUAC),
+    };
+    let mut admin_conn = expect_ok!(admin_app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    recv_chat_msg(&mut admin_conn).await; // Initial State
+    let mut user_conn = expect_ok!(app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    recv_chat_msg(&mut user_conn).await; // Initial State
+    recv_chat_msg(&mut admin_conn).await; // User joined default room
+
+    // Act - Admin creates room
+    send_chat_msg(
+        &mut admin_conn,
+        &ChatMsg::CreateRoom(restricted_room.clone()),
+    );
+
+    // Assert - Admin can see and join the room
+    assert_eq!(
+        recv_chat_msg(&mut admin_conn).await,
+        ChatMsg::RoomCreated(restricted_room.clone())
+    );
+    assert!(matches!(
+        recv_chat_msg(&mut admin_conn).await,
+        ChatMsg::RoomJoined(_)
+    ));
+
+    // Act - User tries to join
+    send_chat_msg(
+        &mut user_conn,
+        &ChatMsg::JoinRoom(restricted_room.name.clone()),
+    );
+
+    // Assert - Not told about the room being created and the room is not found
+    assert_eq!(
+        recv_chat_msg(&mut user_conn).await,
+        ChatMsg::RoomError(RoomErrorBody {
+            room: restricted_room.name,
+            message: "Room not found".to_string()
+        })
+    );
+}
+
+#[tokio::test]
+async fn cannot_create_room_not_visible_to_creator() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_assert().await;
+    let other_branch_room = ChatRoomInfo {
+        name: "other branch".try_into().unwrap(),
+        visibility: RoomVisibility::Branch(BranchId::from(u64::from(u32::MAX))),
+    };
+    let mut conn = expect_ok!(app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    recv_chat_msg(&mut conn).await; // Initial State
+
+    // Act
+    send_chat_msg(&mut conn, &ChatMsg::CreateRoom(other_branch_room.clone()));
+
+    // Assert
+    match recv_chat_msg(&mut conn).await {
+        ChatMsg::RoomError(RoomErrorBody { room, .. }) => {
+            assert_eq!(room, other_branch_room.name)
+        }
+        other => panic!("expected room error but got: {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn direct_ims_only_sent_to_sender_and_recipient() {
+    // Arrange
+    let app = spawn_app().await;
+    let recipient_app = app.create_admin_user().await;
+    let bystander_app = app.create_admin_user().await;
+    app.login_assert().await;
+    recipient_app.login_assert().await;
+    bystander_app.login_assert().await;
+    let author: Username = app.test_user.username.clone().try_into().unwrap();
+    let recipient: Username = recipient_app.test_user.username.clone().try_into().unwrap();
+    let default_room = ChatRoomName::default_room();
+    let mut author_conn = expect_ok!(app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    recv_chat_msg(&mut author_conn).await; // Initial State
+    let mut recipient_conn = expect_ok!(recipient_app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    recv_chat_msg(&mut recipient_conn).await; // Initial State
+    recv_chat_msg(&mut author_conn).await; // Recipient joined default room
+    let mut bystander_conn = expect_ok!(bystander_app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    recv_chat_msg(&mut bystander_conn).await; // Initial State
+    recv_chat_msg(&mut author_conn).await; // Bystander joined default room
+    recv_chat_msg(&mut recipient_conn).await; // Bystander joined default room
+
+    // Act
+    send_chat_msg(
+        &mut author_conn,
+        &direct_im_msg(&recipient, &author, "direct msg"),
+    );
+    send_chat_msg(
+        &mut author_conn,
+        &im_msg(&default_room, &author, "room msg"),
+    );
+
+    // Assert - Both sides of the conversation get the direct IM
+    let expected_direct = (
+        author.clone(),
+        recipient.clone(),
+        "direct msg".try_into().unwrap(),
+    );
+    assert_eq!(
+        unwrap_direct_im(recv_chat_msg(&mut author_conn).await),
+        expected_direct
+    );
+    assert_eq!(
+        unwrap_direct_im(recv_chat_msg(&mut recipient_conn).await),
+        expected_direct
+    );
+
+    // Assert - The bystander only gets the room IM sent afterwards
+    assert_eq!(
+        unwrap_im(recv_chat_msg(&mut bystander_conn).await),
+        (default_room, "room msg".try_into().unwrap())
+    );
+}
+
+#[tokio::test]
+async fn direct_history_returns_sent_direct_ims() {
+    // Arrange
+    let app = spawn_app().await;
+    let recipient_app = app.create_admin_user().await;
+    app.login_assert().await;
+    recipient_app.login_assert().await;
+    let author: Username = app.test_user.username.clone().try_into().unwrap();
+    let recipient: Username = recipient_app.test_user.username.clone().try_into().unwrap();
+    let expected_ims_texts: Vec<ChatImText> = (1..4)
+        .map(|i| format!("direct msg {i}").try_into().unwrap())
+        .collect();
+    let mut author_conn = expect_ok!(app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    recv_chat_msg(&mut author_conn).await; // Initial State
+    let mut recipient_conn = expect_ok!(recipient_app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    recv_chat_msg(&mut recipient_conn).await; // Initial State
+    for im in expected_ims_texts.iter() {
+        send_chat_msg(
+            &mut author_conn,
+            &ChatMsg::DirectIM(DirectIM {
+                id: Default::default(),
+                recipient: recipient.clone(),
+                author: author.clone(),
+                timestamp: Timestamp::now(),
+                content: im.clone(),
+                state: Default::default(),
+            }),
+        );
+        recv_chat_msg(&mut recipient_conn).await; // Direct IM
+    }
+
+    // Act
+    send_chat_msg(
+        &mut recipient_conn,
+        &ChatMsg::ReqDirectHistory(ReqDirectHistoryBody {
+            other_user: author.clone(),
+            qty: CHAT_HISTORY_REQUEST_SIZE,
+            latest_timestamp: Timestamp::now(),
+        }),
+    );
+
+    // Assert
+    let actual = match recv_chat_msg(&mut recipient_conn).await {
+        ChatMsg::RespDirectHistory(body) => {
+            assert_eq!(body.other_user, author);
+            body.history
+        }
+        other => panic!("expected direct history but got: {other:?}"),
+    };
+    let actual_texts: Vec<ChatImText> = actual.ims.into_iter().map(|x| x.content).collect();
+    assert_eq!(actual_texts, expected_ims_texts);
+}
+
+#[tokio::test]
+async fn direct_im_to_unknown_user_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_assert().await;
+    let author: Username = app.test_user.username.clone().try_into().unwrap();
+    let unknown_user: Username = "no such user".try_into().unwrap();
+    let mut conn = expect_ok!(app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    recv_chat_msg(&mut conn).await; // Initial State
+
+    // Act
+    send_chat_msg(&mut conn, &direct_im_msg(&unknown_user, &author, "hello?"));
+
+    // Assert
+    assert_eq!(
+        recv_chat_msg(&mut conn).await,
+        ChatMsg::DirectError(DirectErrorBody {
+            other_user: unknown_user,
+            message: "User not found".to_string()
+        })
+    );
+}
+
+/// Returns the IM or panics if the message is not an IM
+fn expect_im(chat_msg: ChatMsg) -> ChatIM {
+    match chat_msg {
+        ChatMsg::IM(im) => im,
+        other => panic!("expected IM but got: {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn ims_assigned_increasing_ids() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_assert().await;
+    let author: Username = app.test_user.username.clone().try_into().unwrap();
+    let room = ChatRoomName::default_room();
+    let mut conn = expect_ok!(app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    recv_chat_msg(&mut conn).await; // Initial State
+
+    // Act
+    send_chat_msg(&mut conn, &im_msg(&room, &author, "first"));
+    send_chat_msg(&mut conn, &im_msg(&room, &author, "second"));
+
+    // Assert
+    let first = expect_im(recv_chat_msg(&mut conn).await);
+    let second = expect_im(recv_chat_msg(&mut conn).await);
+    assert_ne!(first.id, ChatImId::default());
+    assert!(first.id < second.id);
+}
+
+#[tokio::test]
+async fn author_can_edit_and_delete_im() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_assert().await;
+    let author: Username = app.test_user.username.clone().try_into().unwrap();
+    let room = ChatRoomName::default_room();
+    let mut conn1 = expect_ok!(app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    recv_chat_msg(&mut conn1).await; // Initial State
+    let mut conn2 = expect_ok!(app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    recv_chat_msg(&mut conn2).await; // Initial State
+    recv_chat_msg(&mut conn1).await; // User joined
+    send_chat_msg(&mut conn1, &im_msg(&room, &author, "original"));
+    let id = expect_im(recv_chat_msg(&mut conn1).await).id;
+    recv_chat_msg(&mut conn2).await; // IM
+    let edit = ChatMsg::Edit(EditImBody {
+        id,
+        content: "edited".try_into().unwrap(),
+    });
+
+    // Act - Edit
+    send_chat_msg(&mut conn1, &edit);
+
+    // Assert - Both connections are notified
+    assert_eq!(recv_chat_msg(&mut conn1).await, edit);
+    assert_eq!(recv_chat_msg(&mut conn2).await, edit);
+
+    // Act - Delete
+    send_chat_msg(&mut conn1, &ChatMsg::Delete(id));
+
+    // Assert - Both connections are notified
+    assert_eq!(recv_chat_msg(&mut conn1).await, ChatMsg::Delete(id));
+    assert_eq!(recv_chat_msg(&mut conn2).await, ChatMsg::Delete(id));
+
+    // Act - Edit after delete
+    send_chat_msg(&mut conn1, &edit);
+
+    // Assert
+    assert_eq!(
+        recv_chat_msg(&mut conn1).await,
+        ChatMsg::EditError(EditErrorBody {
+            id,
+            message: "Message has been deleted".to_string()
+        })
+    );
+
+    // Act - Connect again to get the history
+    let mut conn3 = expect_ok!(app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+
+    // Assert - A tombstone is left in history
+    let history = match recv_chat_msg(&mut conn3).await {
+        ChatMsg::InitialState(initial_state) => initial_state.default_room.history,
+        other => panic!("expected initial state but got: {other:?}"),
+    };
+    let tombstone = history
+        .ims
+        .iter()
+        .find(|im| im.id == id)
+        .expect("deleted IM should still be in history");
+    assert_eq!(tombstone.state, ImState::Deleted);
+    assert!(tombstone.content.is_empty());
+}
+
+#[tokio::test]
+async fn only_author_or_moderator_can_change_im() {
+    // Arrange
+    let app = spawn_app().await;
+    let moderator_app = app.create_admin_user().await;
+    app.login_assert().await;
+    moderator_app.login_assert().await;
+    let author: Username = app.test_user.username.clone().try_into().unwrap();
+    let moderator: Username = moderator_app.test_user.username.clone().try_into().unwrap();
+    let room = ChatRoomName::default_room();
+    let mut user_conn = expect_ok!(app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    recv_chat_msg(&mut user_conn).await; // Initial State
+    let mut moderator_conn = expect_ok!(moderator_app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    recv_chat_msg(&mut moderator_conn).await; // Initial State
+    recv_chat_msg(&mut user_conn).await; // Moderator joined
+    send_chat_msg(
+        &mut moderator_conn,
+        &im_msg(&room, &moderator, "by moderator"),
+    );
+    let moderator_im_id = expect_im(recv_chat_msg(&mut user_conn).await).id;
+    recv_chat_msg(&mut moderator_conn).await; // Own IM
+    send_chat_msg(&mut user_conn, &im_msg(&room, &author, "by user"));
+    let user_im_id = expect_im(recv_chat_msg(&mut user_conn).await).id;
+    recv_chat_msg(&mut moderator_conn).await; // User's IM
+
+    // Act - User without moderator permission deletes someone else's IM
+    send_chat_msg(&mut user_conn, &ChatMsg::Delete(moderator_im_id));
+
+    // Assert
+    assert_eq!(
+        recv_chat_msg(&mut user_conn).await,
+        ChatMsg::EditError(EditErrorBody {
+            id: moderator_im_id,
+            message: "You are not allowed to change this message".to_string()
+        })
+    );
+
+    // Act - Moderator deletes the user's IM
+    send_chat_msg(&mut moderator_conn, &ChatMsg::Delete(user_im_id));
+
+    // Assert
+    assert_eq!(
+        recv_chat_msg(&mut moderator_conn).await,
+        ChatMsg::Delete(user_im_id)
+    );
+    assert_eq!(
+        recv_chat_msg(&mut user_conn).await,
+        ChatMsg::Delete(user_im_id)
+    );
+}
+
+fn rate_limit_settings(
+    connection_capacity: u16,
+    user_capacity: u16,
+    max_consecutive_violations: u16,
+) -> ChatRateLimitSettings {
+    ChatRateLimitSettings {
+        connection: TokenBucketSettings {
+            capacity: connection_capacity,
+            refill_per_sec: 1,
+        },
+        user: TokenBucketSettings {
+            capacity: user_capacity,
+            refill_per_sec: 1,
+        },
+        history_request_cost: 1,
+        max_consecutive_violations,
+    }
+}
+
+#[tokio::test]
+async fn flooding_ims_are_rate_limited() {
+    // Arrange
+    let app =
+        spawn_app_with_configuration(|c| c.custom.chat.rate_limit = rate_limit_settings(3, 0, 0))
+            .await;
+    app.login_assert().await;
+    let author: Username = app.test_user.username.clone().try_into().unwrap();
+    let room = ChatRoomName::default_room();
+    let mut conn = expect_ok!(app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    recv_chat_msg(&mut conn).await; // Initial State
+
+    // Act
+    for i in 0..4 {
+        send_chat_msg(&mut conn, &im_msg(&room, &author, &format!("flood {i}")));
+    }
+
+    // Assert - Order not checked as the notice does not go through the chat server
+    let mut im_count = 0;
+    let mut rate_limited_count = 0;
+    for _ in 0..4 {
+        match recv_chat_msg(&mut conn).await {
+            ChatMsg::IM(_) => im_count += 1,
+            ChatMsg::RateLimited(RateLimitedBody { retry_after }) => {
+                assert!(!retry_after.is_zero());
+                rate_limited_count += 1;
+            }
+            other => panic!("unexpected message: {other:?}"),
+        }
+    }
+    assert_eq!(im_count, 3);
+    assert_eq!(rate_limited_count, 1);
+}
+
+#[tokio::test]
+async fn user_rate_limit_shared_across_connections() {
+    // Arrange
+    let app =
+        spawn_app_with_configuration(|c| c.custom.chat.rate_limit = rate_limit_settings(0, 3, 0))
+            .await;
+    app.login_assert().await;
+    let author: Username = app.test_user.username.clone().try_into().unwrap();
+    let room = ChatRoomName::default_room();
+    let mut conn1 = expect_ok!(app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    recv_chat_msg(&mut conn1).await; // Initial State
+    let mut conn2 = expect_ok!(app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    recv_chat_msg(&mut conn2).await; // Initial State
+
+    // Act - Use up the user's tokens from the first connection
+    for i in 0..3 {
+        send_chat_msg(
+            &mut conn1,
+            &im_msg(&room, &author, &format!("first conn {i}")),
+        );
+    }
+    for _ in 0..3 {
+        unwrap_im(recv_chat_msg(&mut conn2).await);
+    }
+    send_chat_msg(&mut conn2, &im_msg(&room, &author, "second conn"));
+
+    // Assert
+    let actual = recv_chat_msg(&mut conn2).await;
+    assert!(
+        matches!(actual, ChatMsg::RateLimited(_)),
+        "expected rate limited notice but got: {actual:?}"
+    );
+}
+
+#[tokio::test]
+async fn flooding_connection_disconnected() {
+    // Arrange
+    let app =
+        spawn_app_with_configuration(|c| c.custom.chat.rate_limit = rate_limit_settings(1, 0, 2))
+            .await;
+    app.login_assert().await;
+    let author: Username = app.test_user.username.clone().try_into().unwrap();
+    let room = ChatRoomName::default_room();
+    let mut conn = expect_ok!(app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    recv_chat_msg(&mut conn).await; // Initial State
+
+    // Act - One allowed, two limited and the next one goes over the allowance
+    for i in 0..4 {
+        send_chat_msg(&mut conn, &im_msg(&room, &author, &format!("flood {i}")));
+    }
+
+    // Assert - Connection is closed after the messages that were processed
+    let mut rate_limited_count = 0;
+    loop {
+        let event = conn
+            .recv_with_timeout_ignoring_ping(TEST_MSG_WAIT_TIMEOUT)
+            .await
+            .expect("connection was not closed");
+        match event {
+            WsEvent::Message(WsMessage::Text(text)) => match serde_json::from_str(&text).unwrap() {
+                ChatMsg::IM(_) => {}
+                ChatMsg::RateLimited(_) => rate_limited_count += 1,
+                other => panic!("unexpected message: {other:?}"),
+            },
+            WsEvent::Closed => break,
+            other => panic!("unexpected event: {other:?}"),
+        }
+    }
+    assert_eq!(rate_limited_count, 2);
+}
+
+fn search_msg(scope: SearchScope, query: &str, qty: u8) -> SearchBody {
+    SearchBody {
+        scope,
+        query: query.to_string(),
+        author: None,
+        from: None,
+        to: None,
+        qty,
+        before: None,
+    }
+}
+
+/// Returns the search results or panics if the message is not search results
+fn unwrap_search_results(chat_msg: ChatMsg) -> SearchResultsBody {
+    match chat_msg {
+        ChatMsg::SearchResults(results) => results,
+        other => panic!("expected search results but got: {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn search_finds_matching_ims_newest_first() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_assert().await;
+    let author: Username = app.test_user.username.clone().try_into().unwrap();
+    let room = ChatRoomName::default_room();
+    let mut conn = expect_ok!(app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    recv_chat_msg(&mut conn).await; // Initial State
+    for content in ["Apple pie", "banana split", "apple crumble"] {
+        send_chat_msg(&mut conn, &im_msg(&room, &author, content));
+    }
+    for _ in 0..3 {
+        unwrap_im(recv_chat_msg(&mut conn).await);
+    }
+    let req = search_msg(SearchScope::Room(room), "APPLE", 10);
+
+    // Act
+    send_chat_msg(&mut conn, &ChatMsg::Search(req.clone()));
+
+    // Assert
+    let actual = unwrap_search_results(recv_chat_msg(&mut conn).await);
+    assert_eq!(actual.req, req);
+    let contents: Vec<String> = actual
+        .matches
+        .iter()
+        .map(|x| x.content.to_string())
+        .collect();
+    assert_eq!(contents, ["apple crumble", "Apple pie"]);
+    assert!(!actual.has_more);
+}
+
+#[tokio::test]
+async fn search_applies_filters_and_pages() {
+    // Arrange
+    let app = spawn_app_with_configuration(|c| {
+        c.custom.chat.rate_limit = ChatRateLimitSettings::disabled()
+    })
+    .await;
+    app.login_assert().await;
+    let author: Username = app.test_user.username.clone().try_into().unwrap();
+    let other_user: Username = "someone_else".try_into().unwrap();
+    let room = ChatRoomName::default_room();
+    let mut conn = expect_ok!(app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    recv_chat_msg(&mut conn).await; // Initial State
+    for i in 0..3 {
+        send_chat_msg(&mut conn, &im_msg(&room, &author, &format!("report {i}")));
+    }
+    for _ in 0..3 {
+        unwrap_im(recv_chat_msg(&mut conn).await);
+    }
+
+    // Act - First page
+    let req = search_msg(SearchScope::Room(room.clone()), "report", 2);
+    send_chat_msg(&mut conn, &ChatMsg::Search(req.clone()));
+
+    // Assert
+    let first_page = unwrap_search_results(recv_chat_msg(&mut conn).await);
+    assert_eq!(first_page.matches.len(), 2);
+    assert!(first_page.has_more);
+
+    // Act - Next page
+    let next_req = SearchBody {
+        before: Some(first_page.matches.last().unwrap().id),
+        ..req.clone()
+    };
+    send_chat_msg(&mut conn, &ChatMsg::Search(next_req));
+
+    // Assert
+    let second_page = unwrap_search_results(recv_chat_msg(&mut conn).await);
+    assert_eq!(second_page.matches.len(), 1);
+    assert_eq!(second_page.matches[0].content.to_string(), "report 0");
+    assert!(!second_page.has_more);
+
+    // Act - Filter by another author and by a range that excludes everything
+    let by_other_author = SearchBody {
+        author: Some(other_user),
+        ..req.clone()
+    };
+    send_chat_msg(&mut conn, &ChatMsg::Search(by_other_author));
+    let before_sent = SearchBody {
+        to: Some(Timestamp::from(0u32)),
+        ..req
+    };
+    send_chat_msg(&mut conn, &ChatMsg::Search(before_sent));
+
+    // Assert
+    assert!(
+        unwrap_search_results(recv_chat_msg(&mut conn).await)
+            .matches
+            .is_empty()
+    );
+    assert!(
+        unwrap_search_results(recv_chat_msg(&mut conn).await)
+            .matches
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn search_finds_saved_ims() {
+    // Arrange
+    let app = spawn_app_with_configuration(|c| {
+        c.custom.chat.rate_limit = ChatRateLimitSettings::disabled()
+    })
+    .await;
+    app.login_assert().await;
+    let author: Username = app.test_user.username.clone().try_into().unwrap();
+    let room = ChatRoomName::default_room();
+    let mut conn = expect_ok!(app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    recv_chat_msg(&mut conn).await; // Initial State
+
+    // Arrange - Push the first IM out of recent history so it can only be found in
+    // the DB
+    const MSGS_SENT: usize = CHAT_HISTORY_RECENT_CAPACITY + 10;
+    send_chat_msg(&mut conn, &im_msg(&room, &author, "needle in a haystack"));
+    for i in 1..MSGS_SENT {
+        send_chat_msg(&mut conn, &im_msg(&room, &author, &format!("hay {i}")));
+    }
+    for _ in 0..MSGS_SENT {
+        unwrap_im(recv_chat_msg(&mut conn).await);
+    }
+    sleep(Duration::from_millis(500)).await; // Wait for the save to finish
+
+    // Act
+    let req = search_msg(SearchScope::Room(room), "needle", 10);
+    send_chat_msg(&mut conn, &ChatMsg::Search(req));
+
+    // Assert
+    let actual = unwrap_search_results(recv_chat_msg(&mut conn).await);
+    let contents: Vec<String> = actual
+        .matches
+        .iter()
+        .map(|x| x.content.to_string())
+        .collect();
+    assert_eq!(contents, ["needle in a haystack"]);
+}
+
+#[tokio::test]
+async fn search_only_in_own_conversations() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_assert().await;
+    let not_joined: ChatRoomName = "not joined".try_into().unwrap();
+    let mut conn = expect_ok!(app.core_client.ws_connect(
+        PATH_WS_TOKEN_CHAT,
+        TEST_MSG_WAIT_TIMEOUT,
+        no_cb
+    ));
+    recv_chat_msg(&mut conn).await; // Initial State
+
+    // Act
+    let req = search_msg(SearchScope::Room(not_joined.clone()), "anything", 10);
+    send_chat_msg(&mut conn, &ChatMsg::Search(req));
+
+    // Assert
+    assert_eq!(
+        recv_chat_msg(&mut conn).await,
+        ChatMsg::SearchError(SearchErrorBody {
+            scope: SearchScope::Room(not_joined),
+            message: "You are not in this room".to_string()
+        })
+    );
+}
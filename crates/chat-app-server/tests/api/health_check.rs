@@ -1,4 +1,6 @@
 use crate::helpers::spawn_app;
+use std::time::Duration;
+use wykies_client_core::{RequestTimeoutError, RetryPolicy};
 
 #[tokio::test]
 async fn health_check_works() {
@@ -12,3 +14,35 @@ async fn health_check_works() {
     // Using unwrap so error shows instead of asserting `is_ok``
     actual.unwrap();
 }
+
+#[tokio::test]
+async fn timeout_reported_as_timeout_error() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = app
+        .core_client
+        .clone()
+        .with_request_timeout(Duration::from_nanos(1));
+
+    // Act
+    let actual = client.health_check().await.unwrap();
+
+    // Assert
+    assert!(actual.unwrap_err().is::<RequestTimeoutError>());
+}
+
+#[tokio::test]
+async fn health_check_works_with_retry_policy() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = app
+        .core_client
+        .clone()
+        .with_retry_policy(RetryPolicy::default());
+
+    // Act
+    let actual = client.health_check().await.unwrap();
+
+    // Assert
+    actual.unwrap();
+}
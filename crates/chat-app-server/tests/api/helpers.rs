@@ -4,7 +4,7 @@ use std::{
     ops::{Deref, DerefMut},
 };
 use tracked_cancellations::TrackedCancellationToken;
-use wykies_client_core::LoginOutcome;
+use wykies_client_core::{Client, LoginOutcome};
 use wykies_server::{ApiServerBuilder, ApiServerInitBundle, Configuration};
 use wykies_server_test_helper::{
     TEST_MSG_WAIT_TIMEOUT, TestUser, build_test_app, convert_port_to_test_address, expect_ok,
@@ -37,9 +37,37 @@ pub async fn spawn_app() -> TestApp {
     result
 }
 
+/// Same as [`spawn_app`] but allows the configuration to be changed before the
+/// server is started
+pub async fn spawn_app_with_configuration(
+    customize: impl FnOnce(&mut Configuration<CustomConfiguration>),
+) -> TestApp {
+    let result = spawn_app_without_host_branch_stored_with_configuration(customize).await;
+    store_host_branch(&result).await;
+    result
+}
+
 pub async fn spawn_app_without_host_branch_stored() -> TestApp {
-    let (configuration, db_pool) =
+    spawn_app_without_host_branch_stored_with_configuration(|_| {}).await
+}
+
+/// Host that does not have a branch stored
+const OTHER_HOST: &str = "10.1.2.3";
+
+/// Shares the session of the app's client but the requests appear to come from
+/// [`OTHER_HOST`]
+pub fn client_on_other_host(app: &TestApp) -> Client {
+    app.core_client
+        .clone()
+        .expose_internal_with_header("X-Forwarded-For", OTHER_HOST.to_string())
+}
+
+async fn spawn_app_without_host_branch_stored_with_configuration(
+    customize: impl FnOnce(&mut Configuration<CustomConfiguration>),
+) -> TestApp {
+    let (mut configuration, db_pool) =
         spawn_app_without_host_branch_stored_before_migration::<CustomConfiguration>().await;
+    customize(&mut configuration);
     do_migrations(&db_pool).await;
     let application_port = start_server_in_background(configuration.clone(), db_pool).await;
     TestApp(
@@ -109,6 +137,7 @@ impl TestApp {
                 core_client: wykies_client_core::Client::new(self.address.clone()),
                 login_attempt_limit: self.login_attempt_limit,
                 host_branch_pair: self.host_branch_pair.clone(),
+                notifications_folder: self.notifications_folder.clone(),
             },
         )
     }
@@ -1,6 +1,11 @@
-use crate::helpers::spawn_app;
+use crate::helpers::{spawn_app, spawn_app_with_configuration};
 use wykies_client_core::LoginOutcome;
-use wykies_shared::{req_args::LoginReqArgs, uac::AuthError};
+use wykies_shared::{
+    api_error::{ApiError, ApiErrorCode},
+    req_args::LoginReqArgs,
+    uac::AuthError,
+};
+use wykies_time::Seconds;
 
 #[tokio::test]
 async fn login_failure_invalid_user() {
@@ -213,3 +218,178 @@ async fn ensure_locked_out_is_reset() {
         );
     }
 }
+
+#[tokio::test]
+async fn expired_password_forces_password_change() {
+    // Arrange
+    let app = spawn_app_with_configuration(|c| c.user_auth.password_max_age_days = Some(0)).await;
+    app.test_user.clear_force_pass_change_in_db(&app).await;
+
+    // Act
+    let outcome = app.login().await.unwrap();
+
+    // Assert
+    assert_eq!(outcome, LoginOutcome::ForcePasswordChange);
+}
+
+#[tokio::test]
+async fn warned_when_password_expires_soon() {
+    // Arrange
+    let app = spawn_app_with_configuration(|c| {
+        c.user_auth.password_max_age_days = Some(10);
+        c.user_auth.password_expiry_warning_days = 14;
+    })
+    .await;
+    app.test_user.clear_force_pass_change_in_db(&app).await;
+
+    // Act
+    let outcome = app.login().await.unwrap();
+
+    // Assert
+    assert_eq!(outcome, LoginOutcome::Success);
+    assert_eq!(app.core_client.password_expires_in_days(), Some(10));
+}
+
+#[tokio::test]
+async fn no_warning_when_password_not_close_to_expiring() {
+    // Arrange
+    let app = spawn_app_with_configuration(|c| {
+        c.user_auth.password_max_age_days = Some(90);
+        c.user_auth.password_expiry_warning_days = 14;
+    })
+    .await;
+    app.test_user.clear_force_pass_change_in_db(&app).await;
+
+    // Act
+    let outcome = app.login().await.unwrap();
+
+    // Assert
+    assert_eq!(outcome, LoginOutcome::Success);
+    assert_eq!(app.core_client.password_expires_in_days(), None);
+}
+
+#[tokio::test]
+async fn outdated_password_hash_is_upgraded_on_login() {
+    // Arrange
+    let app = spawn_app_with_configuration(|c| c.user_auth.argon2.memory_kib = 19456).await;
+    let hash_before = app.test_user.get_password_hash_from_db(&app).await;
+
+    // Act
+    app.login_assert().await;
+
+    // Assert
+    let hash_after = app.test_user.get_password_hash_from_db(&app).await;
+    assert!(hash_before.contains("m=15000"));
+    assert!(hash_after.contains("m=19456"));
+    app.logout_assert().await;
+    app.login_assert().await;
+}
+
+#[tokio::test]
+async fn lockout_expires_after_lockout_duration() {
+    // Arrange
+    let app = spawn_app_with_configuration(|c| {
+        c.user_auth.lockout_duration_secs = Some(Seconds::new(0));
+    })
+    .await;
+    let login_args = app
+        .test_user
+        .login_args()
+        .password("random-password".to_string().into());
+    for _ in 1..app.login_attempt_limit {
+        let outcome = app.core_client.login(login_args.clone()).await.unwrap();
+        assert_eq!(
+            outcome.unwrap_err().to_string(),
+            AuthError::InvalidUserOrPassword.to_string()
+        );
+    }
+    let outcome = app.core_client.login(login_args).await.unwrap();
+    assert_eq!(
+        outcome.unwrap_err().to_string(),
+        AuthError::LockedOut.to_string()
+    );
+
+    // Act
+    let outcome = app.login().await;
+
+    // Assert
+    assert!(outcome.is_ok(), "lockout should have expired");
+}
+
+#[tokio::test]
+async fn lockout_set_by_admin_does_not_expire() {
+    // Arrange
+    let app = spawn_app_with_configuration(|c| {
+        c.user_auth.lockout_duration_secs = Some(Seconds::new(0));
+    })
+    .await;
+    app.test_user.set_locked_out_in_db(&app, true).await;
+
+    // Act
+    let outcome = app.login().await;
+
+    // Assert
+    assert_eq!(
+        outcome.unwrap_err().to_string(),
+        AuthError::LockedOut.to_string()
+    );
+}
+
+#[tokio::test]
+async fn login_delayed_after_failed_attempt() {
+    // Arrange
+    let app = spawn_app_with_configuration(|c| {
+        c.user_auth.failed_attempt_base_delay_secs = Seconds::new(60);
+        c.user_auth.failed_attempt_max_delay_secs = Seconds::new(60);
+    })
+    .await;
+    let login_args = app
+        .test_user
+        .login_args()
+        .password("random-password".to_string().into());
+    let outcome = app.core_client.login(login_args).await.unwrap();
+    assert_eq!(
+        outcome.unwrap_err().to_string(),
+        AuthError::InvalidUserOrPassword.to_string()
+    );
+
+    // Act - Correct password is still rejected until the delay has passed
+    let actual = app.login().await.unwrap_err();
+
+    // Assert
+    let actual = actual
+        .downcast_ref::<ApiError>()
+        .expect("failed to decode error");
+    assert_eq!(actual.code, ApiErrorCode::TooManyAttempts);
+    assert!(!app.is_logged_in().await);
+}
+
+#[tokio::test]
+async fn host_throttled_after_failures_across_usernames() {
+    // Arrange
+    let host_login_attempt_limit = 3;
+    let app = spawn_app_with_configuration(|c| {
+        c.user_auth.host_login_attempt_limit = host_login_attempt_limit;
+    })
+    .await;
+    for i in 0..host_login_attempt_limit {
+        let login_args = LoginReqArgs::new(
+            format!("random-username{i}"),
+            "random-password".to_string().into(),
+        );
+        let outcome = app.core_client.login(login_args).await.unwrap();
+        assert_eq!(
+            outcome.unwrap_err().to_string(),
+            AuthError::InvalidUserOrPassword.to_string()
+        );
+    }
+
+    // Act - Valid credentials from the same host
+    let actual = app.login().await.unwrap_err();
+
+    // Assert
+    let actual = actual
+        .downcast_ref::<ApiError>()
+        .expect("failed to decode error");
+    assert_eq!(actual.code, ApiErrorCode::TooManyAttempts);
+}
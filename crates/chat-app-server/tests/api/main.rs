@@ -1,12 +1,23 @@
+mod analytics;
+mod api_keys;
+mod audit;
 mod branch;
 mod change_password;
 mod chat;
 mod health_check;
 mod host_branch;
 mod login;
+mod openapi;
+mod password_reset;
 mod permissions;
 mod roles;
+mod session_host;
+#[cfg(feature = "db-session")]
+mod sessions;
+mod status;
+mod totp;
 mod users;
+mod version;
 mod web_sockets;
 
 mod helpers;
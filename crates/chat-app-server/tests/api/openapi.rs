@@ -0,0 +1,58 @@
+use wykies_server_test_helper::expect_ok;
+use wykies_shared::{const_config::path::PATH_API_USER_SESSIONS, uac::default_permissions};
+
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn openapi_document() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let mut actual = expect_ok!(app.core_client.openapi());
+
+    // Remove paths that are only present with some features to keep the snapshot
+    // stable
+    actual["paths"]
+        .as_object_mut()
+        .expect("paths missing")
+        .retain(|path, _| !path.starts_with("/api/user/sessions"));
+
+    // Assert
+    let schemas = actual["components"]["schemas"]
+        .as_object()
+        .expect("schemas missing");
+    for name in ["ApiError", "LoginReqArgs", "UserLookupReqArgs", "Role"] {
+        assert!(schemas.contains_key(name), "schema missing for {name}");
+    }
+    insta::assert_json_snapshot!(actual, {
+        ".info.version" => "[version]",
+        ".components" => "[components]",
+        ".paths.*.*.parameters[].schema" => "[schema]",
+        ".paths.*.*.requestBody.content.*.schema" => "[schema]",
+        ".paths.*.*.responses.*.content.*.schema" => "[schema]"
+    });
+}
+
+#[tokio::test]
+async fn openapi_document_describes_every_protected_path() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let actual = expect_ok!(app.core_client.openapi());
+
+    // Assert
+    let paths = actual["paths"].as_object().expect("paths missing");
+    let mut missing: Vec<&str> = default_permissions()
+        .into_keys()
+        .filter(|path| !paths.contains_key(*path))
+        // Only registered when sessions are stored in the DB
+        .filter(|path| cfg!(feature = "db-session") || *path != PATH_API_USER_SESSIONS.path)
+        .collect();
+    missing.sort_unstable();
+    assert!(
+        missing.is_empty(),
+        "paths missing from the document: {missing:?}"
+    );
+}
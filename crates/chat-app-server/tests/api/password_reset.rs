@@ -0,0 +1,194 @@
+use crate::helpers::{TestApp, spawn_app, spawn_app_with_configuration};
+use secrecy::SecretString;
+use wykies_client_core::LoginOutcome;
+use wykies_server_test_helper::{TEST_MSG_WAIT_TIMEOUT, expect_ok};
+use wykies_shared::{
+    api_error::{ApiError, ApiErrorCode},
+    req_args::password_reset::{RedeemReqArgs, RequestReqArgs},
+    uac::{PasswordComplexity, PasswordResetTokenError, Username},
+};
+
+fn username(app: &TestApp) -> Username {
+    app.test_user.username.clone().try_into().unwrap()
+}
+
+async fn request_reset(app: &TestApp) {
+    expect_ok!(app.core_client.request_password_reset(RequestReqArgs {
+        username: username(app),
+    }));
+}
+
+async fn redeem(app: &TestApp, token: String, new_password: SecretString) -> anyhow::Result<()> {
+    app.core_client
+        .redeem_password_reset(RedeemReqArgs {
+            username: username(app),
+            token: token.into(),
+            new_password,
+        })
+        .await
+        .expect("failed to receive on rx")
+}
+
+#[tokio::test]
+async fn password_reset_round_trip() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user
+        .set_email_in_db(&app, "user@example.com")
+        .await;
+    let new_password = PasswordComplexity::generate_random_password();
+
+    // Act
+    request_reset(&app).await;
+    let token = app
+        .test_user
+        .wait_for_password_reset_token(&app)
+        .await
+        .expect("token should have been sent");
+    redeem(&app, token, new_password.clone()).await.unwrap();
+
+    // Assert - Old password no longer works
+    assert!(app.login().await.is_err());
+
+    // Assert - New password works and no change is forced
+    let login_args = app.test_user.login_args().password(new_password);
+    let outcome = app.core_client.login(login_args).await.unwrap();
+    assert_eq!(outcome.unwrap(), LoginOutcome::Success);
+}
+
+#[tokio::test]
+async fn password_reset_rejects_invalid_token() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user
+        .set_email_in_db(&app, "user@example.com")
+        .await;
+    request_reset(&app).await;
+
+    // Act
+    let actual = redeem(
+        &app,
+        "not-the-token".to_string(),
+        PasswordComplexity::generate_random_password(),
+    )
+    .await
+    .unwrap_err();
+
+    // Assert
+    assert_eq!(
+        actual.to_string(),
+        PasswordResetTokenError::InvalidToken.to_string()
+    );
+    let actual = actual
+        .downcast_ref::<ApiError>()
+        .expect("failed to decode error");
+    assert_eq!(actual.code, ApiErrorCode::ValidationFailed);
+    assert!(actual.field_error("token").is_some());
+}
+
+#[tokio::test]
+async fn password_reset_token_can_only_be_used_once() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user
+        .set_email_in_db(&app, "user@example.com")
+        .await;
+    request_reset(&app).await;
+    let token = app
+        .test_user
+        .wait_for_password_reset_token(&app)
+        .await
+        .unwrap();
+    redeem(
+        &app,
+        token.clone(),
+        PasswordComplexity::generate_random_password(),
+    )
+    .await
+    .unwrap();
+
+    // Act
+    let actual = redeem(&app, token, PasswordComplexity::generate_random_password()).await;
+
+    // Assert
+    assert_eq!(
+        actual.unwrap_err().to_string(),
+        PasswordResetTokenError::InvalidToken.to_string()
+    );
+}
+
+#[tokio::test]
+async fn password_reset_not_sent_without_email() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    request_reset(&app).await;
+
+    // Assert
+    tokio::time::sleep(TEST_MSG_WAIT_TIMEOUT.into()).await;
+    assert!(app.test_user.last_password_reset_token(&app).is_none());
+}
+
+#[tokio::test]
+async fn password_reset_requests_throttled_by_host() {
+    // Arrange
+    let host_login_attempt_limit = 2;
+    let app = spawn_app_with_configuration(|c| {
+        c.user_auth.host_login_attempt_limit = host_login_attempt_limit;
+    })
+    .await;
+    for _ in 0..host_login_attempt_limit {
+        request_reset(&app).await;
+    }
+
+    // Act
+    let actual = app
+        .core_client
+        .request_password_reset(RequestReqArgs {
+            username: username(&app),
+        })
+        .await
+        .expect("failed to receive on rx")
+        .unwrap_err();
+
+    // Assert
+    let actual = actual
+        .downcast_ref::<ApiError>()
+        .expect("failed to decode error");
+    assert_eq!(actual.code, ApiErrorCode::TooManyAttempts);
+}
+
+#[tokio::test]
+async fn password_reset_redeem_throttled_by_host() {
+    // Arrange
+    let host_login_attempt_limit = 2;
+    let app = spawn_app_with_configuration(|c| {
+        c.user_auth.host_login_attempt_limit = host_login_attempt_limit;
+    })
+    .await;
+    for _ in 0..host_login_attempt_limit {
+        redeem(
+            &app,
+            "not-the-token".to_string(),
+            PasswordComplexity::generate_random_password(),
+        )
+        .await
+        .unwrap_err();
+    }
+
+    // Act
+    let actual = redeem(
+        &app,
+        "not-the-token".to_string(),
+        PasswordComplexity::generate_random_password(),
+    )
+    .await
+    .unwrap_err();
+
+    // Assert
+    let actual = actual
+        .downcast_ref::<ApiError>()
+        .expect("failed to decode error");
+    assert_eq!(actual.code, ApiErrorCode::TooManyAttempts);
+}
@@ -1,4 +1,7 @@
-use wykies_shared::uac::{Permission, PermissionsError};
+use wykies_shared::{
+    api_error::{ApiError, ApiErrorCode},
+    uac::{Permission, PermissionsError},
+};
 
 use crate::helpers::spawn_app;
 
@@ -19,6 +22,31 @@ async fn unprivileged_user_is_denied() {
     assert_eq!(actual.unwrap_err().to_string(), expected_error.to_string());
 }
 
+#[tokio::test]
+async fn missing_permissions_are_returned_to_client() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_assert().await;
+
+    // Act
+    let actual = app
+        .core_client
+        .host_branch_pair_list()
+        .await
+        .unwrap()
+        .unwrap_err();
+
+    // Assert
+    let actual = actual
+        .downcast_ref::<ApiError>()
+        .expect("failed to decode error");
+    assert_eq!(actual.code, ApiErrorCode::MissingPermissions);
+    assert_eq!(
+        actual.missing_permissions,
+        vec![Permission::ManHostBranchAssignment]
+    );
+}
+
 #[tokio::test]
 async fn test_admin_user_works() {
     // Arrange
@@ -1,10 +1,22 @@
+use std::collections::BTreeSet;
+
 use crate::helpers::spawn_app;
 use wykies_server_test_helper::expect_ok;
 use wykies_shared::{
+    api_error::{ApiError, ApiErrorCode},
     req_args::api::user::AssignReqArgs,
-    uac::{Permission, Role, RoleDraft},
+    uac::{Permission, Role, RoleDiff, RoleDraft, RoleError, RoleId},
 };
 
+fn role_draft(name: &str, permissions: Vec<Permission>, parent_roles: &[RoleId]) -> RoleDraft {
+    RoleDraft {
+        name: name.to_string().try_into().unwrap(),
+        description: format!("{name} Description").try_into().unwrap(),
+        permissions: permissions.into(),
+        parent_roles: parent_roles.iter().copied().collect(),
+    }
+}
+
 #[tokio::test]
 async fn create_and_assign_role_to_user() {
     // Arrange
@@ -18,6 +30,7 @@ async fn create_and_assign_role_to_user() {
             Permission::RecordDiscrepancy,
         ]
         .into(),
+        parent_roles: BTreeSet::new(),
     };
 
     // Act - Login the admin
@@ -33,6 +46,7 @@ async fn create_and_assign_role_to_user() {
         name: role_draft.name,
         description: role_draft.description,
         permissions: role_draft.permissions,
+        parent_roles: role_draft.parent_roles,
     };
     assert_eq!(role, expected);
 
@@ -60,3 +74,252 @@ async fn create_and_assign_role_to_user() {
     let user = app_normal.core_client.user_info().unwrap();
     assert_eq!(user.permissions, role.permissions);
 }
+
+#[tokio::test]
+async fn inherited_permissions_granted_to_user() {
+    // Arrange
+    let app_normal = spawn_app().await;
+    let app_admin = app_normal.create_admin_user().await;
+    app_admin.login_assert().await;
+    let parent_id = expect_ok!(app_admin.core_client.role_new(&role_draft(
+        "Parent Role",
+        vec![Permission::ViewLog],
+        &[]
+    )));
+    let child_id = expect_ok!(app_admin.core_client.role_new(&role_draft(
+        "Child Role",
+        vec![Permission::RecordDiscrepancy],
+        &[parent_id]
+    )));
+    let req_args = AssignReqArgs {
+        username: app_normal.test_user.username.clone().try_into().unwrap(),
+        role_id: child_id,
+    };
+    expect_ok!(app_admin.core_client.assign_role(&req_args));
+
+    // Act
+    let actual = expect_ok!(
+        app_admin
+            .core_client
+            .user_permissions(req_args.username.clone())
+    );
+
+    // Assert
+    assert!(actual.effective.0.contains(&Permission::ViewLog));
+    assert!(actual.effective.0.contains(&Permission::RecordDiscrepancy));
+    assert!(!actual.direct.0.contains(&Permission::ViewLog));
+    assert!(actual.direct.0.contains(&Permission::RecordDiscrepancy));
+
+    // Act - Login the Normal user
+    app_normal.login_assert().await;
+
+    // Assert - Session uses the effective permissions
+    let user = app_normal.core_client.user_info().unwrap();
+    assert_eq!(user.permissions, actual.effective);
+}
+
+#[tokio::test]
+async fn role_inheritance_cycle_rejected() {
+    // Arrange
+    let app = spawn_app().await.create_admin_user().await;
+    app.login_assert().await;
+    let first_id = expect_ok!(app.core_client.role_new(&role_draft(
+        "First Role",
+        vec![Permission::ViewLog],
+        &[]
+    )));
+    let second_id = expect_ok!(app.core_client.role_new(&role_draft(
+        "Second Role",
+        vec![],
+        &[first_id]
+    )));
+
+    // Act
+    let actual = app
+        .core_client
+        .role_parents_set(first_id, BTreeSet::from([second_id]))
+        .await
+        .unwrap()
+        .unwrap_err();
+
+    // Assert
+    let actual = actual
+        .downcast_ref::<ApiError>()
+        .expect("failed to decode error");
+    assert_eq!(actual.code, ApiErrorCode::ValidationFailed);
+    assert_eq!(actual.field_errors[0].field, "parent_roles");
+    let role = expect_ok!(app.core_client.role_get(first_id));
+    assert!(role.parent_roles.is_empty());
+}
+
+#[tokio::test]
+async fn role_update_changes_only_diff() {
+    // Arrange
+    let app = spawn_app().await.create_admin_user().await;
+    app.login_assert().await;
+    let role_id = expect_ok!(app.core_client.role_new(&role_draft(
+        "Update Role",
+        vec![Permission::ViewLog],
+        &[]
+    )));
+    let parent_id = expect_ok!(app.core_client.role_new(&role_draft(
+        "Parent Role",
+        vec![Permission::ManBranches],
+        &[]
+    )));
+    let original = expect_ok!(app.core_client.role_get(role_id));
+    let mut edited = original.clone();
+    edited.name = "Renamed Role".to_string().try_into().unwrap();
+    edited.permissions = vec![Permission::ViewLog, Permission::RunReports].into();
+    edited.parent_roles = BTreeSet::from([parent_id]);
+    let diff = RoleDiff::from_diff(&original, &edited).unwrap().unwrap();
+
+    // Act
+    expect_ok!(app.core_client.role_update(&diff));
+
+    // Assert
+    let actual = expect_ok!(app.core_client.role_get(role_id));
+    assert_eq!(actual, edited);
+    let roles = expect_ok!(app.core_client.role_list());
+    assert!(roles.contains(&edited));
+}
+
+#[tokio::test]
+async fn role_update_rejects_cycle_without_saving() {
+    // Arrange
+    let app = spawn_app().await.create_admin_user().await;
+    app.login_assert().await;
+    let first_id = expect_ok!(app.core_client.role_new(&role_draft(
+        "First Role",
+        vec![Permission::ViewLog],
+        &[]
+    )));
+    let second_id = expect_ok!(app.core_client.role_new(&role_draft(
+        "Second Role",
+        vec![],
+        &[first_id]
+    )));
+    let original = expect_ok!(app.core_client.role_get(first_id));
+    let mut edited = original.clone();
+    edited.description = "Should not be saved".to_string().try_into().unwrap();
+    edited.parent_roles = BTreeSet::from([second_id]);
+    let diff = RoleDiff::from_diff(&original, &edited).unwrap().unwrap();
+
+    // Act
+    let actual = app.core_client.role_update(&diff).await.unwrap();
+
+    // Assert
+    let expected_error = RoleError::Cycle {
+        role_id: first_id,
+        parent_id: second_id,
+    };
+    assert_eq!(actual.unwrap_err().to_string(), expected_error.to_string());
+    let actual = expect_ok!(app.core_client.role_get(first_id));
+    assert_eq!(actual, original);
+}
+
+#[tokio::test]
+async fn role_update_failure_leaves_parents_unchanged() {
+    // Arrange
+    let app = spawn_app().await.create_admin_user().await;
+    app.login_assert().await;
+    let role_id = expect_ok!(app.core_client.role_new(&role_draft(
+        "Update Role",
+        vec![Permission::ViewLog],
+        &[]
+    )));
+    let other_id = expect_ok!(
+        app.core_client
+            .role_new(&role_draft("Other Role", vec![], &[]))
+    );
+    let parent_id = expect_ok!(app.core_client.role_new(&role_draft(
+        "Parent Role",
+        vec![Permission::ManBranches],
+        &[]
+    )));
+    let other = expect_ok!(app.core_client.role_get(other_id));
+    let original = expect_ok!(app.core_client.role_get(role_id));
+    let mut edited = original.clone();
+    edited.name = other.name; // Names must be unique so saving the row fails
+    edited.parent_roles = BTreeSet::from([parent_id]);
+    let diff = RoleDiff::from_diff(&original, &edited).unwrap().unwrap();
+
+    // Act
+    let actual = app.core_client.role_update(&diff).await.unwrap();
+
+    // Assert
+    assert!(actual.is_err());
+    let actual = expect_ok!(app.core_client.role_get(role_id));
+    assert_eq!(actual, original);
+}
+
+#[tokio::test]
+async fn role_delete_unused_role() {
+    // Arrange
+    let app = spawn_app().await.create_admin_user().await;
+    app.login_assert().await;
+    let role_id = expect_ok!(app.core_client.role_new(&role_draft(
+        "Unused Role",
+        vec![Permission::ViewLog],
+        &[]
+    )));
+
+    // Act
+    expect_ok!(app.core_client.role_delete(role_id));
+
+    // Assert
+    let actual = app.core_client.role_get(role_id).await.unwrap();
+    let actual = actual.unwrap_err();
+    let actual = actual
+        .downcast_ref::<ApiError>()
+        .expect("failed to decode error");
+    assert_eq!(actual.code, ApiErrorCode::NotFound);
+}
+
+#[tokio::test]
+async fn role_delete_rejected_while_assigned_or_inherited() {
+    // Arrange
+    let app_normal = spawn_app().await;
+    let app_admin = app_normal.create_admin_user().await;
+    app_admin.login_assert().await;
+    let parent_id = expect_ok!(app_admin.core_client.role_new(&role_draft(
+        "Parent Role",
+        vec![Permission::ViewLog],
+        &[]
+    )));
+    let child_id = expect_ok!(app_admin.core_client.role_new(&role_draft(
+        "Child Role",
+        vec![],
+        &[parent_id]
+    )));
+    let username = app_normal.test_user.username.clone();
+    let req_args = AssignReqArgs {
+        username: username.clone().try_into().unwrap(),
+        role_id: child_id,
+    };
+    expect_ok!(app_admin.core_client.assign_role(&req_args));
+
+    // Act
+    let actual_assigned = app_admin.core_client.role_delete(child_id).await.unwrap();
+    let actual_inherited = app_admin.core_client.role_delete(parent_id).await.unwrap();
+
+    // Assert
+    let expected_error = RoleError::AssignedToUsers {
+        role_id: child_id,
+        usernames: vec![username],
+    };
+    assert_eq!(
+        actual_assigned.unwrap_err().to_string(),
+        expected_error.to_string()
+    );
+    let expected_error = RoleError::InheritedBy {
+        role_id: parent_id,
+        child_roles: vec![child_id],
+    };
+    assert_eq!(
+        actual_inherited.unwrap_err().to_string(),
+        expected_error.to_string()
+    );
+    expect_ok!(app_admin.core_client.role_get(parent_id));
+    expect_ok!(app_admin.core_client.role_get(child_id));
+}
@@ -0,0 +1,86 @@
+use wykies_server_test_helper::expect_ok;
+use wykies_shared::{
+    api_error::{ApiError, ApiErrorCode},
+    uac::SessionExpiredError,
+};
+
+use crate::helpers::{TestApp, client_on_other_host, spawn_app_with_configuration};
+
+async fn spawn_admin_app(enforce_session_host_binding: bool) -> TestApp {
+    spawn_app_with_configuration(|c| {
+        c.user_auth.enforce_session_host_binding = enforce_session_host_binding;
+    })
+    .await
+    .create_admin_user()
+    .await
+}
+
+fn assert_host_mismatch(actual: anyhow::Result<impl std::fmt::Debug>) {
+    let actual = actual.expect_err("request from another host should be rejected");
+    assert_eq!(
+        actual.downcast_ref::<ApiError>().map(|x| x.code),
+        Some(ApiErrorCode::SessionExpired(
+            SessionExpiredError::HostMismatch
+        ))
+    );
+}
+
+#[tokio::test]
+async fn session_from_other_host_rejected_when_enforced() {
+    // Arrange
+    let app = spawn_admin_app(true).await;
+    app.login_assert().await;
+
+    // Act
+    let actual = client_on_other_host(&app)
+        .list_users_and_roles()
+        .await
+        .expect("failed to receive on rx");
+
+    // Assert
+    assert_host_mismatch(actual);
+}
+
+#[tokio::test]
+async fn session_from_other_host_allowed_when_not_enforced() {
+    // Arrange
+    let app = spawn_admin_app(false).await;
+    app.login_assert().await;
+
+    // Act
+    let actual = client_on_other_host(&app)
+        .list_users_and_roles()
+        .await
+        .expect("failed to receive on rx");
+
+    // Assert - Only logged
+    assert!(actual.is_ok());
+}
+
+#[tokio::test]
+async fn session_bound_to_host_used_for_login() {
+    // Arrange
+    let app = spawn_admin_app(true).await;
+    let other_host_client = client_on_other_host(&app);
+    // The other host does not have a branch yet
+    let login_args = app
+        .test_user
+        .login_args()
+        .branch_to_set(Some(app.host_branch_pair.branch_id));
+    assert!(expect_ok!(other_host_client.login(login_args)).is_any_success());
+
+    // Act
+    let from_login_host = other_host_client
+        .list_users_and_roles()
+        .await
+        .expect("failed to receive on rx");
+    let from_original_host = app
+        .core_client
+        .list_users_and_roles()
+        .await
+        .expect("failed to receive on rx");
+
+    // Assert
+    assert!(from_login_host.is_ok());
+    assert_host_mismatch(from_original_host);
+}
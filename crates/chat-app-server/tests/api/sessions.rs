@@ -0,0 +1,84 @@
+use wykies_server_test_helper::expect_ok;
+use wykies_shared::uac::Username;
+
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn list_sessions_includes_logged_in_user() {
+    // Arrange
+    let app = spawn_app().await.create_admin_user().await;
+    app.login_assert().await;
+    let username: Username = app.test_user.username.clone().try_into().unwrap();
+
+    // Act
+    let actual = expect_ok!(app.core_client.user_sessions(username.clone()));
+
+    // Assert
+    assert_eq!(actual.len(), 1);
+    assert_eq!(actual[0].username, username);
+    assert!(actual[0].created < actual[0].expires);
+}
+
+#[tokio::test]
+async fn revoke_all_sessions_logs_out_user() {
+    // Arrange
+    let app = spawn_app().await;
+    let admin = app.create_admin_user().await;
+    admin.login_assert().await;
+    app.login_assert().await;
+    assert!(app.is_logged_in().await);
+    let username: Username = app.test_user.username.clone().try_into().unwrap();
+
+    // Act
+    expect_ok!(
+        admin
+            .core_client
+            .user_sessions_revoke(username.clone(), None)
+    );
+
+    // Assert
+    assert!(!app.is_logged_in().await);
+    let sessions = expect_ok!(admin.core_client.user_sessions(username));
+    assert!(sessions.is_empty());
+}
+
+#[tokio::test]
+async fn revoke_single_session() {
+    // Arrange
+    let app = spawn_app().await;
+    let admin = app.create_admin_user().await;
+    admin.login_assert().await;
+    app.login_assert().await;
+    let username: Username = app.test_user.username.clone().try_into().unwrap();
+    let sessions = expect_ok!(admin.core_client.user_sessions(username.clone()));
+    assert_eq!(sessions.len(), 1);
+
+    // Act
+    expect_ok!(
+        admin
+            .core_client
+            .user_sessions_revoke(username, Some(sessions[0].id))
+    );
+
+    // Assert
+    assert!(!app.is_logged_in().await);
+    assert!(admin.is_logged_in().await);
+}
+
+#[tokio::test]
+async fn revoke_unknown_session_fails() {
+    // Arrange
+    let app = spawn_app().await.create_admin_user().await;
+    app.login_assert().await;
+    let username: Username = app.test_user.username.clone().try_into().unwrap();
+
+    // Act
+    let actual = app
+        .core_client
+        .user_sessions_revoke(username, Some(999_999.into()))
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(actual.is_err());
+}
@@ -0,0 +1,23 @@
+use wykies_server_test_helper::expect_ok;
+use wykies_shared::health::HealthStatus;
+
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn status_reports_server_and_plugin_checks() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let actual = expect_ok!(app.core_client.status());
+
+    // Assert
+    let db_check = actual
+        .checks
+        .iter()
+        .find(|x| x.name == "Connect to Database")
+        .expect("database check missing");
+    assert_eq!(db_check.status, HealthStatus::Ok);
+    assert!(actual.checks.iter().any(|x| x.name == "Chat Server"));
+    assert!(actual.status >= db_check.status);
+}
@@ -0,0 +1,261 @@
+use secrecy::SecretString;
+use totp_rs::TOTP;
+use wykies_client_core::LoginOutcome;
+use wykies_server_test_helper::expect_ok;
+use wykies_shared::{
+    api_error::{ApiError, ApiErrorCode},
+    uac::{TotpEnrolment, TotpRecoveryCodes, Username},
+};
+
+use crate::helpers::{TestApp, client_on_other_host, spawn_app, spawn_app_with_configuration};
+
+fn current_code(enrolment: &TotpEnrolment) -> SecretString {
+    TOTP::from_url(&enrolment.provisioning_uri)
+        .expect("invalid provisioning uri")
+        .generate_current()
+        .expect("failed to generate code")
+        .into()
+}
+
+/// Code for the step after the current one, which is still accepted because of
+/// the allowed clock drift. Used as the current code cannot be used again once
+/// it was accepted
+fn next_code(enrolment: &TotpEnrolment) -> SecretString {
+    let totp = TOTP::from_url(&enrolment.provisioning_uri).expect("invalid provisioning uri");
+    let next_step_time = totp.next_step_current().expect("failed to get next step");
+    totp.generate(next_step_time).into()
+}
+
+/// Enrols the logged in user and returns the enrolment and recovery codes
+async fn enrol(app: &TestApp) -> (TotpEnrolment, TotpRecoveryCodes) {
+    let enrolment = expect_ok!(app.core_client.totp_enrol());
+    let recovery_codes = expect_ok!(app.core_client.totp_confirm(current_code(&enrolment)));
+    (enrolment, recovery_codes)
+}
+
+async fn login_with_code(app: &TestApp, code: SecretString) -> anyhow::Result<LoginOutcome> {
+    app.core_client
+        .login(app.test_user.login_args().totp_code(Some(code)))
+        .await
+        .expect("failed to receive on rx")
+}
+
+#[tokio::test]
+async fn login_requires_code_after_enrolment() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_assert().await;
+    let (enrolment, recovery_codes) = enrol(&app).await;
+    assert_eq!(recovery_codes.codes.len(), 10);
+    app.logout_assert().await;
+
+    // Act
+    let without_code = app.login().await.unwrap();
+    let with_code = login_with_code(&app, next_code(&enrolment)).await.unwrap();
+
+    // Assert
+    assert_eq!(without_code, LoginOutcome::TotpRequired);
+    assert!(with_code.is_any_success());
+    assert!(app.is_logged_in().await);
+}
+
+#[tokio::test]
+async fn login_with_invalid_code_fails() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_assert().await;
+    enrol(&app).await;
+    app.logout_assert().await;
+
+    // Act
+    let actual = login_with_code(&app, "000000".to_string().into())
+        .await
+        .expect_err("login should fail with an invalid code");
+
+    // Assert
+    let actual = actual
+        .downcast_ref::<ApiError>()
+        .expect("failed to decode error");
+    assert_eq!(actual.code, ApiErrorCode::InvalidTotpCode);
+    assert!(!app.is_logged_in().await);
+}
+
+#[tokio::test]
+async fn confirm_with_invalid_code_fails() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_assert().await;
+    expect_ok!(app.core_client.totp_enrol());
+
+    // Act
+    let actual = app
+        .core_client
+        .totp_confirm("not a code".to_string().into())
+        .await
+        .expect("failed to receive on rx")
+        .expect_err("confirm should fail with an invalid code");
+
+    // Assert
+    let actual = actual
+        .downcast_ref::<ApiError>()
+        .expect("failed to decode error");
+    assert!(actual.field_error("code").is_some());
+    app.logout_assert().await;
+    assert!(app.login().await.unwrap().is_any_success());
+}
+
+#[tokio::test]
+async fn recovery_code_can_only_be_used_once() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_assert().await;
+    let (_, recovery_codes) = enrol(&app).await;
+    app.logout_assert().await;
+    let recovery_code: SecretString = recovery_codes.codes[0].clone().into();
+
+    // Act
+    let first = login_with_code(&app, recovery_code.clone()).await.unwrap();
+    app.logout_assert().await;
+    let second = login_with_code(&app, recovery_code).await;
+
+    // Assert
+    assert!(first.is_any_success());
+    let second = second.expect_err("recovery code should not work twice");
+    assert_eq!(
+        second.downcast_ref::<ApiError>().map(|x| x.code),
+        Some(ApiErrorCode::InvalidTotpCode)
+    );
+}
+
+#[tokio::test]
+async fn admin_reset_removes_code_requirement() {
+    // Arrange
+    let app = spawn_app().await;
+    let admin = app.create_admin_user().await;
+    app.login_assert().await;
+    enrol(&app).await;
+    app.logout_assert().await;
+    admin.login_assert().await;
+    let username: Username = app.test_user.username.clone().try_into().unwrap();
+
+    // Act
+    expect_ok!(admin.core_client.user_totp_reset(username));
+
+    // Assert
+    assert!(app.login().await.unwrap().is_any_success());
+}
+
+#[tokio::test]
+async fn man_uac_users_must_enrol_before_using_the_api() {
+    // Arrange
+    let app = spawn_app_with_configuration(|c| c.user_auth.totp_required_for_man_uac = true)
+        .await
+        .create_admin_user()
+        .await;
+
+    // Act
+    let login_outcome = app.login().await.unwrap();
+    let before_enrolment = app
+        .core_client
+        .list_users_and_roles()
+        .await
+        .expect("failed to receive on rx");
+    enrol(&app).await;
+    let after_enrolment = app
+        .core_client
+        .list_users_and_roles()
+        .await
+        .expect("failed to receive on rx");
+
+    // Assert
+    assert_eq!(login_outcome, LoginOutcome::TotpEnrolmentRequired);
+    let before_enrolment = before_enrolment.expect_err("should not be allowed before enrolment");
+    assert_eq!(
+        before_enrolment.downcast_ref::<ApiError>().map(|x| x.code),
+        Some(ApiErrorCode::TotpEnrolmentRequired)
+    );
+    assert!(after_enrolment.is_ok());
+}
+
+#[tokio::test]
+async fn code_cannot_be_used_twice() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_assert().await;
+    let (enrolment, _) = enrol(&app).await;
+    app.logout_assert().await;
+    let code = next_code(&enrolment);
+    assert!(
+        login_with_code(&app, code.clone())
+            .await
+            .unwrap()
+            .is_any_success()
+    );
+    app.logout_assert().await;
+
+    // Act
+    let actual = login_with_code(&app, code).await;
+
+    // Assert
+    let actual = actual.expect_err("code should not work twice");
+    assert_eq!(
+        actual.downcast_ref::<ApiError>().map(|x| x.code),
+        Some(ApiErrorCode::InvalidTotpCode)
+    );
+}
+
+#[tokio::test]
+async fn code_can_be_resent_to_set_branch() {
+    // Arrange
+    let app = spawn_app().await.create_admin_user().await;
+    app.login_assert().await;
+    let (enrolment, _) = enrol(&app).await;
+    app.logout_assert().await;
+    let other_host_client = client_on_other_host(&app);
+    let code = next_code(&enrolment);
+
+    // Act
+    let first = expect_ok!(
+        other_host_client.login(app.test_user.login_args().totp_code(Some(code.clone())))
+    );
+    let resend = expect_ok!(
+        other_host_client.login(
+            app.test_user
+                .login_args()
+                .totp_code(Some(code.clone()))
+                .branch_to_set(Some(app.host_branch_pair.branch_id))
+        )
+    );
+    expect_ok!(other_host_client.logout());
+    let after_login = login_with_code(&app, code).await;
+
+    // Assert
+    assert_eq!(first, LoginOutcome::RetryWithBranchSet);
+    assert!(resend.is_any_success());
+    let after_login = after_login.expect_err("code should not work after the login succeeded");
+    assert_eq!(
+        after_login.downcast_ref::<ApiError>().map(|x| x.code),
+        Some(ApiErrorCode::InvalidTotpCode)
+    );
+}
+
+#[tokio::test]
+async fn enrolment_code_cannot_be_used_to_login() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_assert().await;
+    let enrolment = expect_ok!(app.core_client.totp_enrol());
+    let code = current_code(&enrolment);
+    expect_ok!(app.core_client.totp_confirm(code.clone()));
+    app.logout_assert().await;
+
+    // Act
+    let actual = login_with_code(&app, code).await;
+
+    // Assert
+    let actual = actual.expect_err("code used for enrolment should not work for login");
+    assert_eq!(
+        actual.downcast_ref::<ApiError>().map(|x| x.code),
+        Some(ApiErrorCode::InvalidTotpCode)
+    );
+}
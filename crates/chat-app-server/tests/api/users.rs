@@ -1,13 +1,19 @@
+use std::collections::BTreeSet;
+
 use secrecy::{ExposeSecret, SecretString};
 use uuid::Uuid;
 use wykies_client_core::LoginOutcome;
 use wykies_server_test_helper::expect_ok;
 use wykies_shared::{
+    api_error::{ApiError, ApiErrorCode},
     req_args::{
         LoginReqArgs,
         api::user::{NewUserReqArgs, PasswordResetReqArgs},
     },
-    uac::{PasswordComplexity, ResetPasswordError, UserMetadata, UserMetadataDiff, Username},
+    uac::{
+        PasswordComplexity, ResetPasswordError, SessionExpiredError, UserMetadata,
+        UserMetadataDiff, Username,
+    },
 };
 
 use crate::helpers::spawn_app;
@@ -70,9 +76,9 @@ async fn user_update_force_pass_change() {
 }
 
 #[tokio::test]
-async fn user_update_assigned_role() {
+async fn user_update_assigned_roles() {
     common_update_user_test(|mut user| {
-        user.assigned_role = None;
+        user.assigned_roles = BTreeSet::from([1.into()]);
         user
     })
     .await
@@ -101,7 +107,7 @@ async fn user_update_locked_out() {
 async fn user_update_all() {
     common_update_user_test(|mut user| {
         user.display_name = "All Changed".to_string().try_into().unwrap();
-        user.assigned_role = Some(1.into());
+        user.assigned_roles = BTreeSet::from([1.into()]);
         user.enabled = false;
         user.force_pass_change = false;
         user.locked_out = true;
@@ -112,13 +118,15 @@ async fn user_update_all() {
 }
 
 async fn common_update_user_test(f: impl FnOnce(UserMetadata) -> UserMetadata) {
-    // Arrange
-    let app = spawn_app().await.create_admin_user().await;
-    app.login_assert().await;
+    // Arrange -- Edit another user as disabling a user also ends their sessions
+    let app = spawn_app().await;
+    let admin_app = app.create_admin_user().await;
+    admin_app.login_assert().await;
 
     // Arrange -- Get User from DB
     let original_user = expect_ok!(
-        app.core_client
+        admin_app
+            .core_client
             .user_get(app.test_user.username.clone().try_into().unwrap())
     );
 
@@ -131,11 +139,12 @@ async fn common_update_user_test(f: impl FnOnce(UserMetadata) -> UserMetadata) {
         .expect("no difference found");
 
     // Act -- Push change
-    expect_ok!(app.core_client.update_user(diff));
+    expect_ok!(admin_app.core_client.update_user(diff));
 
     // Act -- Get updated user
     let actual = expect_ok!(
-        app.core_client
+        admin_app
+            .core_client
             .user_get(app.test_user.username.clone().try_into().unwrap())
     );
 
@@ -154,7 +163,8 @@ async fn new_user() {
         username: username.clone(),
         display_name: "Display New".to_string().try_into().unwrap(),
         password: password.clone(),
-        assigned_role: None,
+        assigned_roles: BTreeSet::new(),
+        email: Some("new.user@example.com".try_into().unwrap()),
     };
 
     // Act
@@ -167,11 +177,12 @@ async fn new_user() {
         username: req_args.username,
         display_name: req_args.display_name,
         force_pass_change: true,
-        assigned_role: req_args.assigned_role,
+        assigned_roles: req_args.assigned_roles,
         enabled: true,
         locked_out: false,
         failed_attempts: 0,
         pass_change_date: chrono::Utc::now().date_naive(),
+        email: req_args.email,
     };
     assert_eq!(actual, expected);
 
@@ -237,3 +248,80 @@ async fn password_reset_blocked_same_user() {
         ResetPasswordError::NoResetOwnPassword.to_string()
     );
 }
+
+#[tokio::test]
+async fn password_reset_logs_out_user() {
+    // Arrange
+    let app_normal = spawn_app().await;
+    let app_admin = app_normal.create_admin_user().await;
+    let password_reset_req_args = PasswordResetReqArgs {
+        username: app_normal.test_user.username.clone().try_into().unwrap(),
+        new_password: PasswordComplexity::generate_random_password(),
+    };
+    app_admin.login_assert().await;
+    app_normal.login_assert().await;
+    assert!(app_normal.is_logged_in().await);
+
+    // Act
+    expect_ok!(
+        app_admin
+            .core_client
+            .reset_password(password_reset_req_args)
+    );
+
+    // Assert
+    assert!(!app_normal.is_logged_in().await);
+}
+
+#[tokio::test]
+async fn revoke_all_sessions_logs_out_user() {
+    // Arrange
+    let app = spawn_app().await;
+    let admin = app.create_admin_user().await;
+    admin.login_assert().await;
+    app.login_assert().await;
+    assert!(app.is_logged_in().await);
+
+    // Act
+    expect_ok!(
+        admin
+            .core_client
+            .user_sessions_revoke(app.test_user.username.clone().try_into().unwrap(), None)
+    );
+
+    // Assert
+    assert!(!app.is_logged_in().await);
+    assert!(admin.is_logged_in().await);
+}
+
+#[tokio::test]
+async fn invalidated_session_reports_reason() {
+    // Arrange
+    let app = spawn_app().await;
+    let admin = app.create_admin_user().await;
+    let other_admin = app.create_admin_user().await;
+    admin.login_assert().await;
+    other_admin.login_assert().await;
+    expect_ok!(admin.core_client.user_sessions_revoke(
+        other_admin.test_user.username.clone().try_into().unwrap(),
+        None
+    ));
+
+    // Act
+    let actual = other_admin
+        .core_client
+        .list_users_and_roles()
+        .await
+        .expect("failed to receive on rx")
+        .expect_err("failed to extract error");
+
+    // Assert
+    let actual = actual
+        .downcast_ref::<ApiError>()
+        .expect("failed to decode error");
+    assert_eq!(
+        actual.code,
+        ApiErrorCode::SessionExpired(SessionExpiredError::Invalidated)
+    );
+    assert!(actual.is_login_required());
+}
@@ -0,0 +1,43 @@
+use plugin_chat::consts::CHAT_PLUGIN_ID;
+use wykies_server_test_helper::expect_ok;
+use wykies_shared::version::CompatibilityOutcome;
+
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn same_version_is_compatible() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let actual = expect_ok!(
+        app.core_client
+            .check_compatibility(env!("CARGO_PKG_VERSION"), &[CHAT_PLUGIN_ID])
+    );
+
+    // Assert
+    assert_eq!(actual, CompatibilityOutcome::Compatible);
+    assert_eq!(app.core_client.compatibility(), Some(actual));
+}
+
+#[tokio::test]
+async fn login_refused_if_plugin_missing() {
+    // Arrange
+    let app = spawn_app().await;
+    let outcome = expect_ok!(
+        app.core_client
+            .check_compatibility(env!("CARGO_PKG_VERSION"), &["not-a-plugin"])
+    );
+    assert!(matches!(outcome, CompatibilityOutcome::Refuse(_)));
+
+    // Act
+    let actual = app
+        .core_client
+        .login(app.test_user.login_args())
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(actual.is_err());
+    assert!(!app.core_client.is_logged_in());
+}
@@ -3,6 +3,8 @@
 
 use wykies_time::Seconds;
 
+/// Identifies the plugin when checking if the server supports it
+pub const CHAT_PLUGIN_ID: &str = "chat";
 pub const CHAT_HISTORY_RECENT_CAPACITY: usize = 100;
 pub const CHAT_HISTORY_REQUEST_SIZE: u8 = 50;
 /// This controls the max number of messages buffered before saving to the
@@ -12,9 +14,15 @@ pub const CHAT_HISTORY_REQUEST_SIZE: u8 = 50;
 /// otherwise some history will not be accessible to recently joined users
 pub const CHAT_MAX_IMS_BEFORE_SAVE: u8 = 80;
 pub const CHAT_MAX_TIME_BEFORE_SAVE: Seconds = Seconds::new(20);
-// TODO 6: Rate limit on server end
+/// Client side only, the server limits history requests through its rate limit
+/// settings
 pub const CHAT_MIN_TIME_BETWEEN_HISTORY_REQUESTS: Seconds = Seconds::new(5);
 pub const CHAT_SYSTEM_USERNAME: &str = "System";
+/// The room every connection is placed in when it connects. It is created by
+/// the migrations and is visible to everyone
+///
+/// NOTE: Must match the default value of the room column in the migrations
+pub const CHAT_DEFAULT_ROOM: &str = "general";
 
 #[cfg(test)]
 mod tests {
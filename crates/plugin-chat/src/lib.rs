@@ -6,5 +6,9 @@ mod msg_types;
 pub mod server_only;
 
 pub use msg_types::{
-    ChatIM, ChatImText, ChatMsg, ChatMsgsHistory, ChatUser, InitialStateBody, ReqHistoryBody,
+    ChatIM, ChatImId, ChatImText, ChatMsg, ChatMsgsHistory, ChatRoomInfo, ChatRoomName,
+    ChatRoomUser, ChatUser, DirectErrorBody, DirectIM, DirectMsgsHistory, EditErrorBody,
+    EditImBody, HistoryIm, ImState, InitialStateBody, RateLimitedBody, ReqDirectHistoryBody,
+    ReqHistoryBody, RespDirectHistoryBody, RespHistoryBody, RoomErrorBody, RoomStateBody,
+    RoomVisibility, SearchBody, SearchErrorBody, SearchMatch, SearchResultsBody, SearchScope,
 };
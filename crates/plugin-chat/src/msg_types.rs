@@ -1,33 +1,97 @@
-use anyhow::{Context, bail};
+use anyhow::bail;
 use std::fmt::Display;
 #[cfg(feature = "server_only")]
 use wykies_shared::db_types::Db;
-use wykies_shared::{AlwaysCase, errors::ConversionError, string_wrapper, uac::Username};
-use wykies_time::Timestamp;
+use wykies_shared::{
+    AlwaysCase,
+    branch::BranchId,
+    errors::ConversionError,
+    string_wrapper,
+    uac::{Permission, UserInfo, Username},
+};
+use wykies_time::{Seconds, Timestamp};
 
-string_wrapper!(ChatImText, 255, AlwaysCase::Any);
-
-impl TryFrom<Vec<u8>> for ChatImText {
-    type Error = anyhow::Error;
+use crate::consts::CHAT_DEFAULT_ROOM;
 
-    fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
-        String::from_utf8(value)
-            .context("failed to convert byte array into String")?
-            .trim_matches(char::from(0))
-            .try_into()
-            .context("failed to convert string into ChatImText")
-    }
-}
+string_wrapper!(ChatImText, 255, AlwaysCase::Any);
+string_wrapper!(ChatRoomName, 32, AlwaysCase::Lower);
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
 /// Messages sent between the server and client
 pub enum ChatMsg {
-    UserJoined(ChatUser),
-    UserLeft(ChatUser),
+    UserJoined(ChatRoomUser),
+    UserLeft(ChatRoomUser),
     IM(ChatIM),
     InitialState(InitialStateBody),
     ReqHistory(ReqHistoryBody),
-    RespHistory(ChatMsgsHistory),
+    RespHistory(RespHistoryBody),
+    /// Sent by the client to create a room (creator joins it automatically)
+    CreateRoom(ChatRoomInfo),
+    /// Sent by the server to each client that is able to see the new room
+    RoomCreated(ChatRoomInfo),
+    JoinRoom(ChatRoomName),
+    /// Sent by the server to the client that joined the room
+    RoomJoined(RoomStateBody),
+    LeaveRoom(ChatRoomName),
+    /// Sent by the server to the client that left the room
+    RoomLeft(ChatRoomName),
+    /// Sent by the server when a room command or IM from this client could not
+    /// be processed
+    RoomError(RoomErrorBody),
+    /// Only sent to the connections of the author and the recipient
+    DirectIM(DirectIM),
+    ReqDirectHistory(ReqDirectHistoryBody),
+    RespDirectHistory(RespDirectHistoryBody),
+    /// Sent by the server when a direct IM or history request from this client
+    /// could not be processed
+    DirectError(DirectErrorBody),
+    /// Sent by the client to edit an IM and by the server to the connections
+    /// that received the IM once the edit is applied
+    Edit(EditImBody),
+    /// Sent by the client to delete an IM and by the server to the connections
+    /// that received the IM once it has been replaced by a tombstone
+    Delete(ChatImId),
+    /// Sent by the server when an edit or delete from this client could not be
+    /// processed
+    EditError(EditErrorBody),
+    /// Sent by the server when a message from this client was dropped because
+    /// it is sending too fast
+    RateLimited(RateLimitedBody),
+    /// Sent by the client to search the saved history of a room it is in or of
+    /// one of its direct message conversations
+    Search(SearchBody),
+    /// Sent by the server with the matches of a search (newest first)
+    SearchResults(SearchResultsBody),
+    /// Sent by the server when a search could not be done
+    SearchError(SearchErrorBody),
+}
+
+/// Identifies an IM (room or direct). Assigned by the server, the value sent by
+/// the client is ignored. Zero is never assigned so it is used for IMs that
+/// have not been assigned an ID (such as local system messages)
+#[derive(
+    Debug,
+    Default,
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+)]
+pub struct ChatImId(u64);
+
+/// Whether an IM has been changed since it was sent
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ImState {
+    #[default]
+    Original,
+    Edited,
+    /// The content has been removed but the IM is kept so history has no gaps
+    Deleted,
 }
 
 #[derive(
@@ -35,23 +99,69 @@ pub enum ChatMsg {
 )]
 pub struct ChatUser(Username);
 
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+pub struct ChatRoomUser {
+    pub room: ChatRoomName,
+    pub user: ChatUser,
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
 pub struct ChatIM {
+    pub id: ChatImId,
+    pub room: ChatRoomName,
     pub author: Username,
     pub timestamp: Timestamp,
     pub content: ChatImText,
+    pub state: ImState,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+pub struct DirectIM {
+    pub id: ChatImId,
+    pub recipient: Username,
+    pub author: Username,
+    pub timestamp: Timestamp,
+    pub content: ChatImText,
+    pub state: ImState,
+}
+
+/// Controls which users are able to see (and therefore join) a room
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+pub enum RoomVisibility {
+    #[default]
+    Everyone,
+    /// Only users with this permission
+    Permission(Permission),
+    /// Only users logged in at this branch
+    Branch(BranchId),
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+pub struct ChatRoomInfo {
+    pub name: ChatRoomName,
+    pub visibility: RoomVisibility,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
 pub struct InitialStateBody {
-    /// The users connected right now including their multiplicity (saturates at
-    /// 256)
+    /// The rooms this user is able to see
+    pub rooms: Vec<ChatRoomInfo>,
+    /// The state of the default room which all connections join on connect
+    pub default_room: RoomStateBody,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+pub struct RoomStateBody {
+    pub room: ChatRoomName,
+    /// The users in the room right now including their multiplicity (saturates
+    /// at 256)
     pub connected_users: Vec<(ChatUser, u8)>,
     pub history: ChatMsgsHistory,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
 pub struct ReqHistoryBody {
+    pub room: ChatRoomName,
     /// We use a u8 so no matter what value the client sets it will always be
     /// reasonable
     pub qty: u8,
@@ -61,29 +171,322 @@ pub struct ReqHistoryBody {
     pub latest_timestamp: Timestamp,
 }
 
-#[derive(Debug, Default, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
-pub struct ChatMsgsHistory {
-    pub ims: Vec<ChatIM>,
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+pub struct RespHistoryBody {
+    pub room: ChatRoomName,
+    pub history: ChatMsgsHistory,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+pub struct RoomErrorBody {
+    pub room: ChatRoomName,
+    pub message: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+pub struct ReqDirectHistoryBody {
+    /// The user on the other side of the conversation
+    pub other_user: Username,
+    /// See [`ReqHistoryBody::qty`]
+    pub qty: u8,
+    /// See [`ReqHistoryBody::latest_timestamp`]
+    pub latest_timestamp: Timestamp,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+pub struct RespDirectHistoryBody {
+    pub other_user: Username,
+    pub history: DirectMsgsHistory,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+pub struct DirectErrorBody {
+    pub other_user: Username,
+    pub message: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+pub struct EditImBody {
+    pub id: ChatImId,
+    pub content: ChatImText,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+pub struct EditErrorBody {
+    pub id: ChatImId,
+    pub message: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+pub struct RateLimitedBody {
+    /// Time until the message would have been accepted
+    pub retry_after: Seconds,
+}
+
+/// The conversation to search
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+pub enum SearchScope {
+    Room(ChatRoomName),
+    /// The direct messages with this user
+    Direct(Username),
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+pub struct SearchBody {
+    pub scope: SearchScope,
+    /// Only IMs that contain all of the words in the query match (See
+    /// [`SearchBody::terms`])
+    pub query: String,
+    pub author: Option<Username>,
+    /// The earliest timestamp allowed in the results
+    pub from: Option<Timestamp>,
+    /// The latest timestamp allowed in the results
+    pub to: Option<Timestamp>,
+    /// See [`ReqHistoryBody::qty`]
+    pub qty: u8,
+    /// Only IMs older than this one are included (Used to get the next page of
+    /// results)
+    pub before: Option<ChatImId>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+pub struct SearchResultsBody {
+    /// The search the results are for
+    pub req: SearchBody,
+    pub matches: Vec<SearchMatch>,
+    /// True if there are more matches older than the last one included
+    pub has_more: bool,
+}
+
+/// An IM that matched a search (Includes what is needed to find it in history)
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+pub struct SearchMatch {
+    pub id: ChatImId,
+    pub author: Username,
+    pub timestamp: Timestamp,
+    pub content: ChatImText,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+pub struct SearchErrorBody {
+    pub scope: SearchScope,
+    pub message: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+pub struct ChatMsgsHistory<T = ChatIM> {
+    pub ims: Vec<T>,
+}
+
+pub type DirectMsgsHistory = ChatMsgsHistory<DirectIM>;
+
+/// The parts of an IM needed to keep and show history
+pub trait HistoryIm {
+    fn id(&self) -> ChatImId;
+    fn author(&self) -> &Username;
+    fn timestamp(&self) -> Timestamp;
+    fn content(&self) -> &ChatImText;
+    fn state(&self) -> ImState;
+    /// Replaces the content and marks the IM as edited
+    fn edit(&mut self, content: ChatImText);
+    /// Removes the content and marks the IM as deleted
+    fn delete(&mut self);
+}
+
+impl ChatImId {
+    pub fn new(value: u64) -> Self {
+        Self(value)
+    }
+
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns the ID that follows this one
+    pub fn next(&self) -> Self {
+        Self(self.0 + 1)
+    }
+}
+
+impl ImState {
+    /// Converts from the flags stored in the DB (deleted takes precedence)
+    pub fn from_flags(is_edited: bool, is_deleted: bool) -> Self {
+        match (is_edited, is_deleted) {
+            (_, true) => Self::Deleted,
+            (true, false) => Self::Edited,
+            (false, false) => Self::Original,
+        }
+    }
+
+    pub fn is_edited(&self) -> bool {
+        matches!(self, Self::Edited)
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        matches!(self, Self::Deleted)
+    }
+}
+
+impl SearchBody {
+    /// The words to search for in lower case. Only letters and digits are kept
+    /// so the terms never contain any full-text search operators
+    pub fn terms(&self) -> Vec<String> {
+        search_terms(&self.query)
+    }
+
+    /// Returns true if the IM satisfies all the conditions of the search
+    /// (Used for IMs that may not have been saved yet)
+    pub fn is_match<T: HistoryIm>(&self, im: &T) -> bool {
+        let terms = self.terms();
+        let words = search_terms(im.content().as_ref());
+        !terms.is_empty()
+            && !im.state().is_deleted()
+            && terms.iter().all(|term| words.contains(term))
+            && self
+                .author
+                .as_ref()
+                .is_none_or(|author| author == im.author())
+            && self.from.is_none_or(|from| from <= im.timestamp())
+            && self.to.is_none_or(|to| im.timestamp() <= to)
+            && self.before.is_none_or(|before| im.id() < before)
+    }
+}
+
+impl SearchMatch {
+    pub fn from_im<T: HistoryIm>(im: &T) -> Self {
+        Self {
+            id: im.id(),
+            author: im.author().clone(),
+            timestamp: im.timestamp(),
+            content: im.content().clone(),
+        }
+    }
+}
+
+impl Display for SearchMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let time = self.timestamp.as_local_datetime().format("%F %T");
+        write!(f, "{time} {}: {}", self.author, self.content)
+    }
+}
+
+fn search_terms(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+impl ChatRoomName {
+    /// The room every connection joins when it connects
+    pub fn default_room() -> Self {
+        CHAT_DEFAULT_ROOM
+            .try_into()
+            .expect("room name is from a constant should either always work or always fail")
+    }
+}
+
+impl RoomVisibility {
+    pub fn is_visible_to(&self, user_info: &UserInfo) -> bool {
+        match self {
+            RoomVisibility::Everyone => true,
+            RoomVisibility::Permission(permission) => user_info.permissions.0.contains(permission),
+            RoomVisibility::Branch(branch_id) => &user_info.branch_id == branch_id,
+        }
+    }
+}
+
+impl Display for RoomVisibility {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RoomVisibility::Everyone => write!(f, "Everyone"),
+            RoomVisibility::Permission(permission) => write!(f, "Permission: {permission}"),
+            RoomVisibility::Branch(branch_id) => write!(f, "Branch: {branch_id}"),
+        }
+    }
 }
 
 impl ChatUser {
     pub fn new(value: Username) -> Self {
         Self(value)
     }
+
+    pub fn username(&self) -> &Username {
+        &self.0
+    }
+}
+
+impl DirectIM {
+    /// Returns the user on the other side of the conversation from `username`
+    pub fn other_user(&self, username: &Username) -> &Username {
+        if &self.author == username {
+            &self.recipient
+        } else {
+            &self.author
+        }
+    }
+}
+
+macro_rules! impl_history_im {
+    ($name:ident) => {
+        impl HistoryIm for $name {
+            fn id(&self) -> ChatImId {
+                self.id
+            }
+
+            fn author(&self) -> &Username {
+                &self.author
+            }
+
+            fn timestamp(&self) -> Timestamp {
+                self.timestamp
+            }
+
+            fn content(&self) -> &ChatImText {
+                &self.content
+            }
+
+            fn state(&self) -> ImState {
+                self.state
+            }
+
+            fn edit(&mut self, content: ChatImText) {
+                self.content = content;
+                self.state = ImState::Edited;
+            }
+
+            fn delete(&mut self) {
+                self.content = ChatImText(String::new());
+                self.state = ImState::Deleted;
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                let time = self.timestamp.as_local_datetime().format("%T");
+                let author = self.author.to_string();
+                match self.state {
+                    ImState::Original => write!(f, "{time} {author}: {}", self.content),
+                    ImState::Edited => write!(f, "{time} {author}: {} (edited)", self.content),
+                    ImState::Deleted => write!(f, "{time} {author}: (deleted)"),
+                }
+            }
+        }
+    };
 }
 
+impl_history_im!(ChatIM);
+impl_history_im!(DirectIM);
+
 impl From<ChatUser> for Username {
     fn from(value: ChatUser) -> Self {
         value.0
     }
 }
 
-impl Display for ChatIM {
+impl Display for ChatImId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let time = self.timestamp.as_local_datetime().format("%T");
-        let author = self.author.to_string();
-        let msg = self.content.to_string();
-        write!(f, "{time} {author}: {msg}")
+        self.0.fmt(f)
     }
 }
 
@@ -93,8 +496,16 @@ impl Display for ChatUser {
     }
 }
 
-impl ChatMsgsHistory {
-    pub fn push(&mut self, im: ChatIM) {
+impl<T> Default for ChatMsgsHistory<T> {
+    fn default() -> Self {
+        Self {
+            ims: Default::default(),
+        }
+    }
+}
+
+impl<T: HistoryIm + std::fmt::Debug> ChatMsgsHistory<T> {
+    pub fn push(&mut self, im: T) {
         self.ims.push(im);
     }
 
@@ -107,7 +518,7 @@ impl ChatMsgsHistory {
 
         // Ensure the precondition about the objects is met
         match (self.first(), other.last()) {
-            (Some(first), Some(last)) if first.timestamp < last.timestamp => {
+            (Some(first), Some(last)) if first.timestamp() < last.timestamp() => {
                 bail!(
                     "Prepending Chat IM history failed. Last message in other is after our first message. Our first: {first:?}, Other Last: {last:?}"
                 );
@@ -115,25 +526,16 @@ impl ChatMsgsHistory {
             _ => (), // No possible conflict in any other case
         }
 
-        // Remove any duplicates from the end of end of what was returned.
-        let possibly_duplicated_timestamp = self.first().map(|x| x.timestamp);
-        if let Some(possibly_duplicated_timestamp) = possibly_duplicated_timestamp {
-            // Get range of possibly duplicated values
-            let mut last_index_with_same_timestamp = 0;
-            for (i, im) in self.ims.iter().enumerate() {
-                if im.timestamp == possibly_duplicated_timestamp {
-                    last_index_with_same_timestamp = i;
-                } else {
-                    // No more can match because we sorted the list
-                    break;
-                }
-            }
-            let range_to_consider = &self.ims[..=last_index_with_same_timestamp];
-
-            // Only keep non-duplicated values
-            other.ims.retain(|x| {
-                x.timestamp != possibly_duplicated_timestamp || !range_to_consider.contains(x)
-            });
+        // Remove any duplicates from the end of what was returned. Only IMs that
+        // share our first timestamp can be duplicates
+        if let Some(possibly_duplicated_timestamp) = self.first().map(|x| x.timestamp()) {
+            let ids_to_consider: Vec<ChatImId> = self
+                .ims
+                .iter()
+                .take_while(|x| x.timestamp() == possibly_duplicated_timestamp)
+                .map(|x| x.id())
+                .collect();
+            other.ims.retain(|x| !ids_to_consider.contains(&x.id()));
         }
 
         other.ims.append(&mut self.ims);
@@ -144,24 +546,29 @@ impl ChatMsgsHistory {
         Ok(())
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = &ChatIM> {
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
         self.ims.iter()
     }
 
-    pub fn first(&self) -> Option<&ChatIM> {
+    pub fn first(&self) -> Option<&T> {
         self.ims.first()
     }
-    pub fn last(&self) -> Option<&ChatIM> {
+    pub fn last(&self) -> Option<&T> {
         self.ims.last()
     }
 
+    /// Sorts by timestamp using the ID to order IMs sent in the same second
     pub fn sort_by_timestamp(&mut self) {
-        self.ims.sort_by_key(|x| x.timestamp);
+        self.ims.sort_by_key(|x| (x.timestamp(), x.id()));
+    }
+
+    pub fn get_mut(&mut self, id: ChatImId) -> Option<&mut T> {
+        self.ims.iter_mut().find(|x| x.id() == id)
     }
 
     pub fn earliest_timestamp_or_now(&self) -> Timestamp {
         self.first()
-            .map(|chat_im| chat_im.timestamp)
+            .map(|im| im.timestamp())
             .unwrap_or_else(Timestamp::now)
     }
 
@@ -1,9 +1,14 @@
 mod client_control_loop;
+mod health;
 mod history;
 mod plugin_impl;
+mod rate_limit;
+mod rooms;
+mod search;
 mod server;
 mod server_handler;
 
 pub use client_control_loop::chat_ws_start_client_handler_loop;
 pub use plugin_impl::{ChatPlugin, ChatPluginConfig, ChatSettings};
+pub use rate_limit::{ChatRateLimitSettings, TokenBucketSettings};
 pub use server_handler::ChatServerHandle;
@@ -1,14 +1,17 @@
 //! Code related to the loop that handles incoming and outgoing messages to the
 //! client (Outgoing messages include those from other threads)
 
-use super::ChatServerHandle;
-use crate::{ChatIM, ChatMsg};
+use super::{
+    ChatServerHandle,
+    rate_limit::{ConnectionRateLimiter, RateLimitOutcome},
+};
+use crate::{ChatMsg, ImState, RateLimitedBody};
 use actix_ws::{CloseCode, CloseReason};
 use anyhow::{Context, bail};
 use futures_util::StreamExt as _;
 use std::{pin::pin, sync::Arc};
 use tokio::{select, sync::mpsc};
-use tracing::{Span, info, instrument};
+use tracing::{Span, info, instrument, warn};
 use ws_helpers::client_control_loop::{
     StreamOutcome, process_stream_from_client, send_message_to_client,
 };
@@ -32,6 +35,7 @@ pub async fn chat_ws_start_client_handler_loop(
     initial_msg_timeout: Seconds,
 ) {
     let mut heartbeat = chat_server_handle.heartbeat_config.start_new_monitor();
+    let mut rate_limit = chat_server_handle.new_connection_rate_limiter();
     let username = user_info.username.clone();
 
     let (conn_tx, mut conn_rx) = mpsc::channel(CHANNEL_BUFFER_SIZE);
@@ -67,8 +71,24 @@ pub async fn chat_ws_start_client_handler_loop(
                 let outcome = process_stream_from_client(stream_msg, &mut heartbeat, &mut ws_session).await;
                 match outcome{
                     StreamOutcome::MsgFromClient(msg) => {
-                        let r = process_msg_from_client(&chat_server_handle, &msg, &conn_id, &username).await;
-                        log_err_as_error!(r);
+                        let r = process_msg_from_client(&chat_server_handle, &msg, &conn_id, &username, &mut rate_limit).await;
+                        log_err_as_error!(&r);
+                        match r {
+                            Ok(RateLimitOutcome::Limited { retry_after }) => {
+                                let notice = ChatMsg::RateLimited(RateLimitedBody { retry_after });
+                                if let Some(reason) = send_message_to_client(Some(notice), &mut ws_session).await {
+                                    break reason;
+                                }
+                            },
+                            Ok(RateLimitOutcome::Disconnect) => {
+                                warn!("Closing connection for {conn_id:?} because it kept sending while rate limited");
+                                break CloseReason {
+                                    code: CloseCode::Policy,
+                                    description: Some("Rate limit exceeded".into()),
+                                };
+                            },
+                            Ok(RateLimitOutcome::Allowed) | Err(_) => {},
+                        }
                     },
                     StreamOutcome::CloseSession(close_reason) => break close_reason,
                     StreamOutcome::None => {},
@@ -97,44 +117,105 @@ pub async fn chat_ws_start_client_handler_loop(
     let _ = ws_session.close(Some(close_reason)).await;
 }
 
-#[instrument]
+/// Messages over the rate limit are dropped without being processed. Messages
+/// that cannot be parsed are also charged so they cannot be sent without limit
+#[instrument(skip(rate_limit))]
 async fn process_msg_from_client(
     chat_server: &ChatServerHandle,
     text: &str,
     conn_id: &WsConnId,
     username: &Username,
-) -> anyhow::Result<()> {
-    let chat_msg: ChatMsg =
-        serde_json::from_str(text).context("failed to deserialize chat msg received")?;
+    rate_limit: &mut ConnectionRateLimiter,
+) -> anyhow::Result<RateLimitOutcome> {
+    let chat_msg: ChatMsg = match serde_json::from_str(text) {
+        Ok(chat_msg) => chat_msg,
+        Err(e) => {
+            let outcome = chat_server.check_rate_limit_for_invalid_msg(rate_limit, username);
+            if outcome != RateLimitOutcome::Allowed {
+                return Ok(outcome);
+            }
+            return Err(e).context("failed to deserialize chat msg received");
+        }
+    };
+
+    let outcome = chat_server.check_rate_limit(rate_limit, username, &chat_msg);
+    if outcome != RateLimitOutcome::Allowed {
+        return Ok(outcome);
+    }
 
     match chat_msg {
         ChatMsg::UserJoined(_)
         | ChatMsg::UserLeft(_)
         | ChatMsg::InitialState(_)
-        | ChatMsg::RespHistory(_) => {
+        | ChatMsg::RespHistory(_)
+        | ChatMsg::RoomCreated(_)
+        | ChatMsg::RoomJoined(_)
+        | ChatMsg::RoomLeft(_)
+        | ChatMsg::RoomError(_)
+        | ChatMsg::RespDirectHistory(_)
+        | ChatMsg::DirectError(_)
+        | ChatMsg::EditError(_)
+        | ChatMsg::RateLimited(_)
+        | ChatMsg::SearchResults(_)
+        | ChatMsg::SearchError(_) => {
             bail!("unexpected message type received from the client: {chat_msg:?}")
         }
         ChatMsg::IM(mut chat_im) => {
-            validate_im_from_client(&mut chat_im, username).context("IM validation failed")?;
+            validate_im_from_client(
+                &mut chat_im.timestamp,
+                &mut chat_im.author,
+                &mut chat_im.state,
+                username,
+            )
+            .context("IM validation failed")?;
 
             // Also send to original author so they receive the correct timestamp
-            chat_server.send_msg_to_clients(ChatMsg::IM(chat_im)).await;
+            chat_server.send_im(conn_id, chat_im).await;
         }
         ChatMsg::ReqHistory(req) => chat_server.process_history_request(conn_id, req).await,
+        ChatMsg::CreateRoom(info) => chat_server.create_room(conn_id, info).await,
+        ChatMsg::JoinRoom(room) => chat_server.join_room(conn_id, room).await,
+        ChatMsg::LeaveRoom(room) => chat_server.leave_room(conn_id, room).await,
+        ChatMsg::DirectIM(mut direct_im) => {
+            validate_im_from_client(
+                &mut direct_im.timestamp,
+                &mut direct_im.author,
+                &mut direct_im.state,
+                username,
+            )
+            .context("direct IM validation failed")?;
+            chat_server.send_direct_im(conn_id, direct_im).await;
+        }
+        ChatMsg::ReqDirectHistory(req) => {
+            chat_server
+                .process_direct_history_request(conn_id, req)
+                .await
+        }
+        ChatMsg::Edit(body) => chat_server.edit_im(conn_id, body).await,
+        ChatMsg::Delete(id) => chat_server.delete_im(conn_id, id).await,
+        ChatMsg::Search(req) => chat_server.search(conn_id, req).await,
     }
-    Ok(())
+    Ok(RateLimitOutcome::Allowed)
 }
 
-fn validate_im_from_client(im: &mut ChatIM, username: &Username) -> anyhow::Result<()> {
-    im.timestamp = Timestamp::now(); // Replace timestamp with server time to ensure monotonicity
+/// Used for both room and direct IMs (The ID is assigned when the IM is added to
+/// history)
+fn validate_im_from_client(
+    timestamp: &mut Timestamp,
+    author: &mut Username,
+    state: &mut ImState,
+    username: &Username,
+) -> anyhow::Result<()> {
+    *timestamp = Timestamp::now(); // Replace timestamp with server time to ensure monotonicity
+    *state = ImState::Original; // New IMs cannot already be edited or deleted
 
-    if &im.author != username {
+    if author != username {
         debug_panic!(
             "unexpected message author found. Author has been reset to expected value. Expected '{}' Found: '{}'",
             username,
-            im.author,
+            author,
         );
-        im.author = username.clone();
+        *author = username.clone();
     }
 
     Ok(())
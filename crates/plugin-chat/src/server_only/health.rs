@@ -0,0 +1,62 @@
+use super::ChatServerHandle;
+use futures_util::future::BoxFuture;
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+use wykies_server::health::HealthCheck;
+use wykies_shared::health::{HealthCheckResult, HealthStatus};
+
+/// Counters updated by the chat server and its DB writer for health reporting
+#[derive(Debug, Default)]
+pub(crate) struct ChatStats {
+    connections: AtomicUsize,
+    unsaved_ims: AtomicUsize,
+}
+
+impl ChatStats {
+    pub(crate) fn set_connections(&self, value: usize) {
+        self.connections.store(value, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_unsaved_ims(&self, value: usize) {
+        self.unsaved_ims.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub(crate) fn sub_unsaved_ims(&self, value: usize) {
+        self.unsaved_ims.fetch_sub(value, Ordering::Relaxed);
+    }
+
+    fn connections(&self) -> usize {
+        self.connections.load(Ordering::Relaxed)
+    }
+
+    fn unsaved_ims(&self) -> usize {
+        self.unsaved_ims.load(Ordering::Relaxed)
+    }
+}
+
+/// Reports the command queue depth, connections and IMs waiting to be saved.
+/// Degraded if the command queue is full
+#[derive(Debug)]
+pub(crate) struct ChatHealthCheck(pub Arc<ChatServerHandle>);
+
+impl HealthCheck for ChatHealthCheck {
+    fn check(&self) -> BoxFuture<'_, HealthCheckResult> {
+        Box::pin(async {
+            let (queue_depth, queue_capacity) = self.0.queue_depth();
+            let stats = self.0.stats();
+            let status = if queue_depth < queue_capacity {
+                HealthStatus::Ok
+            } else {
+                HealthStatus::Degraded
+            };
+            let message = format!(
+                "queue depth: {queue_depth}/{queue_capacity}, connections: {}, unsaved IMs: {}",
+                stats.connections(),
+                stats.unsaved_ims()
+            );
+            HealthCheckResult::new("Chat Server", status, message)
+        })
+    }
+}
@@ -1,4 +1,4 @@
-use std::fmt::Debug;
+use std::{collections::HashMap, fmt::Debug, sync::Arc};
 
 use anyhow::{Context, bail};
 use ringbuffer::{AllocRingBuffer, RingBuffer};
@@ -9,31 +9,83 @@ use tracked_cancellations::TrackedCancellationToken;
 use wykies_shared::{
     const_config::CHANNEL_BUFFER_SIZE,
     db_types::{Db, DbPool},
-    log_as_error,
+    log_as_error, log_err_as_error,
+    uac::Username,
 };
 use wykies_time::{Seconds, Timestamp};
 
-use crate::ChatIM;
+use super::health::ChatStats;
+use crate::{ChatIM, ChatImId, ChatImText, ChatRoomName, DirectIM, HistoryIm, ImState};
 
 #[derive(Debug)]
 pub struct ChatHistory {
-    recent: AllocRingBuffer<ChatIM>,
+    /// The most recent IMs of each room
+    recent: HashMap<ChatRoomName, AllocRingBuffer<ChatIM>>,
+    /// The most recent direct IMs of each conversation. Used to include direct
+    /// IMs that may not have been saved yet when history is requested
+    recent_direct: HashMap<Conversation, AllocRingBuffer<DirectIM>>,
+    recent_capacity: usize,
+    /// The last ID assigned to an IM
+    last_id: ChatImId,
     db_writer_handle: ChatDbWriterHandle,
 }
 
+/// A change to an existing IM requested by a client
+#[derive(Debug, Clone)]
+pub enum ImChange {
+    Edit(ChatImText),
+    Delete,
+}
+
+/// Where an IM was sent and who sent it, used to check who may change it and
+/// who to notify when it changes
+#[derive(Debug)]
+pub struct ImLocation {
+    pub author: Username,
+    pub target: ImTarget,
+    pub state: ImState,
+}
+
+#[derive(Debug)]
+pub enum ImTarget {
+    Room(ChatRoomName),
+    Direct { recipient: Username },
+}
+
+/// The usernames of both users in a direct conversation (in sorted order so
+/// it is the same regardless of who the author is)
+type Conversation = (Username, Username);
+
+/// An IM waiting to be saved to the DB
+#[derive(Debug)]
+enum UnsavedIM {
+    Room(ChatIM),
+    Direct(DirectIM),
+}
+
+/// A write waiting to be done to the DB (Sent through the same channel so
+/// changes are always applied after the IM they change has been saved)
+#[derive(Debug)]
+enum PendingWrite {
+    IM(UnsavedIM),
+    Change { id: ChatImId, change: ImChange },
+}
+
 #[derive(Debug)]
 struct ChatDbWriterHandle {
-    tx: mpsc::Sender<ChatIM>,
+    tx: mpsc::Sender<PendingWrite>,
+    stats: Arc<ChatStats>,
 }
 
 // Update Debug impl if adding new fields
 struct ChatDbWriter {
-    rx: mpsc::Receiver<ChatIM>,
+    rx: mpsc::Receiver<PendingWrite>,
     last_save_time: Timestamp,
     max_time_before_save: Seconds,
     max_ims_before_save: u8,
     pool: DbPool,
-    buffer: Vec<ChatIM>,
+    buffer: Vec<PendingWrite>,
+    stats: Arc<ChatStats>,
 }
 
 impl ChatHistory {
@@ -44,31 +96,171 @@ impl ChatHistory {
         cancellation_token: TrackedCancellationToken,
         max_time_before_save: Seconds,
         max_ims_before_save: u8,
+        stats: Arc<ChatStats>,
     ) -> Self {
         let handle = ChatDbWriterHandle::new(
             pool,
             cancellation_token,
             max_time_before_save,
             max_ims_before_save,
+            stats,
         );
         Self {
-            recent: AllocRingBuffer::new(recent_capacity),
+            recent: Default::default(),
+            recent_direct: Default::default(),
+            recent_capacity,
+            last_id: Default::default(),
             db_writer_handle: handle,
         }
     }
 
+    /// Continues assigning IDs from the last one saved in the database
+    #[instrument(err(Debug))]
+    pub async fn load_last_id(&mut self, pool: &DbPool) -> anyhow::Result<()> {
+        #[cfg(feature = "mysql")]
+        let row = sqlx::query!("SELECT MAX(`ChatID`) AS max_id FROM chat")
+            .fetch_one(pool)
+            .await
+            .context("failed to get last chat IM ID")?;
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        let row = sqlx::query!("SELECT MAX(chat_id) AS max_id FROM chat")
+            .fetch_one(pool)
+            .await
+            .context("failed to get last chat IM ID")?;
+        let last_id: u64 = row
+            .max_id
+            .map(|x| x.try_into())
+            .transpose()
+            .context("invalid chat IM ID found in DB")?
+            .unwrap_or_default();
+        self.last_id = ChatImId::new(last_id);
+        Ok(())
+    }
+
+    fn next_id(&mut self) -> ChatImId {
+        self.last_id = self.last_id.next();
+        self.last_id
+    }
+
+    /// Assigns the IM its ID, saves it and returns it
     #[instrument]
-    pub async fn push(&mut self, im: ChatIM) -> anyhow::Result<()> {
-        self.recent.enqueue(im.clone());
+    pub async fn push(&mut self, mut im: ChatIM) -> anyhow::Result<ChatIM> {
+        im.id = self.next_id();
+        self.recent
+            .entry(im.room.clone())
+            .or_insert_with(|| AllocRingBuffer::new(self.recent_capacity))
+            .enqueue(im.clone());
         self.db_writer_handle
-            .enqueue_for_saving(im)
+            .enqueue_for_saving(PendingWrite::IM(UnsavedIM::Room(im.clone())))
             .await
-            .context("failed to enqueue IM to be saved")
+            .context("failed to enqueue IM to be saved")?;
+        Ok(im)
     }
 
+    /// Assigns the direct IM its ID, saves it and returns it
     #[instrument]
-    pub fn get_recent(&self) -> Vec<ChatIM> {
-        self.recent.to_vec()
+    pub async fn push_direct(&mut self, mut im: DirectIM) -> anyhow::Result<DirectIM> {
+        im.id = self.next_id();
+        self.recent_direct
+            .entry(conversation(&im.author, &im.recipient))
+            .or_insert_with(|| AllocRingBuffer::new(self.recent_capacity))
+            .enqueue(im.clone());
+        self.db_writer_handle
+            .enqueue_for_saving(PendingWrite::IM(UnsavedIM::Direct(im.clone())))
+            .await
+            .context("failed to enqueue direct IM to be saved")?;
+        Ok(im)
+    }
+
+    /// Applies the change to the recent copy of the IM (if any) and saves it
+    #[instrument]
+    pub async fn change(&mut self, id: ChatImId, change: ImChange) -> anyhow::Result<()> {
+        let recent_im = self
+            .recent
+            .values_mut()
+            .find_map(|recent| recent.iter_mut().find(|im| im.id == id));
+        if let Some(im) = recent_im {
+            apply_change(im, change.clone());
+        } else if let Some(im) = self
+            .recent_direct
+            .values_mut()
+            .find_map(|recent| recent.iter_mut().find(|im| im.id == id))
+        {
+            apply_change(im, change.clone());
+        }
+        self.db_writer_handle
+            .enqueue_for_saving(PendingWrite::Change { id, change })
+            .await
+            .context("failed to enqueue IM change to be saved")
+    }
+
+    /// Returns the location of the IM if it is still in recent history
+    pub fn find_recent(&self, id: ChatImId) -> Option<ImLocation> {
+        let room_im = self
+            .recent
+            .values()
+            .find_map(|recent| recent.iter().find(|im| im.id == id));
+        if let Some(im) = room_im {
+            return Some(ImLocation {
+                author: im.author.clone(),
+                target: ImTarget::Room(im.room.clone()),
+                state: im.state,
+            });
+        }
+        self.recent_direct
+            .values()
+            .find_map(|recent| recent.iter().find(|im| im.id == id))
+            .map(|im| ImLocation {
+                author: im.author.clone(),
+                target: ImTarget::Direct {
+                    recipient: im.recipient.clone(),
+                },
+                state: im.state,
+            })
+    }
+
+    /// Replaces IMs loaded from the DB with their recent copy as the recent
+    /// copy includes changes that may not have been saved yet
+    pub fn refresh_from_recent(&self, room: &ChatRoomName, ims: &mut [ChatIM]) {
+        let Some(recent) = self.recent.get(room) else {
+            return;
+        };
+        for im in ims.iter_mut() {
+            if let Some(recent_im) = recent.iter().find(|x| x.id == im.id) {
+                *im = recent_im.clone();
+            }
+        }
+    }
+
+    #[instrument]
+    pub fn get_recent(&self, room: &ChatRoomName) -> Vec<ChatIM> {
+        self.recent
+            .get(room)
+            .map(|recent| recent.to_vec())
+            .unwrap_or_default()
+    }
+
+    #[instrument]
+    pub fn get_recent_direct(&self, user1: &Username, user2: &Username) -> Vec<DirectIM> {
+        self.recent_direct
+            .get(&conversation(user1, user2))
+            .map(|recent| recent.to_vec())
+            .unwrap_or_default()
+    }
+}
+
+fn apply_change<T: HistoryIm>(im: &mut T, change: ImChange) {
+    match change {
+        ImChange::Edit(content) => im.edit(content),
+        ImChange::Delete => im.delete(),
+    }
+}
+
+fn conversation(user1: &Username, user2: &Username) -> Conversation {
+    if user1 <= user2 {
+        (user1.clone(), user2.clone())
+    } else {
+        (user2.clone(), user1.clone())
     }
 }
 
@@ -79,6 +271,7 @@ impl ChatDbWriterHandle {
         cancellation_token: TrackedCancellationToken,
         max_time_before_save: Seconds,
         max_ims_before_save: u8,
+        stats: Arc<ChatStats>,
     ) -> Self {
         let (tx, rx) = mpsc::channel(CHANNEL_BUFFER_SIZE);
         let writer = ChatDbWriter {
@@ -88,17 +281,21 @@ impl ChatDbWriterHandle {
             max_ims_before_save,
             pool,
             buffer: Default::default(),
+            stats: Arc::clone(&stats),
         };
         tokio::spawn(writer.run(cancellation_token));
-        Self { tx }
+        Self { tx, stats }
     }
 
     #[instrument]
-    async fn enqueue_for_saving(&self, im: ChatIM) -> anyhow::Result<()> {
-        self.tx
-            .send(im)
-            .await
-            .context("failed to send IM to writer")
+    async fn enqueue_for_saving(&self, write: PendingWrite) -> anyhow::Result<()> {
+        // Counted before sending so the writer can never subtract it first
+        self.stats.add_unsaved_ims(1);
+        let result = self.tx.send(write).await;
+        if result.is_err() {
+            self.stats.sub_unsaved_ims(1);
+        }
+        result.context("failed to send IM to writer")
     }
 }
 
@@ -114,7 +311,7 @@ impl ChatDbWriter {
                     self.save("cancellation").await.context("failed to save after receiving cancellation request")?;
                     bail!("Received cancellation request. Shutdown ChatDbWriter");
                 }
-                im = self.rx.recv() => self.process_im(im).await?,
+                write = self.rx.recv() => self.process_write(write).await?,
                 _ = max_time_before_save, if !self.buffer.is_empty() => self.save("time").await.context("failed to save at max time reached")?,
             }
         }
@@ -137,14 +334,14 @@ impl ChatDbWriter {
     }
 
     #[instrument(err(Debug))]
-    async fn process_im(&mut self, im: Option<ChatIM>) -> anyhow::Result<()> {
-        match im {
-            Some(im) => {
+    async fn process_write(&mut self, write: Option<PendingWrite>) -> anyhow::Result<()> {
+        match write {
+            Some(write) => {
                 if self.buffer.is_empty() {
                     // Was empty no point saving right away
                     self.last_save_time = Timestamp::now();
                 }
-                self.buffer.push(im);
+                self.buffer.push(write);
                 if self.buffer.len() >= self.max_ims_before_save as usize {
                     self.save("buffer full")
                         .await
@@ -167,18 +364,84 @@ impl ChatDbWriter {
             return Ok(());
         }
 
+        let write_count = self.buffer.len();
+        let mut ims = Vec::new();
+        let mut changes = Vec::new();
+        for write in self.buffer.drain(..) {
+            match write {
+                PendingWrite::IM(im) => ims.push(im),
+                PendingWrite::Change { id, change } => changes.push((id, change)),
+            }
+        }
+
+        // IMs are saved first so that changes to IMs in the same batch find them
+        match self.insert_ims(ims).await {
+            Ok(_) => {
+                info!("IMs save succeeded")
+            }
+            Err(err) => {
+                log_as_error!("failed to save IMs: {err:?}");
+            }
+        };
+        for (id, change) in changes {
+            let r = self.save_change(id, change).await;
+            log_err_as_error!(r);
+        }
+        self.stats.sub_unsaved_ims(write_count);
+        self.last_save_time = Timestamp::now();
+        Ok(())
+    }
+
+    #[instrument(err(Debug))]
+    async fn insert_ims(&self, ims: Vec<UnsavedIM>) -> anyhow::Result<()> {
+        if ims.is_empty() {
+            return Ok(());
+        }
+
         #[cfg(feature = "mysql")]
-        let mut query_builder: QueryBuilder<Db> =
-            QueryBuilder::new("INSERT INTO `chat` (`Author`, `Timestamp`, `Content`) ");
+        let mut query_builder: QueryBuilder<Db> = QueryBuilder::new(
+            "INSERT INTO `chat` (`ChatID`, `Room`, `Recipient`, `Author`, `Timestamp`, `Content`) ",
+        );
         #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
-        let mut query_builder: QueryBuilder<Db> =
-            QueryBuilder::new("INSERT INTO chat (author, unix_timestamp, content) ");
-
-        query_builder.push_values(self.buffer.drain(..), |mut b, im| {
-            b.push_bind(im.author)
-                .push_bind(im.timestamp)
-                .push_bind(im.content);
-        });
+        let mut query_builder: QueryBuilder<Db> = QueryBuilder::new(
+            "INSERT INTO chat (chat_id, room, recipient, author, unix_timestamp, content) ",
+        );
+
+        // Exactly one of room and recipient is set for each IM
+        let rows = ims
+            .into_iter()
+            .map(|im| {
+                Ok(match im {
+                    UnsavedIM::Room(im) => (
+                        db_id(im.id)?,
+                        Some(im.room),
+                        None,
+                        im.author,
+                        im.timestamp,
+                        im.content,
+                    ),
+                    UnsavedIM::Direct(im) => (
+                        db_id(im.id)?,
+                        None,
+                        Some(im.recipient),
+                        im.author,
+                        im.timestamp,
+                        im.content,
+                    ),
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        query_builder.push_values(
+            rows,
+            |mut b, (id, room, recipient, author, timestamp, content)| {
+                b.push_bind(id)
+                    .push_bind(room)
+                    .push_bind(recipient)
+                    .push_bind(author)
+                    .push_bind(timestamp)
+                    .push_bind(content);
+            },
+        );
         debug!(query_builder.sql = ?query_builder.sql(), "Query Builder SQL");
 
         // TODO 5: Optimizations left on the table are to try to have the size sent be
@@ -187,23 +450,61 @@ impl ChatDbWriter {
         // Persistent is set to false because the sizes changes and each would have to
         // be cached separately
         let query = query_builder.build().persistent(false);
-        match query
+        query
             .execute(&self.pool)
             .await
-            .context("failed to save chat IMs to DB")
-        {
-            Ok(_) => {
-                info!("IMs save succeeded")
-            }
-            Err(err) => {
-                log_as_error!("failed to save IMs: {err:?}");
-            }
+            .context("failed to save chat IMs to DB")?;
+        Ok(())
+    }
+
+    #[instrument(err(Debug))]
+    async fn save_change(&self, id: ChatImId, change: ImChange) -> anyhow::Result<()> {
+        let id = db_id(id)?;
+        #[cfg(feature = "mysql")]
+        let query = match &change {
+            ImChange::Edit(content) => sqlx::query!(
+                "UPDATE `chat` SET `Content` = ?, `Edited` = 1 WHERE `ChatID` = ?;",
+                content,
+                id
+            ),
+            ImChange::Delete => sqlx::query!(
+                "UPDATE `chat` SET `Content` = '', `Deleted` = 1 WHERE `ChatID` = ?;",
+                id
+            ),
         };
-        self.last_save_time = Timestamp::now();
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        let query = match &change {
+            ImChange::Edit(content) => sqlx::query!(
+                "UPDATE chat SET content = $1, edited = true WHERE chat_id = $2;",
+                content.as_ref(),
+                id
+            ),
+            ImChange::Delete => sqlx::query!(
+                "UPDATE chat SET content = '', deleted = true WHERE chat_id = $1;",
+                id
+            ),
+        };
+        query
+            .execute(&self.pool)
+            .await
+            .with_context(|| format!("failed to save change to chat IM {id}: {change:?}"))?;
         Ok(())
     }
 }
 
+#[cfg(feature = "mysql")]
+pub fn db_id(id: ChatImId) -> anyhow::Result<u64> {
+    Ok(id.as_u64())
+}
+
+#[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+pub fn db_id(id: ChatImId) -> anyhow::Result<i32> {
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    id.as_u64()
+        .try_into()
+        .context("failed to convert chat IM ID into DB format")
+}
+
 impl Debug for ChatDbWriter {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ChatDbWriter")
@@ -213,6 +514,7 @@ impl Debug for ChatDbWriter {
             .field("max_ims_before_save", &self.max_ims_before_save)
             .field("pool", &self.pool)
             .field("buffer_len", &self.buffer.len())
+            .field("stats", &self.stats)
             .finish()
     }
 }
@@ -1,4 +1,9 @@
-use super::{ChatServerHandle, server::ChatServer};
+use super::{
+    ChatServerHandle, health::ChatHealthCheck, rate_limit::ChatRateLimitSettings,
+    server::ChatServer,
+};
+use crate::consts::CHAT_PLUGIN_ID;
+use anyhow::Context as _;
 use std::sync::Arc;
 use tracked_cancellations::TrackedCancellationToken;
 use ws_helpers::WebSocketSettings;
@@ -8,6 +13,8 @@ use wykies_shared::db_types::DbPool;
 #[derive(serde::Deserialize, Clone)]
 pub struct ChatSettings {
     pub heartbeat_interval_secs: u8,
+    #[serde(default)]
+    pub rate_limit: ChatRateLimitSettings,
 }
 
 pub struct ChatPluginConfig {
@@ -17,6 +24,8 @@ pub struct ChatPluginConfig {
 pub struct ChatPlugin;
 
 impl ServerPlugin for ChatPlugin {
+    const ID: &'static str = CHAT_PLUGIN_ID;
+
     type Config = ChatPluginConfig;
 
     type Task = ChatServer;
@@ -30,11 +39,18 @@ impl ServerPlugin for ChatPlugin {
         ws_config: &WebSocketSettings,
     ) -> anyhow::Result<wykies_server::plugin::ServerPluginArtifacts<Self::Task, Self::Handle>>
     {
+        config
+            .settings
+            .rate_limit
+            .validate()
+            .context("invalid chat rate limit settings")?;
         let (chat_server, chat_server_handle) =
             ChatServer::new(&config.settings, ws_config, db_pool, cancellation_token);
+        let handle = Arc::new(chat_server_handle);
         Ok(ServerPluginArtifacts {
             task: chat_server,
-            handle: Arc::new(chat_server_handle),
+            health_checks: vec![Arc::new(ChatHealthCheck(Arc::clone(&handle)))],
+            handle,
         })
     }
 }
@@ -0,0 +1,395 @@
+//! Limits how fast clients can send messages to the chat server
+//!
+//! Each message from a client uses tokens from the bucket of its connection and
+//! from the bucket of its user (shared by all of the user's connections).
+//! Buckets refill at a constant rate up to their capacity. Messages received
+//! when there are not enough tokens are dropped and the client is told when to
+//! try again. Connections that keep sending while limited are disconnected.
+//! Messages that cannot be parsed use one token and always count towards
+//! disconnecting.
+
+use crate::ChatMsg;
+use anyhow::ensure;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use wykies_shared::uac::Username;
+use wykies_time::Seconds;
+
+/// How often buckets that have refilled are removed from the users' buckets
+const USER_BUCKETS_PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, serde::Deserialize, Clone, Copy)]
+pub struct ChatRateLimitSettings {
+    pub connection: TokenBucketSettings,
+    pub user: TokenBucketSettings,
+    /// Tokens used by a history request or a search (Other messages use one
+    /// token each)
+    pub history_request_cost: u16,
+    /// Number of messages in a row that can be dropped for being over the
+    /// limit before the connection is closed. Zero never closes the connection
+    pub max_consecutive_violations: u16,
+}
+
+#[derive(Debug, serde::Deserialize, Clone, Copy)]
+pub struct TokenBucketSettings {
+    /// Maximum number of tokens (the largest burst allowed). Zero disables the
+    /// limit
+    pub capacity: u16,
+    pub refill_per_sec: u16,
+}
+
+/// The outcome of checking if a message from the client is within the limits
+#[derive(Debug, PartialEq, Eq)]
+pub enum RateLimitOutcome {
+    Allowed,
+    Limited { retry_after: Seconds },
+    Disconnect,
+}
+
+/// The buckets of each user. Shared by all connections
+#[derive(Debug)]
+pub struct ChatRateLimiter {
+    settings: ChatRateLimitSettings,
+    user_buckets: Mutex<UserBuckets>,
+}
+
+#[derive(Debug)]
+struct UserBuckets {
+    buckets: HashMap<Username, TokenBucket>,
+    last_pruned: Instant,
+}
+
+/// The bucket of a single connection
+#[derive(Debug)]
+pub struct ConnectionRateLimiter {
+    bucket: TokenBucket,
+    consecutive_violations: u16,
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    settings: TokenBucketSettings,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl Default for ChatRateLimitSettings {
+    fn default() -> Self {
+        Self {
+            connection: TokenBucketSettings {
+                capacity: 20,
+                refill_per_sec: 2,
+            },
+            user: TokenBucketSettings {
+                capacity: 40,
+                refill_per_sec: 4,
+            },
+            history_request_cost: 5,
+            max_consecutive_violations: 10,
+        }
+    }
+}
+
+impl ChatRateLimitSettings {
+    /// Fails if a history request costs more than an enabled bucket can ever
+    /// hold as those requests would never be allowed
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for (name, bucket) in [("connection", self.connection), ("user", self.user)] {
+            ensure!(
+                bucket.capacity == 0 || self.history_request_cost <= bucket.capacity,
+                "history request cost of {} is more than the {name} capacity of {}",
+                self.history_request_cost,
+                bucket.capacity
+            );
+        }
+        Ok(())
+    }
+
+    /// No limits on either connections or users
+    pub fn disabled() -> Self {
+        let unlimited = TokenBucketSettings {
+            capacity: 0,
+            refill_per_sec: 0,
+        };
+        Self {
+            connection: unlimited,
+            user: unlimited,
+            history_request_cost: 0,
+            max_consecutive_violations: 0,
+        }
+    }
+}
+
+impl ChatRateLimiter {
+    pub fn new(settings: ChatRateLimitSettings) -> Self {
+        Self {
+            settings,
+            user_buckets: Mutex::new(UserBuckets {
+                buckets: HashMap::new(),
+                last_pruned: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn new_connection(&self) -> ConnectionRateLimiter {
+        ConnectionRateLimiter {
+            bucket: TokenBucket::new(self.settings.connection, Instant::now()),
+            consecutive_violations: 0,
+        }
+    }
+
+    /// Takes the tokens for the message if both the connection and the user
+    /// have enough (See [`Self::try_take`])
+    pub fn check(
+        &self,
+        connection: &mut ConnectionRateLimiter,
+        username: &Username,
+        chat_msg: &ChatMsg,
+    ) -> RateLimitOutcome {
+        match self.try_take(connection, username, self.cost(chat_msg)) {
+            Ok(()) => {
+                connection.consecutive_violations = 0;
+                RateLimitOutcome::Allowed
+            }
+            Err(wait) => self.violation_outcome(connection, wait),
+        }
+    }
+
+    /// Same as [`Self::check`] for a message that could not be parsed but it
+    /// always counts as a violation
+    pub fn check_invalid_msg(
+        &self,
+        connection: &mut ConnectionRateLimiter,
+        username: &Username,
+    ) -> RateLimitOutcome {
+        let result = self.try_take(connection, username, 1);
+        match self.violation_outcome(connection, result.err().unwrap_or_default()) {
+            // Not over the limit so the client does not need to wait
+            RateLimitOutcome::Limited { .. } if result.is_ok() => RateLimitOutcome::Allowed,
+            outcome => outcome,
+        }
+    }
+
+    /// Takes the tokens from both the connection and the user if both have
+    /// enough (neither is charged otherwise)
+    fn try_take(
+        &self,
+        connection: &mut ConnectionRateLimiter,
+        username: &Username,
+        cost: u16,
+    ) -> Result<(), Duration> {
+        let now = Instant::now();
+        connection.bucket.try_take(cost, now).and_then(|()| {
+            self.try_take_from_user(username, cost, now)
+                .inspect_err(|_| connection.bucket.refund(cost))
+        })
+    }
+
+    /// Counts the violation and disconnects if there have been too many in a row
+    fn violation_outcome(
+        &self,
+        connection: &mut ConnectionRateLimiter,
+        wait: Duration,
+    ) -> RateLimitOutcome {
+        connection.consecutive_violations = connection.consecutive_violations.saturating_add(1);
+        let max_violations = self.settings.max_consecutive_violations;
+        if max_violations > 0 && connection.consecutive_violations > max_violations {
+            RateLimitOutcome::Disconnect
+        } else {
+            RateLimitOutcome::Limited {
+                retry_after: Seconds::new(wait.as_secs_f64().ceil() as u64),
+            }
+        }
+    }
+
+    fn try_take_from_user(
+        &self,
+        username: &Username,
+        cost: u16,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        if self.settings.user.capacity == 0 {
+            return Ok(());
+        }
+        let mut user_buckets = self.user_buckets.lock().expect("mutex poisoned");
+        user_buckets.prune_if_due(now);
+        user_buckets
+            .buckets
+            .entry(username.clone())
+            .or_insert_with(|| TokenBucket::new(self.settings.user, now))
+            .try_take(cost, now)
+    }
+
+    fn cost(&self, chat_msg: &ChatMsg) -> u16 {
+        match chat_msg {
+            ChatMsg::ReqHistory(_) | ChatMsg::ReqDirectHistory(_) | ChatMsg::Search(_) => {
+                self.settings.history_request_cost
+            }
+            _ => 1,
+        }
+    }
+}
+
+impl UserBuckets {
+    /// Full buckets are the same as new ones so there is no need to keep them
+    fn prune_if_due(&mut self, now: Instant) {
+        if now.saturating_duration_since(self.last_pruned) < USER_BUCKETS_PRUNE_INTERVAL {
+            return;
+        }
+        self.buckets.retain(|_, bucket| !bucket.is_full(now));
+        self.last_pruned = now;
+    }
+}
+
+impl TokenBucket {
+    fn new(settings: TokenBucketSettings, now: Instant) -> Self {
+        Self {
+            settings,
+            tokens: settings.capacity.into(),
+            last_refill: now,
+        }
+    }
+
+    /// Returns how long until enough tokens will be available if there are not
+    /// enough now
+    fn try_take(&mut self, cost: u16, now: Instant) -> Result<(), Duration> {
+        if self.settings.capacity == 0 {
+            return Ok(());
+        }
+        self.refill(now);
+        let cost = f64::from(cost);
+        if self.tokens >= cost {
+            self.tokens -= cost;
+            return Ok(());
+        }
+        let missing = cost - self.tokens;
+        Err(match self.settings.refill_per_sec {
+            0 => Duration::MAX,
+            refill_per_sec => Duration::from_secs_f64(missing / f64::from(refill_per_sec)),
+        })
+    }
+
+    fn refund(&mut self, cost: u16) {
+        self.tokens = (self.tokens + f64::from(cost)).min(self.settings.capacity.into());
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * f64::from(self.settings.refill_per_sec))
+            .min(self.settings.capacity.into());
+        self.last_refill = now;
+    }
+
+    fn is_full(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= self.settings.capacity.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bucket(capacity: u16, refill_per_sec: u16, now: Instant) -> TokenBucket {
+        TokenBucket::new(
+            TokenBucketSettings {
+                capacity,
+                refill_per_sec,
+            },
+            now,
+        )
+    }
+
+    #[test]
+    fn bucket_allows_burst_up_to_capacity() {
+        let now = Instant::now();
+        let mut bucket = bucket(3, 1, now);
+        for _ in 0..3 {
+            assert_eq!(bucket.try_take(1, now), Ok(()));
+        }
+        assert_eq!(bucket.try_take(1, now), Err(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn bucket_refills_over_time() {
+        let now = Instant::now();
+        let mut bucket = bucket(2, 2, now);
+        assert_eq!(bucket.try_take(2, now), Ok(()));
+        assert!(bucket.try_take(1, now).is_err());
+        let later = now + Duration::from_millis(500);
+        assert_eq!(bucket.try_take(1, later), Ok(()));
+        assert!(bucket.try_take(1, later).is_err());
+    }
+
+    #[test]
+    fn bucket_never_exceeds_capacity() {
+        let now = Instant::now();
+        let mut bucket = bucket(2, 10, now);
+        let much_later = now + Duration::from_secs(60);
+        assert!(bucket.is_full(much_later));
+        assert_eq!(bucket.try_take(2, much_later), Ok(()));
+        assert!(bucket.try_take(1, much_later).is_err());
+    }
+
+    #[test]
+    fn full_user_buckets_only_pruned_after_interval() {
+        let now = Instant::now();
+        let mut user_buckets = UserBuckets {
+            buckets: HashMap::from([(
+                Username::try_from("user".to_string()).unwrap(),
+                bucket(2, 1, now),
+            )]),
+            last_pruned: now,
+        };
+        user_buckets.prune_if_due(now + Duration::from_secs(1));
+        assert_eq!(user_buckets.buckets.len(), 1);
+        user_buckets.prune_if_due(now + USER_BUCKETS_PRUNE_INTERVAL);
+        assert!(user_buckets.buckets.is_empty());
+    }
+
+    #[test]
+    fn invalid_msgs_count_as_violations() {
+        let limiter = ChatRateLimiter::new(ChatRateLimitSettings {
+            max_consecutive_violations: 2,
+            ..ChatRateLimitSettings::default()
+        });
+        let mut connection = limiter.new_connection();
+        let username = Username::try_from("user".to_string()).unwrap();
+        for _ in 0..2 {
+            assert_eq!(
+                limiter.check_invalid_msg(&mut connection, &username),
+                RateLimitOutcome::Allowed
+            );
+        }
+        assert_eq!(
+            limiter.check_invalid_msg(&mut connection, &username),
+            RateLimitOutcome::Disconnect
+        );
+    }
+
+    #[test]
+    fn history_cost_over_capacity_rejected() {
+        let mut settings = ChatRateLimitSettings::default();
+        assert!(settings.validate().is_ok());
+        settings.history_request_cost = settings.connection.capacity + 1;
+        assert!(settings.validate().is_err());
+        assert!(ChatRateLimitSettings::disabled().validate().is_ok());
+        settings.connection.capacity = 0;
+        settings.history_request_cost = settings.user.capacity;
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn zero_capacity_disables_limit() {
+        let now = Instant::now();
+        let mut bucket = bucket(0, 0, now);
+        for _ in 0..1000 {
+            assert_eq!(bucket.try_take(1, now), Ok(()));
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use crate::{ChatRoomInfo, ChatRoomName, RoomVisibility};
+use anyhow::{Context, bail};
+use std::collections::{BTreeMap, HashSet};
+use tracing::instrument;
+use wykies_shared::{
+    db_types::DbPool,
+    uac::{Permission, UserInfo},
+    websockets::WsConnId,
+};
+
+/// The rooms that exist and which connections are in each of them
+#[derive(Debug, Default)]
+pub struct ChatRooms {
+    rooms: BTreeMap<ChatRoomName, ChatRoom>,
+}
+
+#[derive(Debug)]
+struct ChatRoom {
+    info: ChatRoomInfo,
+    members: HashSet<WsConnId>,
+}
+
+impl ChatRooms {
+    /// Loads the rooms stored in the database (no room has any members after
+    /// loading)
+    #[instrument(err(Debug))]
+    pub async fn load(pool: &DbPool) -> anyhow::Result<Self> {
+        #[cfg(feature = "mysql")]
+        let rows = sqlx::query!(
+            "SELECT `RoomName`, `VisiblePermission`, `VisibleBranch` FROM `chat_room`"
+        )
+        .fetch_all(pool)
+        .await
+        .context("failed to get chat rooms")?;
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        let rows =
+            sqlx::query!("SELECT room_name, visible_permission, visible_branch FROM chat_room")
+                .fetch_all(pool)
+                .await
+                .context("failed to get chat rooms")?;
+
+        let mut result = Self::default();
+        for row in rows {
+            #[cfg(feature = "mysql")]
+            let (name, permission, branch) =
+                (row.RoomName, row.VisiblePermission, row.VisibleBranch);
+            #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+            let (name, permission, branch) =
+                (row.room_name, row.visible_permission, row.visible_branch);
+            let info = ChatRoomInfo {
+                name: name.try_into().context("invalid room name found in DB")?,
+                visibility: visibility_from_db(permission, branch)?,
+            };
+            result.insert(info);
+        }
+        Ok(result)
+    }
+
+    /// Saves the room to the database and adds it to the list of rooms
+    #[instrument(err(Debug))]
+    pub async fn create(&mut self, pool: &DbPool, info: ChatRoomInfo) -> anyhow::Result<()> {
+        if self.rooms.contains_key(&info.name) {
+            bail!("room already exists");
+        }
+        let (permission, branch) = match &info.visibility {
+            RoomVisibility::Everyone => (None, None),
+            RoomVisibility::Permission(permission) => {
+                (Some(<&'static str>::from(permission)), None)
+            }
+            RoomVisibility::Branch(branch_id) => (None, Some(*branch_id)),
+        };
+
+        #[cfg(feature = "mysql")]
+        sqlx::query!(
+            "INSERT INTO `chat_room` (`RoomName`, `VisiblePermission`, `VisibleBranch`)
+            VALUES (?, ?, ?);",
+            info.name,
+            permission,
+            branch,
+        )
+        .execute(pool)
+        .await
+        .context("failed to save chat room")?;
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        {
+            // TODO 5: Check why encode trait impl doesn't make converting not necessary
+            let branch: Option<i32> = branch.map(|x| x.try_into()).transpose()?;
+            sqlx::query!(
+                "INSERT INTO chat_room (room_name, visible_permission, visible_branch)
+                VALUES ($1, $2, $3);",
+                info.name.as_ref(),
+                permission,
+                branch,
+            )
+            .execute(pool)
+            .await
+            .context("failed to save chat room")?;
+        }
+
+        self.insert(info);
+        Ok(())
+    }
+
+    fn insert(&mut self, info: ChatRoomInfo) {
+        self.rooms.insert(
+            info.name.clone(),
+            ChatRoom {
+                info,
+                members: Default::default(),
+            },
+        );
+    }
+
+    /// Returns the room if it exists and the user is able to see it
+    pub fn get_visible(&self, name: &ChatRoomName, user_info: &UserInfo) -> Option<&ChatRoomInfo> {
+        self.rooms
+            .get(name)
+            .map(|room| &room.info)
+            .filter(|info| info.visibility.is_visible_to(user_info))
+    }
+
+    pub fn visible_to(&self, user_info: &UserInfo) -> Vec<ChatRoomInfo> {
+        self.rooms
+            .values()
+            .filter(|room| room.info.visibility.is_visible_to(user_info))
+            .map(|room| room.info.clone())
+            .collect()
+    }
+
+    pub fn is_member(&self, name: &ChatRoomName, conn_id: &WsConnId) -> bool {
+        self.rooms
+            .get(name)
+            .is_some_and(|room| room.members.contains(conn_id))
+    }
+
+    /// Returns true if the connection was not already a member
+    pub fn join(&mut self, name: &ChatRoomName, conn_id: WsConnId) -> bool {
+        self.rooms
+            .get_mut(name)
+            .is_some_and(|room| room.members.insert(conn_id))
+    }
+
+    /// Returns true if the connection was a member
+    pub fn leave(&mut self, name: &ChatRoomName, conn_id: &WsConnId) -> bool {
+        self.rooms
+            .get_mut(name)
+            .is_some_and(|room| room.members.remove(conn_id))
+    }
+
+    /// Removes the connection from all rooms and returns the rooms it was in
+    pub fn leave_all(&mut self, conn_id: &WsConnId) -> Vec<ChatRoomName> {
+        self.rooms
+            .values_mut()
+            .filter_map(|room| room.members.remove(conn_id).then(|| room.info.name.clone()))
+            .collect()
+    }
+
+    pub fn members(&self, name: &ChatRoomName) -> impl Iterator<Item = &WsConnId> {
+        self.rooms
+            .get(name)
+            .into_iter()
+            .flat_map(|room| room.members.iter())
+    }
+}
+
+fn visibility_from_db(
+    permission: Option<String>,
+    branch: Option<i32>,
+) -> anyhow::Result<RoomVisibility> {
+    Ok(match (permission, branch) {
+        (None, None) => RoomVisibility::Everyone,
+        (Some(permission), None) => RoomVisibility::Permission(
+            permission
+                .parse::<Permission>()
+                .with_context(|| format!("unknown permission found for room: {permission:?}"))?,
+        ),
+        (None, Some(branch)) => RoomVisibility::Branch(branch.try_into()?),
+        (Some(permission), Some(branch)) => bail!(
+            "room visibility restricted by both permission ({permission:?}) and branch ({branch})"
+        ),
+    })
+}
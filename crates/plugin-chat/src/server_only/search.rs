@@ -0,0 +1,141 @@
+//! Full-text search over the chat history saved in the database
+
+use super::history::db_id;
+use crate::{ChatImId, SearchBody, SearchMatch, SearchScope};
+use anyhow::Context;
+use sqlx::{QueryBuilder, Row as _};
+use tracing::{debug, instrument};
+use wykies_shared::{
+    db_types::{Db, DbPool},
+    uac::Username,
+};
+
+/// Names of the columns of the `chat` table used when building the query
+#[cfg(feature = "mysql")]
+mod col {
+    pub const ID: &str = "`ChatID`";
+    pub const ROOM: &str = "`Room`";
+    pub const RECIPIENT: &str = "`Recipient`";
+    pub const AUTHOR: &str = "`Author`";
+    pub const TIMESTAMP: &str = "`Timestamp`";
+}
+
+#[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+mod col {
+    pub const ID: &str = "chat_id";
+    pub const ROOM: &str = "room";
+    pub const RECIPIENT: &str = "recipient";
+    pub const AUTHOR: &str = "author";
+    pub const TIMESTAMP: &str = "unix_timestamp";
+}
+
+/// Returns up to `limit` saved IMs that match the search (newest first)
+///
+/// Each DB applies its own full-text rules. Both match whole words ignoring
+/// case but MySQL also ignores stopwords and words shorter than its minimum
+/// token size
+#[instrument(err(Debug))]
+pub async fn search_saved(
+    pool: &DbPool,
+    username: &Username,
+    req: &SearchBody,
+    terms: &[String],
+    limit: u16,
+) -> anyhow::Result<Vec<SearchMatch>> {
+    #[cfg(feature = "mysql")]
+    let mut query_builder: QueryBuilder<Db> = {
+        let mut query_builder = QueryBuilder::new(
+            "SELECT `ChatID`, `Author`, `Timestamp`, `Content` FROM `chat`
+            WHERE `Deleted` = 0 AND MATCH(`Content`) AGAINST (",
+        );
+        // Every term is required (Terms only contain letters and digits so they cannot
+        // add any other operators)
+        let boolean_query: Vec<String> = terms.iter().map(|term| format!("+{term}")).collect();
+        query_builder
+            .push_bind(boolean_query.join(" "))
+            .push(" IN BOOLEAN MODE)");
+        query_builder
+    };
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    let mut query_builder: QueryBuilder<Db> = {
+        // Must match the configuration used by the index (see migrations)
+        let mut query_builder = QueryBuilder::new(
+            "SELECT chat_id, author, unix_timestamp, content FROM chat
+            WHERE NOT deleted AND to_tsvector('simple', content) @@ plainto_tsquery('simple', ",
+        );
+        query_builder.push_bind(terms.join(" ")).push(")");
+        query_builder
+    };
+
+    match &req.scope {
+        SearchScope::Room(room) => {
+            query_builder
+                .push(format!(" AND {} = ", col::ROOM))
+                .push_bind(room.clone());
+        }
+        SearchScope::Direct(other_user) => {
+            query_builder
+                .push(format!(" AND (({} = ", col::AUTHOR))
+                .push_bind(username.clone())
+                .push(format!(" AND {} = ", col::RECIPIENT))
+                .push_bind(other_user.clone())
+                .push(format!(") OR ({} = ", col::AUTHOR))
+                .push_bind(other_user.clone())
+                .push(format!(" AND {} = ", col::RECIPIENT))
+                .push_bind(username.clone())
+                .push("))");
+        }
+    }
+    if let Some(author) = &req.author {
+        query_builder
+            .push(format!(" AND {} = ", col::AUTHOR))
+            .push_bind(author.clone());
+    }
+    if let Some(from) = req.from {
+        query_builder
+            .push(format!(" AND {} >= ", col::TIMESTAMP))
+            .push_bind(from);
+    }
+    if let Some(to) = req.to {
+        query_builder
+            .push(format!(" AND {} <= ", col::TIMESTAMP))
+            .push_bind(to);
+    }
+    if let Some(before) = req.before {
+        query_builder
+            .push(format!(" AND {} < ", col::ID))
+            .push_bind(db_id(before)?);
+    }
+    // IDs are assigned in the order IMs are sent
+    query_builder
+        .push(format!(" ORDER BY {} DESC LIMIT ", col::ID))
+        .push_bind(i64::from(limit));
+    debug!(query_builder.sql = ?query_builder.sql(), "Query Builder SQL");
+
+    // Persistent is set to false because the conditions included change
+    let rows = query_builder
+        .build()
+        .persistent(false)
+        .fetch_all(pool)
+        .await
+        .context("failed to search chat IMs")?;
+
+    rows.into_iter()
+        .map(|row| {
+            let id: i32 = row.try_get(0)?;
+            let author: String = row.try_get(1)?;
+            #[cfg(feature = "mysql")]
+            let timestamp = row.try_get::<u32, _>(2)?.into();
+            #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+            let timestamp = row.try_get::<i64, _>(2)?.try_into()?;
+            let content: String = row.try_get(3)?;
+            Ok(SearchMatch {
+                id: ChatImId::new(id.try_into()?),
+                author: author.try_into()?,
+                timestamp,
+                content: content.try_into()?,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()
+        .context("failed to convert rows from DB into search matches")
+}
@@ -1,10 +1,25 @@
-use super::{ChatServerHandle, ChatSettings, history::ChatHistory};
+use super::{
+    ChatServerHandle, ChatSettings,
+    health::ChatStats,
+    history::{ChatHistory, ImChange, ImLocation, ImTarget, db_id},
+    rate_limit::ChatRateLimiter,
+    rooms::ChatRooms,
+    search::search_saved,
+};
 use crate::{
-    ChatIM, ChatMsg, ChatMsgsHistory, ChatUser, InitialStateBody, ReqHistoryBody,
+    ChatIM, ChatImId, ChatMsg, ChatMsgsHistory, ChatRoomInfo, ChatRoomName, ChatRoomUser, ChatUser,
+    DirectErrorBody, DirectIM, DirectMsgsHistory, EditErrorBody, EditImBody, HistoryIm, ImState,
+    InitialStateBody, ReqDirectHistoryBody, ReqHistoryBody, RespDirectHistoryBody, RespHistoryBody,
+    RoomErrorBody, RoomStateBody, SearchBody, SearchErrorBody, SearchMatch, SearchResultsBody,
+    SearchScope,
     consts::{CHAT_HISTORY_RECENT_CAPACITY, CHAT_MAX_IMS_BEFORE_SAVE, CHAT_MAX_TIME_BEFORE_SAVE},
 };
 use anyhow::{Context, anyhow, bail};
-use std::{collections::HashMap, fmt::Debug, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    sync::Arc,
+};
 use tokio::{
     select,
     sync::{mpsc, oneshot},
@@ -13,9 +28,14 @@ use tracing::{error, info, instrument, warn};
 use tracked_cancellations::TrackedCancellationToken;
 use ws_helpers::{WebSocketSettings, heartbeat::HeartbeatConfig};
 use wykies_server::ServerTask;
+#[cfg(feature = "mysql")]
+use wykies_server::db_utils::db_int_to_bool;
 use wykies_shared::{
-    const_config::CHANNEL_BUFFER_SIZE, db_types::DbPool, debug_panic, log_as_error,
-    log_err_as_error, log_err_as_warn, uac::UserInfo, websockets::WsConnId,
+    const_config::CHANNEL_BUFFER_SIZE,
+    db_types::DbPool,
+    debug_panic, log_as_error, log_err_as_error, log_err_as_warn,
+    uac::{Permission, UserInfo, Username},
+    websockets::WsConnId,
 };
 
 /// A command received by the [`ChatServer`].
@@ -31,8 +51,9 @@ pub enum Command {
         conn: WsConnId,
     },
 
-    ForClients {
-        chat_msg: ChatMsg,
+    IM {
+        im: ChatIM,
+        conn_id: WsConnId,
         res_tx: oneshot::Sender<()>,
     },
 
@@ -41,6 +62,49 @@ pub enum Command {
         conn_id: WsConnId,
         res_tx: oneshot::Sender<()>,
     },
+
+    CreateRoom {
+        info: ChatRoomInfo,
+        conn_id: WsConnId,
+        res_tx: oneshot::Sender<()>,
+    },
+
+    JoinRoom {
+        room: ChatRoomName,
+        conn_id: WsConnId,
+        res_tx: oneshot::Sender<()>,
+    },
+
+    LeaveRoom {
+        room: ChatRoomName,
+        conn_id: WsConnId,
+        res_tx: oneshot::Sender<()>,
+    },
+
+    DirectIM {
+        im: DirectIM,
+        conn_id: WsConnId,
+        res_tx: oneshot::Sender<()>,
+    },
+
+    DirectHistoryReq {
+        req: ReqDirectHistoryBody,
+        conn_id: WsConnId,
+        res_tx: oneshot::Sender<()>,
+    },
+
+    ChangeIM {
+        id: ChatImId,
+        change: ImChange,
+        conn_id: WsConnId,
+        res_tx: oneshot::Sender<()>,
+    },
+
+    Search {
+        req: SearchBody,
+        conn_id: WsConnId,
+        res_tx: oneshot::Sender<()>,
+    },
 }
 
 #[derive(Debug)]
@@ -51,8 +115,11 @@ pub struct ChatServer {
     /// Command receiver.
     cmd_rx: mpsc::Receiver<Command>,
 
+    /// Loaded from the DB when the server starts running
+    rooms: ChatRooms,
     history: ChatHistory,
     db_pool: DbPool,
+    stats: Arc<ChatStats>,
 }
 
 impl ServerTask for ChatServer {
@@ -64,6 +131,13 @@ impl ServerTask for ChatServer {
     async fn run(mut self, cancellation_token: TrackedCancellationToken) -> anyhow::Result<()> {
         // Ensure that exiting causes the rest of the app to shut down
         let _drop_guard = cancellation_token.clone().drop_guard();
+        self.rooms = ChatRooms::load(&self.db_pool)
+            .await
+            .context("ChatServer failed to load rooms")?;
+        self.history
+            .load_last_id(&self.db_pool)
+            .await
+            .context("ChatServer failed to load last IM ID")?;
         loop {
             select! {
                 _ = cancellation_token.cancelled() => {
@@ -91,55 +165,87 @@ impl ChatServer {
         let heartbeat_config =
             HeartbeatConfig::new(config.heartbeat_interval_secs.into(), ws_config);
 
+        let stats = Arc::new(ChatStats::default());
+
         let history = ChatHistory::new(
             CHAT_HISTORY_RECENT_CAPACITY,
             db_pool.clone(),
             cancellation_token,
             CHAT_MAX_TIME_BEFORE_SAVE,
             CHAT_MAX_IMS_BEFORE_SAVE,
+            Arc::clone(&stats),
         );
 
         (
             Self {
                 connections: HashMap::new(),
                 cmd_rx,
+                rooms: Default::default(),
                 history,
                 db_pool,
+                stats: Arc::clone(&stats),
             },
-            ChatServerHandle::new(cmd_tx, heartbeat_config),
+            ChatServerHandle::new(
+                cmd_tx,
+                heartbeat_config,
+                stats,
+                ChatRateLimiter::new(config.rate_limit),
+            ),
         )
     }
 
-    /// Send message to other users
+    /// Send message to the connections in the room
     #[instrument]
-    async fn send_msg_to_clients(&mut self, chat_msg: ChatMsg) -> anyhow::Result<()> {
+    async fn send_msg_to_room(&self, room: &ChatRoomName, chat_msg: ChatMsg) {
         let msg = Arc::new(chat_msg);
-
-        // Save a copy of the IMs in recent history
-        if let ChatMsg::IM(im) = msg.as_ref() {
-            self.history
-                .push(im.clone())
-                .await
-                .context("failed to add IM to history")?;
-        }
-
-        for (conn_id, (_, tx)) in self.connections.iter() {
+        for conn_id in self.rooms.members(room) {
+            let Some((_, tx)) = self.connections.get(conn_id) else {
+                debug_panic!("room member without a connection found. Connection id {conn_id:?}");
+                continue;
+            };
             // errors if client disconnected abruptly and hasn't been timed-out yet
             let r = tx.send(Arc::clone(&msg)).await.with_context(|| {
                 format!("failed to send message to one of the clients. Connection id {conn_id:?}")
             });
             log_err_as_warn!(r);
         }
+    }
+
+    /// Saves the IM and sends it to the room if the connection is a member
+    #[instrument]
+    async fn send_im(&mut self, im: ChatIM, conn_id: WsConnId) -> anyhow::Result<()> {
+        if !self.rooms.is_member(&im.room, &conn_id) {
+            self.send_room_error(conn_id, im.room, "You are not in this room")
+                .await;
+            return Ok(());
+        }
+
+        // Save a copy of the IMs in recent history
+        let im = self
+            .history
+            .push(im)
+            .await
+            .context("failed to add IM to history")?;
+
+        let room = im.room.clone();
+        self.send_msg_to_room(&room, ChatMsg::IM(im)).await;
         Ok(())
     }
 
     #[instrument]
     async fn send_history(&self, req: ReqHistoryBody, conn_id: WsConnId) {
+        if !self.rooms.is_member(&req.room, &conn_id) {
+            self.send_room_error(conn_id, req.room, "You are not in this room")
+                .await;
+            return;
+        }
+
         #[cfg(feature = "mysql")]
         let query = sqlx::query!(
-            "SELECT `Author`, `Timestamp`, `Content`
-            FROM chat WHERE `Timestamp` <= ?
+            "SELECT `ChatID`, `Author`, `Timestamp`, `Content`, `Edited`, `Deleted`
+            FROM chat WHERE `Room` = ? AND `Timestamp` <= ?
             ORDER BY `Timestamp` DESC LIMIT ?",
+            req.room,
             req.latest_timestamp,
             req.qty
         );
@@ -160,9 +266,10 @@ impl ChatServer {
             };
             let qty: i64 = req.qty.into();
             sqlx::query!(
-                "SELECT author, unix_timestamp, content
-                FROM chat WHERE unix_timestamp <= $1
-                ORDER BY unix_timestamp DESC LIMIT $2",
+                "SELECT chat_id, author, unix_timestamp, content, edited, deleted
+                FROM chat WHERE room = $1 AND unix_timestamp <= $2
+                ORDER BY unix_timestamp DESC LIMIT $3",
+                req.room.as_ref(),
                 timestamp,
                 qty
             )
@@ -186,23 +293,29 @@ impl ChatServer {
             .map(|x| {
                 #[cfg(feature = "mysql")]
                 return Ok(ChatIM {
+                    id: ChatImId::new(x.ChatID.try_into()?),
+                    room: req.room.clone(),
                     author: x.Author.try_into()?,
                     timestamp: x.Timestamp.into(),
                     content: x.Content.try_into()?,
+                    state: ImState::from_flags(db_int_to_bool(x.Edited), db_int_to_bool(x.Deleted)),
                 });
                 #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
                 {
                     Ok(ChatIM {
+                        id: ChatImId::new(x.chat_id.try_into()?),
+                        room: req.room.clone(),
                         author: x.author.try_into()?,
                         timestamp: x.unix_timestamp.try_into()?,
                         content: x.content.try_into()?,
+                        state: ImState::from_flags(x.edited, x.deleted),
                     })
                 }
             })
             .collect::<anyhow::Result<Vec<_>>>()
             .context("failed to convert rows from DB into chat history");
 
-        let ims = match result {
+        let mut ims = match result {
             Ok(x) => x,
             Err(e) => {
                 // Abort Error occurred
@@ -210,13 +323,457 @@ impl ChatServer {
                 return;
             }
         };
+        self.history.refresh_from_recent(&req.room, &mut ims);
 
         // Sort result because it was sorted the wrong way for LIMIT to get right
         // messages
-        let mut result = ChatMsgsHistory { ims };
-        result.sort_by_timestamp();
-        self.send_to_client(conn_id, Arc::new(ChatMsg::RespHistory(result)))
+        let mut history = ChatMsgsHistory { ims };
+        history.sort_by_timestamp();
+        self.send_to_client(
+            conn_id,
+            Arc::new(ChatMsg::RespHistory(RespHistoryBody {
+                room: req.room,
+                history,
+            })),
+        )
+        .await;
+    }
+
+    /// Saves the direct IM and sends it to all connections of the author and
+    /// the recipient
+    #[instrument]
+    async fn send_direct_im(&mut self, im: DirectIM, conn_id: WsConnId) -> anyhow::Result<()> {
+        if im.recipient == im.author {
+            self.send_direct_error(
+                conn_id,
+                im.recipient,
+                "Cannot send a direct message to yourself",
+            )
             .await;
+            return Ok(());
+        }
+        let is_recipient_connected = self
+            .connections
+            .values()
+            .any(|(user_info, _)| user_info.username == im.recipient);
+        if !is_recipient_connected {
+            match self.user_exists(&im.recipient).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    self.send_direct_error(conn_id, im.recipient, "User not found")
+                        .await;
+                    return Ok(());
+                }
+                Err(err) => {
+                    log_as_error!("{err:?}");
+                    self.send_direct_error(conn_id, im.recipient, "Failed to send direct message")
+                        .await;
+                    return Ok(());
+                }
+            }
+        }
+
+        let im = self
+            .history
+            .push_direct(im)
+            .await
+            .context("failed to add direct IM to history")?;
+
+        let (author, recipient) = (im.author.clone(), im.recipient.clone());
+        self.send_msg_to_conversation(&author, &recipient, ChatMsg::DirectIM(im))
+            .await;
+        Ok(())
+    }
+
+    /// Send message to all connections of both users
+    #[instrument]
+    async fn send_msg_to_conversation(
+        &self,
+        user1: &Username,
+        user2: &Username,
+        chat_msg: ChatMsg,
+    ) {
+        let msg = Arc::new(chat_msg);
+        for (conn_id, (user_info, tx)) in self.connections.iter() {
+            if &user_info.username != user1 && &user_info.username != user2 {
+                continue;
+            }
+            // errors if client disconnected abruptly and hasn't been timed-out yet
+            let r = tx.send(Arc::clone(&msg)).await.with_context(|| {
+                format!("failed to send message to connection with id {conn_id:?}")
+            });
+            log_err_as_warn!(r);
+        }
+    }
+
+    #[instrument(err(Debug))]
+    async fn user_exists(&self, username: &Username) -> anyhow::Result<bool> {
+        #[cfg(feature = "mysql")]
+        let row = sqlx::query!(
+            "SELECT `UserName` FROM `user` WHERE `UserName` = ?",
+            username
+        )
+        .fetch_optional(&self.db_pool)
+        .await
+        .context("failed to check if user exists")?;
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        let row = sqlx::query!(
+            "SELECT user_name FROM users WHERE user_name = $1",
+            username.as_ref()
+        )
+        .fetch_optional(&self.db_pool)
+        .await
+        .context("failed to check if user exists")?;
+        Ok(row.is_some())
+    }
+
+    /// Sends the history of the conversation between the user of the
+    /// connection and the other user in the request
+    #[instrument]
+    async fn send_direct_history(&self, req: ReqDirectHistoryBody, conn_id: WsConnId) {
+        let Some((user_info, _)) = self.connections.get(&conn_id) else {
+            debug_panic!("unable to locate connection for ID: {conn_id:?}");
+            return;
+        };
+        let username = &user_info.username;
+        let mut ims = match self.get_saved_direct_history(username, &req).await {
+            Ok(x) => x,
+            Err(e) => {
+                // Abort Error occurred
+                log_as_error!("{e:?}");
+                return;
+            }
+        };
+
+        // Include IMs and changes that may not have been saved yet
+        for im in self.history.get_recent_direct(username, &req.other_user) {
+            if im.timestamp > req.latest_timestamp {
+                continue;
+            }
+            match ims.iter_mut().find(|x| x.id == im.id) {
+                Some(saved) => *saved = im,
+                None => ims.push(im),
+            }
+        }
+        let mut history = DirectMsgsHistory { ims };
+        history.sort_by_timestamp();
+        let excess = history.len().saturating_sub(req.qty.into());
+        history.ims.drain(..excess);
+
+        self.send_to_client(
+            conn_id,
+            Arc::new(ChatMsg::RespDirectHistory(RespDirectHistoryBody {
+                other_user: req.other_user,
+                history,
+            })),
+        )
+        .await;
+    }
+
+    #[instrument(err(Debug))]
+    async fn get_saved_direct_history(
+        &self,
+        username: &Username,
+        req: &ReqDirectHistoryBody,
+    ) -> anyhow::Result<Vec<DirectIM>> {
+        #[cfg(feature = "mysql")]
+        let rows = sqlx::query!(
+            "SELECT `ChatID`, `Author`, `Timestamp`, `Content`, `Edited`, `Deleted`
+            FROM chat
+            WHERE ((`Author` = ? AND `Recipient` = ?) OR (`Author` = ? AND `Recipient` = ?))
+                AND `Timestamp` <= ?
+            ORDER BY `Timestamp` DESC LIMIT ?",
+            username,
+            req.other_user,
+            req.other_user,
+            username,
+            req.latest_timestamp,
+            req.qty
+        )
+        .fetch_all(&self.db_pool)
+        .await
+        .context("failed to get direct ims")?;
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        let rows = {
+            let timestamp: i64 = req
+                .latest_timestamp
+                .as_secs_since_unix_epoch()
+                .try_into()
+                .context("failed to convert timestamp into DB format")?;
+            let qty: i64 = req.qty.into();
+            sqlx::query!(
+                "SELECT chat_id, author, unix_timestamp, content, edited, deleted
+                FROM chat
+                WHERE ((author = $1 AND recipient = $2) OR (author = $2 AND recipient = $1))
+                    AND unix_timestamp <= $3
+                ORDER BY unix_timestamp DESC LIMIT $4",
+                username.as_ref(),
+                req.other_user.as_ref(),
+                timestamp,
+                qty
+            )
+            .fetch_all(&self.db_pool)
+            .await
+            .context("failed to get direct ims")?
+        };
+
+        rows.into_iter()
+            .map(|x| {
+                #[cfg(feature = "mysql")]
+                let (id, author, timestamp, content, state) = (
+                    x.ChatID,
+                    x.Author,
+                    x.Timestamp.into(),
+                    x.Content,
+                    ImState::from_flags(db_int_to_bool(x.Edited), db_int_to_bool(x.Deleted)),
+                );
+                #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+                let (id, author, timestamp, content, state) = (
+                    x.chat_id,
+                    x.author,
+                    x.unix_timestamp.try_into()?,
+                    x.content,
+                    ImState::from_flags(x.edited, x.deleted),
+                );
+                let author: Username = author.try_into()?;
+                let recipient = if &author == username {
+                    req.other_user.clone()
+                } else {
+                    username.clone()
+                };
+                Ok(DirectIM {
+                    id: ChatImId::new(id.try_into()?),
+                    recipient,
+                    author,
+                    timestamp,
+                    content: content.try_into()?,
+                    state,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()
+            .context("failed to convert rows from DB into direct IM history")
+    }
+
+    #[instrument]
+    async fn send_direct_error(&self, conn_id: WsConnId, other_user: Username, message: &str) {
+        self.send_to_client(
+            conn_id,
+            Arc::new(ChatMsg::DirectError(DirectErrorBody {
+                other_user,
+                message: message.to_string(),
+            })),
+        )
+        .await;
+    }
+
+    /// Sends the saved IMs that match the search combined with the matching IMs
+    /// that may not have been saved yet
+    #[instrument]
+    async fn send_search_results(&self, req: SearchBody, conn_id: WsConnId) {
+        let Some((user_info, _)) = self.connections.get(&conn_id) else {
+            debug_panic!("unable to locate connection for ID: {conn_id:?}");
+            return;
+        };
+        let username = &user_info.username;
+        let terms = req.terms();
+        if terms.is_empty() {
+            self.send_search_error(conn_id, req.scope, "Enter at least one word to search for")
+                .await;
+            return;
+        }
+        let (recent_ids, recent_matches) = match &req.scope {
+            SearchScope::Room(room) => {
+                if !self.rooms.is_member(room, &conn_id) {
+                    self.send_search_error(conn_id, req.scope, "You are not in this room")
+                        .await;
+                    return;
+                }
+                recent_search_matches(&req, self.history.get_recent(room))
+            }
+            SearchScope::Direct(other_user) => {
+                recent_search_matches(&req, self.history.get_recent_direct(username, other_user))
+            }
+        };
+
+        // One extra is requested to know if there are more
+        let limit = u16::from(req.qty) + 1;
+        let saved = match search_saved(&self.db_pool, username, &req, &terms, limit).await {
+            Ok(x) => x,
+            Err(e) => {
+                log_as_error!("{e:?}");
+                self.send_search_error(conn_id, req.scope, "Search failed")
+                    .await;
+                return;
+            }
+        };
+        let qty = usize::from(req.qty);
+        let saved_has_more = saved.len() > qty;
+        let mut matches: Vec<SearchMatch> = saved
+            .into_iter()
+            .filter(|x| !recent_ids.contains(&x.id))
+            .chain(recent_matches)
+            .collect();
+        matches.sort_by_key(|x| std::cmp::Reverse(x.id));
+        let has_more = saved_has_more || matches.len() > qty;
+        matches.truncate(qty);
+
+        self.send_to_client(
+            conn_id,
+            Arc::new(ChatMsg::SearchResults(SearchResultsBody {
+                req,
+                matches,
+                has_more,
+            })),
+        )
+        .await;
+    }
+
+    #[instrument]
+    async fn send_search_error(&self, conn_id: WsConnId, scope: SearchScope, message: &str) {
+        self.send_to_client(
+            conn_id,
+            Arc::new(ChatMsg::SearchError(SearchErrorBody {
+                scope,
+                message: message.to_string(),
+            })),
+        )
+        .await;
+    }
+
+    /// Applies the change if the connection is allowed to make it and notifies
+    /// the connections that received the IM
+    ///
+    /// Room IMs may be changed by their author or a moderator in the room.
+    /// Direct IMs may only be changed by their author
+    #[instrument]
+    async fn change_im(
+        &mut self,
+        id: ChatImId,
+        change: ImChange,
+        conn_id: WsConnId,
+    ) -> anyhow::Result<()> {
+        let Some((user_info, _)) = self.connections.get(&conn_id) else {
+            debug_panic!("unable to locate connection for ID: {conn_id:?}");
+            return Ok(());
+        };
+        let location = match self.history.find_recent(id) {
+            Some(location) => location,
+            None => match self.find_saved_im(id).await {
+                Ok(Some(location)) => location,
+                Ok(None) => {
+                    self.send_edit_error(conn_id, id, "Message not found").await;
+                    return Ok(());
+                }
+                Err(err) => {
+                    log_as_error!("{err:?}");
+                    self.send_edit_error(conn_id, id, "Failed to change message")
+                        .await;
+                    return Ok(());
+                }
+            },
+        };
+        let is_author = location.author == user_info.username;
+        let is_allowed = match &location.target {
+            ImTarget::Room(room) => {
+                self.rooms.is_member(room, &conn_id)
+                    && (is_author || user_info.permissions.0.contains(&Permission::ManChat))
+            }
+            ImTarget::Direct { .. } => is_author,
+        };
+        if !is_allowed {
+            self.send_edit_error(conn_id, id, "You are not allowed to change this message")
+                .await;
+            return Ok(());
+        }
+        if location.state.is_deleted() {
+            self.send_edit_error(conn_id, id, "Message has been deleted")
+                .await;
+            return Ok(());
+        }
+
+        self.history
+            .change(id, change.clone())
+            .await
+            .context("failed to save IM change")?;
+
+        let chat_msg = match change {
+            ImChange::Edit(content) => ChatMsg::Edit(EditImBody { id, content }),
+            ImChange::Delete => ChatMsg::Delete(id),
+        };
+        match location.target {
+            ImTarget::Room(room) => self.send_msg_to_room(&room, chat_msg).await,
+            ImTarget::Direct { recipient } => {
+                self.send_msg_to_conversation(&location.author, &recipient, chat_msg)
+                    .await
+            }
+        }
+        Ok(())
+    }
+
+    /// Looks up IMs that are no longer in recent history
+    #[instrument(err(Debug))]
+    async fn find_saved_im(&self, id: ChatImId) -> anyhow::Result<Option<ImLocation>> {
+        let db_id = db_id(id)?;
+        #[cfg(feature = "mysql")]
+        let row = sqlx::query!(
+            "SELECT `Author`, `Room`, `Recipient`, `Edited`, `Deleted` FROM chat WHERE `ChatID` = ?",
+            db_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await
+        .context("failed to get chat IM")?;
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        let row = sqlx::query!(
+            "SELECT author, room, recipient, edited, deleted FROM chat WHERE chat_id = $1",
+            db_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await
+        .context("failed to get chat IM")?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        #[cfg(feature = "mysql")]
+        let (author, room, recipient, state) = (
+            row.Author,
+            row.Room,
+            row.Recipient,
+            ImState::from_flags(db_int_to_bool(row.Edited), db_int_to_bool(row.Deleted)),
+        );
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        let (author, room, recipient, state) = (
+            row.author,
+            row.room,
+            row.recipient,
+            ImState::from_flags(row.edited, row.deleted),
+        );
+        let target = match (room, recipient) {
+            (Some(room), None) => ImTarget::Room(room.try_into()?),
+            (None, Some(recipient)) => ImTarget::Direct {
+                recipient: recipient.try_into()?,
+            },
+            (room, recipient) => bail!(
+                "expected exactly one of room and recipient for chat IM {id} but found room: {room:?} and recipient: {recipient:?}"
+            ),
+        };
+        Ok(Some(ImLocation {
+            author: author.try_into()?,
+            target,
+            state,
+        }))
+    }
+
+    #[instrument]
+    async fn send_edit_error(&self, conn_id: WsConnId, id: ChatImId, message: &str) {
+        self.send_to_client(
+            conn_id,
+            Arc::new(ChatMsg::EditError(EditErrorBody {
+                id,
+                message: message.to_string(),
+            })),
+        )
+        .await;
     }
 
     #[instrument]
@@ -235,6 +792,18 @@ impl ChatServer {
         log_err_as_error!(r);
     }
 
+    #[instrument]
+    async fn send_room_error(&self, conn_id: WsConnId, room: ChatRoomName, message: &str) {
+        self.send_to_client(
+            conn_id,
+            Arc::new(ChatMsg::RoomError(RoomErrorBody {
+                room,
+                message: message.to_string(),
+            })),
+        )
+        .await;
+    }
+
     /// Register new connection and assign unique ID to this connection
     #[instrument(skip())]
     async fn register_connection(
@@ -242,29 +811,36 @@ impl ChatServer {
         tx: mpsc::Sender<Arc<ChatMsg>>,
         user_info: UserInfo,
     ) -> anyhow::Result<WsConnId> {
-        // notify all users
-        self.send_msg_to_clients(ChatMsg::UserJoined(ChatUser::new(
-            user_info.username.clone(),
-        )))
-        .await
-        .context("failed to send msg to clients")?;
-
         // register session using a connection ID
         let id = WsConnId::new_rand();
+        let rooms = self.rooms.visible_to(&user_info);
         self.connections.insert(id, (user_info, tx.clone()));
+        self.stats.set_connections(self.connections.len());
+
+        // Join default room (notifies the other users in the room)
+        let default_room = self.add_to_room(ChatRoomName::default_room(), id).await;
 
         // Send initial connection information
-        self.send_initial_state(tx).await;
+        let msg = Arc::new(ChatMsg::InitialState(InitialStateBody {
+            rooms,
+            default_room,
+        }));
+        let r = tx
+            .send(msg)
+            .await
+            .map_err(|msg| anyhow::anyhow!("failed to send initial state of: {msg:?}"));
+        log_err_as_error!(r);
 
         // send id back
         Ok(id)
     }
 
-    /// Returns the list of currently connected users with their multiplicity
+    /// Returns the list of users in the room with their multiplicity
     #[instrument]
-    fn get_connected_users(&self) -> Vec<(ChatUser, u8)> {
-        self.connections
-            .values()
+    fn get_room_users(&self, room: &ChatRoomName) -> Vec<(ChatUser, u8)> {
+        self.rooms
+            .members(room)
+            .filter_map(|conn_id| self.connections.get(conn_id))
             .map(|(user_info, _)| ChatUser::new(user_info.username.clone()))
             .fold(HashMap::<ChatUser, u8>::new(), |mut map, user| {
                 let freq = map.entry(user).or_default();
@@ -275,39 +851,141 @@ impl ChatServer {
             .collect()
     }
 
+    /// Notifies the room, adds the connection to it and returns the state of
+    /// the room
+    ///
+    /// Prerequisite: The connection must be registered and not already be in the
+    /// room
     #[instrument]
-    async fn send_initial_state(&self, tx: mpsc::Sender<Arc<ChatMsg>>) {
-        let connected_users = self.get_connected_users();
-        let history = ChatMsgsHistory {
-            ims: self.history.get_recent(),
+    async fn add_to_room(&mut self, room: ChatRoomName, conn_id: WsConnId) -> RoomStateBody {
+        if let Some((user_info, _)) = self.connections.get(&conn_id) {
+            let user = ChatUser::new(user_info.username.clone());
+            self.send_msg_to_room(
+                &room,
+                ChatMsg::UserJoined(ChatRoomUser {
+                    room: room.clone(),
+                    user,
+                }),
+            )
+            .await;
+        } else {
+            debug_panic!("unable to locate connection for ID: {conn_id:?}");
+        }
+        let was_added = self.rooms.join(&room, conn_id);
+        debug_assert!(was_added, "connection should not already be in the room");
+        RoomStateBody {
+            connected_users: self.get_room_users(&room),
+            history: ChatMsgsHistory {
+                ims: self.history.get_recent(&room),
+            },
+            room,
+        }
+    }
+
+    #[instrument]
+    async fn create_room(&mut self, info: ChatRoomInfo, conn_id: WsConnId) {
+        let Some((user_info, _)) = self.connections.get(&conn_id) else {
+            debug_panic!("unable to locate connection for ID: {conn_id:?}");
+            return;
         };
-        let msg = Arc::new(ChatMsg::InitialState(InitialStateBody {
-            connected_users,
-            history,
-        }));
-        let r = tx
-            .send(msg)
-            .await
-            .map_err(|msg| anyhow::anyhow!("failed to send initial state of: {msg:?}"));
-        log_err_as_error!(r);
+        if !info.visibility.is_visible_to(user_info) {
+            self.send_room_error(
+                conn_id,
+                info.name,
+                "Room would not be visible to you. You can only create rooms you are able to see",
+            )
+            .await;
+            return;
+        }
+        let room = info.name.clone();
+        if let Err(err) = self.rooms.create(&self.db_pool, info.clone()).await {
+            warn!(?err, "failed to create room");
+            self.send_room_error(conn_id, room, &format!("Failed to create room: {err}"))
+                .await;
+            return;
+        }
+
+        // Let everyone who can see the room know about it
+        let msg = Arc::new(ChatMsg::RoomCreated(info.clone()));
+        for (other_conn_id, (user_info, tx)) in self.connections.iter() {
+            if !info.visibility.is_visible_to(user_info) {
+                continue;
+            }
+            let r = tx.send(Arc::clone(&msg)).await.with_context(|| {
+                format!("failed to send new room to connection with id {other_conn_id:?}")
+            });
+            log_err_as_warn!(r);
+        }
+
+        self.join_room(room, conn_id).await;
+    }
+
+    #[instrument]
+    async fn join_room(&mut self, room: ChatRoomName, conn_id: WsConnId) {
+        let Some((user_info, _)) = self.connections.get(&conn_id) else {
+            debug_panic!("unable to locate connection for ID: {conn_id:?}");
+            return;
+        };
+        if self.rooms.get_visible(&room, user_info).is_none() {
+            // Rooms the user is not able to see are treated as if they don't exist
+            self.send_room_error(conn_id, room, "Room not found").await;
+            return;
+        }
+        if self.rooms.is_member(&room, &conn_id) {
+            self.send_room_error(conn_id, room, "Already in this room")
+                .await;
+            return;
+        }
+        let room_state = self.add_to_room(room, conn_id).await;
+        self.send_to_client(conn_id, Arc::new(ChatMsg::RoomJoined(room_state)))
+            .await;
+    }
+
+    #[instrument]
+    async fn leave_room(&mut self, room: ChatRoomName, conn_id: WsConnId) {
+        if !self.rooms.leave(&room, &conn_id) {
+            self.send_room_error(conn_id, room, "You are not in this room")
+                .await;
+            return;
+        }
+        self.send_to_client(conn_id, Arc::new(ChatMsg::RoomLeft(room.clone())))
+            .await;
+        if let Some((user_info, _)) = self.connections.get(&conn_id) {
+            let user = ChatUser::new(user_info.username.clone());
+            self.send_msg_to_room(
+                &room.clone(),
+                ChatMsg::UserLeft(ChatRoomUser { room, user }),
+            )
+            .await;
+        }
     }
 
     #[instrument]
     async fn unregister_connection(&mut self, conn_id: WsConnId) -> anyhow::Result<()> {
         // remove sender
         let remove_result = self.connections.remove(&conn_id);
+        self.stats.set_connections(self.connections.len());
+        let rooms = self.rooms.leave_all(&conn_id);
 
         if let Some((user_info, _)) = remove_result {
-            // Notify other users of disconnect
-            self.send_msg_to_clients(ChatMsg::UserLeft(ChatUser::new(user_info.username)))
-                .await
-                .context("failed to unregister connection")
+            // Notify other users in the same rooms of disconnect
+            let user = ChatUser::new(user_info.username);
+            for room in rooms {
+                self.send_msg_to_room(
+                    &room.clone(),
+                    ChatMsg::UserLeft(ChatRoomUser {
+                        room,
+                        user: user.clone(),
+                    }),
+                )
+                .await;
+            }
         } else {
             error!(
                 "Unable to send disconnection message because user info and connection not found for {conn_id:?}"
             );
-            Ok(())
         }
+        Ok(())
     }
 
     /// This is the code used by the server to process commands received over
@@ -345,10 +1023,14 @@ impl ChatServer {
                     .context("fatal error, failed to unregister a connection")?;
             }
 
-            Command::ForClients { chat_msg, res_tx } => {
-                self.send_msg_to_clients(chat_msg)
+            Command::IM {
+                im,
+                conn_id,
+                res_tx,
+            } => {
+                self.send_im(im, conn_id)
                     .await
-                    .context("failed to sent IM to clients")?;
+                    .context("failed to send IM to room")?;
                 self.send_response(res_tx, ()).await;
             }
 
@@ -360,6 +1042,74 @@ impl ChatServer {
                 self.send_history(req, conn_id).await;
                 self.send_response(res_tx, ()).await;
             }
+
+            Command::CreateRoom {
+                info,
+                conn_id,
+                res_tx,
+            } => {
+                self.create_room(info, conn_id).await;
+                self.send_response(res_tx, ()).await;
+            }
+
+            Command::JoinRoom {
+                room,
+                conn_id,
+                res_tx,
+            } => {
+                self.join_room(room, conn_id).await;
+                self.send_response(res_tx, ()).await;
+            }
+
+            Command::LeaveRoom {
+                room,
+                conn_id,
+                res_tx,
+            } => {
+                self.leave_room(room, conn_id).await;
+                self.send_response(res_tx, ()).await;
+            }
+
+            Command::DirectIM {
+                im,
+                conn_id,
+                res_tx,
+            } => {
+                self.send_direct_im(im, conn_id)
+                    .await
+                    .context("failed to send direct IM")?;
+                self.send_response(res_tx, ()).await;
+            }
+
+            Command::DirectHistoryReq {
+                req,
+                conn_id,
+                res_tx,
+            } => {
+                self.send_direct_history(req, conn_id).await;
+                self.send_response(res_tx, ()).await;
+            }
+
+            Command::ChangeIM {
+                id,
+                change,
+                conn_id,
+                res_tx,
+            } => {
+                self.change_im(id, change, conn_id)
+                    .await
+                    .context("failed to change IM")?;
+                self.send_response(res_tx, ()).await;
+            }
+
+            Command::Search {
+                req,
+                conn_id,
+                res_tx,
+            } => {
+                self.send_search_results(req, conn_id).await;
+                self.send_response(res_tx, ()).await;
+            }
         }
         Ok(())
     }
@@ -372,3 +1122,18 @@ impl ChatServer {
         log_err_as_error!(r);
     }
 }
+
+/// Returns the IDs of all the recent IMs (The saved copies of these may be out
+/// of date) and the recent IMs that match the search
+fn recent_search_matches<T: HistoryIm>(
+    req: &SearchBody,
+    recent: Vec<T>,
+) -> (HashSet<ChatImId>, Vec<SearchMatch>) {
+    let ids = recent.iter().map(|im| im.id()).collect();
+    let matches = recent
+        .iter()
+        .filter(|im| req.is_match(*im))
+        .map(SearchMatch::from_im)
+        .collect();
+    (ids, matches)
+}
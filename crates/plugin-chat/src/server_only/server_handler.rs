@@ -1,12 +1,24 @@
-use super::server::Command;
-use crate::{ChatMsg, ReqHistoryBody};
+use super::{
+    health::ChatStats,
+    history::ImChange,
+    rate_limit::{ChatRateLimiter, ConnectionRateLimiter, RateLimitOutcome},
+    server::Command,
+};
+use crate::{
+    ChatIM, ChatImId, ChatMsg, ChatRoomInfo, ChatRoomName, DirectIM, EditImBody,
+    ReqDirectHistoryBody, ReqHistoryBody, SearchBody,
+};
 use anyhow::Context;
 use std::sync::Arc;
 use tokio::sync::{mpsc, oneshot};
 use tracing::instrument;
 use tracked_cancellations::TrackedCancellationToken;
 use ws_helpers::heartbeat::HeartbeatConfig;
-use wykies_shared::{log_err_as_error, uac::UserInfo, websockets::WsConnId};
+use wykies_shared::{
+    log_err_as_error,
+    uac::{UserInfo, Username},
+    websockets::WsConnId,
+};
 
 /// Handle and command sender for chat server.
 ///
@@ -15,15 +27,59 @@ use wykies_shared::{log_err_as_error, uac::UserInfo, websockets::WsConnId};
 pub struct ChatServerHandle {
     cmd_tx: mpsc::Sender<Command>,
     pub heartbeat_config: HeartbeatConfig,
+    stats: Arc<ChatStats>,
+    rate_limiter: Arc<ChatRateLimiter>,
 }
 impl ChatServerHandle {
-    pub(crate) fn new(cmd_tx: mpsc::Sender<Command>, heartbeat_config: HeartbeatConfig) -> Self {
+    pub(crate) fn new(
+        cmd_tx: mpsc::Sender<Command>,
+        heartbeat_config: HeartbeatConfig,
+        stats: Arc<ChatStats>,
+        rate_limiter: ChatRateLimiter,
+    ) -> Self {
         Self {
             cmd_tx,
             heartbeat_config,
+            stats,
+            rate_limiter: Arc::new(rate_limiter),
         }
     }
 
+    /// Returns the number of commands waiting to be processed and the capacity
+    /// of the queue
+    pub(crate) fn queue_depth(&self) -> (usize, usize) {
+        let capacity = self.cmd_tx.max_capacity();
+        (capacity - self.cmd_tx.capacity(), capacity)
+    }
+
+    pub(crate) fn stats(&self) -> &ChatStats {
+        &self.stats
+    }
+
+    pub(crate) fn new_connection_rate_limiter(&self) -> ConnectionRateLimiter {
+        self.rate_limiter.new_connection()
+    }
+
+    /// Checks the message against the limits of both the connection and the
+    /// user
+    pub(crate) fn check_rate_limit(
+        &self,
+        connection: &mut ConnectionRateLimiter,
+        username: &Username,
+        chat_msg: &ChatMsg,
+    ) -> RateLimitOutcome {
+        self.rate_limiter.check(connection, username, chat_msg)
+    }
+
+    /// Checks a message that could not be parsed against the limits
+    pub(crate) fn check_rate_limit_for_invalid_msg(
+        &self,
+        connection: &mut ConnectionRateLimiter,
+        username: &Username,
+    ) -> RateLimitOutcome {
+        self.rate_limiter.check_invalid_msg(connection, username)
+    }
+
     /// Register client message sender and obtain connection ID.
     #[instrument(skip())]
     pub async fn register(
@@ -45,14 +101,21 @@ impl ChatServerHandle {
         .expect("failed to send command")
     }
 
-    /// Broadcast message to other users
+    /// Broadcast IM to the other users in the room
     #[instrument]
-    pub async fn send_msg_to_clients(&self, chat_msg: ChatMsg) {
+    pub async fn send_im(&self, conn_id: &WsConnId, im: ChatIM) {
         let (res_tx, res_rx) = oneshot::channel();
 
-        self.send_cmd_to_server(Command::ForClients { chat_msg, res_tx }, res_rx)
-            .await
-            .expect("failed to send command");
+        self.send_cmd_to_server(
+            Command::IM {
+                im,
+                conn_id: conn_id.to_owned(),
+                res_tx,
+            },
+            res_rx,
+        )
+        .await
+        .expect("failed to send command");
     }
 
     #[instrument]
@@ -71,6 +134,134 @@ impl ChatServerHandle {
         .expect("failed to send command");
     }
 
+    /// Send direct IM to the author and recipient
+    #[instrument]
+    pub async fn send_direct_im(&self, conn_id: &WsConnId, im: DirectIM) {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_cmd_to_server(
+            Command::DirectIM {
+                im,
+                conn_id: conn_id.to_owned(),
+                res_tx,
+            },
+            res_rx,
+        )
+        .await
+        .expect("failed to send command");
+    }
+
+    #[instrument]
+    pub async fn process_direct_history_request(
+        &self,
+        conn_id: &WsConnId,
+        req: ReqDirectHistoryBody,
+    ) {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_cmd_to_server(
+            Command::DirectHistoryReq {
+                req,
+                conn_id: conn_id.to_owned(),
+                res_tx,
+            },
+            res_rx,
+        )
+        .await
+        .expect("failed to send command");
+    }
+
+    #[instrument]
+    pub async fn search(&self, conn_id: &WsConnId, req: SearchBody) {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_cmd_to_server(
+            Command::Search {
+                req,
+                conn_id: conn_id.to_owned(),
+                res_tx,
+            },
+            res_rx,
+        )
+        .await
+        .expect("failed to send command");
+    }
+
+    #[instrument]
+    pub async fn edit_im(&self, conn_id: &WsConnId, body: EditImBody) {
+        self.change_im(conn_id, body.id, ImChange::Edit(body.content))
+            .await;
+    }
+
+    #[instrument]
+    pub async fn delete_im(&self, conn_id: &WsConnId, id: ChatImId) {
+        self.change_im(conn_id, id, ImChange::Delete).await;
+    }
+
+    async fn change_im(&self, conn_id: &WsConnId, id: ChatImId, change: ImChange) {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_cmd_to_server(
+            Command::ChangeIM {
+                id,
+                change,
+                conn_id: conn_id.to_owned(),
+                res_tx,
+            },
+            res_rx,
+        )
+        .await
+        .expect("failed to send command");
+    }
+
+    #[instrument]
+    pub async fn create_room(&self, conn_id: &WsConnId, info: ChatRoomInfo) {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_cmd_to_server(
+            Command::CreateRoom {
+                info,
+                conn_id: conn_id.to_owned(),
+                res_tx,
+            },
+            res_rx,
+        )
+        .await
+        .expect("failed to send command");
+    }
+
+    #[instrument]
+    pub async fn join_room(&self, conn_id: &WsConnId, room: ChatRoomName) {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_cmd_to_server(
+            Command::JoinRoom {
+                room,
+                conn_id: conn_id.to_owned(),
+                res_tx,
+            },
+            res_rx,
+        )
+        .await
+        .expect("failed to send command");
+    }
+
+    #[instrument]
+    pub async fn leave_room(&self, conn_id: &WsConnId, room: ChatRoomName) {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_cmd_to_server(
+            Command::LeaveRoom {
+                room,
+                conn_id: conn_id.to_owned(),
+                res_tx,
+            },
+            res_rx,
+        )
+        .await
+        .expect("failed to send command");
+    }
+
     #[instrument(skip(res_rx))]
     async fn send_cmd_to_server<T>(
         &self,
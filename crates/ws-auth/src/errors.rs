@@ -23,7 +23,20 @@ pub enum WebSocketAuthError {
 #[cfg(not(target_arch = "wasm32"))]
 pub mod conversions {
     use super::*;
-    use actix_web::http::StatusCode;
+    use actix_web::{HttpResponse, http::StatusCode};
+    use wykies_shared::api_error::{ApiError, ApiErrorCode};
+
+    impl From<&WebSocketAuthError> for ApiError {
+        fn from(value: &WebSocketAuthError) -> Self {
+            let code = match value {
+                WebSocketAuthError::UnexpectedClient { .. }
+                | WebSocketAuthError::InvalidToken { .. } => ApiErrorCode::WebSocketAuthFailed,
+                WebSocketAuthError::FailedToStartSession(_)
+                | WebSocketAuthError::UnexpectedError(_) => ApiErrorCode::Internal,
+            };
+            Self::new(code, value.to_string())
+        }
+    }
 
     impl actix_web::error::ResponseError for WebSocketAuthError {
         fn status_code(&self) -> StatusCode {
@@ -36,5 +49,9 @@ pub mod conversions {
                 WebSocketAuthError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             }
         }
+
+        fn error_response(&self) -> HttpResponse {
+            ApiError::from(self).to_response(self.status_code())
+        }
     }
 }
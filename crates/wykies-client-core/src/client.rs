@@ -1,33 +1,55 @@
-use anyhow::{Context, anyhow, bail};
+use anyhow::{anyhow, bail};
 use reqwest_cross::reqwest::{self, Method, RequestBuilder, StatusCode};
 use reqwest_cross::{fetch, fetch_plus, oneshot};
-use secrecy::ExposeSecret as _;
+use secrecy::{ExposeSecret as _, SecretString};
 use std::fmt::Debug;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tracing::{info, warn};
 use wykies_shared::uac::LoginResponse;
 use wykies_shared::{
+    api_error::ApiError,
     branch::Branch,
-    const_config::path::{PATH_BRANCH_LIST, PATH_HEALTH_CHECK, PATH_LOGIN, PathSpec},
-    req_args::LoginReqArgs,
+    const_config::{
+        client::AWAITING_RESPONSE_TIMEOUT,
+        path::{
+            PATH_API_OPENAPI, PATH_BRANCH_LIST, PATH_HEALTH_CHECK, PATH_LOGIN,
+            PATH_PASSWORD_RESET_REDEEM, PATH_PASSWORD_RESET_REQUEST, PATH_STATUS_JSON,
+            PATH_VERSION, PathSpec,
+        },
+    },
+    health::HealthReport,
+    req_args::{LoginReqArgs, password_reset},
     uac::UserInfo,
+    version::{CompatibilityOutcome, VersionInfo},
 };
 
 pub mod api;
+mod retry;
 pub mod websocket;
 
+use retry::retry_if_enabled;
+pub use retry::{RequestTimeoutError, RetryPolicy};
+
 pub const DUMMY_ARGUMENT: &[(&str, &str)] = &[("", "")];
 
 #[derive(Debug, Clone)]
 pub struct Client {
     api_client: reqwest::Client,
     inner: Arc<Mutex<ClientInner>>,
+    request_timeout: Duration,
+    retry_policy: Option<RetryPolicy>,
+    api_key: Option<SecretString>,
+    #[cfg(feature = "expose_internal")]
+    extra_headers: Vec<(&'static str, String)>,
 }
 
 #[derive(Debug)]
 struct ClientInner {
     server_address: String,
     user_info: Option<Arc<UserInfo>>,
+    password_expires_in_days: Option<u16>,
+    compatibility: Option<CompatibilityOutcome>,
 }
 
 impl Default for Client {
@@ -48,17 +70,27 @@ pub enum LoginOutcome {
     Success,
     ForcePasswordChange,
     RetryWithBranchSet,
+    /// Resend the login including the code from the user's authenticator app
+    /// or a recovery code
+    TotpRequired,
+    /// Logged in but the user must complete two-factor authentication
+    /// enrolment before they can do anything else
+    TotpEnrolmentRequired,
 }
 
 impl LoginOutcome {
     /// Returns `true` if the login outcome is
-    /// [`Success`] or [`ForcePasswordChange`]
+    /// [`Success`], [`ForcePasswordChange`] or [`TotpEnrolmentRequired`]
     ///
     /// [`Success`]: LoginOutcome::Success
     /// [`ForcePasswordChange`]: LoginOutcome::ForcePasswordChange
+    /// [`TotpEnrolmentRequired`]: LoginOutcome::TotpEnrolmentRequired
     #[must_use]
     pub fn is_any_success(&self) -> bool {
-        matches!(self, Self::Success) || matches!(self, Self::ForcePasswordChange)
+        matches!(
+            self,
+            Self::Success | Self::ForcePasswordChange | Self::TotpEnrolmentRequired
+        )
     }
 }
 
@@ -68,6 +100,8 @@ impl ClientInner {
         Self {
             server_address,
             user_info: None,
+            password_expires_in_days: None,
+            compatibility: None,
         }
     }
 }
@@ -84,9 +118,45 @@ impl Client {
         Self {
             api_client,
             inner: Arc::new(Mutex::new(ClientInner::new(server_address))),
+            request_timeout: AWAITING_RESPONSE_TIMEOUT.into(),
+            retry_policy: None,
+            api_key: None,
+            #[cfg(feature = "expose_internal")]
+            extra_headers: Vec::new(),
         }
     }
 
+    /// Sets how long to wait for a response before failing with a
+    /// [`RequestTimeoutError`]. Clones share the session so use
+    /// `client.clone().with_request_timeout(..)` to change it for a single
+    /// request
+    pub fn with_request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    /// Enables retrying of GET requests (See [`RetryPolicy`])
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// Authenticates requests using an API key instead of logging in. Used by
+    /// service accounts (See [`Self::api_key_new`])
+    pub fn with_api_key(mut self, api_key: SecretString) -> Self {
+        self.api_key = Some(api_key);
+        self
+    }
+
+    /// Adds a header to every request sent by this client. Clones share the
+    /// session so this can be used to send requests in the same session that
+    /// appear to come from another host (using `X-Forwarded-For`)
+    #[cfg(feature = "expose_internal")]
+    pub fn expose_internal_with_header(mut self, name: &'static str, value: String) -> Self {
+        self.extra_headers.push((name, value));
+        self
+    }
+
     #[tracing::instrument]
     pub fn get_branches(&self) -> oneshot::Receiver<anyhow::Result<Vec<Branch>>> {
         self.send_request_expect_json(PATH_BRANCH_LIST, &DUMMY_ARGUMENT)
@@ -94,25 +164,97 @@ impl Client {
 
     #[tracing::instrument]
     pub fn login(&self, args: LoginReqArgs) -> oneshot::Receiver<anyhow::Result<LoginOutcome>> {
+        if let Some(CompatibilityOutcome::Refuse(reason)) = self.compatibility() {
+            let (tx, rx) = oneshot::channel();
+            let _ = tx.send(Err(anyhow!("server is not compatible: {reason}")));
+            return rx;
+        }
         let args = serde_json::json!({
             "username": args.username,
             "password": args.password.expose_secret(),
             "branch_to_set": args.branch_to_set,
+            "totp_code": args.totp_code.as_ref().map(|x| x.expose_secret()),
         });
         let req = self.create_request_builder(PATH_LOGIN, &args);
         let client = self.clone();
-        // TODO 5: Add timeout
         let response_handler = move |resp: reqwest::Result<reqwest::Response>| async {
             process_login(resp, client).await
         };
         fetch_plus(req, response_handler, || {})
     }
 
+    /// Asks the server to send a password reset token to the user. Succeeds
+    /// even if the user does not exist or has no email address
+    #[tracing::instrument]
+    pub fn request_password_reset(
+        &self,
+        args: password_reset::RequestReqArgs,
+    ) -> oneshot::Receiver<anyhow::Result<()>> {
+        self.send_request_expect_empty(PATH_PASSWORD_RESET_REQUEST, &args)
+    }
+
+    #[tracing::instrument]
+    pub fn redeem_password_reset(
+        &self,
+        args: password_reset::RedeemReqArgs,
+    ) -> oneshot::Receiver<anyhow::Result<()>> {
+        let args = serde_json::json!({
+            "username": args.username,
+            "token": args.token.expose_secret(),
+            "new_password": args.new_password.expose_secret(),
+        });
+        self.send_request_expect_empty(PATH_PASSWORD_RESET_REDEEM, &args)
+    }
+
     #[tracing::instrument]
     pub fn health_check(&self) -> oneshot::Receiver<anyhow::Result<()>> {
         self.send_request_expect_empty(PATH_HEALTH_CHECK, &DUMMY_ARGUMENT)
     }
 
+    /// Gets the server's version info and checks if a client at
+    /// `client_pkg_version` that uses `required_plugins` is able to work with
+    /// it. The outcome is saved and [`Self::login`] fails if the server was
+    /// refused
+    #[tracing::instrument]
+    pub fn check_compatibility(
+        &self,
+        client_pkg_version: &'static str,
+        required_plugins: &'static [&'static str],
+    ) -> oneshot::Receiver<anyhow::Result<CompatibilityOutcome>> {
+        let req = self.create_request_builder(PATH_VERSION, &DUMMY_ARGUMENT);
+        let retry = self.retry_for(Self::is_retry_allowed(&PATH_VERSION), &req);
+        let client = self.clone();
+        let response_handler = move |resp: reqwest::Result<reqwest::Response>| async move {
+            let resp = retry_if_enabled(resp, retry).await;
+            let version_info: VersionInfo = process_json_body(resp).await?;
+            let outcome = version_info.compatibility(client_pkg_version, required_plugins);
+            info!(?version_info, ?outcome, "server compatibility checked");
+            client.inner.lock().expect("mutex poisoned").compatibility = Some(outcome.clone());
+            Ok(outcome)
+        };
+        fetch_plus(req, response_handler, || {})
+    }
+
+    /// The outcome of the last call to [`Self::check_compatibility`] if any
+    pub fn compatibility(&self) -> Option<CompatibilityOutcome> {
+        self.inner
+            .lock()
+            .expect("mutex poisoned")
+            .compatibility
+            .clone()
+    }
+
+    #[tracing::instrument]
+    pub fn status(&self) -> oneshot::Receiver<anyhow::Result<HealthReport>> {
+        self.send_request_expect_json(PATH_STATUS_JSON, &DUMMY_ARGUMENT)
+    }
+
+    /// Gets the OpenAPI document describing the server's API
+    #[tracing::instrument]
+    pub fn openapi(&self) -> oneshot::Receiver<anyhow::Result<serde_json::Value>> {
+        self.send_request_expect_json(PATH_API_OPENAPI, &DUMMY_ARGUMENT)
+    }
+
     #[tracing::instrument(skip(args))]
     // WARNING: Must skip args as it my contain sensitive info and "safe" versions
     // would usually already be logged by the caller
@@ -123,7 +265,19 @@ impl Client {
         let is_get_method = path_spec.method == Method::GET;
         let request = self
             .api_client
-            .request(path_spec.method, self.path_to_url(path_spec.path));
+            .request(path_spec.method, self.path_to_url(path_spec.path))
+            .timeout(self.request_timeout);
+        let request = match &self.api_key {
+            Some(api_key) => request.bearer_auth(api_key.expose_secret()),
+            None => request,
+        };
+        #[cfg(feature = "expose_internal")]
+        let request = self
+            .extra_headers
+            .iter()
+            .fold(request, |request, (name, value)| {
+                request.header(*name, value)
+            });
         if is_get_method {
             request.query(&args)
         } else {
@@ -140,10 +294,12 @@ impl Client {
         T: serde::Serialize + std::fmt::Debug,
         U: Send + std::fmt::Debug + serde::de::DeserializeOwned + 'static,
     {
+        let retry_allowed = Self::is_retry_allowed(&path_spec);
         let req = self.create_request_builder(path_spec, args);
-        let response_handler =
-        // TODO 5: Add timeout
-            move |resp: reqwest::Result<reqwest::Response>| async { process_json_body(resp).await };
+        let retry = self.retry_for(retry_allowed, &req);
+        let response_handler = move |resp: reqwest::Result<reqwest::Response>| async move {
+            process_json_body(retry_if_enabled(resp, retry).await).await
+        };
         fetch_plus(req, response_handler, || {})
     }
 
@@ -180,13 +336,34 @@ impl Client {
     where
         T: serde::Serialize + std::fmt::Debug,
     {
+        let retry_allowed = Self::is_retry_allowed(&path_spec);
         let req = self.create_request_builder(path_spec, args);
-        let response_handler =
-        // TODO 5: Add timeout
-            move |resp: reqwest::Result<reqwest::Response>| async { process_empty(resp).await };
+        let retry = self.retry_for(retry_allowed, &req);
+        let response_handler = move |resp: reqwest::Result<reqwest::Response>| async move {
+            process_empty(retry_if_enabled(resp, retry).await).await
+        };
         fetch_plus(req, response_handler, || {})
     }
 
+    /// Only GET requests are treated as idempotent and therefore safe to retry
+    fn is_retry_allowed(path_spec: &PathSpec) -> bool {
+        path_spec.method == Method::GET
+    }
+
+    /// Returns what is needed to retry the request if a retry policy is set and
+    /// retrying is allowed for the request (See [`Self::is_retry_allowed`])
+    fn retry_for(
+        &self,
+        retry_allowed: bool,
+        req: &RequestBuilder,
+    ) -> Option<(RetryPolicy, RequestBuilder)> {
+        let retry_policy = self.retry_policy?;
+        if !retry_allowed {
+            return None;
+        }
+        req.try_clone().map(|req| (retry_policy, req))
+    }
+
     /// Sends the request but only logs the response
     fn send_request_no_wait<T>(&self, path_spec: PathSpec, args: &T)
     where
@@ -220,6 +397,14 @@ impl Client {
         self.inner.lock().expect("mutex poisoned").user_info.clone()
     }
 
+    /// Set if the server warned at login that the password expires soon
+    pub fn password_expires_in_days(&self) -> Option<u16> {
+        self.inner
+            .lock()
+            .expect("mutex poisoned")
+            .password_expires_in_days
+    }
+
     pub fn is_logged_in(&self) -> bool {
         self.inner
             .lock()
@@ -246,10 +431,10 @@ where
 {
     let (response, status) = extract_response(response)?;
     match status {
-        StatusCode::OK => Ok(response
+        StatusCode::OK => response
             .json()
             .await
-            .context("failed to parse result as json")?),
+            .map_err(|e| timeout_or_context(e, "failed to parse result as json")),
         _ => Err(handle_error(response).await),
     }
 }
@@ -265,14 +450,23 @@ async fn process_login(
             let login_response: LoginResponse = response
                 .json()
                 .await
-                .context("failed to parse result as json")?;
-            let (result, user_info) = match login_response {
-                LoginResponse::Success(user_info) => (LoginOutcome::Success, user_info),
+                .map_err(|e| timeout_or_context(e, "failed to parse result as json"))?;
+            let (result, user_info, password_expires_in_days) = match login_response {
+                LoginResponse::Success {
+                    user_info,
+                    password_expires_in_days,
+                } => (LoginOutcome::Success, user_info, password_expires_in_days),
                 LoginResponse::SuccessForcePassChange(user_info) => {
-                    (LoginOutcome::ForcePasswordChange, user_info)
+                    (LoginOutcome::ForcePasswordChange, user_info, None)
+                }
+                LoginResponse::SuccessTotpEnrolmentRequired(user_info) => {
+                    (LoginOutcome::TotpEnrolmentRequired, user_info, None)
                 }
+                LoginResponse::TotpRequired => return Ok(LoginOutcome::TotpRequired),
             };
-            client.inner.lock().expect("mutex poisoned").user_info = Some(Arc::new(user_info));
+            let mut guard = client.inner.lock().expect("mutex poisoned");
+            guard.user_info = Some(Arc::new(user_info));
+            guard.password_expires_in_days = password_expires_in_days;
             Ok(result)
         }
         StatusCode::FAILED_DEPENDENCY => Ok(LoginOutcome::RetryWithBranchSet),
@@ -287,12 +481,15 @@ async fn handle_error(response: reqwest::Response) -> anyhow::Error {
         !status.is_success(),
         "this is supposed to be an error, right? Status code is: {status}"
     );
-    // TODO 5: Add timeout
-    let Ok(body) = response.text().await else {
-        return anyhow!("failed to get response body");
+    let body = match response.text().await {
+        Ok(body) => body,
+        Err(e) => return timeout_or_context(e, "failed to get response body"),
     };
     if body.is_empty() {
         anyhow!("request failed with status code: {status} and no body")
+    } else if let Ok(err) = serde_json::from_str::<ApiError>(&body) {
+        // Preserve the type so callers can act on the error code
+        anyhow::Error::new(err)
     } else {
         anyhow!("{body}")
     }
@@ -325,6 +522,9 @@ fn extract_response(
                 url = ?err_msg.url(),
                 "reqwest::Error is: {err_msg}"
             );
+            if err_msg.is_timeout() {
+                return Err(anyhow::Error::new(RequestTimeoutError));
+            }
             let custom_msg = match &err_msg {
                 #[cfg(not(target_arch = "wasm32"))]
                 e if e.is_connect() => "Server Not Reachable",
@@ -336,3 +536,12 @@ fn extract_response(
     let status = response.status();
     Ok((response, status))
 }
+
+/// Keeps timeouts distinguishable from other errors
+fn timeout_or_context(err: reqwest::Error, context: &'static str) -> anyhow::Error {
+    if err.is_timeout() {
+        anyhow::Error::new(RequestTimeoutError)
+    } else {
+        anyhow::Error::new(err).context(context)
+    }
+}
@@ -1,14 +1,22 @@
-use crate::Client;
-use reqwest_cross::oneshot;
+use crate::{
+    Client,
+    client::{DUMMY_ARGUMENT, process_empty},
+};
+use reqwest_cross::{fetch_plus, oneshot, reqwest};
 use secrecy::ExposeSecret as _;
 use wykies_shared::{
-    const_config::path::{PATH_API_CHANGE_PASSWORD, PATH_API_LOGOUT},
+    const_config::path::{PATH_API_CHANGE_PASSWORD, PATH_API_LOGOUT, PATH_API_PASSWORD_POLICY},
     req_args::api::ChangePasswordReqArgs,
+    uac::PasswordPolicy,
 };
 
+pub mod analytics;
+pub mod api_key;
+pub mod audit;
 pub mod branch;
 pub mod host_branch;
 pub mod role;
+pub mod totp;
 pub mod user;
 
 impl Client {
@@ -22,7 +30,27 @@ impl Client {
             "new_password": args.new_password.expose_secret(),
             "new_password_check": args.new_password_check.expose_secret()
         });
-        self.send_request_expect_empty(PATH_API_CHANGE_PASSWORD, &args)
+        let req = self.create_request_builder(PATH_API_CHANGE_PASSWORD, &args);
+        let client = self.clone();
+        let response_handler = move |resp: reqwest::Result<reqwest::Response>| async move {
+            let result = process_empty(resp).await;
+            if result.is_ok() {
+                // The new password is not about to expire
+                client
+                    .inner
+                    .lock()
+                    .expect("mutex poisoned")
+                    .password_expires_in_days = None;
+            }
+            result
+        };
+        fetch_plus(req, response_handler, || {})
+    }
+
+    /// Rules new passwords must meet, used to validate them before sending
+    #[tracing::instrument]
+    pub fn password_policy(&self) -> oneshot::Receiver<anyhow::Result<PasswordPolicy>> {
+        self.send_request_expect_json(PATH_API_PASSWORD_POLICY, &DUMMY_ARGUMENT)
     }
 
     #[tracing::instrument]
@@ -38,6 +66,8 @@ impl Client {
     }
 
     fn clear_user_info(&self) {
-        self.inner.lock().expect("mutex poisoned").user_info = None;
+        let mut guard = self.inner.lock().expect("mutex poisoned");
+        guard.user_info = None;
+        guard.password_expires_in_days = None;
     }
 }
@@ -0,0 +1,16 @@
+use crate::Client;
+use reqwest_cross::oneshot;
+use wykies_shared::{
+    analytics::EndpointSummary, const_config::path::PATH_API_ANALYTICS_SUMMARY,
+    req_args::api::analytics::SummaryReqArgs,
+};
+
+impl Client {
+    #[tracing::instrument]
+    pub fn analytics_summary(
+        &self,
+        args: &SummaryReqArgs,
+    ) -> oneshot::Receiver<anyhow::Result<Vec<EndpointSummary>>> {
+        self.send_request_expect_json(PATH_API_ANALYTICS_SUMMARY, args)
+    }
+}
@@ -0,0 +1,35 @@
+use crate::{Client, client::DUMMY_ARGUMENT};
+use reqwest_cross::oneshot;
+use wykies_shared::{
+    const_config::path::{
+        PATH_API_API_KEY_LIST, PATH_API_API_KEY_NEW, PATH_API_API_KEY_REVOKE,
+        PATH_API_API_KEY_ROTATE,
+    },
+    req_args::api::api_key::{LookupReqArgs, NewReqArgs},
+    uac::{ApiKeyId, ApiKeyInfo, ApiKeySecret},
+};
+
+impl Client {
+    #[tracing::instrument]
+    pub fn api_key_list(&self) -> oneshot::Receiver<anyhow::Result<Vec<ApiKeyInfo>>> {
+        self.send_request_expect_json(PATH_API_API_KEY_LIST, &DUMMY_ARGUMENT)
+    }
+
+    /// The key returned needs to be shown to the user as it cannot be
+    /// retrieved again
+    #[tracing::instrument]
+    pub fn api_key_new(&self, args: NewReqArgs) -> oneshot::Receiver<anyhow::Result<ApiKeySecret>> {
+        self.send_request_expect_json(PATH_API_API_KEY_NEW, &args)
+    }
+
+    /// Replaces the key with a new one, the old key stops working immediately
+    #[tracing::instrument]
+    pub fn api_key_rotate(&self, id: ApiKeyId) -> oneshot::Receiver<anyhow::Result<ApiKeySecret>> {
+        self.send_request_expect_json(PATH_API_API_KEY_ROTATE, &LookupReqArgs { id })
+    }
+
+    #[tracing::instrument]
+    pub fn api_key_revoke(&self, id: ApiKeyId) -> oneshot::Receiver<anyhow::Result<()>> {
+        self.send_request_expect_empty(PATH_API_API_KEY_REVOKE, &LookupReqArgs { id })
+    }
+}
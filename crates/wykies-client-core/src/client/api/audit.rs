@@ -0,0 +1,17 @@
+use crate::Client;
+use reqwest_cross::oneshot;
+use wykies_shared::{
+    audit::AuditEntry, const_config::path::PATH_API_AUDIT_LIST, req_args::api::audit::ListReqArgs,
+};
+
+impl Client {
+    /// Returns the audit log entries matching the filters in `args` newest
+    /// first (See [`ListReqArgs`] for paging)
+    #[tracing::instrument]
+    pub fn audit_list(
+        &self,
+        args: &ListReqArgs,
+    ) -> oneshot::Receiver<anyhow::Result<Vec<AuditEntry>>> {
+        self.send_request_expect_json(PATH_API_AUDIT_LIST, args)
+    }
+}
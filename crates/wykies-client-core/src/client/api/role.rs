@@ -1,9 +1,16 @@
-use crate::Client;
+use crate::{Client, client::DUMMY_ARGUMENT};
 use reqwest_cross::oneshot;
+use std::collections::BTreeSet;
 use wykies_shared::{
-    const_config::path::{PATH_API_ROLE, PATH_API_ROLE_NEW, PATH_API_USER_ROLE_SET},
-    req_args::api::{role, user::AssignReqArgs},
-    uac::{Role, RoleDraft, RoleId},
+    const_config::path::{
+        PATH_API_ROLE, PATH_API_ROLE_DELETE, PATH_API_ROLE_LIST, PATH_API_ROLE_NEW,
+        PATH_API_ROLE_PARENTS, PATH_API_ROLE_UPDATE, PATH_API_USER_ROLE_SET,
+    },
+    req_args::api::{
+        role::{self, SetParentsReqArgs},
+        user::AssignReqArgs,
+    },
+    uac::{Role, RoleDiff, RoleDraft, RoleId},
 };
 
 impl Client {
@@ -18,6 +25,39 @@ impl Client {
         self.send_request_expect_json(PATH_API_ROLE, &args)
     }
 
+    #[tracing::instrument]
+    pub fn role_list(&self) -> oneshot::Receiver<anyhow::Result<Vec<Role>>> {
+        self.send_request_expect_json(PATH_API_ROLE_LIST, &DUMMY_ARGUMENT)
+    }
+
+    #[tracing::instrument]
+    pub fn role_update(&self, diff: &RoleDiff) -> oneshot::Receiver<anyhow::Result<()>> {
+        self.send_request_expect_empty(PATH_API_ROLE_UPDATE, diff)
+    }
+
+    /// Fails if the role is still assigned to a user or inherited by another
+    /// role
+    #[tracing::instrument]
+    pub fn role_delete(&self, role_id: RoleId) -> oneshot::Receiver<anyhow::Result<()>> {
+        let args = role::LookupReqArgs { role_id };
+        self.send_request_expect_empty(PATH_API_ROLE_DELETE, &args)
+    }
+
+    /// Replaces the roles that the role inherits permissions from
+    #[tracing::instrument]
+    pub fn role_parents_set(
+        &self,
+        role_id: RoleId,
+        parent_roles: BTreeSet<RoleId>,
+    ) -> oneshot::Receiver<anyhow::Result<()>> {
+        let args = SetParentsReqArgs {
+            role_id,
+            parent_roles,
+        };
+        self.send_request_expect_empty(PATH_API_ROLE_PARENTS, &args)
+    }
+
+    /// Adds the role to the roles already assigned to the user
     #[tracing::instrument]
     pub fn assign_role(&self, args: &AssignReqArgs) -> oneshot::Receiver<anyhow::Result<()>> {
         self.send_request_expect_empty(PATH_API_USER_ROLE_SET, args)
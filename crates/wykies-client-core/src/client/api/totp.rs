@@ -0,0 +1,29 @@
+use crate::{Client, client::DUMMY_ARGUMENT};
+use reqwest_cross::oneshot;
+use secrecy::{ExposeSecret as _, SecretString};
+use wykies_shared::{
+    const_config::path::{PATH_API_TOTP_CONFIRM, PATH_API_TOTP_ENROL},
+    uac::{TotpEnrolment, TotpRecoveryCodes},
+};
+
+impl Client {
+    /// Starts enrolment in two-factor authentication for the logged in user
+    #[tracing::instrument]
+    pub fn totp_enrol(&self) -> oneshot::Receiver<anyhow::Result<TotpEnrolment>> {
+        self.send_request_expect_json(PATH_API_TOTP_ENROL, &DUMMY_ARGUMENT)
+    }
+
+    /// Completes enrolment using a code generated from the secret returned by
+    /// [`Self::totp_enrol`]. The recovery codes returned need to be shown to
+    /// the user as they cannot be retrieved again
+    #[tracing::instrument(skip(code))]
+    pub fn totp_confirm(
+        &self,
+        code: SecretString,
+    ) -> oneshot::Receiver<anyhow::Result<TotpRecoveryCodes>> {
+        let args = serde_json::json!({
+            "code": code.expose_secret(),
+        });
+        self.send_request_expect_json(PATH_API_TOTP_CONFIRM, &args)
+    }
+}
@@ -3,14 +3,18 @@ use reqwest_cross::oneshot;
 use secrecy::ExposeSecret;
 use wykies_shared::{
     const_config::path::{
-        PATH_API_USER, PATH_API_USER_NEW, PATH_API_USER_PASSWORD_RESET, PATH_API_USER_UPDATE,
-        PATH_API_USERS_LIST_AND_ROLES,
+        PATH_API_USER, PATH_API_USER_NEW, PATH_API_USER_PASSWORD_RESET, PATH_API_USER_PERMISSIONS,
+        PATH_API_USER_SESSIONS, PATH_API_USER_SESSIONS_REVOKE, PATH_API_USER_TOTP_RESET,
+        PATH_API_USER_UPDATE, PATH_API_USERS_LIST_AND_ROLES,
     },
     req_args::{
         RonWrapper,
-        api::user::{self, NewUserReqArgs, PasswordResetReqArgs},
+        api::user::{self, NewUserReqArgs, PasswordResetReqArgs, RevokeSessionsReqArgs},
+    },
+    uac::{
+        ListUsersRoles, SessionId, SessionInfo, UserMetadata, UserMetadataDiff, UserPermissions,
+        Username,
     },
-    uac::{ListUsersRoles, UserMetadata, UserMetadataDiff, Username},
 };
 
 impl Client {
@@ -26,7 +30,8 @@ impl Client {
             "username": user.username,
             "display_name": user.display_name,
             "password": user.password.expose_secret(),
-            "assigned_role": user.assigned_role
+            "assigned_roles": user.assigned_roles,
+            "email": user.email
         });
         self.send_request_expect_empty(PATH_API_USER_NEW, &args)
     }
@@ -49,8 +54,50 @@ impl Client {
         self.send_request_expect_empty(PATH_API_USER_UPDATE, &wrapped)
     }
 
+    /// Permissions the user gets from their roles, split into the ones granted
+    /// directly and the effective ones (including inherited)
+    #[tracing::instrument]
+    pub fn user_permissions(
+        &self,
+        username: Username,
+    ) -> oneshot::Receiver<anyhow::Result<UserPermissions>> {
+        let args = user::LookupReqArgs { username };
+        self.send_request_expect_json(PATH_API_USER_PERMISSIONS, &args)
+    }
+
     #[tracing::instrument]
     pub fn list_users_and_roles(&self) -> oneshot::Receiver<anyhow::Result<ListUsersRoles>> {
         self.send_request_expect_json(PATH_API_USERS_LIST_AND_ROLES, &DUMMY_ARGUMENT)
     }
+
+    #[tracing::instrument]
+    pub fn user_sessions(
+        &self,
+        username: Username,
+    ) -> oneshot::Receiver<anyhow::Result<Vec<SessionInfo>>> {
+        let args = user::LookupReqArgs { username };
+        self.send_request_expect_json(PATH_API_USER_SESSIONS, &args)
+    }
+
+    /// Revokes the session identified by `session_id` or all the user's sessions
+    /// if `None` ("log out everywhere")
+    #[tracing::instrument]
+    pub fn user_sessions_revoke(
+        &self,
+        username: Username,
+        session_id: Option<SessionId>,
+    ) -> oneshot::Receiver<anyhow::Result<()>> {
+        let args = RevokeSessionsReqArgs {
+            username,
+            session_id,
+        };
+        self.send_request_expect_empty(PATH_API_USER_SESSIONS_REVOKE, &args)
+    }
+
+    /// Disables two-factor authentication for the user so they can enrol again
+    #[tracing::instrument]
+    pub fn user_totp_reset(&self, username: Username) -> oneshot::Receiver<anyhow::Result<()>> {
+        let args = user::LookupReqArgs { username };
+        self.send_request_expect_empty(PATH_API_USER_TOTP_RESET, &args)
+    }
 }
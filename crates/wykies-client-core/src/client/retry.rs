@@ -0,0 +1,108 @@
+use reqwest_cross::reqwest::{self, RequestBuilder, StatusCode};
+use std::time::Duration;
+use tracing::warn;
+use wykies_shared::const_config::client::{
+    CLIENT_RETRY_INITIAL_BACKOFF, CLIENT_RETRY_MAX_ATTEMPTS, CLIENT_RETRY_MAX_BACKOFF,
+};
+
+/// Controls how GET requests are retried when they fail in a way that may be
+/// temporary (timeouts, connection failures and gateway errors). Other methods
+/// are never retried as they may not be idempotent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Includes the first attempt
+    pub max_attempts: u8,
+    /// Doubled after each retry until it reaches `max_backoff`
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+/// Returned (wrapped in an [`anyhow::Error`]) when no response is received
+/// within the timeout. Use [`anyhow::Error::is`] to detect it
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct RequestTimeoutError;
+
+impl std::fmt::Display for RequestTimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Request timed out waiting for the server to respond")
+    }
+}
+
+impl std::error::Error for RequestTimeoutError {}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: CLIENT_RETRY_MAX_ATTEMPTS,
+            initial_backoff: CLIENT_RETRY_INITIAL_BACKOFF,
+            max_backoff: CLIENT_RETRY_MAX_BACKOFF,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The time to wait before retry number `retry_number` (starting at 1)
+    fn backoff(&self, retry_number: u8) -> Duration {
+        let factor = 2u32.saturating_pow(retry_number.saturating_sub(1).into());
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+
+    /// Sends `req` again while the outcome is one that may succeed on a retry
+    /// and attempts remain
+    #[tracing::instrument(skip(outcome, req))]
+    pub(super) async fn retry(
+        &self,
+        mut outcome: reqwest::Result<reqwest::Response>,
+        req: RequestBuilder,
+    ) -> reqwest::Result<reqwest::Response> {
+        for retry_number in 1..self.max_attempts {
+            if !is_retryable(&outcome) {
+                break;
+            }
+            let Some(req) = req.try_clone() else {
+                warn!("unable to retry because request could not be cloned");
+                break;
+            };
+            let delay = self.backoff(retry_number);
+            warn!(retry_number, ?delay, "retrying request after delay");
+            sleep(delay).await;
+            outcome = req.send().await;
+        }
+        outcome
+    }
+}
+
+/// Retries if a policy and request are provided otherwise returns `outcome`
+/// unchanged
+pub(super) async fn retry_if_enabled(
+    outcome: reqwest::Result<reqwest::Response>,
+    retry: Option<(RetryPolicy, RequestBuilder)>,
+) -> reqwest::Result<reqwest::Response> {
+    match retry {
+        Some((policy, req)) => policy.retry(outcome, req).await,
+        None => outcome,
+    }
+}
+
+fn is_retryable(outcome: &reqwest::Result<reqwest::Response>) -> bool {
+    match outcome {
+        Ok(resp) => matches!(
+            resp.status(),
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+        ),
+        Err(e) => e.is_timeout() || e.is_request(),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await;
+}
+
+/// Uses the browser's timer (`setTimeout`) as there is no tokio runtime on wasm
+#[cfg(target_arch = "wasm32")]
+async fn sleep(duration: Duration) {
+    gloo_timers::future::sleep(duration).await;
+}
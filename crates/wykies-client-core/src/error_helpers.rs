@@ -1,4 +1,5 @@
 use reqwest_cross::{DataState, DataStateError};
+use wykies_shared::api_error::ApiError;
 
 /// Provides a way to use a type to store errors, helpful for reusing a type
 /// that already has code associated with it for error handling
@@ -16,3 +17,12 @@ impl<T> ErrorStore for DataState<T, anyhow::Error> {
         *self = DataState::Failed(DataStateError::FromE(anyhow::anyhow!("{}", s.as_ref())));
     }
 }
+
+/// Returns the [`ApiError`] sent by the server if that is why the request
+/// failed
+pub fn api_error(err: &DataStateError<anyhow::Error>) -> Option<&ApiError> {
+    match err {
+        DataStateError::FromE(e) => e.downcast_ref(),
+        _ => None,
+    }
+}
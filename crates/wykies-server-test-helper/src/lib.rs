@@ -12,11 +12,15 @@ use std::fs::{File, create_dir_all};
 use std::ops::Deref;
 use std::path::PathBuf;
 use std::sync::LazyLock;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 use wykies_server::Configuration;
 use wykies_server::{
-    DatabaseSettings, db_utils::validate_one_row_affected, get_configuration,
-    get_db_connection_pool,
+    DatabaseSettings,
+    authentication::TOKEN_PREFIX_IN_MESSAGE,
+    db_utils::validate_one_row_affected,
+    get_configuration, get_db_connection_pool,
+    notifier::{FileNotifier, NotifierSettings},
 };
 use wykies_shared::{
     branch::BranchId,
@@ -24,13 +28,16 @@ use wykies_shared::{
     host_branch::HostBranchPair,
     req_args::LoginReqArgs,
     telemetry::{get_subscriber, init_subscriber},
-    uac::Username,
+    uac::{Permissions, Username},
 };
 use wykies_time::Seconds;
 
 mod macros;
 
 pub const TEST_MSG_WAIT_TIMEOUT: Seconds = Seconds::new(2);
+/// Longer than [`TEST_MSG_WAIT_TIMEOUT`] as the token is hashed before it is
+/// sent which is slow in debug builds when many tests run at once
+const PASSWORD_RESET_TOKEN_WAIT_TIMEOUT: Duration = Duration::from_secs(15);
 
 // Ensure that the `tracing` stack is only initialised once
 pub static TRACING: LazyLock<String> = LazyLock::new(|| {
@@ -74,6 +81,8 @@ pub struct TestApp<C> {
     pub core_client: C,
     pub login_attempt_limit: u8,
     pub host_branch_pair: HostBranchPair,
+    /// Where notifications sent by the server are written
+    pub notifications_folder: PathBuf,
 }
 
 impl<C> Debug for TestApp<C> {
@@ -118,6 +127,10 @@ where
         branch_id: get_seed_branch_from_db(&db_pool).await,
     };
     let core_client = build_client(address.clone());
+    let notifications_folder = match &configuration.notifier {
+        NotifierSettings::File { folder } => folder.clone(),
+        NotifierSettings::Smtp(_) => panic!("tests expect notifications to be written to files"),
+    };
 
     let test_app = TestApp {
         address,
@@ -126,6 +139,7 @@ where
         core_client,
         login_attempt_limit,
         host_branch_pair,
+        notifications_folder,
     };
 
     test_app.test_user.store(&test_app.db_pool, false).await;
@@ -142,6 +156,15 @@ where
     c.database.database_name = Uuid::new_v4().to_string();
     // Use a random OS port
     c.application.port = 0;
+    // Most tests use admin users and would otherwise need to enrol in two-factor
+    // authentication first
+    c.user_auth.totp_required_for_man_uac = false;
+    // Tests fail logins in quick succession and would otherwise be delayed
+    c.user_auth.failed_attempt_base_delay_secs = Seconds::new(0);
+    // Write notifications to a separate folder for each test so they can be read back
+    c.notifier = NotifierSettings::File {
+        folder: std::env::temp_dir().join(Uuid::new_v4().to_string()),
+    };
     // Use root user to be able to create a new database
     #[cfg(feature = "mysql")]
     {
@@ -242,6 +265,44 @@ impl TestUser {
         validate_one_row_affected(&sql_result).expect("failed to set user to disabled");
     }
 
+    /// Users are created requiring a password change, which hides the normal
+    /// login outcome
+    pub async fn clear_force_pass_change_in_db<C>(&self, app: &TestApp<C>) {
+        #[cfg(feature = "mysql")]
+        let query = sqlx::query!(
+            "UPDATE `user` SET `ForcePassChange` = '0' WHERE `user`.`UserName` = ?;",
+            self.username,
+        );
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        let query = sqlx::query!(
+            "UPDATE users SET force_pass_change = false WHERE users.user_name = $1;",
+            self.username,
+        );
+        let sql_result = query
+            .execute(&app.db_pool)
+            .await
+            .expect("failed to clear force password change");
+        validate_one_row_affected(&sql_result).expect("failed to clear force password change");
+    }
+
+    pub async fn get_password_hash_from_db<C>(&self, app: &TestApp<C>) -> String {
+        #[cfg(feature = "mysql")]
+        let query = sqlx::query!(
+            "SELECT `password_hash` FROM `user` WHERE `UserName` = ?;",
+            self.username,
+        );
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        let query = sqlx::query!(
+            "SELECT password_hash FROM users WHERE user_name = $1;",
+            self.username,
+        );
+        query
+            .fetch_one(&app.db_pool)
+            .await
+            .expect("failed to get password hash")
+            .password_hash
+    }
+
     pub async fn set_locked_out_in_db<C>(&self, app: &TestApp<C>, value: bool) {
         #[cfg(feature = "mysql")]
         let query = {
@@ -265,56 +326,152 @@ impl TestUser {
         validate_one_row_affected(&sql_result).expect("failed to set user to disabled");
     }
 
+    pub async fn set_email_in_db<C>(&self, app: &TestApp<C>, email: &str) {
+        #[cfg(feature = "mysql")]
+        let query = sqlx::query!(
+            "UPDATE `user` SET `Email` = ? WHERE `user`.`UserName` = ?;",
+            email,
+            self.username,
+        );
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        let query = sqlx::query!(
+            "UPDATE users SET email = $1 WHERE users.user_name = $2;",
+            email,
+            self.username,
+        );
+        let sql_result = query
+            .execute(&app.db_pool)
+            .await
+            .expect("failed to set email of user");
+        validate_one_row_affected(&sql_result).expect("failed to set email of user");
+    }
+
+    /// Returns the number of previous passwords stored for the user
+    pub async fn password_history_count_in_db<C>(&self, app: &TestApp<C>) -> i64 {
+        #[cfg(feature = "mysql")]
+        let query = sqlx::query!(
+            "SELECT COUNT(*) AS `count` FROM `password_history` WHERE `UserName` = ?;",
+            self.username,
+        );
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        let query = sqlx::query!(
+            r#"SELECT COUNT(*) AS "count!" FROM password_history WHERE user_name = $1;"#,
+            self.username,
+        );
+        query
+            .fetch_one(&app.db_pool)
+            .await
+            .expect("failed to get password history count")
+            .count
+    }
+
+    /// Returns the token from the last password reset notification sent to the
+    /// user or `None` if none were sent
+    pub fn last_password_reset_token<C>(&self, app: &TestApp<C>) -> Option<String> {
+        let username: Username = self.username.clone().try_into().unwrap();
+        let path = FileNotifier::path_for(&app.notifications_folder, &username);
+        let contents = std::fs::read_to_string(path).ok()?;
+        contents
+            .lines()
+            .filter_map(|line| line.strip_prefix(TOKEN_PREFIX_IN_MESSAGE))
+            .next_back()
+            .map(ToString::to_string)
+    }
+
+    /// Same as [`Self::last_password_reset_token`] but waits up to
+    /// [`PASSWORD_RESET_TOKEN_WAIT_TIMEOUT`] for the notification as they are
+    /// sent in the background
+    pub async fn wait_for_password_reset_token<C>(&self, app: &TestApp<C>) -> Option<String> {
+        let deadline = Instant::now() + PASSWORD_RESET_TOKEN_WAIT_TIMEOUT;
+        loop {
+            let token = self.last_password_reset_token(app);
+            if token.is_some() || Instant::now() >= deadline {
+                return token;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
     pub async fn store(&self, pool: &DbPool, is_admin: bool) {
         let salt = SaltString::generate(&mut rand_core::OsRng);
-        // Match production parameters
-        let password_hash = wykies_server::authentication::argon2_settings()
+        // Match the default parameters
+        let password_hash = wykies_server::authentication::Argon2Settings::default()
+            .argon2()
+            .unwrap()
             .hash_password(self.password.as_bytes(), &salt)
             .unwrap()
             .to_string();
 
         if is_admin {
+            let permissions: String = Permissions::all().into();
+            // Role and display names are unique so later admin users of an app share the
+            // role and use their username as the display name
+            #[cfg(feature = "mysql")]
+            let existing_role =
+                sqlx::query!("SELECT `RoleID` FROM `roles` WHERE `Name` = 'Admin';")
+                    .fetch_optional(pool)
+                    .await
+                    .expect("failed to get admin role");
+            #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+            let existing_role =
+                sqlx::query!("SELECT role_id FROM roles WHERE role_name = 'Admin';")
+                    .fetch_optional(pool)
+                    .await
+                    .expect("failed to get admin role");
+            let display_name = if existing_role.is_some() {
+                self.username.as_str()
+            } else {
+                "Admin User"
+            };
             #[cfg(feature = "mysql")]
-            let role_id = {
+            let role_id = if let Some(row) = existing_role {
+                u64::try_from(row.RoleID).expect("role id should not be negative")
+            } else {
                 let sql_result = sqlx::query!(
-                        "INSERT INTO `roles` 
+                    "INSERT INTO `roles` 
                         (`RoleID`, `Name`, `Description`, `Permissions`, `LockedEditing`) 
-                        VALUES (NULL, 'Admin', 'Full Permissions', '11111111111111111111111111111111111', '0');",
-                    )
-                    .execute(pool)
-                    .await
-                    .expect("failed to store test user");
+                        VALUES (NULL, 'Admin', 'Full Permissions', ?, '0');",
+                    permissions
+                )
+                .execute(pool)
+                .await
+                .expect("failed to store test user");
                 validate_one_row_affected(&sql_result).expect("failed to store admin role");
                 sql_result.last_insert_id()
             };
             #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
-            let role_id = sqlx::query!(
-                "INSERT INTO roles 
-                    (role_name, role_description, permissions) 
-                    VALUES ('Admin', 'Full Permissions', '11111111111111111111111111111111111')
-                    RETURNING role_id;",
-            )
-            .fetch_one(pool)
-            .await
-            .expect("failed to store admin role")
-            .role_id;
+            let role_id = if let Some(row) = existing_role {
+                row.role_id
+            } else {
+                sqlx::query!(
+                    "INSERT INTO roles 
+                        (role_name, role_description, permissions) 
+                        VALUES ('Admin', 'Full Permissions', $1)
+                        RETURNING role_id;",
+                    permissions
+                )
+                .fetch_one(pool)
+                .await
+                .expect("failed to store admin role")
+                .role_id
+            };
             #[cfg(feature = "mysql")]
             let query = sqlx::query!(
                 "INSERT INTO `user`
-                (`UserName`, `Password`, `password_hash`, `salt`, `DisplayName`, `AssignedRole`, `PassChangeDate`, `Enabled`) 
-                VALUES (?, '', ?, '', 'Admin User', ?, CURRENT_DATE(), 1);",
+                (`UserName`, `Password`, `password_hash`, `salt`, `DisplayName`, `PassChangeDate`, `Enabled`) 
+                VALUES (?, '', ?, '', ?, CURRENT_DATE(), 1);",
                 self.username,
                 password_hash,
-                role_id
+                display_name,
             );
             #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
             let query = sqlx::query!(
                 "INSERT INTO users
-                (user_name, password_hash, display_name, assigned_role, pass_change_date, is_enabled) 
-                VALUES ($1, $2, 'Admin User', $3, CURRENT_DATE, true);",
+                (user_name, password_hash, display_name, pass_change_date, is_enabled) 
+                VALUES ($1, $2, $3, CURRENT_DATE, true);",
                 self.username,
                 password_hash,
-                role_id
+                display_name,
             );
 
             let sql_result = query
@@ -322,6 +479,24 @@ impl TestUser {
                 .await
                 .expect("failed to store test user");
             validate_one_row_affected(&sql_result).expect("failed to store admin user");
+
+            #[cfg(feature = "mysql")]
+            let query = sqlx::query!(
+                "INSERT INTO `user_roles` (`UserName`, `RoleID`) VALUES (?, ?);",
+                self.username,
+                role_id
+            );
+            #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+            let query = sqlx::query!(
+                "INSERT INTO user_roles (user_name, role_id) VALUES ($1, $2);",
+                self.username,
+                role_id
+            );
+            let sql_result = query
+                .execute(pool)
+                .await
+                .expect("failed to assign admin role");
+            validate_one_row_affected(&sql_result).expect("failed to assign admin role");
         } else {
             #[cfg(feature = "mysql")]
             let query = sqlx::query!(
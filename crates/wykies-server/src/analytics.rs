@@ -0,0 +1,344 @@
+//! Records which endpoints are used and how long they take to respond
+//!
+//! [`record_request_analytics`] sends a [`RequestRecord`] for each request to
+//! the [`AnalyticsWriter`] which saves them to the database in batches so that
+//! requests are not slowed down by the extra write
+
+use crate::ServerTask;
+use actix_web::{
+    HttpMessage as _,
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    web,
+};
+use anyhow::Context as _;
+use sqlx::QueryBuilder;
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+use tokio::{select, sync::mpsc};
+use tracing::{debug, info, instrument, warn};
+use tracked_cancellations::TrackedCancellationToken;
+use wykies_shared::{
+    analytics::EndpointSummary,
+    branch::BranchId,
+    const_config::server::{
+        ANALYTICS_BUFFER_SIZE, ANALYTICS_MAX_RECORDS_BEFORE_SAVE, ANALYTICS_MAX_TIME_BEFORE_SAVE,
+    },
+    db_types::{Db, DbPool},
+    log_as_error,
+    uac::{UserInfo, Username},
+};
+use wykies_time::Timestamp;
+
+#[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+use crate::db_utils::db_timestamp;
+
+/// Maximum length of the path stored (longer paths are truncated)
+const MAX_PATH_LEN: usize = 255;
+
+#[derive(Debug)]
+pub struct RequestRecord {
+    pub timestamp: Timestamp,
+    pub path: String,
+    pub method: String,
+    pub status: u16,
+    pub latency_ms: u32,
+    pub username: Option<Username>,
+    pub branch_id: Option<BranchId>,
+}
+
+/// Cheap to clone handle used to send records to the [`AnalyticsWriter`]
+#[derive(Debug, Clone)]
+pub struct AnalyticsRecorder {
+    tx: mpsc::Sender<RequestRecord>,
+}
+
+/// Saves the records received from [`AnalyticsRecorder`]s to the database
+#[derive(Debug)]
+pub struct AnalyticsWriter {
+    rx: mpsc::Receiver<RequestRecord>,
+    pool: DbPool,
+    buffer: Vec<RequestRecord>,
+    /// When the oldest record in the buffer was received
+    oldest_unsaved: tokio::time::Instant,
+}
+
+impl AnalyticsRecorder {
+    /// Records are dropped if the writer is not keeping up, analytics should
+    /// never slow down serving requests
+    pub fn record(&self, record: RequestRecord) {
+        if let Err(e) = self.tx.try_send(record) {
+            warn!("analytics record dropped: {e}");
+        }
+    }
+}
+
+impl AnalyticsWriter {
+    pub fn new(pool: DbPool) -> (Self, AnalyticsRecorder) {
+        let (tx, rx) = mpsc::channel(ANALYTICS_BUFFER_SIZE);
+        let writer = Self {
+            rx,
+            pool,
+            buffer: Default::default(),
+            oldest_unsaved: tokio::time::Instant::now(),
+        };
+        (writer, AnalyticsRecorder { tx })
+    }
+
+    fn push(&mut self, record: RequestRecord) {
+        if self.buffer.is_empty() {
+            self.oldest_unsaved = tokio::time::Instant::now();
+        }
+        self.buffer.push(record);
+    }
+
+    #[instrument(skip(self), fields(buffer_len = self.buffer.len()))]
+    async fn save(&mut self, save_reason: &str) {
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        #[cfg(feature = "mysql")]
+        let mut query_builder: QueryBuilder<Db> = QueryBuilder::new(
+            "INSERT INTO `analytics` (`EventTime`, `Path`, `Method`, `Status`, `LatencyMs`, `UserName`, `BranchID`) ",
+        );
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        let mut query_builder: QueryBuilder<Db> = QueryBuilder::new(
+            "INSERT INTO analytics (event_time, path, method, status, latency_ms, user_name, branch_id) ",
+        );
+
+        query_builder.push_values(self.buffer.drain(..), |mut b, record| {
+            #[cfg(feature = "mysql")]
+            b.push_bind(record.timestamp)
+                .push_bind(record.path)
+                .push_bind(record.method)
+                .push_bind(record.status)
+                .push_bind(record.latency_ms)
+                .push_bind(record.username)
+                .push_bind(record.branch_id);
+            #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+            // TODO 5: Check why encode trait impl doesn't make converting not necessary
+            b.push_bind(record.timestamp)
+                .push_bind(record.path)
+                .push_bind(record.method)
+                .push_bind(i32::from(record.status))
+                .push_bind(i32::try_from(record.latency_ms).unwrap_or(i32::MAX))
+                .push_bind(record.username)
+                .push_bind(record.branch_id.and_then(|x| i32::try_from(x).ok()));
+        });
+        debug!(query_builder.sql = ?query_builder.sql(), "Query Builder SQL");
+
+        // Persistent is set to false because the number of rows changes and each
+        // would have to be cached separately
+        let query = query_builder.build().persistent(false);
+        match query
+            .execute(&self.pool)
+            .await
+            .context("failed to save analytics records to DB")
+        {
+            Ok(_) => info!("Analytics save succeeded"),
+            Err(err) => log_as_error!("failed to save analytics records: {err:?}"),
+        }
+    }
+}
+
+impl ServerTask for AnalyticsWriter {
+    fn name(&self) -> &'static str {
+        "Analytics Writer"
+    }
+
+    async fn run(mut self, cancellation_token: TrackedCancellationToken) -> anyhow::Result<()> {
+        // Ensure that exiting causes the rest of the app to shut down
+        let _drop_guard = cancellation_token.clone().drop_guard();
+        loop {
+            let save_deadline =
+                self.oldest_unsaved + Duration::from(ANALYTICS_MAX_TIME_BEFORE_SAVE);
+            select! {
+                _ = cancellation_token.cancelled() => {
+                    self.save("cancellation").await;
+                    info!("shutting down AnalyticsWriter because of cancellation request");
+                    return Ok(())
+                }
+                record = self.rx.recv() => match record {
+                    Some(record) => {
+                        self.push(record);
+                        if self.buffer.len() >= usize::from(ANALYTICS_MAX_RECORDS_BEFORE_SAVE) {
+                            self.save("buffer full").await;
+                        }
+                    }
+                    None => {
+                        self.save("all senders dropped").await;
+                        anyhow::bail!("all analytics recorders dropped, server was likely stopped");
+                    }
+                },
+                _ = tokio::time::sleep_until(save_deadline), if !self.buffer.is_empty() => {
+                    self.save("time").await;
+                }
+            }
+        }
+    }
+}
+
+/// Sends a record of each request to the [`AnalyticsWriter`] if an
+/// [`AnalyticsRecorder`] is found in the app data
+pub async fn record_request_analytics(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let Some(recorder) = req.app_data::<web::Data<AnalyticsRecorder>>().cloned() else {
+        return next.call(req).await;
+    };
+    let timestamp = Timestamp::now();
+    let start = Instant::now();
+    let mut path = req.path().to_string();
+    path.truncate(MAX_PATH_LEN); // Safe because the path of a URI is always ASCII
+    let method = req.method().to_string();
+
+    let result = next.call(req).await;
+
+    let latency_ms = start.elapsed().as_millis().try_into().unwrap_or(u32::MAX);
+    let (status, user_info) = match &result {
+        Ok(res) => (
+            res.status(),
+            res.request().extensions().get::<UserInfo>().cloned(),
+        ),
+        Err(e) => (e.as_response_error().status_code(), None),
+    };
+    recorder.record(RequestRecord {
+        timestamp,
+        path,
+        method,
+        status: status.as_u16(),
+        latency_ms,
+        branch_id: user_info.as_ref().map(|x| x.branch_id),
+        username: user_info.map(|x| x.username),
+    });
+    result
+}
+
+/// Returns the number of requests and latency percentiles for each endpoint
+/// for requests in the range `since..until`
+///
+/// The database counts the requests for each latency value so the memory used
+/// does not grow with the number of requests in the range
+#[instrument(err(Debug), skip(pool))]
+pub async fn endpoint_summary(
+    pool: &DbPool,
+    since: Timestamp,
+    until: Timestamp,
+) -> anyhow::Result<Vec<EndpointSummary>> {
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!(
+        "SELECT `Path`, `Method`, `LatencyMs`, COUNT(*) AS `Count` FROM `analytics`
+        WHERE `EventTime` >= ? AND `EventTime` < ?
+        GROUP BY `Path`, `Method`, `LatencyMs`;",
+        since,
+        until
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let query = {
+        let since = db_timestamp(since)?;
+        let until = db_timestamp(until)?;
+        sqlx::query!(
+            r#"SELECT path, method, latency_ms, COUNT(*) AS "count!" FROM analytics
+            WHERE event_time >= $1 AND event_time < $2
+            GROUP BY path, method, latency_ms;"#,
+            since,
+            until
+        )
+    };
+    let rows = query
+        .fetch_all(pool)
+        .await
+        .context("failed to get analytics records")?;
+
+    let mut histograms: BTreeMap<(String, String), Vec<(u32, u64)>> = BTreeMap::new();
+    for row in rows {
+        #[cfg(feature = "mysql")]
+        let (key, latency, count) = ((row.Path, row.Method), row.LatencyMs, row.Count);
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        let (key, latency, count) = (
+            (row.path, row.method),
+            row.latency_ms.try_into()?,
+            row.count,
+        );
+        histograms
+            .entry(key)
+            .or_default()
+            .push((latency, count.try_into()?));
+    }
+
+    Ok(histograms
+        .into_iter()
+        .map(|((path, method), mut histogram)| {
+            histogram.sort_unstable();
+            EndpointSummary {
+                path,
+                method,
+                count: histogram.iter().map(|(_, count)| count).sum(),
+                p50_latency_ms: percentile(&histogram, 50),
+                p95_latency_ms: percentile(&histogram, 95),
+            }
+        })
+        .collect())
+}
+
+/// Uses the nearest-rank method, `histogram` is the number of requests for
+/// each latency and must be sorted by latency in ascending order
+fn percentile(histogram: &[(u32, u64)], percent: u64) -> u32 {
+    let total: u64 = histogram.iter().map(|(_, count)| count).sum();
+    if total == 0 {
+        return 0;
+    }
+    let rank = (percent * total).div_ceil(100).max(1);
+    let mut seen = 0;
+    for &(latency, count) in histogram {
+        seen += count;
+        if seen >= rank {
+            return latency;
+        }
+    }
+    unreachable!("rank is at most the total count")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::empty(vec![], 50, 0)]
+    #[case::single(vec![7], 95, 7)]
+    #[case::median_odd(vec![1, 2, 3, 4, 5], 50, 3)]
+    #[case::median_even(vec![1, 2, 3, 4], 50, 2)]
+    #[case::p95_of_twenty((1..=20).collect(), 95, 19)]
+    #[case::p95_of_hundred((1..=100).collect(), 95, 95)]
+    #[case::p100((1..=10).collect(), 100, 10)]
+    fn percentile_nearest_rank(
+        #[case] sorted: Vec<u32>,
+        #[case] percent: u64,
+        #[case] expected: u32,
+    ) {
+        let histogram: Vec<(u32, u64)> = sorted.into_iter().map(|x| (x, 1)).collect();
+        let actual = percentile(&histogram, percent);
+        assert_eq!(actual, expected);
+    }
+
+    #[rstest]
+    #[case::all_same(vec![(5, 10)], 95, 5)]
+    #[case::p50_in_first(vec![(1, 6), (9, 4)], 50, 1)]
+    #[case::p95_in_last(vec![(1, 6), (9, 4)], 95, 9)]
+    #[case::boundary(vec![(1, 5), (2, 5)], 50, 1)]
+    fn percentile_with_repeated_latencies(
+        #[case] histogram: Vec<(u32, u64)>,
+        #[case] percent: u64,
+        #[case] expected: u32,
+    ) {
+        let actual = percentile(&histogram, percent);
+        assert_eq!(actual, expected);
+    }
+}
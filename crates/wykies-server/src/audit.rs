@@ -0,0 +1,223 @@
+//! Persistent record of administrative actions (user, role and branch
+//! management)
+//!
+//! Handlers that change state take an [`AuditContext`] and call
+//! [`AuditContext::record`] with the transaction used to make the change, so
+//! the change and its entry are either both saved or neither is.
+
+use actix_web::{FromRequest, HttpMessage as _, HttpRequest, dev::Payload};
+use anyhow::Context as _;
+use sqlx::Transaction;
+use std::{
+    fmt::Display,
+    future::{Ready, ready},
+};
+use tracing::instrument;
+use wykies_shared::{
+    audit::{AuditAction, AuditEntry},
+    const_config::audit::{AUDIT_PAGE_SIZE_DEFAULT, AUDIT_PAGE_SIZE_MAX},
+    db_types::{Db, DbPool},
+    e500,
+    host_branch::HostId,
+    req_args::api::audit::ListReqArgs,
+    uac::{UserInfo, Username},
+};
+use wykies_time::Timestamp;
+
+#[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+use crate::db_utils::db_timestamp;
+use crate::db_utils::validate_one_row_affected;
+
+/// Identifies who performed an action and from where
+///
+/// Can be extracted from any request that passed through
+/// [`crate::authentication::validate_user_access`]
+#[derive(Debug, Clone)]
+pub struct AuditContext {
+    pub actor: Username,
+    pub host_id: HostId,
+}
+
+/// Used as the details of an entry when the state before the change is
+/// available
+#[derive(Debug, serde::Serialize)]
+pub struct AuditChange<'a, B, A> {
+    pub before: &'a B,
+    pub after: &'a A,
+}
+
+impl AuditContext {
+    pub fn new(actor: Username, host_id: HostId) -> Self {
+        Self { actor, host_id }
+    }
+
+    fn try_from_request(req: &HttpRequest) -> anyhow::Result<Self> {
+        let actor = req
+            .extensions()
+            .get::<UserInfo>()
+            .context("user info not found, audit context is only available on protected routes")?
+            .username
+            .clone();
+        let host_id = req
+            .connection_info()
+            .clone()
+            .try_into()
+            .context("failed to get host_id")?;
+        Ok(Self { actor, host_id })
+    }
+
+    /// Saves an entry in the audit log. `details` is stored as JSON
+    ///
+    /// The entry is only saved when `transaction` is committed, which should be
+    /// the same transaction that made the change being audited
+    #[instrument(skip(transaction, details))]
+    pub async fn record<T: serde::Serialize>(
+        &self,
+        transaction: &mut Transaction<'_, Db>,
+        action: AuditAction,
+        target: impl Display + std::fmt::Debug,
+        details: &T,
+    ) -> anyhow::Result<()> {
+        let target = target.to_string();
+        let timestamp = Timestamp::now();
+        let action: &'static str = action.into();
+        let details =
+            serde_json::to_string(details).context("failed to serialize audit details")?;
+        #[cfg(feature = "mysql")]
+        let query = sqlx::query!(
+            "INSERT INTO `audit_log`
+            (`EventTime`, `Actor`, `Action`, `Target`, `Details`, `HostID`)
+            VALUES (?, ?, ?, ?, ?, ?);",
+            timestamp,
+            self.actor,
+            action,
+            target,
+            details,
+            self.host_id
+        );
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        // TODO 5: Check why encode trait impl doesn't make converting not necessary
+        let query = {
+            let timestamp = db_timestamp(timestamp)?;
+            sqlx::query!(
+                "INSERT INTO audit_log
+                (event_time, actor, action, target, details, host_id)
+                VALUES ($1, $2, $3, $4, $5, $6);",
+                timestamp,
+                self.actor.as_ref(),
+                action,
+                target,
+                details,
+                self.host_id.as_ref()
+            )
+        };
+        let sql_result = query
+            .execute(&mut **transaction)
+            .await
+            .context("failed to save audit log entry")?;
+        validate_one_row_affected(&sql_result).context("failed to save audit log entry")
+    }
+}
+
+impl FromRequest for AuditContext {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<AuditContext, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(Self::try_from_request(req).map_err(e500))
+    }
+}
+
+/// Returns the entries matching the filters newest first
+#[instrument(err(Debug), skip(pool))]
+pub async fn list_audit_entries(
+    pool: &DbPool,
+    args: &ListReqArgs,
+) -> anyhow::Result<Vec<AuditEntry>> {
+    let page_size = args
+        .page_size
+        .unwrap_or(AUDIT_PAGE_SIZE_DEFAULT)
+        .min(AUDIT_PAGE_SIZE_MAX);
+    let action: Option<&'static str> = args.action.map(|x| x.into());
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!(
+        "SELECT `AuditID`, `EventTime`, `Actor`, `Action`, `Target`, `Details`, `HostID`
+        FROM `audit_log`
+        WHERE (? IS NULL OR `Actor` = ?)
+        AND (? IS NULL OR `Action` = ?)
+        AND (? IS NULL OR `Target` = ?)
+        AND (? IS NULL OR `EventTime` >= ?)
+        AND (? IS NULL OR `EventTime` < ?)
+        AND (? IS NULL OR `AuditID` < ?)
+        ORDER BY `AuditID` DESC
+        LIMIT ?;",
+        args.actor,
+        args.actor,
+        action,
+        action,
+        args.target,
+        args.target,
+        args.since,
+        args.since,
+        args.until,
+        args.until,
+        args.before_id,
+        args.before_id,
+        page_size
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let query = {
+        let since = args.since.map(db_timestamp).transpose()?;
+        let until = args.until.map(db_timestamp).transpose()?;
+        let before_id: Option<i32> = args.before_id.map(|x| x.try_into()).transpose()?;
+        let page_size: i64 = page_size.into();
+        sqlx::query!(
+            "SELECT audit_id, event_time, actor, action, target, details, host_id
+            FROM audit_log
+            WHERE ($1::text IS NULL OR actor = $1)
+            AND ($2::text IS NULL OR action = $2)
+            AND ($3::text IS NULL OR target = $3)
+            AND ($4::bigint IS NULL OR event_time >= $4)
+            AND ($5::bigint IS NULL OR event_time < $5)
+            AND ($6::integer IS NULL OR audit_id < $6)
+            ORDER BY audit_id DESC
+            LIMIT $7;",
+            args.actor.as_ref().map(|x| x.as_ref()),
+            action,
+            args.target,
+            since,
+            until,
+            before_id,
+            page_size
+        )
+    };
+    query
+        .fetch_all(pool)
+        .await
+        .context("failed to get audit log entries")?
+        .into_iter()
+        .map(|x| {
+            #[cfg(feature = "mysql")]
+            return Ok(AuditEntry {
+                id: x.AuditID.try_into()?,
+                timestamp: x.EventTime.into(),
+                actor: x.Actor.try_into()?,
+                action: x.Action.parse()?,
+                target: x.Target,
+                details: x.Details,
+                host_id: x.HostID.try_into()?,
+            });
+            #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+            Ok(AuditEntry {
+                id: x.audit_id.try_into()?,
+                timestamp: x.event_time.try_into()?,
+                actor: x.actor.try_into()?,
+                action: x.action.parse()?,
+                target: x.target,
+                details: x.details,
+                host_id: x.host_id.try_into()?,
+            })
+        })
+        .collect()
+}
@@ -1,9 +1,38 @@
+mod api_keys;
 mod middleware;
 mod password;
+mod password_reset;
+mod roles;
+mod sessions;
+mod throttle;
+mod totp;
 
+pub use api_keys::{
+    create_api_key, is_name_used_by_api_key, is_name_used_by_user, list_api_keys, revoke_api_key,
+    rotate_api_key, validate_api_key,
+};
 pub use middleware::validate_user_access;
 pub use password::{
-    AuthUserInfo, Credentials, argon2_settings, change_password, validate_credentials,
+    Argon2Settings, AuthUserInfo, Credentials, PasswordExpiry, PasswordHashing, change_password,
+    is_password_reused, validate_credentials,
+};
+pub use password_reset::{
+    PasswordResetSettings, TOKEN_PREFIX_IN_MESSAGE, consume_password_reset_token,
+    delete_password_reset_tokens, request_password_reset, verify_password_reset_token,
+};
+pub use roles::{
+    RoleGraph, add_assigned_role, delete_role, get_all_assigned_roles, get_all_role_parents,
+    get_assigned_roles, get_role_parents, get_role_users, get_user_permissions, set_assigned_roles,
+    set_role_parents,
+};
+pub use sessions::{
+    SessionGenerationCache, SessionHostBinding, SessionLifetimes, get_session_generation,
+    invalidate_user_sessions,
+};
+pub use throttle::{HostLoginThrottle, LoginThrottling};
+pub use totp::{
+    ConfirmedEnrolment, TotpLoginCheck, TotpSettings, VerifiedTotpCode, check_totp_for_login,
+    confirm_enrolment, consume_totp_code, reset_totp, start_enrolment,
 };
 
 #[derive(Debug, Clone, Copy)]
@@ -0,0 +1,500 @@
+//! Keys for service accounts so that scripts can access the API without a
+//! login session
+//!
+//! Keys are sent using the `Bearer` scheme and have the format
+//! `wyk_<id>_<secret>`. Only a hash of the secret is stored so the key is only
+//! available when it is created or rotated. As the secret is long and random a
+//! SHA-256 hash is used instead of a password hash so that checking a key is
+//! cheap (See [`hash_secret`]).
+//!
+//! Requests made with a key act as a user named after the key so key names and
+//! usernames must not collide (See [`is_name_used_by_user`] and
+//! [`is_name_used_by_api_key`])
+
+#[cfg(feature = "mysql")]
+use crate::db_utils::db_int_to_bool;
+#[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+use crate::db_utils::db_timestamp;
+use crate::db_utils::validate_one_row_affected;
+use anyhow::Context;
+use argon2::password_hash::rand_core::{OsRng, RngCore as _};
+use secrecy::{ExposeSecret as _, SecretString};
+use sha2::{Digest as _, Sha256};
+use sqlx::Executor;
+use subtle::ConstantTimeEq as _;
+use wykies_shared::{
+    const_config::server::API_KEY_LAST_USED_UPDATE_INTERVAL,
+    db_types::{Db, DbPool},
+    req_args::api::api_key::NewReqArgs,
+    uac::{ApiKeyError, ApiKeyId, ApiKeyInfo, ApiKeySecret, UserInfo, Username},
+};
+use wykies_time::{Seconds, Timestamp};
+
+const API_KEY_PREFIX: &str = "wyk";
+const SECRET_LENGTH: usize = 40;
+const SECRET_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Creates a new key and returns it. The key cannot be retrieved again later
+#[tracing::instrument(skip(executor))]
+pub async fn create_api_key(
+    args: &NewReqArgs,
+    executor: impl Executor<'_, Database = Db>,
+) -> anyhow::Result<ApiKeySecret> {
+    let created = Timestamp::now();
+    let expires = args
+        .valid_for_days
+        .map(|days| created + Seconds::new(u64::from(days) * SECONDS_PER_DAY));
+    let permissions: String = (&args.permissions).into();
+    let (secret, key_hash) = generate_secret_and_hash();
+
+    #[cfg(feature = "mysql")]
+    let id: ApiKeyId = {
+        let sql_result = sqlx::query!(
+            "INSERT INTO `api_keys`
+            (`Name`, `KeyHash`, `Permissions`, `BranchID`, `Created`, `Expires`, `Revoked`)
+            VALUES (?, ?, ?, ?, ?, ?, 0);",
+            args.name,
+            key_hash.expose_secret(),
+            permissions,
+            args.branch_id,
+            created,
+            expires
+        )
+        .execute(executor)
+        .await
+        .context("failed to insert api key")?;
+        validate_one_row_affected(&sql_result).context("failed to insert api key")?;
+        sql_result.last_insert_id().into()
+    };
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let id: ApiKeyId = {
+        let branch_id: i32 = args.branch_id.try_into()?;
+        let created = db_timestamp(created)?;
+        let expires = expires.map(db_timestamp).transpose()?;
+        sqlx::query!(
+            "INSERT INTO api_keys
+            (key_name, key_hash, permissions, branch_id, created, expires, revoked)
+            VALUES ($1, $2, $3, $4, $5, $6, false) RETURNING api_key_id;",
+            args.name.as_ref(),
+            key_hash.expose_secret(),
+            permissions,
+            branch_id,
+            created,
+            expires
+        )
+        .fetch_one(executor)
+        .await
+        .context("failed to insert api key")?
+        .api_key_id
+        .try_into()?
+    };
+
+    Ok(ApiKeySecret {
+        id,
+        key: format_key(id, &secret),
+    })
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn list_api_keys(pool: &DbPool) -> anyhow::Result<Vec<ApiKeyInfo>> {
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!(
+        "SELECT `ApiKeyID`, `Name`, `Permissions`, `BranchID`, `Created`, `Expires`, `LastUsed`, `Revoked`
+        FROM `api_keys`
+        ORDER BY `ApiKeyID`;"
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    let query = sqlx::query!(
+        "SELECT api_key_id, key_name, permissions, branch_id, created, expires, last_used, revoked
+        FROM api_keys
+        ORDER BY api_key_id;"
+    );
+    query
+        .fetch_all(pool)
+        .await
+        .context("failed to get list of api keys")?
+        .into_iter()
+        .map(|x| {
+            #[cfg(feature = "mysql")]
+            return Ok(ApiKeyInfo {
+                id: x.ApiKeyID.try_into()?,
+                name: x.Name.try_into()?,
+                permissions: x.Permissions.try_into()?,
+                branch_id: x.BranchID.try_into()?,
+                created: x.Created.into(),
+                expires: x.Expires.map(Into::into),
+                last_used: x.LastUsed.map(Into::into),
+                revoked: db_int_to_bool(x.Revoked),
+            });
+            #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+            Ok(ApiKeyInfo {
+                id: x.api_key_id.try_into()?,
+                name: x.key_name.try_into()?,
+                permissions: x.permissions.try_into()?,
+                branch_id: x.branch_id.try_into()?,
+                created: x.created.try_into()?,
+                expires: x.expires.map(Timestamp::try_from).transpose()?,
+                last_used: x.last_used.map(Timestamp::try_from).transpose()?,
+                revoked: x.revoked,
+            })
+        })
+        .collect()
+}
+
+/// Replaces the secret of the key so that the old key stops working. Returns
+/// `None` if there is no key with that ID that has not been revoked
+#[tracing::instrument(skip(executor))]
+pub async fn rotate_api_key(
+    id: ApiKeyId,
+    executor: impl Executor<'_, Database = Db>,
+) -> anyhow::Result<Option<ApiKeySecret>> {
+    let (secret, key_hash) = generate_secret_and_hash();
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!(
+        "UPDATE `api_keys` SET `KeyHash` = ? WHERE `ApiKeyID` = ? AND `Revoked` = 0;",
+        key_hash.expose_secret(),
+        id
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let query = {
+        let id: i32 = id.try_into()?;
+        sqlx::query!(
+            "UPDATE api_keys SET key_hash = $1 WHERE api_key_id = $2 AND NOT revoked;",
+            key_hash.expose_secret(),
+            id
+        )
+    };
+    let sql_result = query
+        .execute(executor)
+        .await
+        .context("failed to rotate api key")?;
+    if sql_result.rows_affected() == 0 {
+        return Ok(None);
+    }
+    validate_one_row_affected(&sql_result).context("failed to rotate api key")?;
+    Ok(Some(ApiKeySecret {
+        id,
+        key: format_key(id, &secret),
+    }))
+}
+
+/// Permanently disables the key. Returns `false` if there is no key with that
+/// ID that has not already been revoked
+#[tracing::instrument(skip(executor))]
+pub async fn revoke_api_key(
+    id: ApiKeyId,
+    executor: impl Executor<'_, Database = Db>,
+) -> anyhow::Result<bool> {
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!(
+        "UPDATE `api_keys` SET `Revoked` = 1 WHERE `ApiKeyID` = ? AND `Revoked` = 0;",
+        id
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let query = {
+        let id: i32 = id.try_into()?;
+        sqlx::query!(
+            "UPDATE api_keys SET revoked = true WHERE api_key_id = $1 AND NOT revoked;",
+            id
+        )
+    };
+    let sql_result = query
+        .execute(executor)
+        .await
+        .context("failed to revoke api key")?;
+    Ok(sql_result.rows_affected() > 0)
+}
+
+/// Returns `true` if a user with this name exists
+#[tracing::instrument(skip(pool))]
+pub async fn is_name_used_by_user(name: &Username, pool: &DbPool) -> anyhow::Result<bool> {
+    #[cfg(feature = "mysql")]
+    let row = sqlx::query!("SELECT `UserName` FROM `user` WHERE `UserName` = ?;", name)
+        .fetch_optional(pool)
+        .await;
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    let row = sqlx::query!(
+        "SELECT user_name FROM users WHERE user_name = $1;",
+        name.as_ref()
+    )
+    .fetch_optional(pool)
+    .await;
+    Ok(row.context("failed to check for user with name")?.is_some())
+}
+
+/// Returns `true` if a key that has not been revoked uses this name
+#[tracing::instrument(skip(pool))]
+pub async fn is_name_used_by_api_key(name: &Username, pool: &DbPool) -> anyhow::Result<bool> {
+    #[cfg(feature = "mysql")]
+    let row = sqlx::query!(
+        "SELECT `ApiKeyID` FROM `api_keys` WHERE `Name` = ? AND `Revoked` = 0 LIMIT 1;",
+        name
+    )
+    .fetch_optional(pool)
+    .await;
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    let row = sqlx::query!(
+        "SELECT api_key_id FROM api_keys WHERE key_name = $1 AND NOT revoked LIMIT 1;",
+        name.as_ref()
+    )
+    .fetch_optional(pool)
+    .await;
+    Ok(row
+        .context("failed to check for api key with name")?
+        .is_some())
+}
+
+/// Checks the key and returns the [`UserInfo`] that requests made with it
+/// should be treated as coming from. Records when the key was last used (See
+/// [`API_KEY_LAST_USED_UPDATE_INTERVAL`])
+#[tracing::instrument(skip(key, pool))]
+pub async fn validate_api_key(key: SecretString, pool: &DbPool) -> Result<UserInfo, ApiKeyError> {
+    let Some((id, secret)) = parse_key(key.expose_secret()) else {
+        return Err(ApiKeyError::Invalid);
+    };
+    let Some((info, key_hash)) = get_api_key(id, pool).await? else {
+        return Err(ApiKeyError::Invalid);
+    };
+    let now = Timestamp::now();
+    check_usable(&info, now)?;
+    if !is_matching_secret(secret, &key_hash) {
+        return Err(ApiKeyError::Invalid);
+    }
+
+    if info.last_used.is_none_or(|last_used| {
+        now.seconds_since(last_used)
+            .is_none_or(|x| x >= API_KEY_LAST_USED_UPDATE_INTERVAL)
+    }) {
+        update_last_used(id, now, pool).await?;
+    }
+    Ok(UserInfo {
+        username: info.name,
+        permissions: info.permissions,
+        branch_id: info.branch_id,
+    })
+}
+
+fn check_usable(info: &ApiKeyInfo, now: Timestamp) -> Result<(), ApiKeyError> {
+    if info.revoked {
+        // Not distinguished from an invalid key so that revoked keys do not stand out
+        return Err(ApiKeyError::Invalid);
+    }
+    if info.expires.is_some_and(|expires| now >= expires) {
+        return Err(ApiKeyError::Expired);
+    }
+    Ok(())
+}
+
+fn format_key(id: ApiKeyId, secret: &SecretString) -> String {
+    format!("{API_KEY_PREFIX}_{id}_{}", secret.expose_secret())
+}
+
+fn parse_key(key: &str) -> Option<(ApiKeyId, &str)> {
+    let (id, secret) = key
+        .strip_prefix(API_KEY_PREFIX)?
+        .strip_prefix('_')?
+        .split_once('_')?;
+    let id = ApiKeyId::try_from(id).ok()?;
+    (!secret.is_empty()).then_some((id, secret))
+}
+
+fn generate_secret_and_hash() -> (SecretString, SecretString) {
+    let secret: String = (0..SECRET_LENGTH)
+        .map(|_| {
+            let index = OsRng.next_u32() as usize % SECRET_ALPHABET.len();
+            SECRET_ALPHABET[index] as char
+        })
+        .collect();
+    let key_hash = hash_secret(&secret);
+    (SecretString::from(secret), key_hash)
+}
+
+/// Returns the hex encoded SHA-256 hash of the secret
+fn hash_secret(secret: &str) -> SecretString {
+    SecretString::from(format!("{:x}", Sha256::digest(secret.as_bytes())))
+}
+
+/// Compared in constant time so that the response time does not reveal how
+/// much of the hash matched
+fn is_matching_secret(secret: &str, key_hash: &SecretString) -> bool {
+    hash_secret(secret)
+        .expose_secret()
+        .as_bytes()
+        .ct_eq(key_hash.expose_secret().as_bytes())
+        .into()
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_api_key(
+    id: ApiKeyId,
+    pool: &DbPool,
+) -> anyhow::Result<Option<(ApiKeyInfo, SecretString)>> {
+    #[cfg(feature = "mysql")]
+    let row = sqlx::query!(
+        "SELECT `Name`, `KeyHash`, `Permissions`, `BranchID`, `Created`, `Expires`, `LastUsed`, `Revoked`
+        FROM `api_keys`
+        WHERE `ApiKeyID` = ?;",
+        id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("failed to get api key")?;
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let row = {
+        let id: i32 = id.try_into()?;
+        sqlx::query!(
+            "SELECT key_name, key_hash, permissions, branch_id, created, expires, last_used, revoked
+            FROM api_keys
+            WHERE api_key_id = $1;",
+            id
+        )
+        .fetch_optional(pool)
+        .await
+        .context("failed to get api key")?
+    };
+    let Some(x) = row else {
+        return Ok(None);
+    };
+
+    #[cfg(feature = "mysql")]
+    let result = (
+        ApiKeyInfo {
+            id,
+            name: x.Name.try_into()?,
+            permissions: x.Permissions.try_into()?,
+            branch_id: x.BranchID.try_into()?,
+            created: x.Created.into(),
+            expires: x.Expires.map(Into::into),
+            last_used: x.LastUsed.map(Into::into),
+            revoked: db_int_to_bool(x.Revoked),
+        },
+        SecretString::from(x.KeyHash),
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    let result = (
+        ApiKeyInfo {
+            id,
+            name: x.key_name.try_into()?,
+            permissions: x.permissions.try_into()?,
+            branch_id: x.branch_id.try_into()?,
+            created: x.created.try_into()?,
+            expires: x.expires.map(Timestamp::try_from).transpose()?,
+            last_used: x.last_used.map(Timestamp::try_from).transpose()?,
+            revoked: x.revoked,
+        },
+        SecretString::from(x.key_hash),
+    );
+    Ok(Some(result))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn update_last_used(id: ApiKeyId, now: Timestamp, pool: &DbPool) -> anyhow::Result<()> {
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!(
+        "UPDATE `api_keys` SET `LastUsed` = ? WHERE `ApiKeyID` = ?;",
+        now,
+        id
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let query = {
+        let now = db_timestamp(now)?;
+        let id: i32 = id.try_into()?;
+        sqlx::query!(
+            "UPDATE api_keys SET last_used = $1 WHERE api_key_id = $2;",
+            now,
+            id
+        )
+    };
+    query
+        .execute(pool)
+        .await
+        .context("failed to update when api key was last used")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn info(revoked: bool, expires: Option<u32>) -> ApiKeyInfo {
+        ApiKeyInfo {
+            id: 1.into(),
+            name: "service".to_string().try_into().unwrap(),
+            permissions: Default::default(),
+            branch_id: 1.into(),
+            created: 0u32.into(),
+            expires: expires.map(Into::into),
+            last_used: None,
+            revoked,
+        }
+    }
+
+    #[test]
+    fn key_round_trip() {
+        // Arrange
+        let id: ApiKeyId = 42.into();
+        let secret = SecretString::from("abc123");
+
+        // Act
+        let key = format_key(id, &secret);
+
+        // Assert
+        assert_eq!(parse_key(&key), Some((id, "abc123")));
+    }
+
+    #[test]
+    fn secret_only_matches_own_hash() {
+        // Arrange
+        let (secret, key_hash) = generate_secret_and_hash();
+        let (other_secret, _) = generate_secret_and_hash();
+
+        // Act
+        let is_own_matching = is_matching_secret(secret.expose_secret(), &key_hash);
+        let is_other_matching = is_matching_secret(other_secret.expose_secret(), &key_hash);
+
+        // Assert
+        assert!(is_own_matching);
+        assert!(!is_other_matching);
+    }
+
+    #[rstest]
+    #[case::empty("")]
+    #[case::wrong_prefix("abc_1_secret")]
+    #[case::missing_secret("wyk_1_")]
+    #[case::id_not_a_number("wyk_a_secret")]
+    #[case::no_separators("wyk1secret")]
+    fn invalid_keys_not_parsed(#[case] key: &str) {
+        assert_eq!(parse_key(key), None);
+    }
+
+    #[rstest]
+    #[case::usable(false, None, 100, None)]
+    #[case::before_expiry(false, Some(200), 100, None)]
+    #[case::expired(false, Some(100), 100, Some("expired"))]
+    #[case::revoked(true, None, 100, Some("invalid"))]
+    fn key_usability(
+        #[case] revoked: bool,
+        #[case] expires: Option<u32>,
+        #[case] now: u32,
+        #[case] expected: Option<&str>,
+    ) {
+        // Act
+        let actual = check_usable(&info(revoked, expires), now.into());
+
+        // Assert
+        let actual = actual.err().map(|e| match e {
+            ApiKeyError::Invalid => "invalid",
+            ApiKeyError::Expired => "expired",
+            ApiKeyError::SessionRequired => "session required",
+            ApiKeyError::UnexpectedError(_) => "unexpected",
+        });
+        assert_eq!(actual, expected);
+    }
+}
@@ -1,26 +1,58 @@
+use super::{SessionGenerationCache, SessionHostBinding, SessionLifetimes, validate_api_key};
 use crate::session_state::TypedSession;
 use actix_web::{
     FromRequest, HttpMessage,
     body::MessageBody,
     dev::{ServiceRequest, ServiceResponse},
+    http::header::AUTHORIZATION,
     middleware::Next,
+    web,
 };
-use tracing::info;
+use anyhow::Context as _;
+use secrecy::SecretString;
+use tracing::{info, warn};
 use wykies_shared::{
+    const_config::path::{
+        PATH_API_CHANGE_PASSWORD, PATH_API_LOGOUT, PATH_API_TOTP_CONFIRM, PATH_API_TOTP_ENROL,
+    },
+    db_types::DbPool,
     e500,
     errors::NotLoggedInError,
-    uac::{PermissionsError, UserInfo},
+    host_branch::HostId,
+    uac::{ApiKeyError, PermissionsError, SessionExpiredError, TotpError, UserInfo},
 };
+use wykies_time::Timestamp;
 
 /// Ensures the user is logged in and has the required permissions to get to the
 /// endpoint The endpoint may do further permission checking based on the
 /// content of the request but top level endpoint permission validation happens
 /// at this point
+///
+/// Requests that include an API key using the `Bearer` scheme are
+/// authenticated using the key instead of a session. Endpoints that act on the
+/// caller's own account are only available with a session (See
+/// [`check_session_only_path`])
 #[tracing::instrument(skip(next))]
 pub async fn validate_user_access(
     mut req: ServiceRequest,
     next: Next<impl MessageBody>,
 ) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if let Some(api_key) = bearer_token(&req) {
+        let pool: &DbPool = req
+            .app_data::<web::Data<DbPool>>()
+            .context("db pool not found in app data")
+            .map_err(e500)?;
+        let user_info = validate_api_key(api_key, pool).await?;
+        check_session_only_path(&req)?;
+        check_permissions(&req, &user_info).await?;
+        info!(
+            "Validated API key request for {:?}",
+            user_info.username.as_ref()
+        );
+        req.extensions_mut().insert(user_info);
+        return next.call(req).await;
+    }
+
     let session = {
         let (http_request, payload) = req.parts_mut();
         TypedSession::from_request(http_request, payload).await
@@ -32,10 +64,23 @@ pub async fn validate_user_access(
 
     match session.get_user_info().map_err(e500)? {
         Some(user_info) => {
+            let session_lifetimes = req
+                .app_data::<web::Data<SessionLifetimes>>()
+                .context("session lifetimes not found in app data")
+                .map_err(e500)?
+                .clone();
+            let last_activity =
+                check_session_still_valid(&req, &session, &user_info, &session_lifetimes).await?;
+            check_session_host(&req, &session, &user_info)?;
+            check_totp_enrolment(&req, &session)?;
             check_permissions(&req, &user_info).await?;
+            if Timestamp::now()
+                .seconds_since(last_activity)
+                .is_none_or(|x| x >= session_lifetimes.activity_update_interval())
+            {
+                session.update_last_activity().map_err(e500)?;
+            }
             info!("Validated request for {:?}", user_info.username.as_ref());
-            // TODO 4: Add check that client identifier still matches what is saved
-            // otherwise log and reject connection
             req.extensions_mut().insert(user_info);
             next.call(req).await
         }
@@ -43,6 +88,131 @@ pub async fn validate_user_access(
     }
 }
 
+/// Returns the key from the `Authorization` header if the `Bearer` scheme is
+/// used
+fn bearer_token(req: &ServiceRequest) -> Option<SecretString> {
+    let value = req.headers().get(AUTHORIZATION)?.to_str().ok()?;
+    value
+        .strip_prefix("Bearer ")
+        .map(|key| SecretString::from(key.trim()))
+}
+
+/// Endpoints that manage the caller's own login (password, two-factor
+/// authentication and logout) only make sense for users and are refused for API
+/// keys
+fn check_session_only_path(req: &ServiceRequest) -> Result<(), ApiKeyError> {
+    let is_session_only = [
+        PATH_API_CHANGE_PASSWORD,
+        PATH_API_LOGOUT,
+        PATH_API_TOTP_CONFIRM,
+        PATH_API_TOTP_ENROL,
+    ]
+    .iter()
+    .any(|path_spec| path_spec.path == req.path());
+    if is_session_only {
+        Err(ApiKeyError::SessionRequired)
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks that the session has not expired and has not been invalidated since
+/// the user logged in. Returns the last activity stored in the session
+#[tracing::instrument(skip(req, session))]
+async fn check_session_still_valid(
+    req: &ServiceRequest,
+    session: &TypedSession,
+    user_info: &UserInfo,
+    session_lifetimes: &SessionLifetimes,
+) -> actix_web::Result<Timestamp> {
+    let (Some(login_timestamp), Some(last_activity), Some(session_generation)) = (
+        session.get_login_timestamp().map_err(e500)?,
+        session.get_last_activity().map_err(e500)?,
+        session.get_session_generation().map_err(e500)?,
+    ) else {
+        // Session was created before these were recorded and cannot be validated
+        return Err(SessionExpiredError::Invalidated.into());
+    };
+
+    session_lifetimes.check(login_timestamp, last_activity, Timestamp::now())?;
+
+    let pool: &DbPool = req
+        .app_data::<web::Data<DbPool>>()
+        .context("db pool not found in app data")
+        .map_err(e500)?;
+    let generation_cache = req
+        .app_data::<web::Data<SessionGenerationCache>>()
+        .context("session generation cache not found in app data")
+        .map_err(e500)?;
+    let current_generation = generation_cache
+        .get(&user_info.username, pool)
+        .await
+        .map_err(e500)?;
+    if current_generation != Some(session_generation) {
+        return Err(SessionExpiredError::Invalidated.into());
+    }
+    Ok(last_activity)
+}
+
+/// Checks that the request comes from the same host that the session was
+/// created for. Mismatches are always logged but only rejected if enforcement
+/// is enabled (See [`SessionHostBinding`])
+#[tracing::instrument(skip(req, session))]
+fn check_session_host(
+    req: &ServiceRequest,
+    session: &TypedSession,
+    user_info: &UserInfo,
+) -> actix_web::Result<()> {
+    let Some(client_binding) = session.get_client_binding().map_err(e500)? else {
+        // Session was created before the host was recorded and cannot be validated
+        return Err(SessionExpiredError::Invalidated.into());
+    };
+    let current_host_id: HostId = req
+        .connection_info()
+        .clone()
+        .try_into()
+        .context("failed to get host_id")
+        .map_err(e500)?;
+    if current_host_id == client_binding.host_id {
+        return Ok(());
+    }
+
+    let session_host_binding = req
+        .app_data::<web::Data<SessionHostBinding>>()
+        .context("session host binding not found in app data")
+        .map_err(e500)?;
+    warn!(
+        security_event = "session_host_mismatch",
+        username = ?user_info.username,
+        expected_host_id = ?client_binding.host_id,
+        ?current_host_id,
+        enforced = session_host_binding.enforce,
+        "Session used from a different host than it was created on"
+    );
+    if session_host_binding.enforce {
+        Err(SessionExpiredError::HostMismatch.into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Users that are required to enrol in two-factor authentication can only
+/// complete the enrolment or logout until they do
+#[tracing::instrument(skip(req, session))]
+fn check_totp_enrolment(req: &ServiceRequest, session: &TypedSession) -> actix_web::Result<()> {
+    if session.get_totp_enrolment_required().map_err(e500)? != Some(true) {
+        return Ok(());
+    }
+    let is_allowed = [PATH_API_TOTP_ENROL, PATH_API_TOTP_CONFIRM, PATH_API_LOGOUT]
+        .iter()
+        .any(|path_spec| path_spec.path == req.path());
+    if is_allowed {
+        Ok(())
+    } else {
+        Err(TotpError::EnrolmentRequired.into())
+    }
+}
+
 /// Checks that the user has the required permissions to access the endpoint.
 /// If no permissions are found for the endpoint a 503 error is returned (See
 /// [`wykies_shared::uac::get_required_permissions`])
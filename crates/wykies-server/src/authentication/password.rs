@@ -1,21 +1,26 @@
 #[cfg(feature = "mysql")]
 use crate::db_utils::db_int_to_bool;
+#[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+use crate::db_utils::db_timestamp;
 use crate::db_utils::validate_one_row_affected;
 use anyhow::Context;
 use argon2::{
     Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version,
     password_hash::{SaltString, rand_core},
 };
+use chrono::NaiveDate;
 use secrecy::{ExposeSecret, SecretString};
-use tracing::{error, info};
+use sqlx::{Executor, Transaction};
+use tracing::{error, info, warn};
 use wykies_shared::branch::BranchId;
-use wykies_shared::db_types::DbPool;
+use wykies_shared::db_types::{Db, DbPool};
 use wykies_shared::{
     telemetry::spawn_blocking_with_tracing,
     uac::{AuthError, LoginResponse, Permissions, UserInfo, Username},
 };
+use wykies_time::Timestamp;
 
-use super::LoginAttemptLimit;
+use super::{LoginAttemptLimit, LoginThrottling, get_user_permissions};
 
 pub struct Credentials {
     pub username: String,
@@ -30,7 +35,18 @@ pub struct DbUser {
     pub permissions: Permissions,
     pub enabled: bool,
     pub locked_out: bool,
+    /// Only set if the user was locked out automatically (not by an
+    /// administrator)
+    pub locked_out_at: Option<Timestamp>,
     pub failed_attempts: i8,
+    pub last_failed_attempt: Option<Timestamp>,
+    pub session_generation: i32,
+    /// Only set if two-factor authentication is enabled (not during enrolment)
+    pub totp_secret: Option<SecretString>,
+    /// Time step of the last code accepted, codes for this step or an earlier
+    /// one are rejected
+    pub totp_last_step: Option<u64>,
+    pub pass_change_date: NaiveDate,
 }
 
 impl DbUser {
@@ -52,7 +68,13 @@ impl Default for DbUser {
             permissions: Default::default(),
             enabled: true, // Needs to be enabled to prevent non-existent users showing as disabled
             locked_out: Default::default(),
+            locked_out_at: Default::default(),
             failed_attempts: Default::default(),
+            last_failed_attempt: Default::default(),
+            session_generation: Default::default(),
+            totp_secret: Default::default(),
+            totp_last_step: Default::default(),
+            pass_change_date: Default::default(),
         }
     }
 }
@@ -62,9 +84,8 @@ async fn get_user_from_db(username: &str, pool: &DbPool) -> anyhow::Result<Optio
     // TODO 5: Remove display name from the queries (already removed from struct)
     #[cfg(feature = "mysql")]
     let query = sqlx::query!(
-        "SELECT UserName, password_hash, ForcePassChange, DisplayName, Enabled, LockedOut, FailedAttempts, Permissions
+        "SELECT UserName, password_hash, ForcePassChange, DisplayName, Enabled, LockedOut, LockedOutAt, FailedAttempts, LastFailedAttempt, SessionGeneration, TotpSecret, TotpEnabled, TotpLastStep, PassChangeDate
         FROM user
-        LEFT JOIN roles ON user.AssignedRole = roles.RoleID
         WHERE UserName = ?
         ",
         username,
@@ -72,9 +93,8 @@ async fn get_user_from_db(username: &str, pool: &DbPool) -> anyhow::Result<Optio
 
     #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
     let query = sqlx::query!(
-        r#"SELECT user_name, password_hash, force_pass_change, display_name, is_enabled, locked_out, failed_attempts, permissions AS "permissions?"
+        r#"SELECT user_name, password_hash, force_pass_change, display_name, is_enabled, locked_out, locked_out_at, failed_attempts, last_failed_attempt, session_generation, totp_secret, totp_enabled, totp_last_step, pass_change_date
         FROM users
-        LEFT JOIN roles ON users.assigned_role = roles.role_id
         WHERE user_name = $1;"#,
         username,
     );
@@ -86,16 +106,26 @@ async fn get_user_from_db(username: &str, pool: &DbPool) -> anyhow::Result<Optio
         info!("User not found: {username}");
         return Ok(None);
     };
+    let permissions = get_user_permissions(username, pool).await?.effective;
 
     #[cfg(feature = "mysql")]
     return Ok(Some(DbUser {
         username: row.UserName,
         password_hash: SecretString::from(row.password_hash),
         force_pass_change: db_int_to_bool(row.ForcePassChange),
-        permissions: row.Permissions.unwrap_or_default().try_into()?,
+        permissions,
         enabled: db_int_to_bool(row.Enabled),
         locked_out: db_int_to_bool(row.LockedOut),
+        locked_out_at: row.LockedOutAt.map(Into::into),
         failed_attempts: row.FailedAttempts,
+        last_failed_attempt: row.LastFailedAttempt.map(Into::into),
+        session_generation: row.SessionGeneration,
+        totp_secret: row
+            .TotpSecret
+            .filter(|_| db_int_to_bool(row.TotpEnabled))
+            .map(SecretString::from),
+        totp_last_step: row.TotpLastStep.map(u64::try_from).transpose()?,
+        pass_change_date: row.PassChangeDate,
     }));
 
     #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
@@ -103,10 +133,22 @@ async fn get_user_from_db(username: &str, pool: &DbPool) -> anyhow::Result<Optio
         username: row.user_name,
         password_hash: SecretString::from(row.password_hash),
         force_pass_change: row.force_pass_change,
-        permissions: row.permissions.try_into()?,
+        permissions,
         enabled: row.is_enabled,
         locked_out: row.locked_out,
+        locked_out_at: row.locked_out_at.map(Timestamp::try_from).transpose()?,
         failed_attempts: row.failed_attempts.try_into()?,
+        last_failed_attempt: row
+            .last_failed_attempt
+            .map(Timestamp::try_from)
+            .transpose()?,
+        session_generation: row.session_generation,
+        totp_secret: row
+            .totp_secret
+            .filter(|_| row.totp_enabled)
+            .map(SecretString::from),
+        totp_last_step: row.totp_last_step.map(u64::try_from).transpose()?,
+        pass_change_date: row.pass_change_date,
     }))
 }
 
@@ -115,10 +157,20 @@ pub struct AuthUserInfo {
     pub username: String,
     pub force_pass_change: bool,
     pub permissions: Permissions,
+    pub session_generation: i32,
+    /// See [`DbUser::totp_secret`]
+    pub totp_secret: Option<SecretString>,
+    /// See [`DbUser::totp_last_step`]
+    pub totp_last_step: Option<u64>,
+    pub pass_change_date: NaiveDate,
 }
 
 impl AuthUserInfo {
-    pub fn into_login_response(self, branch_id: BranchId) -> anyhow::Result<LoginResponse> {
+    pub fn into_login_response(
+        self,
+        branch_id: BranchId,
+        password_expiry: &PasswordExpiry,
+    ) -> anyhow::Result<LoginResponse> {
         let username = self.username.try_into()?;
         let user_info = UserInfo {
             username,
@@ -126,23 +178,60 @@ impl AuthUserInfo {
             permissions: self.permissions,
         };
 
-        if self.force_pass_change {
-            Ok(LoginResponse::SuccessForcePassChange(user_info))
-        } else {
-            Ok(LoginResponse::Success(user_info))
+        let today = chrono::Utc::now().date_naive();
+        let days_remaining = password_expiry.days_remaining(self.pass_change_date, today);
+        if self.force_pass_change || days_remaining.is_some_and(|days| days <= 0) {
+            return Ok(LoginResponse::SuccessForcePassChange(user_info));
         }
+        let password_expires_in_days = days_remaining
+            .filter(|&days| days <= i64::from(password_expiry.warning_days))
+            .map(u16::try_from)
+            .transpose()
+            .context("days remaining before password expires out of range")?;
+        Ok(LoginResponse::Success {
+            user_info,
+            password_expires_in_days,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PasswordExpiry {
+    /// Days after the password is changed that it expires. Disabled if `None`
+    pub max_age_days: Option<u16>,
+    /// Users are warned when the password expires within this many days
+    pub warning_days: u16,
+}
+
+impl PasswordExpiry {
+    /// Zero or negative means the password has expired
+    fn days_remaining(&self, pass_change_date: NaiveDate, today: NaiveDate) -> Option<i64> {
+        let max_age_days = self.max_age_days?;
+        let age_days = (today - pass_change_date).num_days();
+        Some(i64::from(max_age_days) - age_days)
     }
 }
 
 /// Uses a default (empty DbUser) to make it harder to do timing attacks to find
 /// usernames
-#[tracing::instrument(name = "Validate credentials", skip(credentials, pool))]
+///
+/// Attempts made before the delay since the last failed attempt has passed are
+/// rejected even if the password is correct (See [`LoginThrottling`])
+#[tracing::instrument(
+    name = "Validate credentials",
+    skip(credentials, pool, password_hashing)
+)]
 pub async fn validate_credentials(
     credentials: Credentials,
     pool: &DbPool,
     login_attempt_limit: &LoginAttemptLimit,
+    login_throttling: &LoginThrottling,
+    password_hashing: &PasswordHashing,
 ) -> Result<AuthUserInfo, AuthError> {
-    let mut db_user = DbUser::default();
+    let mut db_user = DbUser {
+        password_hash: password_hashing.dummy_hash.clone(),
+        ..Default::default()
+    };
 
     let retrieved_user = match get_user_from_db(&credentials.username, pool).await {
         Ok(x) => x,
@@ -159,6 +248,7 @@ pub async fn validate_credentials(
     }
 
     let expected_password_hash = db_user.password_hash.clone();
+    let password_candidate = credentials.password.clone();
 
     let password_check_status = spawn_blocking_with_tracing(move || {
         verify_password_hash(expected_password_hash, credentials.password)
@@ -174,9 +264,23 @@ pub async fn validate_credentials(
         return Err(AuthError::NotEnabled);
     }
 
+    let now = Timestamp::now();
     if db_user.locked_out {
-        // user is locked out already
-        return Err(AuthError::LockedOut);
+        if !login_throttling.is_lockout_expired(db_user.locked_out_at, now) {
+            // user is locked out already
+            return Err(AuthError::LockedOut);
+        }
+        clear_expired_lockout(&db_user.username, pool).await?;
+        info!("Lockout expired for {}", db_user.username);
+        db_user.failed_attempts = 0;
+        db_user.last_failed_attempt = None;
+    }
+
+    if let Some(retry_after) =
+        login_throttling.retry_after(db_user.failed_attempts, db_user.last_failed_attempt, now)
+    {
+        // Not counted as a failed attempt otherwise the delay would keep growing
+        return Err(AuthError::TooManyAttempts { retry_after });
     }
 
     // Error out if password was wrong after checking for lockout
@@ -187,6 +291,18 @@ pub async fn validate_credentials(
             if db_user.failed_attempts > 0 {
                 reset_failed_login_attempts(&db_user.username, pool).await?;
             }
+            if password_hashing.is_outdated(&db_user.password_hash)
+                && let Err(err_msg) = upgrade_password_hash(
+                    &db_user.username,
+                    password_candidate,
+                    password_hashing,
+                    pool,
+                )
+                .await
+            {
+                // Not worth failing the login over, it will be tried again next login
+                warn!(?err_msg, "failed to upgrade password hash");
+            }
         }
         Err(e) => {
             if !db_user.is_default() {
@@ -203,6 +319,10 @@ pub async fn validate_credentials(
         username,
         force_pass_change,
         permissions,
+        session_generation,
+        totp_secret,
+        totp_last_step,
+        pass_change_date,
         ..
     } = db_user;
 
@@ -210,6 +330,10 @@ pub async fn validate_credentials(
         username,
         force_pass_change,
         permissions,
+        session_generation,
+        totp_secret,
+        totp_last_step,
+        pass_change_date,
     })
 }
 
@@ -233,24 +357,53 @@ async fn reset_failed_login_attempts(username: &str, pool: &DbPool) -> anyhow::R
     validate_one_row_affected(&sql_result).context("failed to to reset `failed attempts`")
 }
 
+#[tracing::instrument(skip(pool))]
+async fn clear_expired_lockout(username: &str, pool: &DbPool) -> anyhow::Result<()> {
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!(
+        "UPDATE `user` SET `LockedOut`=0, `LockedOutAt`=NULL, `FailedAttempts`=0, `LastFailedAttempt`=NULL WHERE `UserName`=?;",
+        username,
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    let query = sqlx::query!(
+        "UPDATE users SET locked_out=false, locked_out_at=NULL, failed_attempts=0, last_failed_attempt=NULL WHERE user_name=$1;",
+        username,
+    );
+
+    let sql_result = query
+        .execute(pool)
+        .await
+        .context("failed to clear expired lockout")?;
+    validate_one_row_affected(&sql_result).context("failed to clear expired lockout")
+}
+
+/// Records when the user was locked out so the lockout can expire (See
+/// [`LoginThrottling::lockout_duration`])
 #[tracing::instrument(skip(pool))]
 async fn set_locked_out_in_db(username: &str, pool: &DbPool, value: bool) -> anyhow::Result<()> {
+    let locked_out_at = value.then(Timestamp::now);
     #[cfg(feature = "mysql")]
     let query = {
         // TODO 5: Do we need the manual conversion to numbers here?
         let value = if value { 1 } else { 0 };
         sqlx::query!(
-            "UPDATE `user` SET `LockedOut` = ? WHERE `user`.`UserName` = ?;",
+            "UPDATE `user` SET `LockedOut` = ?, `LockedOutAt` = ? WHERE `user`.`UserName` = ?;",
             value,
+            locked_out_at,
             username,
         )
     };
     #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
-    let query = sqlx::query!(
-        "UPDATE users SET locked_out = $1 WHERE users.user_name = $2;",
-        value,
-        username,
-    );
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let query = {
+        let locked_out_at = locked_out_at.map(db_timestamp).transpose()?;
+        sqlx::query!(
+            "UPDATE users SET locked_out = $1, locked_out_at = $2 WHERE users.user_name = $3;",
+            value,
+            locked_out_at,
+            username,
+        )
+    };
     let sql_result = query
         .execute(pool)
         .await
@@ -259,22 +412,29 @@ async fn set_locked_out_in_db(username: &str, pool: &DbPool, value: bool) -> any
 }
 
 #[tracing::instrument(skip(pool))]
-async fn increment_locked_out_count(
+pub(super) async fn increment_locked_out_count(
     username: &str,
     pool: &DbPool,
     login_attempt_limit: &LoginAttemptLimit,
 ) -> Result<(), AuthError> {
     // Increment current value in DB
+    let now = Timestamp::now();
     #[cfg(feature = "mysql")]
     let query = sqlx::query!(
-        "UPDATE `user` SET `FailedAttempts`=`FailedAttempts`+1 WHERE `UserName`=?; ",
+        "UPDATE `user` SET `FailedAttempts`=`FailedAttempts`+1, `LastFailedAttempt`=? WHERE `UserName`=?; ",
+        now,
         username,
     );
     #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
-    let query = sqlx::query!(
-        "UPDATE users SET failed_attempts=failed_attempts+1 WHERE user_name=$1;",
-        username,
-    );
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let query = {
+        let now = db_timestamp(now)?;
+        sqlx::query!(
+            "UPDATE users SET failed_attempts=failed_attempts+1, last_failed_attempt=$1 WHERE user_name=$2;",
+            now,
+            username,
+        )
+    };
     let sql_result = query
         .execute(pool)
         .await
@@ -313,7 +473,7 @@ async fn increment_locked_out_count(
 }
 
 #[tracing::instrument(skip(expected_password_hash, password_candidate))]
-fn verify_password_hash(
+pub(super) fn verify_password_hash(
     expected_password_hash: SecretString,
     password_candidate: SecretString,
 ) -> Result<(), AuthError> {
@@ -334,16 +494,24 @@ fn verify_password_hash(
     }
 }
 
-#[tracing::instrument(skip(password, pool))]
+/// Previous passwords that are no longer needed to enforce `history_count` are
+/// removed. Nothing is saved until `transaction` is committed
+#[tracing::instrument(skip(password, password_hashing, transaction))]
 pub async fn change_password(
     username: &Username,
     password: SecretString,
     should_force_pass_change: bool,
-    pool: &DbPool,
+    history_count: u16,
+    password_hashing: &PasswordHashing,
+    transaction: &mut Transaction<'_, Db>,
 ) -> anyhow::Result<()> {
-    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
-        .await?
-        .context("failed to hash password")?;
+    let password_hashing = password_hashing.clone();
+    let password_hash =
+        spawn_blocking_with_tracing(move || password_hashing.compute_password_hash(password))
+            .await?
+            .context("failed to hash password")?;
+    record_password_history(username, &mut **transaction).await?;
+    prune_password_history(username, history_count, transaction).await?;
     #[cfg(feature = "mysql")]
     let query = sqlx::query!(
         "UPDATE `user` SET 
@@ -370,7 +538,7 @@ pub async fn change_password(
         username.as_ref(),
     );
     let sql_result = query
-        .execute(pool)
+        .execute(&mut **transaction)
         .await
         .context("failed to change user's password in the database.")?;
     validate_one_row_affected(&sql_result)?;
@@ -378,20 +546,267 @@ pub async fn change_password(
     Ok(())
 }
 
-fn compute_password_hash(password: SecretString) -> Result<SecretString, anyhow::Error> {
-    let salt = SaltString::generate(&mut rand_core::OsRng);
-    let password_hash = argon2_settings()
-        .hash_password(password.expose_secret().as_bytes(), &salt)?
-        .to_string();
-    Ok(SecretString::from(password_hash))
+/// Saves the user's current password hash so that it can be checked by
+/// [`is_password_reused`] after it has been changed
+#[tracing::instrument(skip(executor))]
+async fn record_password_history(
+    username: &Username,
+    executor: impl Executor<'_, Database = Db>,
+) -> anyhow::Result<()> {
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!(
+        "INSERT INTO `password_history` (`UserName`, `PasswordHash`)
+        SELECT `UserName`, `password_hash` FROM `user` WHERE `UserName` = ?;",
+        username,
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let query = sqlx::query!(
+        "INSERT INTO password_history (user_name, password_hash)
+        SELECT user_name, password_hash FROM users WHERE user_name = $1;",
+        username.as_ref(),
+    );
+    let sql_result = query
+        .execute(executor)
+        .await
+        .context("failed to save password history")?;
+    validate_one_row_affected(&sql_result).context("failed to save password history")
+}
+
+/// Removes the user's previous passwords except the most recent ones that
+/// [`is_password_reused`] checks for `history_count`
+#[tracing::instrument(skip(transaction))]
+async fn prune_password_history(
+    username: &Username,
+    history_count: u16,
+    transaction: &mut Transaction<'_, Db>,
+) -> anyhow::Result<()> {
+    let kept_count = i64::from(history_count.saturating_sub(1));
+
+    // Looked up first as MySQL does not support LIMIT in a subquery
+    #[cfg(feature = "mysql")]
+    let newest_to_remove = sqlx::query!(
+        "SELECT `PasswordHistoryID` FROM `password_history` WHERE `UserName` = ? 
+        ORDER BY `PasswordHistoryID` DESC LIMIT 1 OFFSET ?;",
+        username,
+        kept_count
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("failed to get oldest password history to keep")?
+    .map(|row| row.PasswordHistoryID);
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    let newest_to_remove = sqlx::query!(
+        "SELECT password_history_id FROM password_history WHERE user_name = $1 
+        ORDER BY password_history_id DESC LIMIT 1 OFFSET $2;",
+        username.as_ref(),
+        kept_count
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("failed to get oldest password history to keep")?
+    .map(|row| row.password_history_id);
+
+    let Some(newest_to_remove) = newest_to_remove else {
+        return Ok(());
+    };
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!(
+        "DELETE FROM `password_history` WHERE `UserName` = ? AND `PasswordHistoryID` <= ?;",
+        username,
+        newest_to_remove
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    let query = sqlx::query!(
+        "DELETE FROM password_history WHERE user_name = $1 AND password_history_id <= $2;",
+        username.as_ref(),
+        newest_to_remove
+    );
+    query
+        .execute(&mut **transaction)
+        .await
+        .context("failed to remove old password history")?;
+    Ok(())
 }
 
-pub fn argon2_settings() -> Argon2<'static> {
-    Argon2::new(
-        Algorithm::Argon2id,
-        Version::V0x13,
-        Params::new(15000, 2, 1, None).expect("invalid parameters"),
+/// Checks the candidate against the current password and the most recent
+/// previous passwords such that `history_count` passwords are checked in total
+#[tracing::instrument(skip(password_candidate, pool))]
+pub async fn is_password_reused(
+    username: &Username,
+    password_candidate: SecretString,
+    history_count: u16,
+    pool: &DbPool,
+) -> anyhow::Result<bool> {
+    if history_count == 0 {
+        return Ok(false);
+    }
+    let previous_count = i64::from(history_count - 1);
+
+    #[cfg(feature = "mysql")]
+    let current_hash = sqlx::query!(
+        "SELECT `password_hash` FROM `user` WHERE `UserName` = ?;",
+        username
+    )
+    .fetch_one(pool)
+    .await
+    .context("failed to get current password hash")?
+    .password_hash;
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    let current_hash = sqlx::query!(
+        "SELECT password_hash FROM users WHERE user_name = $1;",
+        username.as_ref()
     )
+    .fetch_one(pool)
+    .await
+    .context("failed to get current password hash")?
+    .password_hash;
+
+    #[cfg(feature = "mysql")]
+    let previous_hashes = sqlx::query!(
+        "SELECT `PasswordHash` FROM `password_history` WHERE `UserName` = ? 
+        ORDER BY `PasswordHistoryID` DESC LIMIT ?;",
+        username,
+        previous_count
+    )
+    .fetch_all(pool)
+    .await
+    .context("failed to get password history")?
+    .into_iter()
+    .map(|row| row.PasswordHash);
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    let previous_hashes = sqlx::query!(
+        "SELECT password_hash FROM password_history WHERE user_name = $1 
+        ORDER BY password_history_id DESC LIMIT $2;",
+        username.as_ref(),
+        previous_count
+    )
+    .fetch_all(pool)
+    .await
+    .context("failed to get password history")?
+    .into_iter()
+    .map(|row| row.password_hash);
+
+    let hashes: Vec<SecretString> = std::iter::once(current_hash)
+        .chain(previous_hashes)
+        .map(SecretString::from)
+        .collect();
+    spawn_blocking_with_tracing(move || {
+        hashes
+            .into_iter()
+            .any(|hash| verify_password_hash(hash, password_candidate.clone()).is_ok())
+    })
+    .await
+    .context("failed to spawn blocking task")
+}
+
+/// Argon2 parameters used for new password hashes. Hashes stored with other
+/// parameters are upgraded on the user's next successful login
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+pub struct Argon2Settings {
+    /// Memory size in KiB
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for Argon2Settings {
+    fn default() -> Self {
+        Self {
+            memory_kib: 15000,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+impl Argon2Settings {
+    pub fn argon2(&self) -> anyhow::Result<Argon2<'static>> {
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, None)
+            .context("invalid argon2 parameters")?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+}
+
+/// Hashes passwords using the configured [`Argon2Settings`]
+#[derive(Debug, Clone)]
+pub struct PasswordHashing {
+    argon2: Argon2<'static>,
+    /// Checked against when the user does not exist so that the time taken
+    /// is the same as for a user that does exist
+    dummy_hash: SecretString,
+}
+
+impl PasswordHashing {
+    pub fn new(settings: Argon2Settings) -> anyhow::Result<Self> {
+        let mut result = Self {
+            argon2: settings.argon2()?,
+            dummy_hash: SecretString::from(""),
+        };
+        let dummy_password = SaltString::generate(&mut rand_core::OsRng);
+        result.dummy_hash = result
+            .compute_password_hash(SecretString::from(dummy_password.as_str()))
+            .context("failed to create dummy hash")?;
+        Ok(result)
+    }
+
+    pub fn compute_password_hash(&self, password: SecretString) -> anyhow::Result<SecretString> {
+        let salt = SaltString::generate(&mut rand_core::OsRng);
+        let password_hash = self
+            .argon2
+            .hash_password(password.expose_secret().as_bytes(), &salt)?
+            .to_string();
+        Ok(SecretString::from(password_hash))
+    }
+
+    /// Returns `true` if the hash was not created using the current settings
+    fn is_outdated(&self, password_hash: &SecretString) -> bool {
+        let Ok(password_hash) = PasswordHash::new(password_hash.expose_secret()) else {
+            return false; // Unable to replace it as we cannot have verified the password
+        };
+        let Ok(params) = Params::try_from(&password_hash) else {
+            return true;
+        };
+        let expected = self.argon2.params();
+        password_hash.algorithm != Algorithm::Argon2id.ident()
+            || password_hash.version != Some(Version::V0x13.into())
+            || params.m_cost() != expected.m_cost()
+            || params.t_cost() != expected.t_cost()
+            || params.p_cost() != expected.p_cost()
+    }
+}
+
+/// Replaces the stored hash with one using the current settings without
+/// affecting when the password was last changed
+#[tracing::instrument(skip(password, password_hashing, pool))]
+async fn upgrade_password_hash(
+    username: &str,
+    password: SecretString,
+    password_hashing: &PasswordHashing,
+    pool: &DbPool,
+) -> anyhow::Result<()> {
+    let password_hashing = password_hashing.clone();
+    let password_hash =
+        spawn_blocking_with_tracing(move || password_hashing.compute_password_hash(password))
+            .await?
+            .context("failed to hash password")?;
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!(
+        "UPDATE `user` SET `password_hash` = ? WHERE `UserName` = ?;",
+        password_hash.expose_secret(),
+        username,
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    let query = sqlx::query!(
+        "UPDATE users SET password_hash = $1 WHERE user_name = $2;",
+        password_hash.expose_secret(),
+        username,
+    );
+    let sql_result = query
+        .execute(pool)
+        .await
+        .context("failed to upgrade password hash")?;
+    validate_one_row_affected(&sql_result).context("failed to upgrade password hash")
 }
 
 #[cfg(test)]
@@ -416,4 +831,39 @@ mod tests {
             "no changes have been made this should still register as a default user"
         );
     }
+
+    #[test]
+    fn hashes_with_other_parameters_are_outdated() {
+        let old = PasswordHashing::new(Argon2Settings::default()).unwrap();
+        let new = PasswordHashing::new(Argon2Settings {
+            memory_kib: 19456,
+            ..Default::default()
+        })
+        .unwrap();
+        let hash = old
+            .compute_password_hash(SecretString::from("password"))
+            .unwrap();
+        assert!(!old.is_outdated(&hash));
+        assert!(new.is_outdated(&hash));
+        assert!(!new.is_outdated(&new.dummy_hash));
+    }
+
+    #[test]
+    fn password_expiry_days_remaining() {
+        let pass_change_date = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let today = NaiveDate::from_ymd_opt(2025, 3, 2).unwrap(); // 60 days later
+        let password_expiry = PasswordExpiry {
+            max_age_days: Some(90),
+            warning_days: 14,
+        };
+        assert_eq!(
+            password_expiry.days_remaining(pass_change_date, today),
+            Some(30)
+        );
+        let disabled = PasswordExpiry {
+            max_age_days: None,
+            ..password_expiry
+        };
+        assert_eq!(disabled.days_remaining(pass_change_date, today), None);
+    }
 }
@@ -0,0 +1,279 @@
+//! Single use tokens that let users set a new password without logging in
+//!
+//! Tokens are sent to the email address stored for the user using a
+//! [`Notifier`] and only a hash is stored. Requesting a new token replaces any
+//! previous one and tokens stop working after
+//! [`PasswordResetSettings::token_lifetime`].
+
+#[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+use crate::db_utils::db_timestamp;
+use crate::{
+    db_utils::validate_one_row_affected,
+    notifier::{Notification, Notifier},
+};
+use anyhow::Context;
+use argon2::password_hash::rand_core::{OsRng, RngCore as _};
+use secrecy::{ExposeSecret as _, SecretString};
+use sqlx::Executor;
+use tracing::info;
+use wykies_shared::{
+    db_types::{Db, DbPool},
+    telemetry::spawn_blocking_with_tracing,
+    uac::{EmailAddress, PasswordResetTokenError, Username},
+};
+use wykies_time::{Seconds, Timestamp};
+
+use super::{PasswordHashing, password::verify_password_hash};
+
+const TOKEN_LENGTH: usize = 24;
+/// Excludes characters that are easily confused with each other
+const TOKEN_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+/// Precedes the token in the body of the notification sent to the user
+pub const TOKEN_PREFIX_IN_MESSAGE: &str = "Reset code: ";
+
+#[derive(Debug, Clone, Copy)]
+pub struct PasswordResetSettings {
+    pub token_lifetime: Seconds,
+}
+
+/// Generates a new token and sends it to the user. Does nothing if the user
+/// does not exist, is disabled or has no email address so that callers cannot
+/// tell which usernames exist
+#[tracing::instrument(skip(pool, password_hashing, notifier))]
+pub async fn request_password_reset(
+    username: &Username,
+    pool: &DbPool,
+    settings: &PasswordResetSettings,
+    password_hashing: &PasswordHashing,
+    notifier: &dyn Notifier,
+) -> anyhow::Result<()> {
+    let Some(email) = get_email_if_enabled(username, pool).await? else {
+        info!("Password reset not sent for {username:?}, no enabled user with an email");
+        return Ok(());
+    };
+
+    let token = generate_token();
+    let token_hash = {
+        let token = SecretString::from(token.clone());
+        let password_hashing = password_hashing.clone();
+        spawn_blocking_with_tracing(move || password_hashing.compute_password_hash(token))
+            .await
+            .context("failed to spawn blocking task")??
+    };
+    delete_password_reset_tokens(username, pool).await?;
+    store_token(
+        username,
+        token_hash,
+        Timestamp::now() + settings.token_lifetime,
+        pool,
+    )
+    .await?;
+
+    let minutes = usize::from(settings.token_lifetime) / 60;
+    notifier
+        .send(Notification {
+            username: username.clone(),
+            to: email,
+            subject: "Password reset".to_string(),
+            body: format!(
+                "A password reset was requested for your account '{username}'.\n\n\
+                {TOKEN_PREFIX_IN_MESSAGE}{token}\n\n\
+                The code expires in {minutes} minutes. If you did not request a reset you can \
+                ignore this message."
+            ),
+        })
+        .await
+        .context("failed to send password reset notification")?;
+    info!("Password reset token sent for {username:?}");
+    Ok(())
+}
+
+/// Returns the ID of the unexpired token for the user that matches `token` or
+/// an error if there is none. The token is not consumed (See
+/// [`consume_password_reset_token`])
+#[tracing::instrument(skip(token, pool))]
+pub async fn verify_password_reset_token(
+    username: &Username,
+    token: SecretString,
+    pool: &DbPool,
+) -> Result<i32, PasswordResetTokenError> {
+    let now = Timestamp::now();
+    #[cfg(feature = "mysql")]
+    let token_hashes: Vec<(i32, SecretString)> = sqlx::query!(
+        "SELECT `PasswordResetTokenID`, `TokenHash` FROM `password_reset_tokens` 
+        WHERE `UserName` = ? AND `Expires` > ?;",
+        username,
+        now
+    )
+    .fetch_all(pool)
+    .await
+    .context("failed to get password reset tokens")?
+    .into_iter()
+    .map(|row| (row.PasswordResetTokenID, SecretString::from(row.TokenHash)))
+    .collect();
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let token_hashes: Vec<(i32, SecretString)> = {
+        let now = db_timestamp(now)?;
+        sqlx::query!(
+            "SELECT password_reset_token_id, token_hash FROM password_reset_tokens 
+            WHERE user_name = $1 AND expires > $2;",
+            username.as_ref(),
+            now
+        )
+        .fetch_all(pool)
+        .await
+        .context("failed to get password reset tokens")?
+        .into_iter()
+        .map(|row| {
+            (
+                row.password_reset_token_id,
+                SecretString::from(row.token_hash),
+            )
+        })
+        .collect()
+    };
+
+    let token = SecretString::from(token.expose_secret().trim().to_lowercase());
+    let token_id = spawn_blocking_with_tracing(move || {
+        token_hashes
+            .into_iter()
+            .find(|(_, token_hash)| verify_password_hash(token_hash.clone(), token.clone()).is_ok())
+            .map(|(token_id, _)| token_id)
+    })
+    .await
+    .context("failed to spawn blocking task")?;
+    token_id.ok_or(PasswordResetTokenError::InvalidToken)
+}
+
+/// Removes the token found by [`verify_password_reset_token`] so it cannot be
+/// used again. Fails the same as an invalid token if another request removed it
+/// first
+#[tracing::instrument(skip(executor))]
+pub async fn consume_password_reset_token(
+    token_id: i32,
+    executor: impl Executor<'_, Database = Db>,
+) -> Result<(), PasswordResetTokenError> {
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!(
+        "DELETE FROM `password_reset_tokens` WHERE `PasswordResetTokenID` = ?;",
+        token_id
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    let query = sqlx::query!(
+        "DELETE FROM password_reset_tokens WHERE password_reset_token_id = $1;",
+        token_id
+    );
+    let sql_result = query
+        .execute(executor)
+        .await
+        .context("failed to remove used password reset token")?;
+    if sql_result.rows_affected() == 0 {
+        return Err(PasswordResetTokenError::InvalidToken);
+    }
+    Ok(())
+}
+
+/// Removes all tokens for the user so they cannot be used again
+#[tracing::instrument(skip(executor))]
+pub async fn delete_password_reset_tokens(
+    username: &Username,
+    executor: impl Executor<'_, Database = Db>,
+) -> anyhow::Result<()> {
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!(
+        "DELETE FROM `password_reset_tokens` WHERE `UserName` = ?;",
+        username
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let query = sqlx::query!(
+        "DELETE FROM password_reset_tokens WHERE user_name = $1;",
+        username.as_ref()
+    );
+    query
+        .execute(executor)
+        .await
+        .context("failed to delete password reset tokens")?;
+    Ok(())
+}
+
+fn generate_token() -> String {
+    (0..TOKEN_LENGTH)
+        .map(|_| {
+            let index = OsRng.next_u32() as usize % TOKEN_ALPHABET.len();
+            TOKEN_ALPHABET[index] as char
+        })
+        .collect()
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_email_if_enabled(
+    username: &Username,
+    pool: &DbPool,
+) -> anyhow::Result<Option<EmailAddress>> {
+    #[cfg(feature = "mysql")]
+    let email = sqlx::query!(
+        "SELECT `Email` FROM `user` WHERE `UserName` = ? AND `Enabled` = 1;",
+        username
+    )
+    .fetch_optional(pool)
+    .await
+    .context("failed to get email of user")?
+    .and_then(|row| row.Email);
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let email = sqlx::query!(
+        "SELECT email FROM users WHERE user_name = $1 AND is_enabled;",
+        username.as_ref()
+    )
+    .fetch_optional(pool)
+    .await
+    .context("failed to get email of user")?
+    .and_then(|row| row.email);
+    Ok(EmailAddress::try_from_opt(email)?)
+}
+
+#[tracing::instrument(skip(token_hash, pool))]
+async fn store_token(
+    username: &Username,
+    token_hash: SecretString,
+    expires: Timestamp,
+    pool: &DbPool,
+) -> anyhow::Result<()> {
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!(
+        "INSERT INTO `password_reset_tokens` (`UserName`, `TokenHash`, `Expires`) VALUES (?, ?, ?);",
+        username,
+        token_hash.expose_secret(),
+        expires
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let query = {
+        let expires = db_timestamp(expires)?;
+        sqlx::query!(
+            "INSERT INTO password_reset_tokens (user_name, token_hash, expires) VALUES ($1, $2, $3);",
+            username.as_ref(),
+            token_hash.expose_secret(),
+            expires
+        )
+    };
+    let sql_result = query
+        .execute(pool)
+        .await
+        .context("failed to store password reset token")?;
+    validate_one_row_affected(&sql_result).context("failed to store password reset token")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_tokens_only_use_alphabet() {
+        let token = generate_token();
+        assert_eq!(token.len(), TOKEN_LENGTH);
+        assert!(token.bytes().all(|c| TOKEN_ALPHABET.contains(&c)));
+    }
+}
@@ -0,0 +1,627 @@
+//! Resolves the permissions of users from the roles assigned to them
+//!
+//! Users can be assigned several roles and each role can inherit from parent
+//! roles. The effective permissions of a user are the union of the permissions
+//! of every role reachable from the roles assigned to them.
+
+use anyhow::Context;
+use sqlx::{Acquire, Executor, Transaction};
+use std::collections::{BTreeMap, BTreeSet};
+use wykies_shared::{
+    db_types::{Db, DbPool},
+    uac::{Permissions, RoleError, RoleId, UserPermissions, Username},
+};
+
+#[derive(Debug, Default)]
+pub struct RoleGraph {
+    roles: BTreeMap<RoleId, RoleNode>,
+}
+
+#[derive(Debug, Default)]
+struct RoleNode {
+    permissions: Permissions,
+    parents: BTreeSet<RoleId>,
+}
+
+impl RoleGraph {
+    #[tracing::instrument(skip(db))]
+    pub async fn load(db: impl Acquire<'_, Database = Db>) -> anyhow::Result<Self> {
+        let mut connection = db.acquire().await.context("failed to acquire connection")?;
+        let mut result = Self::default();
+
+        #[cfg(feature = "mysql")]
+        let roles: Vec<(i32, String)> = sqlx::query!("SELECT `RoleID`, `Permissions` FROM `roles`")
+            .fetch_all(&mut *connection)
+            .await
+            .context("failed to get roles")?
+            .into_iter()
+            .map(|x| (x.RoleID, x.Permissions))
+            .collect();
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        let roles: Vec<(i32, String)> = sqlx::query!("SELECT role_id, permissions FROM roles")
+            .fetch_all(&mut *connection)
+            .await
+            .context("failed to get roles")?
+            .into_iter()
+            .map(|x| (x.role_id, x.permissions))
+            .collect();
+        for (id, permissions) in roles {
+            result.insert_role(id.try_into()?, permissions.try_into()?);
+        }
+
+        #[cfg(feature = "mysql")]
+        let links: Vec<(i32, i32)> =
+            sqlx::query!("SELECT `RoleID`, `ParentRoleID` FROM `role_parents`")
+                .fetch_all(&mut *connection)
+                .await
+                .context("failed to get role parents")?
+                .into_iter()
+                .map(|x| (x.RoleID, x.ParentRoleID))
+                .collect();
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        let links: Vec<(i32, i32)> =
+            sqlx::query!("SELECT role_id, parent_role_id FROM role_parents")
+                .fetch_all(&mut *connection)
+                .await
+                .context("failed to get role parents")?
+                .into_iter()
+                .map(|x| (x.role_id, x.parent_role_id))
+                .collect();
+        for (id, parent_id) in links {
+            result.insert_parent(id.try_into()?, parent_id.try_into()?);
+        }
+
+        Ok(result)
+    }
+
+    fn insert_role(&mut self, id: RoleId, permissions: Permissions) {
+        self.roles.entry(id).or_default().permissions = permissions;
+    }
+
+    fn insert_parent(&mut self, id: RoleId, parent_id: RoleId) {
+        self.roles.entry(id).or_default().parents.insert(parent_id);
+    }
+
+    /// Union of the permissions granted directly by `roles` (ignores parents)
+    pub fn direct_permissions<'a>(
+        &self,
+        roles: impl IntoIterator<Item = &'a RoleId>,
+    ) -> Permissions {
+        self.union_of(roles)
+    }
+
+    /// Union of the permissions of `roles` and all the roles they inherit from
+    pub fn effective_permissions<'a>(
+        &self,
+        roles: impl IntoIterator<Item = &'a RoleId>,
+    ) -> Permissions {
+        self.union_of(&self.with_ancestors(roles))
+    }
+
+    /// Ensures all `parents` exist and that none of them already inherit from
+    /// `role_id` (directly or indirectly) as that would create a cycle
+    pub fn check_parents(
+        &self,
+        role_id: RoleId,
+        parents: &BTreeSet<RoleId>,
+    ) -> Result<(), RoleError> {
+        if !self.roles.contains_key(&role_id) {
+            return Err(RoleError::NotFound(role_id));
+        }
+        for &parent_id in parents {
+            if !self.roles.contains_key(&parent_id) {
+                return Err(RoleError::ParentNotFound(parent_id));
+            }
+            if self.with_ancestors([&parent_id]).contains(&role_id) {
+                return Err(RoleError::Cycle { role_id, parent_id });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the roles that inherit directly from `role_id`
+    pub fn children_of(&self, role_id: RoleId) -> BTreeSet<RoleId> {
+        self.roles
+            .iter()
+            .filter(|(_, node)| node.parents.contains(&role_id))
+            .map(|(&id, _)| id)
+            .collect()
+    }
+
+    /// Returns `roles` and every role reachable by following parents. Stops at
+    /// roles already visited so cycles in stored data cannot loop forever
+    fn with_ancestors<'a>(&self, roles: impl IntoIterator<Item = &'a RoleId>) -> BTreeSet<RoleId> {
+        let mut result = BTreeSet::new();
+        let mut to_visit: Vec<RoleId> = roles.into_iter().copied().collect();
+        while let Some(id) = to_visit.pop() {
+            if !result.insert(id) {
+                continue;
+            }
+            if let Some(node) = self.roles.get(&id) {
+                to_visit.extend(node.parents.iter().copied());
+            }
+        }
+        result
+    }
+
+    fn union_of<'a>(&self, roles: impl IntoIterator<Item = &'a RoleId>) -> Permissions {
+        let mut result = Permissions::default();
+        for node in roles.into_iter().filter_map(|id| self.roles.get(id)) {
+            result.0.extend(node.permissions.0.iter().cloned());
+        }
+        result
+    }
+}
+
+/// Returns the permissions the user gets from the roles assigned to them
+#[tracing::instrument(ret, skip(pool))]
+pub async fn get_user_permissions(
+    username: &str,
+    pool: &DbPool,
+) -> anyhow::Result<UserPermissions> {
+    let assigned_roles = get_assigned_roles(username, pool).await?;
+    let graph = RoleGraph::load(pool).await?;
+    Ok(UserPermissions {
+        direct: graph.direct_permissions(&assigned_roles),
+        effective: graph.effective_permissions(&assigned_roles),
+    })
+}
+
+#[tracing::instrument(ret, skip(executor))]
+pub async fn get_assigned_roles(
+    username: &str,
+    executor: impl Executor<'_, Database = Db>,
+) -> anyhow::Result<BTreeSet<RoleId>> {
+    #[cfg(feature = "mysql")]
+    let role_ids: Vec<i32> = sqlx::query!(
+        "SELECT `RoleID` FROM `user_roles` WHERE `UserName` = ?;",
+        username
+    )
+    .fetch_all(executor)
+    .await
+    .context("failed to get roles assigned to user")?
+    .into_iter()
+    .map(|x| x.RoleID)
+    .collect();
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    let role_ids: Vec<i32> = sqlx::query!(
+        "SELECT role_id FROM user_roles WHERE user_name = $1;",
+        username
+    )
+    .fetch_all(executor)
+    .await
+    .context("failed to get roles assigned to user")?
+    .into_iter()
+    .map(|x| x.role_id)
+    .collect();
+    role_ids
+        .into_iter()
+        .map(|x| Ok(RoleId::try_from(x)?))
+        .collect()
+}
+
+/// Returns the roles assigned to each user that has at least one role
+#[tracing::instrument(skip(pool))]
+pub async fn get_all_assigned_roles(
+    pool: &DbPool,
+) -> anyhow::Result<BTreeMap<String, BTreeSet<RoleId>>> {
+    #[cfg(feature = "mysql")]
+    let rows: Vec<(String, i32)> = sqlx::query!("SELECT `UserName`, `RoleID` FROM `user_roles`;")
+        .fetch_all(pool)
+        .await
+        .context("failed to get roles assigned to users")?
+        .into_iter()
+        .map(|x| (x.UserName, x.RoleID))
+        .collect();
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    let rows: Vec<(String, i32)> = sqlx::query!("SELECT user_name, role_id FROM user_roles;")
+        .fetch_all(pool)
+        .await
+        .context("failed to get roles assigned to users")?
+        .into_iter()
+        .map(|x| (x.user_name, x.role_id))
+        .collect();
+    let mut result: BTreeMap<String, BTreeSet<RoleId>> = BTreeMap::new();
+    for (username, role_id) in rows {
+        result
+            .entry(username)
+            .or_default()
+            .insert(role_id.try_into()?);
+    }
+    Ok(result)
+}
+
+/// Adds the role to the user. Does nothing if it is already assigned
+#[tracing::instrument(skip(executor))]
+pub async fn add_assigned_role(
+    username: &Username,
+    role_id: RoleId,
+    executor: impl Executor<'_, Database = Db>,
+) -> anyhow::Result<()> {
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!(
+        "INSERT IGNORE INTO `user_roles` (`UserName`, `RoleID`) VALUES (?, ?);",
+        username,
+        role_id
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let query = {
+        let role_id: i32 = role_id.try_into()?;
+        sqlx::query!(
+            "INSERT INTO user_roles (user_name, role_id) VALUES ($1, $2) ON CONFLICT DO NOTHING;",
+            username.as_ref(),
+            role_id
+        )
+    };
+    query
+        .execute(executor)
+        .await
+        .context("failed to assign role to user")?;
+    Ok(())
+}
+
+/// Replaces all the roles assigned to the user. Nothing is saved until
+/// `transaction` is committed
+#[tracing::instrument(skip(transaction))]
+pub async fn set_assigned_roles(
+    username: &Username,
+    roles: &BTreeSet<RoleId>,
+    transaction: &mut Transaction<'_, Db>,
+) -> anyhow::Result<()> {
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!("DELETE FROM `user_roles` WHERE `UserName` = ?;", username);
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let query = sqlx::query!(
+        "DELETE FROM user_roles WHERE user_name = $1;",
+        username.as_ref()
+    );
+    query
+        .execute(&mut **transaction)
+        .await
+        .context("failed to remove roles assigned to user")?;
+
+    for &role_id in roles {
+        #[cfg(feature = "mysql")]
+        let query = sqlx::query!(
+            "INSERT INTO `user_roles` (`UserName`, `RoleID`) VALUES (?, ?);",
+            username,
+            role_id
+        );
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        // TODO 5: Check why encode trait impl doesn't make converting not necessary
+        let query = {
+            let role_id: i32 = role_id.try_into()?;
+            sqlx::query!(
+                "INSERT INTO user_roles (user_name, role_id) VALUES ($1, $2);",
+                username.as_ref(),
+                role_id
+            )
+        };
+        query
+            .execute(&mut **transaction)
+            .await
+            .context("failed to assign role to user")?;
+    }
+    Ok(())
+}
+
+#[tracing::instrument(ret, skip(executor))]
+pub async fn get_role_parents(
+    role_id: RoleId,
+    executor: impl Executor<'_, Database = Db>,
+) -> anyhow::Result<BTreeSet<RoleId>> {
+    #[cfg(feature = "mysql")]
+    let parent_ids: Vec<i32> = sqlx::query!(
+        "SELECT `ParentRoleID` FROM `role_parents` WHERE `RoleID` = ?;",
+        role_id
+    )
+    .fetch_all(executor)
+    .await
+    .context("failed to get role parents")?
+    .into_iter()
+    .map(|x| x.ParentRoleID)
+    .collect();
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let parent_ids: Vec<i32> = {
+        let role_id: i32 = role_id.try_into()?;
+        sqlx::query!(
+            "SELECT parent_role_id FROM role_parents WHERE role_id = $1;",
+            role_id
+        )
+        .fetch_all(executor)
+        .await
+        .context("failed to get role parents")?
+        .into_iter()
+        .map(|x| x.parent_role_id)
+        .collect()
+    };
+    parent_ids
+        .into_iter()
+        .map(|x| Ok(RoleId::try_from(x)?))
+        .collect()
+}
+
+/// Returns the parents of each role that has at least one parent
+#[tracing::instrument(skip(pool))]
+pub async fn get_all_role_parents(
+    pool: &DbPool,
+) -> anyhow::Result<BTreeMap<RoleId, BTreeSet<RoleId>>> {
+    #[cfg(feature = "mysql")]
+    let rows: Vec<(i32, i32)> =
+        sqlx::query!("SELECT `RoleID`, `ParentRoleID` FROM `role_parents`;")
+            .fetch_all(pool)
+            .await
+            .context("failed to get role parents")?
+            .into_iter()
+            .map(|x| (x.RoleID, x.ParentRoleID))
+            .collect();
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    let rows: Vec<(i32, i32)> = sqlx::query!("SELECT role_id, parent_role_id FROM role_parents;")
+        .fetch_all(pool)
+        .await
+        .context("failed to get role parents")?
+        .into_iter()
+        .map(|x| (x.role_id, x.parent_role_id))
+        .collect();
+    let mut result: BTreeMap<RoleId, BTreeSet<RoleId>> = BTreeMap::new();
+    for (role_id, parent_id) in rows {
+        result
+            .entry(role_id.try_into()?)
+            .or_default()
+            .insert(parent_id.try_into()?);
+    }
+    Ok(result)
+}
+
+/// Returns the usernames of the users the role is assigned to
+#[tracing::instrument(ret, skip(executor))]
+pub async fn get_role_users(
+    role_id: RoleId,
+    executor: impl Executor<'_, Database = Db>,
+) -> anyhow::Result<Vec<String>> {
+    #[cfg(feature = "mysql")]
+    let result = sqlx::query!(
+        "SELECT `UserName` FROM `user_roles` WHERE `RoleID` = ?;",
+        role_id
+    )
+    .fetch_all(executor)
+    .await
+    .context("failed to get users assigned to role")?
+    .into_iter()
+    .map(|x| x.UserName)
+    .collect();
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let result = {
+        let role_id: i32 = role_id.try_into()?;
+        sqlx::query!(
+            "SELECT user_name FROM user_roles WHERE role_id = $1;",
+            role_id
+        )
+        .fetch_all(executor)
+        .await
+        .context("failed to get users assigned to role")?
+        .into_iter()
+        .map(|x| x.user_name)
+        .collect()
+    };
+    Ok(result)
+}
+
+/// Deletes the role along with the links to the roles it inherits from.
+/// Refused if the role is still assigned to a user or inherited by another
+/// role. Nothing is saved until `transaction` is committed
+#[tracing::instrument(skip(transaction))]
+pub async fn delete_role(
+    role_id: RoleId,
+    transaction: &mut Transaction<'_, Db>,
+) -> Result<(), RoleError> {
+    let usernames = get_role_users(role_id, &mut **transaction).await?;
+    if !usernames.is_empty() {
+        return Err(RoleError::AssignedToUsers { role_id, usernames });
+    }
+    let child_roles = RoleGraph::load(&mut **transaction)
+        .await?
+        .children_of(role_id);
+    if !child_roles.is_empty() {
+        return Err(RoleError::InheritedBy {
+            role_id,
+            child_roles: child_roles.into_iter().collect(),
+        });
+    }
+
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!("DELETE FROM `role_parents` WHERE `RoleID` = ?;", role_id);
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let query = {
+        let role_id: i32 = role_id.try_into().context("failed to convert role id")?;
+        sqlx::query!("DELETE FROM role_parents WHERE role_id = $1;", role_id)
+    };
+    query
+        .execute(&mut **transaction)
+        .await
+        .context("failed to remove role parents")?;
+
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!("DELETE FROM `roles` WHERE `RoleID` = ?;", role_id);
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let query = {
+        let role_id: i32 = role_id.try_into().context("failed to convert role id")?;
+        sqlx::query!("DELETE FROM roles WHERE role_id = $1;", role_id)
+    };
+    let sql_result = query
+        .execute(&mut **transaction)
+        .await
+        .context("failed to delete role")?;
+    if sql_result.rows_affected() == 0 {
+        return Err(RoleError::NotFound(role_id));
+    }
+    Ok(())
+}
+
+/// Replaces the parents of the role after checking that no cycle would be
+/// created (See [`RoleGraph::check_parents`]). Nothing is saved until
+/// `transaction` is committed, so it can be combined with other changes to the
+/// role
+#[tracing::instrument(skip(transaction))]
+pub async fn set_role_parents(
+    role_id: RoleId,
+    parents: &BTreeSet<RoleId>,
+    transaction: &mut Transaction<'_, Db>,
+) -> Result<(), RoleError> {
+    RoleGraph::load(&mut **transaction)
+        .await?
+        .check_parents(role_id, parents)?;
+
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!("DELETE FROM `role_parents` WHERE `RoleID` = ?;", role_id);
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let query = {
+        let role_id: i32 = role_id.try_into().context("failed to convert role id")?;
+        sqlx::query!("DELETE FROM role_parents WHERE role_id = $1;", role_id)
+    };
+    query
+        .execute(&mut **transaction)
+        .await
+        .context("failed to remove role parents")?;
+
+    for &parent_id in parents {
+        #[cfg(feature = "mysql")]
+        let query = sqlx::query!(
+            "INSERT INTO `role_parents` (`RoleID`, `ParentRoleID`) VALUES (?, ?);",
+            role_id,
+            parent_id
+        );
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        // TODO 5: Check why encode trait impl doesn't make converting not necessary
+        let query = {
+            let role_id: i32 = role_id.try_into().context("failed to convert role id")?;
+            let parent_id: i32 = parent_id.try_into().context("failed to convert role id")?;
+            sqlx::query!(
+                "INSERT INTO role_parents (role_id, parent_role_id) VALUES ($1, $2);",
+                role_id,
+                parent_id
+            )
+        };
+        query
+            .execute(&mut **transaction)
+            .await
+            .context("failed to store role parent")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use wykies_shared::uac::Permission;
+
+    /// Builds 1 <- 2 <- 3 (3 inherits from 2 which inherits from 1) and 4 on
+    /// its own. Each role grants one permission
+    fn graph() -> RoleGraph {
+        let mut result = RoleGraph::default();
+        let perms = [
+            Permission::ManUAC,
+            Permission::ManRoles,
+            Permission::ViewLog,
+            Permission::ManBranches,
+        ];
+        for (id, perm) in (1..=4u64).zip(perms) {
+            result.insert_role(id.into(), Permissions([perm].into()));
+        }
+        result.insert_parent(2.into(), 1.into());
+        result.insert_parent(3.into(), 2.into());
+        result
+    }
+
+    fn ids(values: &[u64]) -> BTreeSet<RoleId> {
+        values.iter().map(|&x| x.into()).collect()
+    }
+
+    #[test]
+    fn effective_includes_inherited_permissions() {
+        // Arrange
+        let graph = graph();
+        let assigned = ids(&[3]);
+
+        // Act
+        let direct = graph.direct_permissions(&assigned);
+        let effective = graph.effective_permissions(&assigned);
+
+        // Assert
+        assert_eq!(direct, Permissions([Permission::ViewLog].into()));
+        assert_eq!(
+            effective,
+            Permissions(
+                [
+                    Permission::ManUAC,
+                    Permission::ManRoles,
+                    Permission::ViewLog
+                ]
+                .into()
+            )
+        );
+    }
+
+    #[test]
+    fn effective_is_union_of_assigned_roles() {
+        let graph = graph();
+        let actual = graph.effective_permissions(&ids(&[1, 4]));
+        assert_eq!(
+            actual,
+            Permissions([Permission::ManUAC, Permission::ManBranches].into())
+        );
+    }
+
+    #[test]
+    fn stored_cycle_does_not_loop_forever() {
+        let mut graph = graph();
+        graph.insert_parent(1.into(), 3.into());
+        let actual = graph.effective_permissions(&ids(&[1]));
+        assert_eq!(actual.0.len(), 3);
+    }
+
+    #[rstest]
+    #[case::no_parents(4, &[], None)]
+    #[case::unrelated(4, &[3], None)]
+    #[case::self_parent(1, &[1], Some("cycle"))]
+    #[case::direct_cycle(1, &[2], Some("cycle"))]
+    #[case::indirect_cycle(1, &[3], Some("cycle"))]
+    #[case::unknown_parent(1, &[9], Some("parent not found"))]
+    #[case::unknown_role(9, &[1], Some("not found"))]
+    fn check_parents(
+        #[case] role_id: u64,
+        #[case] parents: &[u64],
+        #[case] expected: Option<&str>,
+    ) {
+        // Act
+        let actual = graph().check_parents(role_id.into(), &ids(parents));
+
+        // Assert
+        let actual = actual.err().map(|e| match e {
+            RoleError::NotFound(_) => "not found",
+            RoleError::ParentNotFound(_) => "parent not found",
+            RoleError::Cycle { .. } => "cycle",
+            RoleError::AssignedToUsers { .. } | RoleError::InheritedBy { .. } => "in use",
+            RoleError::UnexpectedError(_) => "unexpected",
+        });
+        assert_eq!(actual, expected);
+    }
+
+    #[rstest]
+    #[case::has_child(1, &[2])]
+    #[case::grandchild_excluded(2, &[3])]
+    #[case::leaf(3, &[])]
+    #[case::unrelated(4, &[])]
+    fn children_of(#[case] role_id: u64, #[case] expected: &[u64]) {
+        let actual = graph().children_of(role_id.into());
+        assert_eq!(actual, ids(expected));
+    }
+}
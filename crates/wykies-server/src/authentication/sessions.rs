@@ -0,0 +1,245 @@
+use crate::db_utils::validate_one_row_affected;
+use anyhow::Context;
+use sqlx::Executor;
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+use wykies_shared::{
+    const_config::server::SESSION_ACTIVITY_UPDATE_INTERVAL,
+    db_types::{Db, DbPool},
+    uac::{SessionExpiredError, Username},
+};
+use wykies_time::{Seconds, Timestamp};
+
+#[derive(Debug, Clone, Copy)]
+pub struct SessionLifetimes {
+    pub max_lifetime: Seconds,
+    pub idle_timeout: Seconds,
+}
+
+impl SessionLifetimes {
+    /// Returns an error if the session should be treated as expired at `now`
+    pub fn check(
+        &self,
+        login_timestamp: Timestamp,
+        last_activity: Timestamp,
+        now: Timestamp,
+    ) -> Result<(), SessionExpiredError> {
+        if now
+            .seconds_since(login_timestamp)
+            .is_some_and(|x| x > self.max_lifetime)
+        {
+            return Err(SessionExpiredError::MaxLifetimeReached);
+        }
+        if now
+            .seconds_since(last_activity)
+            .is_some_and(|x| x > self.idle_timeout)
+        {
+            return Err(SessionExpiredError::IdleTimeout);
+        }
+        Ok(())
+    }
+
+    /// How old the last activity stored in a session needs to be before it is
+    /// updated
+    ///
+    /// Because updates are skipped a session can be treated as idle up to this
+    /// long before the user actually stopped making requests. It is capped at
+    /// half the idle timeout so that short idle timeouts still allow at least
+    /// half their length of real inactivity
+    pub fn activity_update_interval(&self) -> Seconds {
+        let half_idle_timeout = Duration::from(self.idle_timeout) / 2;
+        SESSION_ACTIVITY_UPDATE_INTERVAL.min(half_idle_timeout.into())
+    }
+}
+
+/// Controls if a mismatch between the host a session was created from and the
+/// host of the current request causes the request to be rejected
+#[derive(Debug, Clone, Copy)]
+pub struct SessionHostBinding {
+    pub enforce: bool,
+}
+
+/// Recently read session generations so the database does not need to be
+/// queried on every request
+///
+/// Invalidations made through this server remove the entry as soon as they are
+/// committed. Changes made directly in the database or by another server are
+/// only noticed once the entry is older than `max_age`. Entries older than `max_age` are removed
+/// at most once every `max_age` so users that stop making requests do not stay
+/// in the cache
+#[derive(Debug)]
+pub struct SessionGenerationCache {
+    max_age: Seconds,
+    inner: Mutex<CacheEntries>,
+}
+
+#[derive(Debug, Default)]
+struct CacheEntries {
+    entries: HashMap<Username, CachedGeneration>,
+    last_pruned: Timestamp,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CachedGeneration {
+    generation: Option<i32>,
+    read_at: Timestamp,
+}
+
+impl SessionGenerationCache {
+    pub fn new(max_age: Seconds) -> Self {
+        Self {
+            max_age,
+            inner: Default::default(),
+        }
+    }
+
+    /// Same as [`get_session_generation`] but uses the cached value if it is
+    /// recent enough
+    #[tracing::instrument(skip(self, pool))]
+    pub async fn get(&self, username: &Username, pool: &DbPool) -> anyhow::Result<Option<i32>> {
+        let now = Timestamp::now();
+        if let Some(cached) = self.lock().entries.get(username)
+            && now
+                .seconds_since(cached.read_at)
+                .is_some_and(|age| age < self.max_age)
+        {
+            return Ok(cached.generation);
+        }
+        let generation = get_session_generation(username, pool).await?;
+        let mut guard = self.lock();
+        if now
+            .seconds_since(guard.last_pruned)
+            .is_none_or(|x| x >= self.max_age)
+        {
+            guard.entries.retain(|_, cached| {
+                now.seconds_since(cached.read_at)
+                    .is_none_or(|age| age < self.max_age)
+            });
+            guard.last_pruned = now;
+        }
+        guard.entries.insert(
+            username.clone(),
+            CachedGeneration {
+                generation,
+                read_at: now,
+            },
+        );
+        Ok(generation)
+    }
+
+    pub fn remove(&self, username: &Username) {
+        self.lock().entries.remove(username);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheEntries> {
+        self.inner.lock().expect("mutex poisoned")
+    }
+}
+
+/// Returns the current session generation for the user or `None` if the user
+/// is not found
+///
+/// Sessions store the generation at login and are only valid while it matches
+/// the one in the database
+#[tracing::instrument(skip(pool))]
+pub async fn get_session_generation(
+    username: &Username,
+    pool: &DbPool,
+) -> anyhow::Result<Option<i32>> {
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!(
+        "SELECT `SessionGeneration` FROM `user` WHERE `UserName` = ?;",
+        username
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    let query = sqlx::query!(
+        "SELECT session_generation FROM users WHERE user_name = $1;",
+        username.as_ref()
+    );
+    let record = query
+        .fetch_optional(pool)
+        .await
+        .context("failed to get session generation")?;
+    #[cfg(feature = "mysql")]
+    return Ok(record.map(|x| x.SessionGeneration));
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    Ok(record.map(|x| x.session_generation))
+}
+
+/// Invalidates all existing sessions for the user
+///
+/// The user must be removed from the [`SessionGenerationCache`] after the
+/// change is committed, otherwise a request made before the commit could cache
+/// the old generation again
+#[tracing::instrument(skip(executor))]
+pub async fn invalidate_user_sessions(
+    username: &Username,
+    executor: impl Executor<'_, Database = Db>,
+) -> anyhow::Result<()> {
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!(
+        "UPDATE `user` SET `SessionGeneration` = `SessionGeneration` + 1 WHERE `UserName` = ?;",
+        username
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    let query = sqlx::query!(
+        "UPDATE users SET session_generation = session_generation + 1 WHERE user_name = $1;",
+        username.as_ref()
+    );
+    let sql_result = query
+        .execute(executor)
+        .await
+        .context("failed to increment session generation")?;
+    validate_one_row_affected(&sql_result).context("failed to invalidate user sessions")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const LIFETIMES: SessionLifetimes = SessionLifetimes {
+        max_lifetime: Seconds::new(100),
+        idle_timeout: Seconds::new(10),
+    };
+
+    #[rstest]
+    #[case::fresh(0, 0, 0, Ok(()))]
+    #[case::active(0, 95, 100, Ok(()))]
+    #[case::max_lifetime(0, 100, 101, Err(SessionExpiredError::MaxLifetimeReached))]
+    #[case::idle_at_limit(0, 10, 20, Ok(()))]
+    #[case::idle(0, 10, 21, Err(SessionExpiredError::IdleTimeout))]
+    #[case::both_prefers_max_lifetime(0, 0, 200, Err(SessionExpiredError::MaxLifetimeReached))]
+    fn session_lifetime_check(
+        #[case] login_timestamp: u32,
+        #[case] last_activity: u32,
+        #[case] now: u32,
+        #[case] expected: Result<(), SessionExpiredError>,
+    ) {
+        // Act
+        let actual = LIFETIMES.check(login_timestamp.into(), last_activity.into(), now.into());
+
+        // Assert
+        assert_eq!(actual, expected);
+    }
+
+    #[rstest]
+    #[case::long_idle_timeout(3600, SESSION_ACTIVITY_UPDATE_INTERVAL)]
+    #[case::short_idle_timeout(30, Seconds::new(15))]
+    fn activity_update_interval_fits_idle_timeout(
+        #[case] idle_timeout: u64,
+        #[case] expected: Seconds,
+    ) {
+        // Arrange
+        let lifetimes = SessionLifetimes {
+            idle_timeout: idle_timeout.into(),
+            ..LIFETIMES
+        };
+
+        // Act
+        let actual = lifetimes.activity_update_interval();
+
+        // Assert
+        assert_eq!(actual, expected);
+    }
+}
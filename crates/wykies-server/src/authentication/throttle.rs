@@ -0,0 +1,227 @@
+//! Slows down repeated failed logins
+//!
+//! Per user, each failed attempt doubles the time before the next attempt is
+//! accepted and lockouts can be set to expire. Per host, failures are counted
+//! regardless of username so that trying a few passwords against many accounts
+//! is also slowed down.
+
+use std::{collections::HashMap, sync::Mutex};
+use wykies_shared::{host_branch::HostId, uac::AuthError};
+use wykies_time::{Seconds, Timestamp};
+
+/// Caps the exponential growth of the delay so that it cannot overflow. The
+/// configured maximum delay is expected to be reached long before this
+const MAX_DELAY_DOUBLINGS: u32 = 16;
+
+#[derive(Debug, Clone, Copy)]
+pub struct LoginThrottling {
+    /// Time after being locked out that the user is automatically unlocked.
+    /// Lockouts only expire if set and never expire if set by an administrator
+    pub lockout_duration: Option<Seconds>,
+    /// Delay required after the first failed attempt, doubles with each
+    /// subsequent failed attempt. Zero disables the delay
+    pub failed_attempt_base_delay: Seconds,
+    pub failed_attempt_max_delay: Seconds,
+}
+
+impl LoginThrottling {
+    /// Returns true if the user was automatically locked out at `locked_out_at`
+    /// and that lockout is over at `now`
+    pub fn is_lockout_expired(&self, locked_out_at: Option<Timestamp>, now: Timestamp) -> bool {
+        locked_out_at
+            .zip(self.lockout_duration)
+            .is_some_and(|(locked_out_at, duration)| now >= locked_out_at + duration)
+    }
+
+    /// Returns how long to wait before another attempt is accepted or `None`
+    /// if an attempt is allowed at `now`
+    pub fn retry_after(
+        &self,
+        failed_attempts: i8,
+        last_failed_attempt: Option<Timestamp>,
+        now: Timestamp,
+    ) -> Option<Seconds> {
+        let last_failed_attempt = last_failed_attempt?;
+        let next_allowed = last_failed_attempt + self.delay(failed_attempts);
+        next_allowed.seconds_since(now).filter(|x| !x.is_zero())
+    }
+
+    fn delay(&self, failed_attempts: i8) -> Seconds {
+        let Ok(doublings) = u32::try_from(failed_attempts.saturating_sub(1)) else {
+            return Seconds::new(0);
+        };
+        let doublings = doublings.min(MAX_DELAY_DOUBLINGS);
+        (self.failed_attempt_base_delay * Seconds::new(1 << doublings))
+            .min(self.failed_attempt_max_delay)
+    }
+}
+
+/// Tracks failed logins by the host they came from. Requests that could be used
+/// to probe accounts without logging in (like password reset requests) are also
+/// counted. Only kept in memory so counts reset when the server restarts
+#[derive(Debug)]
+pub struct HostLoginThrottle {
+    /// Number of failed attempts allowed within the window. Zero disables
+    /// throttling by host
+    max_failures: u16,
+    window: Seconds,
+    failures: Mutex<HashMap<HostId, Vec<Timestamp>>>,
+}
+
+impl HostLoginThrottle {
+    pub fn new(max_failures: u16, window: Seconds) -> Self {
+        Self {
+            max_failures,
+            window,
+            failures: Default::default(),
+        }
+    }
+
+    /// Returns how long the host must wait before trying to login again or
+    /// `None` if it is allowed to try now
+    pub fn retry_after(&self, host_id: &HostId, now: Timestamp) -> Option<Seconds> {
+        if self.max_failures == 0 {
+            return None;
+        }
+        let mut failures = self.failures.lock().expect("mutex poisoned");
+        let host_failures = failures.get_mut(host_id)?;
+        host_failures.retain(|&x| !self.is_outside_window(x, now));
+        if host_failures.len() < usize::from(self.max_failures) {
+            return None;
+        }
+        let oldest = host_failures.iter().min()?;
+        (*oldest + self.window)
+            .seconds_since(now)
+            .filter(|x| !x.is_zero())
+    }
+
+    /// Counts the error against the host if it was caused by the credentials
+    /// provided
+    pub fn record(&self, host_id: &HostId, error: &AuthError, now: Timestamp) {
+        if is_failed_attempt(error) {
+            self.record_attempt(host_id, now);
+        }
+    }
+
+    /// Counts an attempt against the host without regard to its outcome
+    pub fn record_attempt(&self, host_id: &HostId, now: Timestamp) {
+        if self.max_failures == 0 {
+            return;
+        }
+        let mut failures = self.failures.lock().expect("mutex poisoned");
+        failures.retain(|_, host_failures| {
+            host_failures.retain(|&x| !self.is_outside_window(x, now));
+            !host_failures.is_empty()
+        });
+        failures.entry(host_id.clone()).or_default().push(now);
+    }
+
+    fn is_outside_window(&self, failure: Timestamp, now: Timestamp) -> bool {
+        now >= failure + self.window
+    }
+}
+
+fn is_failed_attempt(error: &AuthError) -> bool {
+    match error {
+        AuthError::InvalidUserOrPassword
+        | AuthError::LockedOut
+        | AuthError::NotEnabled
+        | AuthError::InvalidTotpCode => true,
+        AuthError::BranchNotSetAndUnableToSet { .. }
+        | AuthError::BranchNotSetResend { .. }
+        | AuthError::TooManyAttempts { .. }
+        | AuthError::UnexpectedError(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const THROTTLING: LoginThrottling = LoginThrottling {
+        lockout_duration: Some(Seconds::new(100)),
+        failed_attempt_base_delay: Seconds::new(2),
+        failed_attempt_max_delay: Seconds::new(10),
+    };
+
+    #[rstest]
+    #[case::no_failures(0, None, 50, None)]
+    #[case::first_failure_waiting(1, Some(50), 51, Some(1))]
+    #[case::first_failure_done(1, Some(50), 52, None)]
+    #[case::third_failure_waiting(3, Some(50), 55, Some(3))]
+    #[case::capped_at_max(7, Some(50), 50, Some(10))]
+    #[case::capped_done(120, Some(50), 60, None)]
+    fn failed_attempt_retry_after(
+        #[case] failed_attempts: i8,
+        #[case] last_failed_attempt: Option<u32>,
+        #[case] now: u32,
+        #[case] expected: Option<u64>,
+    ) {
+        // Act
+        let actual = THROTTLING.retry_after(
+            failed_attempts,
+            last_failed_attempt.map(Into::into),
+            now.into(),
+        );
+
+        // Assert
+        assert_eq!(actual, expected.map(Seconds::new));
+    }
+
+    #[rstest]
+    #[case::admin_lockout(None, 1000, false)]
+    #[case::not_yet(Some(50), 149, false)]
+    #[case::expired(Some(50), 150, true)]
+    fn lockout_expiry(
+        #[case] locked_out_at: Option<u32>,
+        #[case] now: u32,
+        #[case] expected: bool,
+    ) {
+        // Act
+        let actual = THROTTLING.is_lockout_expired(locked_out_at.map(Into::into), now.into());
+
+        // Assert
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn host_throttled_after_max_failures_within_window() {
+        // Arrange
+        let throttle = HostLoginThrottle::new(2, Seconds::new(60));
+        let host_id: HostId = "127.0.0.1".to_string().try_into().unwrap();
+        let other_host_id: HostId = "127.0.0.2".to_string().try_into().unwrap();
+        let error = AuthError::InvalidUserOrPassword;
+
+        // Act
+        throttle.record(&host_id, &error, 100u32.into());
+        throttle.record(&host_id, &error, 110u32.into());
+
+        // Assert
+        assert_eq!(
+            throttle.retry_after(&host_id, 120u32.into()),
+            Some(Seconds::new(40))
+        );
+        assert_eq!(throttle.retry_after(&other_host_id, 120u32.into()), None);
+        assert_eq!(throttle.retry_after(&host_id, 160u32.into()), None);
+    }
+
+    #[test]
+    fn host_not_throttled_for_errors_not_caused_by_credentials() {
+        // Arrange
+        let throttle = HostLoginThrottle::new(1, Seconds::new(60));
+        let host_id: HostId = "127.0.0.1".to_string().try_into().unwrap();
+
+        // Act
+        throttle.record(
+            &host_id,
+            &AuthError::TooManyAttempts {
+                retry_after: Seconds::new(1),
+            },
+            100u32.into(),
+        );
+
+        // Assert
+        assert_eq!(throttle.retry_after(&host_id, 100u32.into()), None);
+    }
+}
@@ -0,0 +1,544 @@
+//! Optional RFC 6238 time based one time passwords used as a second factor
+//! during login
+//!
+//! Enrolment stores a secret that is only used for logins after the user
+//! confirms it with a valid code. Recovery codes are generated when enrolment
+//! is confirmed and are only stored hashed.
+//!
+//! The time step of the last code accepted for each user is stored and codes
+//! for that step or an earlier one are rejected so that a code cannot be used
+//! twice (See [`record_totp_step`]).
+
+#[cfg(feature = "mysql")]
+use crate::db_utils::db_int_to_bool;
+use crate::db_utils::validate_one_row_affected;
+use anyhow::{Context, anyhow};
+use argon2::password_hash::rand_core::{OsRng, RngCore as _};
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::{Executor, Transaction};
+use std::time::{SystemTime, UNIX_EPOCH};
+use totp_rs::{Algorithm, Secret, TOTP};
+use tracing::info;
+use wykies_shared::{
+    db_types::{Db, DbPool},
+    telemetry::spawn_blocking_with_tracing,
+    uac::{AuthError, Permission, TotpEnrolment, TotpError, TotpRecoveryCodes, Username},
+};
+
+use super::{
+    AuthUserInfo, LoginAttemptLimit, PasswordHashing,
+    password::{increment_locked_out_count, verify_password_hash},
+};
+
+const TOTP_RECOVERY_CODE_COUNT: usize = 10;
+const TOTP_RECOVERY_CODE_LENGTH: usize = 10;
+/// Excludes characters that are easily confused with each other
+const TOTP_RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP_SECS: u64 = 30;
+/// Number of steps before and after the current one that are also accepted to
+/// allow for clock drift
+const TOTP_SKEW: u8 = 1;
+
+#[derive(Debug, Clone)]
+pub struct TotpSettings {
+    /// See [`crate::configuration::UserAuthSettings::totp_required_for_man_uac`]
+    pub required_for_man_uac: bool,
+    pub issuer: String,
+}
+
+/// What is needed to complete a login after the password was validated
+#[derive(Debug)]
+pub enum TotpLoginCheck {
+    /// User does not have two-factor authentication enabled and is not required
+    /// to
+    NotRequired,
+    /// The code provided was valid. It must be consumed using
+    /// [`consume_totp_code`] once the login succeeds
+    Verified(VerifiedTotpCode),
+    /// The user has two-factor authentication enabled but no code was provided
+    CodeRequired,
+    /// The user is required to have two-factor authentication but has not
+    /// enrolled yet
+    EnrolmentRequired,
+}
+
+/// A code accepted by [`check_totp_for_login`]. Not used up until the login
+/// succeeds so that a resend to set the branch does not need a new code
+#[derive(Debug)]
+pub enum VerifiedTotpCode {
+    /// Time step of the code from the authenticator app
+    Step(u64),
+    RecoveryCode(i32),
+}
+
+#[derive(Debug)]
+struct TotpState {
+    secret: Option<SecretString>,
+    enabled: bool,
+}
+
+/// Wrong codes count towards the user's failed login attempts the same as
+/// wrong passwords
+#[tracing::instrument(skip(auth_user_info, code, pool))]
+pub async fn check_totp_for_login(
+    auth_user_info: &AuthUserInfo,
+    code: Option<SecretString>,
+    pool: &DbPool,
+    login_attempt_limit: &LoginAttemptLimit,
+    totp_settings: &TotpSettings,
+) -> Result<TotpLoginCheck, AuthError> {
+    let Some(secret) = auth_user_info.totp_secret.as_ref() else {
+        let is_enrolment_required = totp_settings.required_for_man_uac
+            && auth_user_info
+                .permissions
+                .includes(&[Permission::ManUAC])
+                .has_required_permissions();
+        return Ok(if is_enrolment_required {
+            TotpLoginCheck::EnrolmentRequired
+        } else {
+            TotpLoginCheck::NotRequired
+        });
+    };
+    let Some(code) = code else {
+        return Ok(TotpLoginCheck::CodeRequired);
+    };
+
+    let username = &auth_user_info.username;
+    if is_totp_code_format(&code) {
+        if let Some(step) = find_totp_code_step(secret, &code, &totp_settings.issuer, username)?
+            && auth_user_info
+                .totp_last_step
+                .is_none_or(|last_step| step > last_step)
+        {
+            return Ok(TotpLoginCheck::Verified(VerifiedTotpCode::Step(step)));
+        }
+    } else if let Some(id) = find_recovery_code(username, code, pool).await? {
+        return Ok(TotpLoginCheck::Verified(VerifiedTotpCode::RecoveryCode(id)));
+    }
+
+    increment_locked_out_count(username, pool, login_attempt_limit).await?;
+    Err(AuthError::InvalidTotpCode)
+}
+
+/// Generates a new secret for the user. It is not used for logins until
+/// confirmed with [`confirm_enrolment`]. Starting again replaces any secret
+/// from a previous unconfirmed enrolment
+#[tracing::instrument(skip(pool))]
+pub async fn start_enrolment(
+    username: &Username,
+    pool: &DbPool,
+    totp_settings: &TotpSettings,
+) -> Result<TotpEnrolment, TotpError> {
+    if get_totp_state(username, pool).await?.enabled {
+        return Err(TotpError::AlreadyEnabled);
+    }
+    let secret = Secret::generate_secret()
+        .to_bytes()
+        .map_err(|e| anyhow!("failed to generate totp secret: {e:?}"))?;
+    let totp = new_totp(secret, &totp_settings.issuer, username)?;
+    let secret = totp.get_secret_base32();
+    set_totp_state(username, Some(&secret), false, pool).await?;
+    info!("TOTP enrolment started for {username:?}");
+    Ok(TotpEnrolment {
+        provisioning_uri: totp.get_url(),
+        secret,
+    })
+}
+
+/// A code that matched the pending secret along with the new recovery codes,
+/// ready to be saved with [`ConfirmedEnrolment::save`]
+pub struct ConfirmedEnrolment {
+    secret: SecretString,
+    step: u64,
+    codes: Vec<String>,
+    code_hashes: Vec<SecretString>,
+}
+
+/// Checks the code against the secret from [`start_enrolment`] and generates
+/// new recovery codes. Nothing is saved (See [`ConfirmedEnrolment::save`]) so
+/// that the recovery codes are hashed before a transaction is started
+#[tracing::instrument(skip(code, pool, password_hashing))]
+pub async fn confirm_enrolment(
+    username: &Username,
+    code: SecretString,
+    pool: &DbPool,
+    totp_settings: &TotpSettings,
+    password_hashing: &PasswordHashing,
+) -> Result<ConfirmedEnrolment, TotpError> {
+    let state = get_totp_state(username, pool).await?;
+    if state.enabled {
+        return Err(TotpError::AlreadyEnabled);
+    }
+    let Some(secret) = state.secret else {
+        return Err(TotpError::NoPendingEnrolment);
+    };
+    if !is_totp_code_format(&code) {
+        return Err(TotpError::InvalidCode);
+    }
+    let Some(step) = find_totp_code_step(&secret, &code, &totp_settings.issuer, username)? else {
+        return Err(TotpError::InvalidCode);
+    };
+
+    let codes: Vec<String> = (0..TOTP_RECOVERY_CODE_COUNT)
+        .map(|_| generate_recovery_code())
+        .collect();
+    let mut code_hashes = Vec::with_capacity(codes.len());
+    for code in codes.iter() {
+        let code = SecretString::from(normalize_recovery_code(code));
+        let password_hashing = password_hashing.clone();
+        let code_hash =
+            spawn_blocking_with_tracing(move || password_hashing.compute_password_hash(code))
+                .await
+                .context("failed to spawn blocking task")??;
+        code_hashes.push(code_hash);
+    }
+    Ok(ConfirmedEnrolment {
+        secret,
+        step,
+        codes,
+        code_hashes,
+    })
+}
+
+impl ConfirmedEnrolment {
+    /// Enables two-factor authentication for the user and returns the new
+    /// recovery codes. Nothing is saved until `transaction` is committed
+    #[tracing::instrument(skip(self, transaction))]
+    pub async fn save(
+        self,
+        username: &Username,
+        transaction: &mut Transaction<'_, Db>,
+    ) -> Result<TotpRecoveryCodes, TotpError> {
+        if !record_totp_step(username.as_ref(), self.step, &mut **transaction).await? {
+            return Err(TotpError::InvalidCode);
+        }
+        delete_recovery_codes(username, &mut **transaction).await?;
+        for code_hash in self.code_hashes {
+            store_recovery_code(username, code_hash, &mut **transaction).await?;
+        }
+        set_totp_state(
+            username,
+            Some(self.secret.expose_secret()),
+            true,
+            &mut **transaction,
+        )
+        .await?;
+        info!("TOTP enabled for {username:?}");
+        Ok(TotpRecoveryCodes { codes: self.codes })
+    }
+}
+
+/// Disables two-factor authentication for the user and removes their recovery
+/// codes. Nothing is saved until `transaction` is committed
+#[tracing::instrument(skip(transaction))]
+pub async fn reset_totp(
+    username: &Username,
+    transaction: &mut Transaction<'_, Db>,
+) -> anyhow::Result<()> {
+    delete_recovery_codes(username, &mut **transaction).await?;
+    set_totp_state(username, None, false, &mut **transaction).await
+}
+
+/// Uses up a code accepted by [`check_totp_for_login`] so it cannot be used
+/// again. Fails the same as a wrong code if another login used it first
+#[tracing::instrument(skip(pool))]
+pub async fn consume_totp_code(
+    username: &str,
+    code: VerifiedTotpCode,
+    pool: &DbPool,
+    login_attempt_limit: &LoginAttemptLimit,
+) -> Result<(), AuthError> {
+    let is_consumed = match code {
+        VerifiedTotpCode::Step(step) => record_totp_step(username, step, pool).await?,
+        VerifiedTotpCode::RecoveryCode(id) => consume_recovery_code(id, pool).await?,
+    };
+    if is_consumed {
+        return Ok(());
+    }
+    increment_locked_out_count(username, pool, login_attempt_limit).await?;
+    Err(AuthError::InvalidTotpCode)
+}
+
+/// Removes a recovery code so it cannot be used again. Returns `false` if it
+/// was already removed
+#[tracing::instrument(skip(pool))]
+async fn consume_recovery_code(recovery_code_id: i32, pool: &DbPool) -> anyhow::Result<bool> {
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!(
+        "DELETE FROM `totp_recovery_codes` WHERE `RecoveryCodeID` = ?;",
+        recovery_code_id
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    let query = sqlx::query!(
+        "DELETE FROM totp_recovery_codes WHERE recovery_code_id = $1;",
+        recovery_code_id
+    );
+    let sql_result = query
+        .execute(pool)
+        .await
+        .context("failed to remove used recovery code")?;
+    Ok(sql_result.rows_affected() > 0)
+}
+
+fn new_totp(secret: Vec<u8>, issuer: &str, username: &str) -> anyhow::Result<TOTP> {
+    TOTP::new(
+        Algorithm::SHA1,
+        TOTP_DIGITS,
+        TOTP_SKEW,
+        TOTP_STEP_SECS,
+        secret,
+        Some(issuer.to_string()),
+        username.to_string(),
+    )
+    .context("failed to create totp")
+}
+
+fn is_totp_code_format(code: &SecretString) -> bool {
+    let code = code.expose_secret();
+    code.len() == TOTP_DIGITS && code.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Returns the time step that the code is for or `None` if it is not valid for
+/// the current step or any of the steps allowed by [`TOTP_SKEW`]
+fn find_totp_code_step(
+    secret: &SecretString,
+    code: &SecretString,
+    issuer: &str,
+    username: &str,
+) -> anyhow::Result<Option<u64>> {
+    let secret = Secret::Encoded(secret.expose_secret().to_string())
+        .to_bytes()
+        .map_err(|e| anyhow!("invalid totp secret stored: {e:?}"))?;
+    let mut totp = new_totp(secret, issuer, username)?;
+    // Each step is checked on its own to find out which one matched
+    totp.skew = 0;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context("system time is before the unix epoch")?
+        .as_secs();
+    let current_step = now / TOTP_STEP_SECS;
+    let skew = u64::from(TOTP_SKEW);
+    Ok((current_step.saturating_sub(skew)..=current_step + skew)
+        .find(|step| totp.check(code.expose_secret(), step * TOTP_STEP_SECS)))
+}
+
+/// Stores `step` as the last step a code was accepted for. Returns `false`
+/// without storing it if a code for this step or a later one was already
+/// accepted, which means the code is being reused
+#[tracing::instrument(skip(executor))]
+async fn record_totp_step(
+    username: &str,
+    step: u64,
+    executor: impl Executor<'_, Database = Db>,
+) -> anyhow::Result<bool> {
+    let step: i64 = step.try_into().context("totp step out of range")?;
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!(
+        "UPDATE `user` SET `TotpLastStep` = ?
+        WHERE `UserName` = ? AND (`TotpLastStep` IS NULL OR `TotpLastStep` < ?);",
+        step,
+        username,
+        step
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    let query = sqlx::query!(
+        "UPDATE users SET totp_last_step = $1
+        WHERE user_name = $2 AND (totp_last_step IS NULL OR totp_last_step < $1);",
+        step,
+        username
+    );
+    let sql_result = query
+        .execute(executor)
+        .await
+        .context("failed to record totp step of user")?;
+    Ok(sql_result.rows_affected() > 0)
+}
+
+/// Formatted in two groups for readability, the separator is ignored when
+/// checking codes
+fn generate_recovery_code() -> String {
+    let mut result = String::with_capacity(TOTP_RECOVERY_CODE_LENGTH + 1);
+    for i in 0..TOTP_RECOVERY_CODE_LENGTH {
+        if i == TOTP_RECOVERY_CODE_LENGTH / 2 {
+            result.push('-');
+        }
+        let index = OsRng.next_u32() as usize % TOTP_RECOVERY_CODE_ALPHABET.len();
+        result.push(TOTP_RECOVERY_CODE_ALPHABET[index] as char);
+    }
+    result
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Returns the ID of the stored recovery code that matches if any
+#[tracing::instrument(skip(code, pool))]
+async fn find_recovery_code(
+    username: &str,
+    code: SecretString,
+    pool: &DbPool,
+) -> anyhow::Result<Option<i32>> {
+    #[cfg(feature = "mysql")]
+    let stored_codes: Vec<(i32, SecretString)> = sqlx::query!(
+        "SELECT `RecoveryCodeID`, `CodeHash` FROM `totp_recovery_codes` WHERE `UserName` = ?;",
+        username
+    )
+    .fetch_all(pool)
+    .await
+    .context("failed to get recovery codes")?
+    .into_iter()
+    .map(|row| (row.RecoveryCodeID, SecretString::from(row.CodeHash)))
+    .collect();
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    let stored_codes: Vec<(i32, SecretString)> = sqlx::query!(
+        "SELECT recovery_code_id, code_hash FROM totp_recovery_codes WHERE user_name = $1;",
+        username
+    )
+    .fetch_all(pool)
+    .await
+    .context("failed to get recovery codes")?
+    .into_iter()
+    .map(|row| (row.recovery_code_id, SecretString::from(row.code_hash)))
+    .collect();
+
+    let code = SecretString::from(normalize_recovery_code(code.expose_secret()));
+    spawn_blocking_with_tracing(move || {
+        stored_codes
+            .into_iter()
+            .find(|(_, code_hash)| verify_password_hash(code_hash.clone(), code.clone()).is_ok())
+            .map(|(id, _)| id)
+    })
+    .await
+    .context("failed to spawn blocking task")
+}
+
+#[tracing::instrument(skip(code_hash, executor))]
+async fn store_recovery_code(
+    username: &Username,
+    code_hash: SecretString,
+    executor: impl Executor<'_, Database = Db>,
+) -> anyhow::Result<()> {
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!(
+        "INSERT INTO `totp_recovery_codes` (`UserName`, `CodeHash`) VALUES (?, ?);",
+        username,
+        code_hash.expose_secret()
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let query = sqlx::query!(
+        "INSERT INTO totp_recovery_codes (user_name, code_hash) VALUES ($1, $2);",
+        username.as_ref(),
+        code_hash.expose_secret()
+    );
+    let sql_result = query
+        .execute(executor)
+        .await
+        .context("failed to store recovery code")?;
+    validate_one_row_affected(&sql_result).context("failed to store recovery code")
+}
+
+#[tracing::instrument(skip(executor))]
+async fn delete_recovery_codes(
+    username: &Username,
+    executor: impl Executor<'_, Database = Db>,
+) -> anyhow::Result<()> {
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!(
+        "DELETE FROM `totp_recovery_codes` WHERE `UserName` = ?;",
+        username
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let query = sqlx::query!(
+        "DELETE FROM totp_recovery_codes WHERE user_name = $1;",
+        username.as_ref()
+    );
+    query
+        .execute(executor)
+        .await
+        .context("failed to delete recovery codes")?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_totp_state(username: &Username, pool: &DbPool) -> anyhow::Result<TotpState> {
+    #[cfg(feature = "mysql")]
+    let result = sqlx::query!(
+        "SELECT `TotpSecret`, `TotpEnabled` FROM `user` WHERE `UserName` = ?;",
+        username
+    )
+    .fetch_one(pool)
+    .await
+    .map(|row| TotpState {
+        secret: row.TotpSecret.map(SecretString::from),
+        enabled: db_int_to_bool(row.TotpEnabled),
+    });
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let result = sqlx::query!(
+        "SELECT totp_secret, totp_enabled FROM users WHERE user_name = $1;",
+        username.as_ref()
+    )
+    .fetch_one(pool)
+    .await
+    .map(|row| TotpState {
+        secret: row.totp_secret.map(SecretString::from),
+        enabled: row.totp_enabled,
+    });
+    result.context("failed to get totp state of user")
+}
+
+#[tracing::instrument(skip(secret, executor))]
+async fn set_totp_state(
+    username: &Username,
+    secret: Option<&str>,
+    enabled: bool,
+    executor: impl Executor<'_, Database = Db>,
+) -> anyhow::Result<()> {
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!(
+        "UPDATE `user` SET `TotpSecret` = ?, `TotpEnabled` = ? WHERE `user`.`UserName` = ?;",
+        secret,
+        enabled,
+        username,
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let query = sqlx::query!(
+        "UPDATE users SET totp_secret = $1, totp_enabled = $2 WHERE users.user_name = $3;",
+        secret,
+        enabled,
+        username.as_ref(),
+    );
+    let sql_result = query
+        .execute(executor)
+        .await
+        .context("failed to update totp state of user")?;
+    validate_one_row_affected(&sql_result).context("failed to update totp state of user")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recovery_codes_normalize_to_expected_length() {
+        let code = generate_recovery_code();
+        let actual = normalize_recovery_code(&code.to_uppercase());
+        assert_eq!(actual.len(), TOTP_RECOVERY_CODE_LENGTH);
+        assert_eq!(actual, code.replace('-', ""));
+    }
+
+    #[test]
+    fn recovery_codes_are_not_totp_codes() {
+        let code = SecretString::from(generate_recovery_code());
+        assert!(!is_totp_code_format(&code));
+        assert!(is_totp_code_format(&SecretString::from("012345")));
+    }
+}
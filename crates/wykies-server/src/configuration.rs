@@ -1,3 +1,4 @@
+use crate::{authentication::Argon2Settings, notifier::NotifierSettings};
 use secrecy::{ExposeSecret, SecretString};
 use serde::de::DeserializeOwned;
 use serde_aux::field_attributes::deserialize_number_from_string;
@@ -5,8 +6,12 @@ use sqlx::ConnectOptions;
 use std::convert::{TryFrom, TryInto};
 use tracing::info;
 use ws_helpers::WebSocketSettings;
+use wykies_time::Seconds;
 
-use wykies_shared::db_types::{DbConnectOptions, DbSslMode};
+use wykies_shared::{
+    db_types::{DbConnectOptions, DbSslMode},
+    uac::PasswordPolicy,
+};
 
 // TODO 5: Add comments to any settings that are no longer obvious
 #[derive(serde::Deserialize, Clone)]
@@ -16,6 +21,7 @@ pub struct Configuration<T: Clone> {
     pub redis_uri: SecretString,
     pub user_auth: UserAuthSettings,
     pub websockets: WebSocketSettings,
+    pub notifier: NotifierSettings,
     pub custom: T,
 }
 
@@ -46,6 +52,41 @@ pub struct DatabaseSettings {
 #[derive(serde::Deserialize, Clone)]
 pub struct UserAuthSettings {
     pub login_attempt_limit: u8,
+    /// Time after being locked out by too many failed attempts that the user
+    /// is unlocked. Only an administrator can unlock users if not set
+    pub lockout_duration_secs: Option<Seconds>,
+    /// Delay enforced after a failed login, doubles with each consecutive
+    /// failure up to the maximum. Zero disables the delay
+    pub failed_attempt_base_delay_secs: Seconds,
+    pub failed_attempt_max_delay_secs: Seconds,
+    /// Failed logins allowed from the same host within the window regardless
+    /// of the username used. Zero disables the limit
+    pub host_login_attempt_limit: u16,
+    pub host_login_window_secs: Seconds,
+    /// Maximum time a session stays valid after login regardless of activity
+    pub session_max_lifetime_secs: Seconds,
+    /// Maximum time allowed between requests before the session expires
+    pub session_idle_timeout_secs: Seconds,
+    /// If requests from a different host than the one used to login should be
+    /// rejected. Disable for deployments behind NAT where the address seen by
+    /// the server can change for the same client (mismatches are still logged)
+    pub enforce_session_host_binding: bool,
+    /// If users with permission to manage user account control must enrol in
+    /// two-factor authentication before they can do anything else
+    pub totp_required_for_man_uac: bool,
+    /// Shown next to the username in authenticator apps
+    pub totp_issuer: String,
+    #[serde(default)]
+    pub password_policy: PasswordPolicy,
+    /// Days after a password is changed before the user is forced to change
+    /// it again. Passwords do not expire if not set
+    pub password_max_age_days: Option<u16>,
+    /// Users are warned this many days before their password expires
+    pub password_expiry_warning_days: u16,
+    /// Time a password reset token can be used after it is requested
+    pub password_reset_token_lifetime_secs: Seconds,
+    #[serde(default)]
+    pub argon2: Argon2Settings,
 }
 
 impl DatabaseSettings {
@@ -110,7 +151,18 @@ pub fn get_configuration<T: Clone + DeserializeOwned>()
         )
         .build()?;
 
-    settings.try_deserialize::<Configuration<T>>()
+    let configuration = settings.try_deserialize::<Configuration<T>>()?;
+    if matches!(environment, Environment::Production)
+        && matches!(configuration.notifier, NotifierSettings::File { .. })
+    {
+        // Password reset tokens would be written to disk instead of reaching users
+        return Err(config::ConfigError::Message(
+            "the file notifier is only for local use, set [notifier] to kind = \"smtp\" for \
+            production"
+                .to_string(),
+        ));
+    }
+    Ok(configuration)
 }
 
 /// The possible runtime environment for our application.
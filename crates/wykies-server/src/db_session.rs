@@ -0,0 +1,443 @@
+//! Session storage backed by the database
+//!
+//! Alternative to Redis or cookie only sessions for deployments that only run a
+//! database. Unlike cookie only sessions these can be revoked server side.
+
+#[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+use crate::db_utils::db_timestamp;
+use crate::{ServerTask, db_utils::validate_one_row_affected, session_state::TypedSession};
+use actix_session::storage::{LoadError, SaveError, SessionKey, SessionStore, UpdateError};
+use actix_web::cookie::time::Duration;
+use anyhow::Context as _;
+use sqlx::Executor;
+use std::collections::HashMap;
+use tokio::select;
+use tracing::{info, instrument};
+use tracked_cancellations::TrackedCancellationToken;
+use wykies_shared::{
+    db_types::{Db, DbPool},
+    log_err_as_error, random_string,
+    uac::{SessionId, SessionInfo, UserInfo, Username},
+};
+use wykies_time::{Seconds, Timestamp};
+
+/// Matches the type used by `actix_session` for the session state (values are
+/// stored JSON encoded)
+type SessionState = HashMap<String, String>;
+
+const SESSION_KEY_LENGTH: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct DbSessionStore {
+    pool: DbPool,
+}
+
+/// Periodically removes expired sessions from the database
+#[derive(Debug)]
+pub struct SessionPurgeTask {
+    pool: DbPool,
+    interval: Seconds,
+}
+
+impl DbSessionStore {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    #[instrument(err(Debug), skip(self))]
+    async fn exists(&self, session_key: &SessionKey) -> anyhow::Result<bool> {
+        #[cfg(feature = "mysql")]
+        let query = sqlx::query!(
+            "SELECT `SessionID` FROM `sessions` WHERE `SessionKey` = ?",
+            session_key.as_ref()
+        );
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        let query = sqlx::query!(
+            "SELECT session_id FROM sessions WHERE session_key = $1",
+            session_key.as_ref()
+        );
+        Ok(query
+            .fetch_optional(&self.pool)
+            .await
+            .context("failed to check if session exists")?
+            .is_some())
+    }
+}
+
+impl SessionStore for DbSessionStore {
+    #[instrument(err(Debug), skip_all)]
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+        let now = Timestamp::now();
+        #[cfg(feature = "mysql")]
+        let query = sqlx::query!(
+            "SELECT `SessionState` FROM `sessions` WHERE `SessionKey` = ? AND `Expires` > ?",
+            session_key.as_ref(),
+            now
+        );
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        // TODO 5: Check why encode trait impl doesn't make converting not necessary
+        let query = {
+            let now = db_timestamp(now).map_err(LoadError::Other)?;
+            sqlx::query!(
+                "SELECT session_state FROM sessions WHERE session_key = $1 AND expires > $2",
+                session_key.as_ref(),
+                now
+            )
+        };
+        let Some(record) = query
+            .fetch_optional(&self.pool)
+            .await
+            .context("failed to load session")
+            .map_err(LoadError::Other)?
+        else {
+            return Ok(None);
+        };
+
+        #[cfg(feature = "mysql")]
+        let session_state = record.SessionState;
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        let session_state = record.session_state;
+        serde_json::from_str(&session_state)
+            .map(Some)
+            .context("failed to deserialize session state")
+            .map_err(LoadError::Deserialization)
+    }
+
+    #[instrument(err(Debug), skip_all)]
+    async fn save(
+        &self,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        let session_key = generate_session_key();
+        let username = username_from_state(&session_state);
+        let session_state = serde_json::to_string(&session_state)
+            .context("failed to serialize session state")
+            .map_err(SaveError::Serialization)?;
+        let created = Timestamp::now();
+        let expires = expiry_from_ttl(created, ttl);
+        #[cfg(feature = "mysql")]
+        let query = sqlx::query!(
+            "INSERT INTO `sessions`
+            (`SessionKey`, `UserName`, `SessionState`, `Created`, `Expires`)
+            VALUES (?, ?, ?, ?, ?);",
+            session_key.as_ref(),
+            username,
+            session_state,
+            created,
+            expires
+        );
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        // TODO 5: Check why encode trait impl doesn't make converting not necessary
+        let query = {
+            let created = db_timestamp(created).map_err(SaveError::Other)?;
+            let expires = db_timestamp(expires).map_err(SaveError::Other)?;
+            sqlx::query!(
+                "INSERT INTO sessions
+                (session_key, user_name, session_state, created, expires)
+                VALUES ($1, $2, $3, $4, $5);",
+                session_key.as_ref(),
+                username.as_ref().map(|x| x.as_ref()),
+                session_state,
+                created,
+                expires
+            )
+        };
+        let sql_result = query
+            .execute(&self.pool)
+            .await
+            .context("failed to save session")
+            .map_err(SaveError::Other)?;
+        validate_one_row_affected(&sql_result)
+            .context("failed to save new session")
+            .map_err(SaveError::Other)?;
+        Ok(session_key)
+    }
+
+    #[instrument(err(Debug), skip_all)]
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        let username = username_from_state(&session_state);
+        let serialized_state = serde_json::to_string(&session_state)
+            .context("failed to serialize session state")
+            .map_err(UpdateError::Serialization)?;
+        let expires = expiry_from_ttl(Timestamp::now(), ttl);
+        #[cfg(feature = "mysql")]
+        let query = sqlx::query!(
+            "UPDATE `sessions` SET
+            `UserName` = ?,
+            `SessionState` = ?,
+            `Expires` = ?
+            WHERE `SessionKey` = ?;",
+            username,
+            serialized_state,
+            expires,
+            session_key.as_ref()
+        );
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        // TODO 5: Check why encode trait impl doesn't make converting not necessary
+        let query = {
+            let expires = db_timestamp(expires).map_err(UpdateError::Other)?;
+            sqlx::query!(
+                "UPDATE sessions SET
+                user_name = $1,
+                session_state = $2,
+                expires = $3
+                WHERE session_key = $4;",
+                username.as_ref().map(|x| x.as_ref()),
+                serialized_state,
+                expires,
+                session_key.as_ref()
+            )
+        };
+        let sql_result = query
+            .execute(&self.pool)
+            .await
+            .context("failed to update session")
+            .map_err(UpdateError::Other)?;
+
+        // MySQL only reports changed rows so zero rows does not mean the session is
+        // gone, need to check before creating a new one
+        if sql_result.rows_affected() == 0
+            && !self
+                .exists(&session_key)
+                .await
+                .map_err(UpdateError::Other)?
+        {
+            // Session was removed (expired or revoked) so start a new one instead
+            return self
+                .save(session_state, ttl)
+                .await
+                .map_err(|err| match err {
+                    SaveError::Serialization(e) => UpdateError::Serialization(e),
+                    SaveError::Other(e) => UpdateError::Other(e),
+                });
+        }
+        Ok(session_key)
+    }
+
+    #[instrument(err(Debug), skip_all)]
+    async fn update_ttl(&self, session_key: &SessionKey, ttl: &Duration) -> anyhow::Result<()> {
+        let expires = expiry_from_ttl(Timestamp::now(), ttl);
+        #[cfg(feature = "mysql")]
+        let query = sqlx::query!(
+            "UPDATE `sessions` SET `Expires` = ? WHERE `SessionKey` = ?;",
+            expires,
+            session_key.as_ref()
+        );
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        // TODO 5: Check why encode trait impl doesn't make converting not necessary
+        let query = {
+            let expires = db_timestamp(expires)?;
+            sqlx::query!(
+                "UPDATE sessions SET expires = $1 WHERE session_key = $2;",
+                expires,
+                session_key.as_ref()
+            )
+        };
+        query
+            .execute(&self.pool)
+            .await
+            .context("failed to update session ttl")?;
+        Ok(())
+    }
+
+    #[instrument(err(Debug), skip_all)]
+    async fn delete(&self, session_key: &SessionKey) -> anyhow::Result<()> {
+        #[cfg(feature = "mysql")]
+        let query = sqlx::query!(
+            "DELETE FROM `sessions` WHERE `SessionKey` = ?;",
+            session_key.as_ref()
+        );
+        #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+        let query = sqlx::query!(
+            "DELETE FROM sessions WHERE session_key = $1;",
+            session_key.as_ref()
+        );
+        query
+            .execute(&self.pool)
+            .await
+            .context("failed to delete session")?;
+        Ok(())
+    }
+}
+
+impl SessionPurgeTask {
+    pub fn new(pool: DbPool, interval: Seconds) -> Self {
+        Self { pool, interval }
+    }
+}
+
+impl ServerTask for SessionPurgeTask {
+    fn name(&self) -> &'static str {
+        "Session Purge"
+    }
+
+    async fn run(self, cancellation_token: TrackedCancellationToken) -> anyhow::Result<()> {
+        // Ensure that exiting causes the rest of the app to shut down
+        let _drop_guard = cancellation_token.clone().drop_guard();
+        loop {
+            select! {
+                _ = cancellation_token.cancelled() => {
+                    info!("shutting down SessionPurgeTask because of cancellation request");
+                    return Ok(())
+                }
+                _ = tokio::time::sleep(self.interval.into()) => {
+                    let r = purge_expired_sessions(&self.pool).await;
+                    log_err_as_error!(r);
+                }
+            }
+        }
+    }
+}
+
+/// Removes all sessions that have expired and returns the number removed
+#[instrument(ret, err(Debug), skip(pool))]
+pub async fn purge_expired_sessions(pool: &DbPool) -> anyhow::Result<u64> {
+    let now = Timestamp::now();
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!("DELETE FROM `sessions` WHERE `Expires` <= ?;", now);
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let query = {
+        let now = db_timestamp(now)?;
+        sqlx::query!("DELETE FROM sessions WHERE expires <= $1;", now)
+    };
+    let sql_result = query
+        .execute(pool)
+        .await
+        .context("failed to purge expired sessions")?;
+    Ok(sql_result.rows_affected())
+}
+
+/// Lists the sessions that have not yet expired for a user. Sessions that were
+/// invalidated are left in the store so that they can report why they ended
+/// but are not listed.
+#[instrument(err(Debug), skip(pool))]
+pub async fn list_user_sessions(
+    pool: &DbPool,
+    username: &Username,
+) -> anyhow::Result<Vec<SessionInfo>> {
+    let now = Timestamp::now();
+    let current_generation = crate::authentication::get_session_generation(username, pool)
+        .await
+        .context("failed to get session generation of user")?;
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!(
+        "SELECT `SessionID`, `UserName`, `SessionState`, `Created`, `Expires`
+        FROM `sessions`
+        WHERE `UserName` = ? AND `Expires` > ?
+        ORDER BY `Created`;",
+        username,
+        now
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let query = {
+        let now = db_timestamp(now)?;
+        sqlx::query!(
+            r#"SELECT session_id, user_name AS "user_name!", session_state, created, expires
+            FROM sessions
+            WHERE user_name = $1 AND expires > $2
+            ORDER BY created;"#,
+            username.as_ref(),
+            now
+        )
+    };
+    query
+        .fetch_all(pool)
+        .await
+        .context("failed to get list of sessions")?
+        .into_iter()
+        .filter(|x| {
+            #[cfg(feature = "mysql")]
+            let session_state = &x.SessionState;
+            #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+            let session_state = &x.session_state;
+            serde_json::from_str::<SessionState>(session_state)
+                .is_ok_and(|state| generation_from_state(&state) == current_generation)
+        })
+        .map(|x| {
+            #[cfg(feature = "mysql")]
+            return Ok(SessionInfo {
+                id: x.SessionID.try_into()?,
+                username: x
+                    .UserName
+                    .context("username missing from filtered session")?
+                    .try_into()?,
+                created: x.Created.into(),
+                expires: x.Expires.into(),
+            });
+            #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+            Ok(SessionInfo {
+                id: x.session_id.try_into()?,
+                username: x.user_name.try_into()?,
+                created: x.created.try_into()?,
+                expires: x.expires.try_into()?,
+            })
+        })
+        .collect()
+}
+
+/// Revokes the session with `session_id` for the user or all of the user's
+/// sessions if `session_id` is `None`. Returns the number of sessions removed.
+#[instrument(ret, err(Debug), skip(executor))]
+pub async fn revoke_user_sessions(
+    executor: impl Executor<'_, Database = Db>,
+    username: &Username,
+    session_id: Option<SessionId>,
+) -> anyhow::Result<u64> {
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!(
+        "DELETE FROM `sessions`
+        WHERE `UserName` = ? AND (? IS NULL OR `SessionID` = ?);",
+        username,
+        session_id,
+        session_id
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let query = {
+        let session_id: Option<i32> = session_id.map(|x| x.try_into()).transpose()?;
+        sqlx::query!(
+            "DELETE FROM sessions
+            WHERE user_name = $1 AND ($2::integer IS NULL OR session_id = $2);",
+            username.as_ref(),
+            session_id
+        )
+    };
+    let sql_result = query
+        .execute(executor)
+        .await
+        .context("failed to revoke sessions")?;
+    Ok(sql_result.rows_affected())
+}
+
+fn generate_session_key() -> SessionKey {
+    random_string(SESSION_KEY_LENGTH)
+        .try_into()
+        .expect("generated session keys should always be valid")
+}
+
+fn expiry_from_ttl(start: Timestamp, ttl: &Duration) -> Timestamp {
+    start + Seconds::new(ttl.whole_seconds().try_into().unwrap_or_default())
+}
+
+/// Extracts the session generation the session was created with
+fn generation_from_state(session_state: &SessionState) -> Option<i32> {
+    session_state
+        .get(TypedSession::SESSION_GENERATION_KEY)
+        .and_then(|value| serde_json::from_str(value).ok())
+}
+
+/// Extracts the username from the session if the user is logged in
+fn username_from_state(session_state: &SessionState) -> Option<Username> {
+    session_state
+        .get(TypedSession::USER_INFO_KEY)
+        .and_then(|value| serde_json::from_str::<UserInfo>(value).ok())
+        .map(|user_info| user_info.username)
+}
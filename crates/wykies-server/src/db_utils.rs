@@ -19,3 +19,12 @@ pub fn validate_rows_affected(
 pub fn db_int_to_bool(value: i8) -> bool {
     value != 0
 }
+
+#[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+pub fn db_timestamp(timestamp: wykies_time::Timestamp) -> anyhow::Result<i64> {
+    use anyhow::Context as _;
+    timestamp
+        .as_secs_since_unix_epoch()
+        .try_into()
+        .context("failed to convert timestamp into DB format")
+}
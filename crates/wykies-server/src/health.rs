@@ -0,0 +1,123 @@
+//! Health reporting for the server and its plugins
+//!
+//! Each [`HealthCheck`] is run when a status is requested and the results are
+//! combined into a [`HealthReport`]. The server registers checks for the
+//! database, Redis (when used for sessions) and the TLS certificate (when TLS
+//! is enabled). Plugins provide their own via
+//! [`crate::plugin::ServerPluginArtifacts::health_checks`].
+
+use anyhow::Context as _;
+use futures_util::future::{BoxFuture, join_all};
+use std::sync::Arc;
+use wykies_shared::{
+    db_types::DbPool,
+    health::{HealthCheckResult, HealthReport},
+};
+
+#[cfg(not(feature = "disable-tls"))]
+use wykies_shared::{const_config::server::HEALTH_TLS_CERT_EXPIRY_WARNING, health::HealthStatus};
+
+pub trait HealthCheck: Send + Sync {
+    fn check(&self) -> BoxFuture<'_, HealthCheckResult>;
+}
+
+/// All the checks that are run to produce a [`HealthReport`]
+#[derive(Clone, Default)]
+pub struct HealthChecks(Vec<Arc<dyn HealthCheck>>);
+
+impl HealthChecks {
+    pub fn push(&mut self, check: Arc<dyn HealthCheck>) {
+        self.0.push(check);
+    }
+
+    /// Runs all checks concurrently
+    #[tracing::instrument(skip(self))]
+    pub async fn run(&self) -> HealthReport {
+        let checks = join_all(self.0.iter().map(|x| x.check())).await;
+        HealthReport::new(checks)
+    }
+}
+
+impl Extend<Arc<dyn HealthCheck>> for HealthChecks {
+    fn extend<I: IntoIterator<Item = Arc<dyn HealthCheck>>>(&mut self, iter: I) {
+        self.0.extend(iter);
+    }
+}
+
+impl std::fmt::Debug for HealthChecks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HealthChecks")
+            .field("count", &self.0.len())
+            .finish()
+    }
+}
+
+/// Checks that a connection to the database can be acquired
+#[derive(Debug)]
+pub struct DbHealthCheck(pub DbPool);
+
+impl HealthCheck for DbHealthCheck {
+    fn check(&self) -> BoxFuture<'_, HealthCheckResult> {
+        Box::pin(async {
+            let result = self
+                .0
+                .acquire()
+                .await
+                .map(|_| String::new())
+                .context("failed to acquire a connection");
+            HealthCheckResult::from_result("Connect to Database", result)
+        })
+    }
+}
+
+/// Checks that the Redis server used for sessions responds to a PING
+#[cfg(feature = "redis-session-rustls")]
+pub struct RedisHealthCheck(pub secrecy::SecretString);
+
+#[cfg(feature = "redis-session-rustls")]
+impl HealthCheck for RedisHealthCheck {
+    fn check(&self) -> BoxFuture<'_, HealthCheckResult> {
+        use secrecy::ExposeSecret as _;
+        Box::pin(async {
+            let result = async {
+                let client =
+                    redis::Client::open(self.0.expose_secret()).context("invalid redis uri")?;
+                let mut connection = client
+                    .get_multiplexed_async_connection()
+                    .await
+                    .context("failed to connect to Redis")?;
+                redis::cmd("PING")
+                    .query_async::<String>(&mut connection)
+                    .await
+                    .context("failed to PING Redis")
+            }
+            .await;
+            HealthCheckResult::from_result("Connect to Redis", result)
+        })
+    }
+}
+
+/// Checks how long until the TLS certificate expires
+#[cfg(not(feature = "disable-tls"))]
+#[derive(Debug)]
+pub struct TlsCertHealthCheck;
+
+#[cfg(not(feature = "disable-tls"))]
+impl HealthCheck for TlsCertHealthCheck {
+    fn check(&self) -> BoxFuture<'_, HealthCheckResult> {
+        const NAME: &str = "TLS Certificate";
+        Box::pin(async {
+            let expiry = match crate::tls::load_cert_expiry() {
+                Ok(x) => x,
+                Err(e) => return HealthCheckResult::from_result(NAME, Err(e)),
+            };
+            let message = format!("expires {}", expiry.display_as_utc_datetime_long());
+            let status = match expiry.seconds_since(wykies_time::Timestamp::now()) {
+                Some(remaining) if remaining >= HEALTH_TLS_CERT_EXPIRY_WARNING => HealthStatus::Ok,
+                Some(remaining) if !remaining.is_zero() => HealthStatus::Degraded,
+                _ => HealthStatus::Error,
+            };
+            HealthCheckResult::new(NAME, status, message)
+        })
+    }
+}
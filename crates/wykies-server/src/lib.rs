@@ -11,6 +11,7 @@ use wykies_time as _;
 mod warning_suppress_disabled_tls {
     use rustls as _;
     use rustls_pki_types as _;
+    use x509_parser as _;
 }
 
 #[cfg(all(feature = "disable-cors", not(debug_assertions)))]
@@ -23,9 +24,16 @@ mod warning_suppress_release {
 #[cfg(all(not(feature = "mysql"), not(feature = "postgres")))]
 compile_error!("At least one database must be selected using feature flags");
 
+pub mod analytics;
+pub mod audit;
 pub mod authentication;
 mod configuration;
+#[cfg(feature = "db-session")]
+mod db_session;
 pub mod db_utils;
+pub mod health;
+pub mod notifier;
+pub mod openapi;
 pub mod plugin;
 pub mod routes;
 mod session_state;
@@ -33,6 +41,8 @@ mod startup;
 mod tls;
 
 pub use configuration::{Configuration, DatabaseSettings, get_configuration};
+#[cfg(feature = "db-session")]
+pub use db_session::{DbSessionStore, SessionPurgeTask, purge_expired_sessions};
 pub use startup::{
     ApiServerBuilder, ApiServerInitBundle, ServerTask, get_db_connection_pool, get_socket_address,
     initialize_tracing,
@@ -0,0 +1,151 @@
+//! Delivers messages to users outside of the application (for example
+//! password reset tokens)
+//!
+//! Which [`Notifier`] is used is selected by [`NotifierSettings`]. Emails are
+//! sent using [`SmtpNotifier`] and [`FileNotifier`] is intended for local
+//! development and tests where no mail server is available.
+
+use anyhow::Context as _;
+use futures_util::future::BoxFuture;
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport as _, Message, Tokio1Executor, message::Mailbox,
+    transport::smtp::authentication::Credentials,
+};
+use secrecy::{ExposeSecret as _, SecretString};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write as _,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use wykies_shared::{
+    telemetry::spawn_blocking_with_tracing,
+    uac::{EmailAddress, Username},
+};
+
+pub trait Notifier: Send + Sync {
+    fn send(&self, notification: Notification) -> BoxFuture<'_, anyhow::Result<()>>;
+}
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub username: Username,
+    pub to: EmailAddress,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(serde::Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum NotifierSettings {
+    Smtp(SmtpSettings),
+    File {
+        /// Each user's notifications are appended to a file named after them
+        /// in this folder
+        folder: PathBuf,
+    },
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct SmtpSettings {
+    /// Host name of the server to relay through (TLS is required)
+    pub relay: String,
+    pub username: String,
+    pub password: SecretString,
+    /// Mailbox the emails are sent from, for example `Name <user@example.com>`
+    pub from: String,
+}
+
+impl NotifierSettings {
+    pub fn build(&self) -> anyhow::Result<Arc<dyn Notifier>> {
+        Ok(match self {
+            NotifierSettings::Smtp(settings) => Arc::new(SmtpNotifier::new(settings)?),
+            NotifierSettings::File { folder } => Arc::new(FileNotifier::new(folder.clone())?),
+        })
+    }
+}
+
+pub struct SmtpNotifier {
+    from: Mailbox,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpNotifier {
+    pub fn new(settings: &SmtpSettings) -> anyhow::Result<Self> {
+        let from = settings
+            .from
+            .parse()
+            .context("failed to parse from mailbox")?;
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::relay(&settings.relay)
+            .context("failed to build AsyncSmtpTransport")?
+            .credentials(Credentials::new(
+                settings.username.clone(),
+                settings.password.expose_secret().to_string(),
+            ))
+            .build();
+        Ok(Self { from, transport })
+    }
+}
+
+impl Notifier for SmtpNotifier {
+    fn send(&self, notification: Notification) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async move {
+            let to: Mailbox = notification
+                .to
+                .as_str()
+                .parse()
+                .context("failed to parse to email address")?;
+            let message = Message::builder()
+                .from(self.from.clone())
+                .to(to)
+                .subject(notification.subject)
+                .body(notification.body)
+                .context("failed to build email")?;
+            self.transport
+                .send(message)
+                .await
+                .context("failed to send email")?;
+            Ok(())
+        })
+    }
+}
+
+pub struct FileNotifier {
+    folder: PathBuf,
+}
+
+impl FileNotifier {
+    pub fn new(folder: PathBuf) -> anyhow::Result<Self> {
+        fs::create_dir_all(&folder)
+            .with_context(|| format!("failed to create notifications folder: {folder:?}"))?;
+        Ok(Self { folder })
+    }
+
+    /// The file that notifications for `username` are written to
+    pub fn path_for(folder: &Path, username: &Username) -> PathBuf {
+        folder.join(format!("{username}.txt"))
+    }
+}
+
+impl Notifier for FileNotifier {
+    fn send(&self, notification: Notification) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async move {
+            let path = Self::path_for(&self.folder, &notification.username);
+            spawn_blocking_with_tracing(move || {
+                let mut file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+                    .with_context(|| format!("failed to open notification file: {path:?}"))?;
+                writeln!(
+                    file,
+                    "To: {}\nSubject: {}\n\n{}\n",
+                    notification.to, notification.subject, notification.body
+                )
+                .with_context(|| format!("failed to write notification to: {path:?}"))
+            })
+            .await
+            .context("failed to spawn blocking task")?
+        })
+    }
+}
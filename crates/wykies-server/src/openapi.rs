@@ -0,0 +1,299 @@
+//! OpenAPI 3 description of the API for integrators not using our client
+//!
+//! Paths and methods come from the [`PathSpec`] constants, the required
+//! permissions from the permission map (see
+//! [`wykies_shared::uac::get_required_permissions`]) and the schemas are
+//! generated from the argument and response types. Plugins and applications
+//! describe the routes they add via
+//! [`crate::ApiServerBuilder::api_operations`].
+
+use schemars::{JsonSchema, Schema, SchemaGenerator, generate::SchemaSettings};
+use serde_json::{Map, Value, json};
+use wykies_shared::{
+    api_error::ApiError,
+    branch::{Branch, BranchDraft, BranchId},
+    const_config::path::*,
+    health::HealthReport,
+    host_branch::HostBranchPair,
+    req_args::{
+        LoginReqArgs, RonWrapper,
+        api::{ChangePasswordReqArgs, analytics, api_key, audit, host_branch, role, totp, user},
+        password_reset,
+    },
+    uac::{
+        ApiKeyInfo, ApiKeySecret, ListUsersRoles, LoginResponse, PasswordPolicy, Role, RoleDiff,
+        RoleDraft, RoleId, TotpEnrolment, TotpRecoveryCodes, UserMetadata, UserPermissions,
+    },
+    version::VersionInfo,
+};
+
+/// Name of the security scheme used for endpoints that require login
+const SECURITY_SCHEME: &str = "session";
+
+/// Name of the security scheme used for service accounts (See
+/// [`crate::authentication::validate_api_key`])
+const API_KEY_SECURITY_SCHEME: &str = "api_key";
+
+type SchemaFn = fn(&mut SchemaGenerator) -> Schema;
+
+/// Describes a single endpoint
+#[derive(Debug, Clone)]
+pub struct ApiOperation {
+    path_spec: PathSpec,
+    summary: &'static str,
+    request: Option<SchemaFn>,
+    response: Option<SchemaFn>,
+}
+
+/// The generated document, served as is
+#[derive(Debug)]
+pub struct OpenApiDocument(pub Value);
+
+impl ApiOperation {
+    pub fn new(path_spec: PathSpec, summary: &'static str) -> Self {
+        Self {
+            path_spec,
+            summary,
+            request: None,
+            response: None,
+        }
+    }
+
+    /// Sets the type of the arguments. They are expected as query parameters
+    /// for GET requests and as a JSON body otherwise
+    pub fn request<T: JsonSchema>(mut self) -> Self {
+        self.request = Some(subschema::<T>);
+        self
+    }
+
+    /// Sets the type of the JSON body returned on success
+    pub fn response<T: JsonSchema>(mut self) -> Self {
+        self.response = Some(subschema::<T>);
+        self
+    }
+
+    fn method(&self) -> String {
+        self.path_spec.method.as_str().to_lowercase()
+    }
+
+    fn to_value(&self, generator: &mut SchemaGenerator, error_schema: &Schema) -> Value {
+        let success = match self.response {
+            Some(response) => json!({
+                "description": "Success",
+                "content": { "application/json": { "schema": response(generator) } }
+            }),
+            None => json!({ "description": "Success" }),
+        };
+        let mut result = json!({
+            "operationId": format!(
+                "{}_{}",
+                self.method(),
+                self.path_spec.path.trim_matches('/').replace(['/', '.'], "_")
+            ),
+            "summary": self.summary,
+            "responses": {
+                "200": success,
+                "default": {
+                    "description": "Error",
+                    "content": { "application/json": { "schema": error_schema } }
+                }
+            }
+        });
+        if let Some(request) = self.request {
+            let schema = request(generator);
+            if self.path_spec.method.as_str() == "GET" {
+                result["parameters"] = json!([{
+                    "name": "args",
+                    "in": "query",
+                    "required": true,
+                    "style": "form",
+                    "explode": true,
+                    "schema": schema
+                }]);
+            } else {
+                result["requestBody"] = json!({
+                    "required": true,
+                    "content": { "application/json": { "schema": schema } }
+                });
+            }
+        }
+        match wykies_shared::uac::get_required_permissions(self.path_spec.path) {
+            Some(permissions) => {
+                result["security"] =
+                    json!([{ SECURITY_SCHEME: [] }, { API_KEY_SECURITY_SCHEME: [] }]);
+                result["x-required-permissions"] = json!(permissions);
+            }
+            None => result["security"] = json!([]),
+        }
+        result
+    }
+}
+
+impl OpenApiDocument {
+    /// Generates the document from the operations provided
+    ///
+    /// # Panics
+    ///
+    /// If the permissions have not been initialized
+    pub fn new(title: &str, version: &str, operations: &[ApiOperation]) -> Self {
+        let mut generator = SchemaSettings::openapi3().into_generator();
+        let error_schema = generator.subschema_for::<ApiError>();
+        let mut paths = Map::new();
+        for operation in operations {
+            let item = paths
+                .entry(operation.path_spec.path)
+                .or_insert_with(|| json!({}));
+            item[operation.method().as_str()] = operation.to_value(&mut generator, &error_schema);
+        }
+        Self(json!({
+            "openapi": "3.0.3",
+            "info": { "title": title, "version": version },
+            "paths": paths,
+            "components": {
+                "schemas": generator.take_definitions(true),
+                "securitySchemes": {
+                    SECURITY_SCHEME: { "type": "apiKey", "in": "cookie", "name": "id" },
+                    API_KEY_SECURITY_SCHEME: { "type": "http", "scheme": "bearer" }
+                }
+            }
+        }))
+    }
+}
+
+fn subschema<T: JsonSchema>(generator: &mut SchemaGenerator) -> Schema {
+    generator.subschema_for::<T>()
+}
+
+/// The endpoints provided by the server itself
+pub(crate) fn server_operations() -> Vec<ApiOperation> {
+    let mut result = vec![
+        ApiOperation::new(PATH_API_ANALYTICS_SUMMARY, "Usage of each endpoint")
+            .request::<analytics::SummaryReqArgs>()
+            .response::<Vec<wykies_shared::analytics::EndpointSummary>>(),
+        ApiOperation::new(PATH_API_API_KEY_LIST, "List all API keys").response::<Vec<ApiKeyInfo>>(),
+        ApiOperation::new(
+            PATH_API_API_KEY_NEW,
+            "Create an API key for a service account. The key is only returned once",
+        )
+        .request::<api_key::NewReqArgs>()
+        .response::<ApiKeySecret>(),
+        ApiOperation::new(PATH_API_API_KEY_REVOKE, "Permanently disable an API key")
+            .request::<api_key::LookupReqArgs>(),
+        ApiOperation::new(
+            PATH_API_API_KEY_ROTATE,
+            "Replace an API key with a new one. The key is only returned once",
+        )
+        .request::<api_key::LookupReqArgs>()
+        .response::<ApiKeySecret>(),
+        ApiOperation::new(PATH_API_AUDIT_LIST, "Page of the audit log, newest first")
+            .request::<audit::ListReqArgs>()
+            .response::<Vec<wykies_shared::audit::AuditEntry>>(),
+        ApiOperation::new(PATH_API_BRANCH_NEW, "Create a branch")
+            .request::<BranchDraft>()
+            .response::<BranchId>(),
+        ApiOperation::new(
+            PATH_API_CHANGE_PASSWORD,
+            "Change the logged in user's password",
+        )
+        .request::<ChangePasswordReqArgs>(),
+        ApiOperation::new(PATH_API_HOSTBRANCH_LIST, "List all host branch pairs")
+            .response::<Vec<HostBranchPair>>(),
+        ApiOperation::new(PATH_API_HOSTBRANCH_SET, "Set the branch for a host")
+            .request::<HostBranchPair>(),
+        ApiOperation::new(PATH_API_HOSTBRANCH, "Lookup the branch for a host")
+            .request::<host_branch::LookupReqArgs>()
+            .response::<Option<BranchId>>(),
+        ApiOperation::new(PATH_API_LOGOUT, "End the current session"),
+        ApiOperation::new(PATH_API_OPENAPI, "This document"),
+        ApiOperation::new(
+            PATH_API_PASSWORD_POLICY,
+            "Get the rules new passwords must meet",
+        )
+        .response::<PasswordPolicy>(),
+        ApiOperation::new(PATH_API_ROLE_DELETE, "Delete a role that is no longer used")
+            .request::<role::LookupReqArgs>(),
+        ApiOperation::new(PATH_API_ROLE_LIST, "List all roles").response::<Vec<Role>>(),
+        ApiOperation::new(PATH_API_ROLE_NEW, "Create a role")
+            .request::<RoleDraft>()
+            .response::<RoleId>(),
+        ApiOperation::new(
+            PATH_API_ROLE_PARENTS,
+            "Set the roles a role inherits permissions from",
+        )
+        .request::<role::SetParentsReqArgs>(),
+        ApiOperation::new(PATH_API_ROLE_UPDATE, "Update a role").request::<RoleDiff>(),
+        ApiOperation::new(PATH_API_ROLE, "Lookup a role")
+            .request::<role::LookupReqArgs>()
+            .response::<Role>(),
+        ApiOperation::new(
+            PATH_API_TOTP_CONFIRM,
+            "Enable two-factor authentication using a code from the enrolment secret",
+        )
+        .request::<totp::ConfirmReqArgs>()
+        .response::<TotpRecoveryCodes>(),
+        ApiOperation::new(
+            PATH_API_TOTP_ENROL,
+            "Start enrolment in two-factor authentication",
+        )
+        .response::<TotpEnrolment>(),
+        ApiOperation::new(PATH_API_USER_NEW, "Create a user").request::<user::NewUserReqArgs>(),
+        ApiOperation::new(PATH_API_USER_PASSWORD_RESET, "Set a user's password")
+            .request::<user::PasswordResetReqArgs>(),
+        ApiOperation::new(
+            PATH_API_USER_PERMISSIONS,
+            "Permissions of a user from directly assigned and inherited roles",
+        )
+        .request::<user::LookupReqArgs>()
+        .response::<UserPermissions>(),
+        ApiOperation::new(PATH_API_USER_ROLE_SET, "Add a role to a user")
+            .request::<user::AssignReqArgs>(),
+        ApiOperation::new(
+            PATH_API_USER_SESSIONS_REVOKE,
+            "Revoke one of a user's sessions or all of them to force the user to login again \
+            on all hosts",
+        )
+        .request::<user::RevokeSessionsReqArgs>(),
+        ApiOperation::new(
+            PATH_API_USER_TOTP_RESET,
+            "Disable a user's two-factor authentication",
+        )
+        .request::<user::LookupReqArgs>(),
+        ApiOperation::new(
+            PATH_API_USER_UPDATE,
+            "Update a user. The data is a `UserMetadataDiff` encoded as RON",
+        )
+        .request::<RonWrapper>(),
+        ApiOperation::new(PATH_API_USER, "Lookup a user")
+            .request::<user::LookupReqArgs>()
+            .response::<UserMetadata>(),
+        ApiOperation::new(PATH_API_USERS_LIST_AND_ROLES, "List all users and roles")
+            .response::<ListUsersRoles>(),
+        ApiOperation::new(PATH_BRANCH_LIST, "List all branches").response::<Vec<Branch>>(),
+        ApiOperation::new(PATH_HEALTH_CHECK, "Succeeds if the server is running"),
+        ApiOperation::new(PATH_LOGIN, "Start a session")
+            .request::<LoginReqArgs>()
+            .response::<LoginResponse>(),
+        ApiOperation::new(
+            PATH_PASSWORD_RESET_REDEEM,
+            "Set a new password using a password reset token",
+        )
+        .request::<password_reset::RedeemReqArgs>(),
+        ApiOperation::new(
+            PATH_PASSWORD_RESET_REQUEST,
+            "Send a password reset token to the user's email address",
+        )
+        .request::<password_reset::RequestReqArgs>(),
+        ApiOperation::new(PATH_STATUS_JSON, "Results of the health checks")
+            .response::<HealthReport>(),
+        ApiOperation::new(PATH_VERSION, "Versions used to check compatibility")
+            .response::<VersionInfo>(),
+    ];
+    if cfg!(feature = "db-session") {
+        result.push(
+            ApiOperation::new(PATH_API_USER_SESSIONS, "List a user's active sessions")
+                .request::<user::LookupReqArgs>()
+                .response::<Vec<wykies_shared::uac::SessionInfo>>(),
+        );
+    }
+    result
+}
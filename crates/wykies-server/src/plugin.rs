@@ -1,4 +1,4 @@
-use crate::ServerTask;
+use crate::{ServerTask, health::HealthCheck};
 use std::sync::Arc;
 use tracked_cancellations::TrackedCancellationToken;
 use ws_helpers::WebSocketSettings;
@@ -10,9 +10,14 @@ where
 {
     pub task: T,
     pub handle: Arc<H>,
+    /// Included in the server's health report (See [`crate::health`])
+    pub health_checks: Vec<Arc<dyn HealthCheck>>,
 }
 
 pub trait ServerPlugin {
+    /// Reported to clients so they can check that the plugins they need are
+    /// available
+    const ID: &'static str;
     type Config;
     type Task: ServerTask;
     type Handle: Send;
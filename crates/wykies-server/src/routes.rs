@@ -1,26 +1,51 @@
+mod analytics;
+mod api_key;
+mod audit;
 mod branch;
 mod health_check;
 mod host_branch;
 mod login;
 mod logout;
+mod openapi;
 mod password;
 mod role;
+#[cfg(feature = "db-session")]
+mod session;
 mod status;
+mod totp;
 mod user;
+mod version;
 
-use actix_web::{HttpRequest, HttpResponse};
+use actix_web::{HttpRequest, HttpResponse, http::StatusCode};
+pub use analytics::analytics_summary;
 use anyhow::Context;
+pub use api_key::{api_key_list, api_key_new, api_key_revoke, api_key_rotate};
+pub use audit::audit_list;
 pub use branch::{branch_list, branch_new};
 pub use health_check::health_check;
 pub use host_branch::{host_branch_pair_list, host_branch_pair_lookup, host_branch_pair_set};
 pub use login::login;
 pub use logout::log_out;
-pub use password::change_password;
-pub use role::{role, role_new};
-pub use status::status;
+pub use openapi::openapi;
+pub use password::{
+    change_password, password_policy, password_reset_redeem, password_reset_request,
+};
+pub use role::{role, role_delete, role_list, role_new, role_parents_set, role_update};
+#[cfg(feature = "db-session")]
+pub use session::user_sessions;
+pub use status::{status, status_json};
+pub use totp::{totp_confirm, totp_enrol, user_totp_reset};
 use tracing::Level;
-pub use user::{password_reset, role_assign, user, user_new, user_update, users_and_roles_list};
-use wykies_shared::{debug_panic, uac::Permissions};
+pub use user::{
+    password_reset, role_assign, user, user_new, user_permissions, user_sessions_revoke,
+    user_update, users_and_roles_list,
+};
+pub use version::version;
+use wykies_shared::{
+    api_error::{ApiError, ApiErrorCode},
+    debug_panic,
+    uac::Permissions,
+};
 
 pub fn execute_chained_handler<T>(
     path: &str,
@@ -38,9 +63,9 @@ pub fn execute_chained_handler<T>(
 #[tracing::instrument(level = Level::WARN)]
 pub async fn route_not_found(req: HttpRequest) -> actix_web::Result<HttpResponse> {
     debug_panic!("404 - {} to '{}' Not found\n", req.method(), req.path());
-    Ok(HttpResponse::NotFound().body(format!(
-        "404 - {} to '{}' Not found\n",
-        req.method(),
-        req.path()
-    )))
+    Ok(ApiError::new(
+        ApiErrorCode::NotFound,
+        format!("404 - {} to '{}' Not found", req.method(), req.path()),
+    )
+    .to_response(StatusCode::NOT_FOUND))
 }
@@ -0,0 +1,21 @@
+use crate::analytics::endpoint_summary;
+use actix_web::web;
+use wykies_shared::{
+    analytics::EndpointSummary, db_types::DbPool, e400, e500,
+    req_args::api::analytics::SummaryReqArgs,
+};
+use wykies_time::Timestamp;
+
+#[tracing::instrument(err(Debug), skip(pool))]
+pub async fn analytics_summary(
+    pool: web::Data<DbPool>,
+    web::Query(SummaryReqArgs { since, until }): web::Query<SummaryReqArgs>,
+) -> actix_web::Result<web::Json<Vec<EndpointSummary>>> {
+    let pool: &DbPool = &pool;
+    let until = until.unwrap_or_else(Timestamp::now);
+    if until <= since {
+        return Err(e400("until must be after since"));
+    }
+    let result = endpoint_summary(pool, since, until).await.map_err(e500)?;
+    Ok(web::Json(result))
+}
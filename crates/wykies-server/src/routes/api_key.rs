@@ -0,0 +1,130 @@
+use crate::{audit::AuditContext, authentication};
+use actix_web::{HttpResponse, web};
+use anyhow::Context as _;
+use wykies_shared::{
+    audit::AuditAction,
+    db_types::DbPool,
+    e400, e500,
+    req_args::api::api_key::{LookupReqArgs, NewReqArgs},
+    uac::{ApiKeyInfo, ApiKeySecret, UserInfo},
+};
+
+#[tracing::instrument(ret, err(Debug), skip(pool))]
+pub async fn api_key_list(
+    pool: web::Data<DbPool>,
+) -> actix_web::Result<web::Json<Vec<ApiKeyInfo>>> {
+    Ok(web::Json(
+        authentication::list_api_keys(&pool).await.map_err(e500)?,
+    ))
+}
+
+/// The key is only returned by this call, it cannot be retrieved later.
+/// Permissions that the caller does not hold cannot be given to a key and the
+/// name cannot be the same as an existing user's
+#[tracing::instrument(err(Debug), skip(pool))]
+pub async fn api_key_new(
+    pool: web::Data<DbPool>,
+    web::Json(args): web::Json<NewReqArgs>,
+    user_info: web::ReqData<UserInfo>,
+    audit: AuditContext,
+) -> actix_web::Result<web::Json<ApiKeySecret>> {
+    let requested: Vec<_> = args.permissions.0.iter().cloned().collect();
+    user_info
+        .permissions
+        .includes(&requested)
+        .converting_missing_perms_to_error()?;
+    if authentication::is_name_used_by_user(&args.name, &pool)
+        .await
+        .map_err(e500)?
+    {
+        return Err(e400("name is already used by a user"));
+    }
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("failed to start transaction")
+        .map_err(e500)?;
+    let api_key = authentication::create_api_key(&args, &mut *transaction)
+        .await
+        .map_err(e500)?;
+    audit
+        .record(
+            &mut transaction,
+            AuditAction::ApiKeyCreated,
+            api_key.id,
+            &serde_json::json!({
+                "name": args.name,
+                "permissions": String::from(&args.permissions),
+                "branch_id": args.branch_id,
+                "valid_for_days": args.valid_for_days,
+            }),
+        )
+        .await
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("failed to commit new api key")
+        .map_err(e500)?;
+    Ok(web::Json(api_key))
+}
+
+/// Returns a new key that replaces the old one. Like [`api_key_new`] this is
+/// the only time the key is available
+#[tracing::instrument(err(Debug), skip(pool))]
+pub async fn api_key_rotate(
+    pool: web::Data<DbPool>,
+    web::Json(LookupReqArgs { id }): web::Json<LookupReqArgs>,
+    audit: AuditContext,
+) -> actix_web::Result<web::Json<ApiKeySecret>> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("failed to start transaction")
+        .map_err(e500)?;
+    let Some(api_key) = authentication::rotate_api_key(id, &mut *transaction)
+        .await
+        .map_err(e500)?
+    else {
+        return Err(e400("no active api key found with that id"));
+    };
+    audit
+        .record(&mut transaction, AuditAction::ApiKeyRotated, id, &())
+        .await
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("failed to commit api key rotation")
+        .map_err(e500)?;
+    Ok(web::Json(api_key))
+}
+
+#[tracing::instrument(ret, err(Debug), skip(pool))]
+pub async fn api_key_revoke(
+    pool: web::Data<DbPool>,
+    web::Json(LookupReqArgs { id }): web::Json<LookupReqArgs>,
+    audit: AuditContext,
+) -> actix_web::Result<HttpResponse> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("failed to start transaction")
+        .map_err(e500)?;
+    if !authentication::revoke_api_key(id, &mut *transaction)
+        .await
+        .map_err(e500)?
+    {
+        return Err(e400("no active api key found with that id"));
+    }
+    audit
+        .record(&mut transaction, AuditAction::ApiKeyRevoked, id, &())
+        .await
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("failed to commit api key revocation")
+        .map_err(e500)?;
+    Ok(HttpResponse::Ok().finish())
+}
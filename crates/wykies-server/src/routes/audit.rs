@@ -0,0 +1,13 @@
+use crate::audit::list_audit_entries;
+use actix_web::web;
+use wykies_shared::{audit::AuditEntry, db_types::DbPool, e500, req_args::api::audit::ListReqArgs};
+
+#[tracing::instrument(err(Debug), skip(pool))]
+pub async fn audit_list(
+    pool: web::Data<DbPool>,
+    web::Query(args): web::Query<ListReqArgs>,
+) -> actix_web::Result<web::Json<Vec<AuditEntry>>> {
+    let pool: &DbPool = &pool;
+    let result = list_audit_entries(pool, &args).await.map_err(e500)?;
+    Ok(web::Json(result))
+}
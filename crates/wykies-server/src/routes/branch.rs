@@ -1,8 +1,10 @@
+use crate::audit::AuditContext;
 #[cfg(feature = "mysql")]
 use crate::db_utils::validate_one_row_affected;
 use actix_web::web;
 use anyhow::Context;
 use wykies_shared::{
+    audit::AuditAction,
     branch::BranchId,
     branch::{Branch, BranchDraft},
     db_types::DbPool,
@@ -49,10 +51,16 @@ pub async fn branch_list(pool: web::Data<DbPool>) -> actix_web::Result<web::Json
 pub async fn branch_new(
     pool: web::Data<DbPool>,
     web::Json(draft): web::Json<BranchDraft>,
+    audit: AuditContext,
 ) -> actix_web::Result<web::Json<BranchId>> {
     let pool: &DbPool = &pool;
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("failed to start transaction")
+        .map_err(e500)?;
     #[cfg(feature = "mysql")]
-    let result = {
+    let result: BranchId = {
         let sql_result = sqlx::query!(
             "INSERT INTO `branch` 
             (`BranchID`, `BranchName`, `ShortName`, `BranchAddress`) 
@@ -60,7 +68,7 @@ pub async fn branch_new(
             draft.name,
             draft.short_name
         )
-        .execute(pool)
+        .execute(&mut *transaction)
         .await
         .context("failed to insert branch")
         .map_err(e500)?;
@@ -68,7 +76,7 @@ pub async fn branch_new(
         sql_result.last_insert_id().into()
     };
     #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
-    let result = {
+    let result: BranchId = {
         // TODO 5: Check why encode trait impl doesn't make converting not necessary
         sqlx::query!(
             "INSERT INTO branch
@@ -77,11 +85,20 @@ pub async fn branch_new(
             draft.name.as_ref(),
             draft.short_name.to_string(),
         )
-        .fetch_one(pool)
+        .fetch_one(&mut *transaction)
         .await
         .map_err(e500)?
         .branch_id
         .try_into()?
     };
+    audit
+        .record(&mut transaction, AuditAction::BranchCreated, result, &draft)
+        .await
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("failed to commit new branch")
+        .map_err(e500)?;
     Ok(web::Json(result))
 }
@@ -1,7 +1,8 @@
+use crate::audit::{AuditChange, AuditContext};
 use actix_web::{HttpResponse, web};
 use anyhow::Context as _;
 use wykies_shared::{
-    branch::BranchId, db_types::DbPool, e500, host_branch::HostBranchPair,
+    audit::AuditAction, branch::BranchId, db_types::DbPool, e500, host_branch::HostBranchPair,
     req_args::api::host_branch,
 };
 
@@ -9,8 +10,21 @@ use wykies_shared::{
 pub async fn host_branch_pair_set(
     pool: web::Data<DbPool>,
     web::Json(pair): web::Json<HostBranchPair>,
+    audit: AuditContext,
 ) -> actix_web::Result<HttpResponse> {
+    let web::Json(before) = host_branch_pair_lookup(
+        pool.clone(),
+        web::Query(host_branch::LookupReqArgs {
+            host_id: pair.host_id.clone(),
+        }),
+    )
+    .await?;
     let pool: &DbPool = &pool;
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("failed to start transaction")
+        .map_err(e500)?;
     #[cfg(feature = "mysql")]
     let query = sqlx::query!(
         "INSERT INTO `hostbranch` 
@@ -37,11 +51,28 @@ pub async fn host_branch_pair_set(
     };
 
     query
-        .execute(pool)
+        .execute(&mut *transaction)
         .await
         .context("failed to set host_branch_pair")
         .map_err(e500)?;
     // Can not validate number of rows because it can change if update to same, insert new or update https://dev.mysql.com/doc/refman/8.4/en/insert-on-duplicate.html
+    audit
+        .record(
+            &mut transaction,
+            AuditAction::HostBranchPairSet,
+            &pair.host_id,
+            &AuditChange {
+                before: &before,
+                after: &pair.branch_id,
+            },
+        )
+        .await
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("failed to commit host_branch_pair")
+        .map_err(e500)?;
     Ok(HttpResponse::Ok().finish())
 }
 
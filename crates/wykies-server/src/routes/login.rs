@@ -1,8 +1,13 @@
 use super::{execute_chained_handler, host_branch_pair_set};
 use crate::{
-    authentication::{AuthUserInfo, Credentials, LoginAttemptLimit, validate_credentials},
+    audit::AuditContext,
+    authentication::{
+        AuthUserInfo, Credentials, HostLoginThrottle, LoginAttemptLimit, LoginThrottling,
+        PasswordExpiry, PasswordHashing, TotpLoginCheck, TotpSettings, check_totp_for_login,
+        consume_totp_code, validate_credentials,
+    },
     routes::host_branch_pair_lookup,
-    session_state::TypedSession,
+    session_state::{ClientBinding, TypedSession},
 };
 use actix_web::{HttpResponse, dev::ConnectionInfo, web};
 use anyhow::{Context, anyhow};
@@ -14,26 +19,41 @@ use wykies_shared::{
     req_args::{LoginReqArgs, api::host_branch},
     uac::{AuthError, LoginResponse},
 };
+use wykies_time::Timestamp;
 
 /// Provides a way for users to create a login session
 ///
 /// - Only successful responses should return a 200 and errors should either by
-///   401 or 500
+///   401, 429 or 500
 /// - A successful login provides a cookie to access authenticated routes
 /// - A login can fail for various reasons and should provide a suitable error
 ///   message
-/// - Sessions should automatically timeout after a period of time
+/// - Sessions automatically timeout after a period of time (See
+///   [`crate::authentication::SessionLifetimes`])
+/// - Users with two-factor authentication enabled get
+///   [`LoginResponse::TotpRequired`] and no session until they resend the
+///   login including a code
+/// - Expired passwords get [`LoginResponse::SuccessForcePassChange`] and
+///   passwords close to expiring include a warning (See [`PasswordExpiry`])
+/// - Repeated failures are slowed down per user and per host and get a 429
+///   while throttled (See [`LoginThrottling`] and [`HostLoginThrottle`])
 #[tracing::instrument(
     ret,
     err(Debug, level = tracing::Level::INFO),
-    skip(req_args, pool, session),
+    skip(req_args, pool, password_hashing, host_login_throttle, session),
     fields(username=tracing::field::Empty)
 )]
+#[expect(clippy::too_many_arguments)] // All arguments are well typed, no material benefit from creating a type
 pub async fn login(
     conn: ConnectionInfo,
     web::Json(req_args): web::Json<LoginReqArgs>,
     pool: web::Data<DbPool>,
     login_attempt_limit: web::Data<LoginAttemptLimit>,
+    login_throttling: web::Data<LoginThrottling>,
+    host_login_throttle: web::Data<HostLoginThrottle>,
+    totp_settings: web::Data<TotpSettings>,
+    password_expiry: web::Data<PasswordExpiry>,
+    password_hashing: web::Data<PasswordHashing>,
     session: TypedSession,
 ) -> Result<HttpResponse, AuthError> {
     let credentials = Credentials {
@@ -42,9 +62,44 @@ pub async fn login(
     };
 
     tracing::Span::current().record("username", tracing::field::display(&credentials.username));
-    let auth_user_info = validate_credentials(credentials, &pool, &login_attempt_limit).await?;
-    let set_user_branch_result =
-        set_user_branch(&pool, auth_user_info, conn, req_args.branch_to_set).await;
+    let client_identifier: HostId = conn.try_into().context("failed to get host_id")?;
+    if let Some(retry_after) = host_login_throttle.retry_after(&client_identifier, Timestamp::now())
+    {
+        return Err(AuthError::TooManyAttempts { retry_after });
+    }
+    let record_host_failure =
+        |e: &AuthError| host_login_throttle.record(&client_identifier, e, Timestamp::now());
+    let auth_user_info = validate_credentials(
+        credentials,
+        &pool,
+        &login_attempt_limit,
+        &login_throttling,
+        &password_hashing,
+    )
+    .await
+    .inspect_err(record_host_failure)?;
+    let totp_login_check = check_totp_for_login(
+        &auth_user_info,
+        req_args.totp_code,
+        &pool,
+        &login_attempt_limit,
+        &totp_settings,
+    )
+    .await
+    .inspect_err(record_host_failure)?;
+    if matches!(totp_login_check, TotpLoginCheck::CodeRequired) {
+        return Ok(HttpResponse::Ok().json(LoginResponse::TotpRequired));
+    }
+    let username = auth_user_info.username.clone();
+    let session_generation = auth_user_info.session_generation;
+    let set_user_branch_result = set_user_branch(
+        &pool,
+        auth_user_info,
+        client_identifier.clone(),
+        req_args.branch_to_set,
+        &password_expiry,
+    )
+    .await;
     if set_user_branch_result
         .as_ref()
         .is_err_and(|x| x.is_branch_not_set_resend())
@@ -55,13 +110,53 @@ pub async fn login(
             HttpResponse::FailedDependency().body(set_user_branch_result.unwrap_err().to_string())
         );
     }
-    let login_response = set_user_branch_result?;
+    let mut login_response = set_user_branch_result?;
+    let is_totp_enrolment_required = match totp_login_check {
+        TotpLoginCheck::Verified(code) => {
+            // Only consumed now so that a resend to set the branch does not need a new code
+            consume_totp_code(&username, code, &pool, &login_attempt_limit)
+                .await
+                .inspect_err(record_host_failure)?;
+            false
+        }
+        TotpLoginCheck::EnrolmentRequired => {
+            // Enrolment takes priority, a forced password change is still requested on the
+            // next login
+            login_response = match login_response {
+                LoginResponse::Success { user_info, .. }
+                | LoginResponse::SuccessForcePassChange(user_info) => {
+                    LoginResponse::SuccessTotpEnrolmentRequired(user_info)
+                }
+                other => other,
+            };
+            true
+        }
+        TotpLoginCheck::NotRequired | TotpLoginCheck::CodeRequired => false,
+    };
     session.renew();
     match &login_response {
-        LoginResponse::Success(user_info) | LoginResponse::SuccessForcePassChange(user_info) => {
+        LoginResponse::Success { user_info, .. }
+        | LoginResponse::SuccessForcePassChange(user_info)
+        | LoginResponse::SuccessTotpEnrolmentRequired(user_info) => {
             session
                 .insert_user_info(user_info.clone())
                 .context("session update failed")?;
+            session
+                .insert_session_start(session_generation)
+                .context("session update failed")?;
+            session
+                .insert_client_binding(ClientBinding {
+                    host_id: client_identifier,
+                })
+                .context("session update failed")?;
+            session
+                .insert_totp_enrolment_required(is_totp_enrolment_required)
+                .context("session update failed")?;
+        }
+        LoginResponse::TotpRequired => {
+            return Err(AuthError::UnexpectedError(anyhow!(
+                "totp required should have been returned before the session was created"
+            )));
         }
     }
     Ok(HttpResponse::Ok().json(login_response))
@@ -71,12 +166,10 @@ pub async fn login(
 async fn set_user_branch(
     pool: &DbPool,
     auth_user_info: AuthUserInfo,
-    conn: ConnectionInfo,
+    client_identifier: HostId,
     branch_to_set: Option<BranchId>,
+    password_expiry: &PasswordExpiry,
 ) -> Result<LoginResponse, AuthError> {
-    // Extract Client Host Identifier
-    let client_identifier: HostId = conn.try_into().context("failed to get host_id")?;
-
     // Lookup DB for Client Host Identifier
     let lookup_result = execute_chained_handler(
         PATH_API_HOSTBRANCH.path,
@@ -111,12 +204,21 @@ async fn set_user_branch(
                     }
                     (true, Some(branch_to_set)) => {
                         // Set Branch as per user request
+                        let audit = AuditContext::new(
+                            auth_user_info
+                                .username
+                                .clone()
+                                .try_into()
+                                .context("invalid username")?,
+                            client_identifier.clone(),
+                        );
                         if let Err(e) = host_branch_pair_set(
                             web::Data::new(pool.clone()),
                             web::Json(HostBranchPair {
                                 host_id: client_identifier,
                                 branch_id: branch_to_set,
                             }),
+                            audit,
                         )
                         .await
                         {
@@ -137,5 +239,5 @@ async fn set_user_branch(
     };
 
     // Return LoginResponse
-    Ok(auth_user_info.into_login_response(branch_id)?)
+    Ok(auth_user_info.into_login_response(branch_id, password_expiry)?)
 }
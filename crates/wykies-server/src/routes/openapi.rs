@@ -0,0 +1,6 @@
+use crate::openapi::OpenApiDocument;
+use actix_web::{HttpResponse, web};
+
+pub async fn openapi(document: web::Data<OpenApiDocument>) -> HttpResponse {
+    HttpResponse::Ok().json(&document.0)
+}
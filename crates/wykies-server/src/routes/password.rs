@@ -1,21 +1,45 @@
-use crate::authentication::{Credentials, LoginAttemptLimit, validate_credentials};
-use actix_web::{HttpResponse, web};
+use crate::{
+    audit::AuditContext,
+    authentication::{
+        Credentials, HostLoginThrottle, LoginAttemptLimit, LoginThrottling, PasswordHashing,
+        PasswordResetSettings, SessionGenerationCache, consume_password_reset_token,
+        delete_password_reset_tokens, invalidate_user_sessions, is_password_reused,
+        request_password_reset, validate_credentials, verify_password_reset_token,
+    },
+    notifier::Notifier,
+};
+use actix_web::{HttpResponse, dev::ConnectionInfo, web};
+use anyhow::Context as _;
 use secrecy::ExposeSecret as _;
+use tracing::{Instrument as _, error};
 use wykies_shared::{
+    audit::AuditAction,
     db_types::DbPool,
-    req_args::api::ChangePasswordReqArgs,
-    uac::{ChangePasswordError, PasswordComplexity, UserInfo},
+    e500,
+    host_branch::HostId,
+    req_args::{api::ChangePasswordReqArgs, password_reset},
+    uac::{
+        AuthError, ChangePasswordError, PasswordComplexity, PasswordPolicy,
+        PasswordResetTokenError, UserInfo,
+    },
 };
+use wykies_time::Timestamp;
 
-#[tracing::instrument(skip(req_args, pool))]
+#[tracing::instrument(skip(req_args, pool, password_hashing))]
+#[expect(clippy::too_many_arguments)] // All arguments are well typed, no material benefit from creating a type
 pub async fn change_password(
     req_args: web::Json<ChangePasswordReqArgs>,
     pool: web::Data<DbPool>,
     login_attempt_limit: web::Data<LoginAttemptLimit>,
+    login_throttling: web::Data<LoginThrottling>,
+    password_policy: web::Data<PasswordPolicy>,
+    password_hashing: web::Data<PasswordHashing>,
     user_info: web::ReqData<UserInfo>,
+    audit: AuditContext,
 ) -> Result<HttpResponse, ChangePasswordError> {
     let username = user_info.into_inner().username;
-    let password_complexity = PasswordComplexity::new(&username, &req_args.new_password);
+    let password_complexity =
+        PasswordComplexity::new(&password_policy, &username, &req_args.new_password);
     if !password_complexity.does_meet_requirements() {
         return Err(ChangePasswordError::Complexity(password_complexity));
     }
@@ -28,17 +52,191 @@ pub async fn change_password(
         password: req_args.0.current_password,
     };
 
-    validate_credentials(credentials, &pool, &login_attempt_limit).await?;
+    validate_credentials(
+        credentials,
+        &pool,
+        &login_attempt_limit,
+        &login_throttling,
+        &password_hashing,
+    )
+    .await?;
+
+    let history_count = password_policy.history_count;
+    if is_password_reused(
+        &username,
+        req_args.0.new_password.clone(),
+        history_count,
+        &pool,
+    )
+    .await?
+    {
+        return Err(ChangePasswordError::ReusedPassword(history_count));
+    }
 
     let should_force_pass_change = false;
+    let mut transaction = pool.begin().await.context("failed to start transaction")?;
     crate::authentication::change_password(
         &username,
         req_args.0.new_password,
         should_force_pass_change,
-        &pool,
+        history_count,
+        &password_hashing,
+        &mut transaction,
     )
     .await
     .map_err(ChangePasswordError::UnexpectedError)?;
 
+    audit
+        .record(
+            &mut transaction,
+            AuditAction::PasswordChanged,
+            &username,
+            &(),
+        )
+        .await?;
+    transaction
+        .commit()
+        .await
+        .context("failed to commit password change")?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// The password policy is needed by the client to validate new passwords
+/// before they are sent
+#[tracing::instrument(skip(password_policy))]
+pub async fn password_policy(
+    password_policy: web::Data<PasswordPolicy>,
+) -> web::Json<PasswordPolicy> {
+    web::Json(password_policy.as_ref().clone())
+}
+
+/// Sends a password reset token to the user. The token is generated and sent
+/// after responding so that neither the response nor how long it takes can be
+/// used to find out which usernames exist or have an email address. Requests
+/// count against the host's login throttle (See [`HostLoginThrottle`])
+#[tracing::instrument(
+    ret,
+    err(Debug),
+    skip(pool, password_hashing, notifier, host_login_throttle)
+)]
+pub async fn password_reset_request(
+    conn: ConnectionInfo,
+    web::Json(req_args): web::Json<password_reset::RequestReqArgs>,
+    pool: web::Data<DbPool>,
+    password_reset_settings: web::Data<PasswordResetSettings>,
+    password_hashing: web::Data<PasswordHashing>,
+    notifier: web::Data<dyn Notifier>,
+    host_login_throttle: web::Data<HostLoginThrottle>,
+) -> actix_web::Result<HttpResponse> {
+    let host_id: HostId = conn
+        .try_into()
+        .context("failed to get host_id")
+        .map_err(e500)?;
+    let now = Timestamp::now();
+    if let Some(retry_after) = host_login_throttle.retry_after(&host_id, now) {
+        return Err(AuthError::TooManyAttempts { retry_after }.into());
+    }
+    host_login_throttle.record_attempt(&host_id, now);
+
+    tokio::spawn(
+        async move {
+            if let Err(e) = request_password_reset(
+                &req_args.username,
+                &pool,
+                &password_reset_settings,
+                &password_hashing,
+                notifier.get_ref(),
+            )
+            .await
+            {
+                error!(?e, "failed to process password reset request");
+            }
+        }
+        .in_current_span(),
+    );
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Sets a new password for the user if the token matches the one sent by
+/// [`password_reset_request`]. The token can only be used once and all
+/// existing sessions of the user are ended. Invalid tokens count against the
+/// host's login throttle (See [`HostLoginThrottle`])
+#[tracing::instrument(
+    ret,
+    err(Debug),
+    skip(
+        req_args,
+        pool,
+        password_hashing,
+        generation_cache,
+        host_login_throttle
+    )
+)]
+pub async fn password_reset_redeem(
+    conn: ConnectionInfo,
+    web::Json(req_args): web::Json<password_reset::RedeemReqArgs>,
+    pool: web::Data<DbPool>,
+    password_policy: web::Data<PasswordPolicy>,
+    password_hashing: web::Data<PasswordHashing>,
+    generation_cache: web::Data<SessionGenerationCache>,
+    host_login_throttle: web::Data<HostLoginThrottle>,
+) -> Result<HttpResponse, PasswordResetTokenError> {
+    let host_id: HostId = conn.try_into().context("failed to get host_id")?;
+    if let Some(retry_after) = host_login_throttle.retry_after(&host_id, Timestamp::now()) {
+        return Err(PasswordResetTokenError::TooManyAttempts { retry_after });
+    }
+    let password_reset::RedeemReqArgs {
+        username,
+        token,
+        new_password,
+    } = req_args;
+    let password_complexity = PasswordComplexity::new(&password_policy, &username, &new_password);
+    if !password_complexity.does_meet_requirements() {
+        return Err(PasswordResetTokenError::Complexity(password_complexity));
+    }
+
+    let token_id = verify_password_reset_token(&username, token, &pool)
+        .await
+        .inspect_err(|e| {
+            if matches!(e, PasswordResetTokenError::InvalidToken) {
+                host_login_throttle.record_attempt(&host_id, Timestamp::now());
+            }
+        })?;
+
+    let history_count = password_policy.history_count;
+    if is_password_reused(&username, new_password.clone(), history_count, &pool).await? {
+        return Err(PasswordResetTokenError::ReusedPassword(history_count));
+    }
+
+    let should_force_pass_change = false;
+    let mut transaction = pool.begin().await.context("failed to start transaction")?;
+    // Consumed first so a concurrent request with the same token waits and then fails
+    consume_password_reset_token(token_id, &mut *transaction).await?;
+    crate::authentication::change_password(
+        &username,
+        new_password,
+        should_force_pass_change,
+        history_count,
+        &password_hashing,
+        &mut transaction,
+    )
+    .await?;
+    delete_password_reset_tokens(&username, &mut *transaction).await?;
+    invalidate_user_sessions(&username, &mut *transaction).await?;
+    AuditContext::new(username.clone(), host_id)
+        .record(
+            &mut transaction,
+            AuditAction::PasswordResetByToken,
+            &username,
+            &(),
+        )
+        .await?;
+    transaction
+        .commit()
+        .await
+        .context("failed to commit password reset")?;
+    generation_cache.remove(&username);
+
     Ok(HttpResponse::Ok().finish())
 }
@@ -1,64 +1,143 @@
-#[cfg(feature = "mysql")]
-use crate::db_utils::validate_one_row_affected;
-use actix_web::web;
+use crate::{
+    audit::{AuditChange, AuditContext},
+    authentication,
+    db_utils::validate_one_row_affected,
+};
+use actix_web::{HttpResponse, web};
 use anyhow::Context;
+use sqlx::{Acquire, Transaction};
 use wykies_shared::{
-    db_types::DbPool,
+    audit::AuditAction,
+    db_types::{Db, DbPool},
     e400, e500,
-    req_args::api::role::{self},
-    uac::{Role, RoleDraft, RoleId},
+    req_args::api::role::{self, SetParentsReqArgs},
+    uac::{Role, RoleDiff, RoleDraft, RoleError, RoleId},
 };
 
 #[tracing::instrument(ret, err(Debug), skip(pool))]
 pub async fn role(
     pool: web::Data<DbPool>,
     web::Query(role::LookupReqArgs { role_id }): web::Query<role::LookupReqArgs>,
-) -> actix_web::Result<web::Json<Role>> {
+) -> Result<web::Json<Role>, RoleError> {
+    Ok(web::Json(get_role(pool.get_ref(), role_id).await?))
+}
+
+#[tracing::instrument(ret, err(Debug), skip(pool))]
+pub async fn role_list(pool: web::Data<DbPool>) -> actix_web::Result<web::Json<Vec<Role>>> {
     let pool: &DbPool = &pool;
     #[cfg(feature = "mysql")]
+    let query = sqlx::query!("SELECT `RoleID`, `Name`, `Description`, `Permissions` FROM `roles`");
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    let query = sqlx::query!("SELECT role_id, role_name, role_description, permissions FROM roles");
+    let mut parent_roles = authentication::get_all_role_parents(pool)
+        .await
+        .map_err(e500)?;
+    let result = query
+        .fetch_all(pool)
+        .await
+        .context("failed to get list of roles")
+        .map_err(e500)?
+        .into_iter()
+        .map(|x| {
+            #[cfg(feature = "mysql")]
+            let id: RoleId = x.RoleID.try_into()?;
+            #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+            let id: RoleId = x.role_id.try_into()?;
+            let parent_roles = parent_roles.remove(&id).unwrap_or_default();
+            #[cfg(feature = "mysql")]
+            return Ok(Role {
+                id,
+                name: x.Name.try_into()?,
+                description: x.Description.try_into()?,
+                permissions: x.Permissions.try_into()?,
+                parent_roles,
+            });
+            #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+            Ok(Role {
+                id,
+                name: x.role_name.try_into()?,
+                description: x.role_description.try_into()?,
+                permissions: x.permissions.try_into()?,
+                parent_roles,
+            })
+        })
+        .collect::<anyhow::Result<Vec<Role>>>()
+        .map_err(e500)?;
+    Ok(web::Json(result))
+}
+
+async fn get_role(db: impl Acquire<'_, Database = Db>, role_id: RoleId) -> Result<Role, RoleError> {
+    let mut connection = db.acquire().await.context("failed to acquire connection")?;
+    #[cfg(feature = "mysql")]
     let query = sqlx::query!(
         "SELECT `RoleID`, `Name`, `Description`, `Permissions` FROM `roles` WHERE `RoleID` = ?",
         role_id
     );
     #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
     let query = {
-        let role_id: i32 = role_id.try_into().map_err(e500)?;
+        let role_id: i32 = role_id.try_into().context("failed to convert role id")?;
         sqlx::query!(
             "SELECT role_id, role_name, role_description, permissions FROM roles WHERE role_id = $1",
             role_id
         )
     };
-    let row = query
-        .fetch_one(pool)
+    let Some(row) = query
+        .fetch_optional(&mut *connection)
         .await
-        .context("failed to find role")
-        .map_err(e400)?;
+        .context("failed to get role")?
+    else {
+        return Err(RoleError::NotFound(role_id));
+    };
+    let parent_roles = authentication::get_role_parents(role_id, &mut *connection).await?;
     #[cfg(feature = "mysql")]
     let result = Role {
         id: role_id,
-        name: row.Name.try_into()?,
-        description: row.Description.try_into()?,
-        permissions: row.Permissions.try_into()?,
+        name: row.Name.try_into().context("invalid role name in db")?,
+        description: row
+            .Description
+            .try_into()
+            .context("invalid role description in db")?,
+        permissions: row
+            .Permissions
+            .try_into()
+            .context("invalid role permissions in db")?,
+        parent_roles,
     };
     #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
     let result = Role {
         id: role_id,
-        name: row.role_name.try_into()?,
-        description: row.role_description.try_into()?,
-        permissions: row.permissions.try_into()?,
+        name: row
+            .role_name
+            .try_into()
+            .context("invalid role name in db")?,
+        description: row
+            .role_description
+            .try_into()
+            .context("invalid role description in db")?,
+        permissions: row
+            .permissions
+            .try_into()
+            .context("invalid role permissions in db")?,
+        parent_roles,
     };
-    Ok(web::Json(result))
+    Ok(result)
 }
 
 #[tracing::instrument(ret, err(Debug), skip(pool))]
 pub async fn role_new(
     pool: web::Data<DbPool>,
     web::Json(draft_role): web::Json<RoleDraft>,
+    audit: AuditContext,
 ) -> actix_web::Result<web::Json<RoleId>> {
     let pool: &DbPool = &pool;
-    let permissions: String = draft_role.permissions.into();
+    let permissions: String = draft_role.permissions.clone().into();
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("failed to start transaction")
+        .map_err(e500)?;
     #[cfg(feature = "mysql")]
-    let result = {
+    let result: RoleId = {
         let sql_result = sqlx::query!(
             "INSERT INTO `roles` 
             (`RoleID`, `Name`, `Description`, `Permissions`, `LockedEditing`)
@@ -67,7 +146,7 @@ pub async fn role_new(
             draft_role.description,
             permissions
         )
-        .execute(pool)
+        .execute(&mut *transaction)
         .await
         .context("failed to insert role")
         .map_err(e500)?;
@@ -77,7 +156,7 @@ pub async fn role_new(
 
     #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
     // TODO 5: Check why encode trait impl doesn't make converting not necessary
-    let result = {
+    let result: RoleId = {
         let name: &str = draft_role.name.as_ref();
         let description: &str = draft_role.description.as_ref();
         sqlx::query!(
@@ -88,7 +167,7 @@ pub async fn role_new(
             description,
             permissions
         )
-        .fetch_one(pool)
+        .fetch_one(&mut *transaction)
         .await
         .map_err(e500)?
         .role_id
@@ -96,5 +175,177 @@ pub async fn role_new(
         .map_err(e500)?
     };
 
+    if !draft_role.parent_roles.is_empty() {
+        // A new role cannot be part of a cycle but the parents still need to be checked
+        authentication::set_role_parents(result, &draft_role.parent_roles, &mut transaction)
+            .await?;
+    }
+
+    audit
+        .record(
+            &mut transaction,
+            AuditAction::RoleCreated,
+            result,
+            &draft_role,
+        )
+        .await
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("failed to commit new role")
+        .map_err(e500)?;
     Ok(web::Json(result))
 }
+
+/// Replaces the roles that the role inherits permissions from
+#[tracing::instrument(ret, err(Debug), skip(pool))]
+pub async fn role_parents_set(
+    pool: web::Data<DbPool>,
+    web::Json(SetParentsReqArgs {
+        role_id,
+        parent_roles,
+    }): web::Json<SetParentsReqArgs>,
+    audit: AuditContext,
+) -> Result<HttpResponse, RoleError> {
+    let pool: &DbPool = &pool;
+    let before = authentication::get_role_parents(role_id, pool).await?;
+    let mut transaction = pool.begin().await.context("failed to start transaction")?;
+    authentication::set_role_parents(role_id, &parent_roles, &mut transaction).await?;
+    audit
+        .record(
+            &mut transaction,
+            AuditAction::RoleParentsSet,
+            role_id,
+            &AuditChange {
+                before: &before,
+                after: &parent_roles,
+            },
+        )
+        .await?;
+    transaction
+        .commit()
+        .await
+        .context("failed to commit role parents")?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Changes to the parents are checked for cycles before anything is saved
+#[tracing::instrument(ret, err(Debug), skip(pool))]
+pub async fn role_update(
+    pool: web::Data<DbPool>,
+    web::Json(diff): web::Json<RoleDiff>,
+    audit: AuditContext,
+) -> actix_web::Result<HttpResponse> {
+    let pool: &DbPool = &pool;
+    diff.is_valid().map_err(e400)?;
+    let before = get_role(pool, diff.id).await?;
+    // Both parts of the change are saved together with the audit entry so a
+    // failure leaves the role unchanged
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("failed to start transaction")
+        .map_err(e500)?;
+    if let Some(parent_roles) = &diff.parent_roles {
+        authentication::set_role_parents(diff.id, parent_roles, &mut transaction).await?;
+    }
+    if diff.has_role_row_changes() {
+        update_role_row(&mut transaction, &diff).await?;
+    }
+
+    let after = get_role(&mut *transaction, diff.id).await?;
+    audit
+        .record(
+            &mut transaction,
+            AuditAction::RoleUpdated,
+            diff.id,
+            &AuditChange {
+                before: &before,
+                after: &after,
+            },
+        )
+        .await
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("failed to commit role update")
+        .map_err(e500)?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Updates the fields of the diff that are stored in the roles table
+async fn update_role_row(
+    transaction: &mut Transaction<'_, Db>,
+    diff: &RoleDiff,
+) -> actix_web::Result<()> {
+    let permissions: Option<String> = diff.permissions.as_ref().map(String::from);
+    #[cfg(feature = "mysql")]
+    let query = sqlx::query!(
+        "UPDATE `roles` SET
+        `Name` = CASE WHEN ? IS NULL THEN `Name` ELSE ? end,
+        `Description` = CASE WHEN ? IS NULL THEN `Description` ELSE ? end,
+        `Permissions` = CASE WHEN ? IS NULL THEN `Permissions` ELSE ? end
+        WHERE `RoleID`=?;",
+        diff.name,
+        diff.name,
+        diff.description,
+        diff.description,
+        permissions,
+        permissions,
+        diff.id
+    );
+    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+    // TODO 5: Check why encode trait impl doesn't make converting not necessary
+    let query = {
+        let name = diff.name.as_ref().map(|x| x.to_string());
+        let description = diff.description.as_ref().map(|x| x.to_string());
+        let role_id: i32 = diff.id.try_into().map_err(e500)?;
+        sqlx::query!(
+            "UPDATE roles SET
+            role_name = CASE WHEN $1 THEN role_name ELSE $2 end,
+            role_description = CASE WHEN $3 THEN role_description ELSE $4 end,
+            permissions = CASE WHEN $5 THEN permissions ELSE $6 end
+            WHERE role_id=$7",
+            name.is_none(),
+            name,
+            description.is_none(),
+            description,
+            permissions.is_none(),
+            permissions,
+            role_id
+        )
+    };
+    let sql_result = query
+        .execute(&mut **transaction)
+        .await
+        .context("failed to update role")
+        .map_err(e500)?;
+    validate_one_row_affected(&sql_result)
+        .context("wrong number of rows changed when updating role")
+        .map_err(e500)?;
+    Ok(())
+}
+
+/// Only roles that are not assigned to any user and not inherited by any other
+/// role can be deleted
+#[tracing::instrument(ret, err(Debug), skip(pool))]
+pub async fn role_delete(
+    pool: web::Data<DbPool>,
+    web::Json(role::LookupReqArgs { role_id }): web::Json<role::LookupReqArgs>,
+    audit: AuditContext,
+) -> Result<HttpResponse, RoleError> {
+    let pool: &DbPool = &pool;
+    let before = get_role(pool, role_id).await?;
+    let mut transaction = pool.begin().await.context("failed to start transaction")?;
+    authentication::delete_role(role_id, &mut transaction).await?;
+    audit
+        .record(&mut transaction, AuditAction::RoleDeleted, role_id, &before)
+        .await?;
+    transaction
+        .commit()
+        .await
+        .context("failed to commit role deletion")?;
+    Ok(HttpResponse::Ok().finish())
+}
@@ -0,0 +1,13 @@
+use crate::db_session::list_user_sessions;
+use actix_web::web;
+use wykies_shared::{db_types::DbPool, e500, req_args::api::user, uac::SessionInfo};
+
+#[tracing::instrument(ret, err(Debug), skip(pool))]
+pub async fn user_sessions(
+    pool: web::Data<DbPool>,
+    web::Query(user::LookupReqArgs { username }): web::Query<user::LookupReqArgs>,
+) -> actix_web::Result<web::Json<Vec<SessionInfo>>> {
+    let pool: &DbPool = &pool;
+    let result = list_user_sessions(pool, &username).await.map_err(e500)?;
+    Ok(web::Json(result))
+}
@@ -1,10 +1,11 @@
-// TODO 5: Decide if this should be updated or dropped
-use actix_web::{HttpResponse, web};
-use std::error::Error;
-use tracing::error;
-use wykies_shared::db_types::DbPool;
+use crate::health::HealthChecks;
+use actix_web::{HttpResponse, HttpResponseBuilder, http::StatusCode, web};
+use tracing::{error, warn};
+use wykies_shared::health::{HealthCheckResult, HealthReport, HealthStatus};
 
-pub async fn status(pool: web::Data<DbPool>) -> HttpResponse {
+/// Renders the [`HealthReport`] as an HTML table
+pub async fn status(health_checks: web::Data<HealthChecks>) -> HttpResponse {
+    let report = health_checks.run().await;
     let mut result = r#"<!DOCTYPE html>
 <html lang="en">
 <head>
@@ -42,22 +43,57 @@ pub async fn status(pool: web::Data<DbPool>) -> HttpResponse {
     </tr>"#
         .to_string();
 
-    // Acquire a connection to the database to test it
-    result += &format_status_row("Connect to Database", pool.acquire().await);
+    for check in report.checks.iter() {
+        result += &format_status_row(check);
+    }
+    result += &format!(
+        "</table>\n<p>Overall: {}</p>\n",
+        format_status(report.status)
+    );
 
     // Close body and html tags
     result += "</body>
 </html>";
 
-    HttpResponse::Ok().body(result)
+    response_builder(&report).body(result)
 }
 
-fn format_status_row<T, E: Error>(name: &str, result: Result<T, E>) -> String {
-    let (stat, msg) = if let Err(e) = result {
-        error!("Error for {name:?} - {e}");
-        ("<span class = red>Error</span>", e.to_string())
-    } else {
-        ("Ok", "".to_string())
-    };
-    format!("<tr><td>{name}</td><td>{stat}</td><td>{msg}</td></tr>")
+pub async fn status_json(health_checks: web::Data<HealthChecks>) -> HttpResponse {
+    let report = health_checks.run().await;
+    response_builder(&report).json(report)
+}
+
+/// Uses 503 if any check failed so monitoring tools do not need to parse the
+/// body
+fn response_builder(report: &HealthReport) -> HttpResponseBuilder {
+    match report.status {
+        HealthStatus::Ok | HealthStatus::Degraded => HttpResponse::Ok(),
+        HealthStatus::Error => HttpResponseBuilder::new(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+fn format_status(status: HealthStatus) -> String {
+    match status {
+        HealthStatus::Ok => status.to_string(),
+        HealthStatus::Degraded | HealthStatus::Error => {
+            format!("<span class = red>{status}</span>")
+        }
+    }
+}
+
+fn format_status_row(check: &HealthCheckResult) -> String {
+    let HealthCheckResult {
+        name,
+        status,
+        message,
+    } = check;
+    match status {
+        HealthStatus::Ok => {}
+        HealthStatus::Degraded => warn!("{status} for {name:?} - {message}"),
+        HealthStatus::Error => error!("{status} for {name:?} - {message}"),
+    }
+    format!(
+        "<tr><td>{name}</td><td>{}</td><td>{message}</td></tr>",
+        format_status(*status)
+    )
 }
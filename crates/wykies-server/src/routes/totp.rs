@@ -0,0 +1,86 @@
+use crate::{
+    audit::AuditContext,
+    authentication::{self, PasswordHashing, TotpSettings},
+    session_state::TypedSession,
+};
+use actix_web::{HttpResponse, web};
+use anyhow::Context as _;
+use wykies_shared::{
+    audit::AuditAction,
+    db_types::DbPool,
+    req_args::api::{totp::ConfirmReqArgs, user},
+    uac::{TotpEnrolment, TotpError, TotpRecoveryCodes, UserInfo},
+};
+
+#[tracing::instrument(err(Debug), skip(pool))]
+pub async fn totp_enrol(
+    pool: web::Data<DbPool>,
+    totp_settings: web::Data<TotpSettings>,
+    user_info: web::ReqData<UserInfo>,
+) -> Result<web::Json<TotpEnrolment>, TotpError> {
+    let username = user_info.into_inner().username;
+    Ok(web::Json(
+        authentication::start_enrolment(&username, &pool, &totp_settings).await?,
+    ))
+}
+
+/// The recovery codes are only returned by this call, they cannot be retrieved
+/// later
+#[tracing::instrument(err(Debug), skip(req_args, pool, password_hashing, session))]
+pub async fn totp_confirm(
+    web::Json(req_args): web::Json<ConfirmReqArgs>,
+    pool: web::Data<DbPool>,
+    totp_settings: web::Data<TotpSettings>,
+    password_hashing: web::Data<PasswordHashing>,
+    user_info: web::ReqData<UserInfo>,
+    session: TypedSession,
+    audit: AuditContext,
+) -> Result<web::Json<TotpRecoveryCodes>, TotpError> {
+    let username = user_info.into_inner().username;
+    let confirmed_enrolment = authentication::confirm_enrolment(
+        &username,
+        req_args.code,
+        &pool,
+        &totp_settings,
+        &password_hashing,
+    )
+    .await?;
+    let mut transaction = pool.begin().await.context("failed to start transaction")?;
+    let recovery_codes = confirmed_enrolment
+        .save(&username, &mut transaction)
+        .await?;
+    audit
+        .record(&mut transaction, AuditAction::TotpEnabled, &username, &())
+        .await?;
+    transaction
+        .commit()
+        .await
+        .context("failed to commit totp enrolment")?;
+    session
+        .insert_totp_enrolment_required(false)
+        .context("session update failed")?;
+    Ok(web::Json(recovery_codes))
+}
+
+#[tracing::instrument(ret, err(Debug), skip(pool))]
+pub async fn user_totp_reset(
+    pool: web::Data<DbPool>,
+    web::Json(user::LookupReqArgs { username }): web::Json<user::LookupReqArgs>,
+    user_info: web::ReqData<UserInfo>,
+    audit: AuditContext,
+) -> Result<HttpResponse, TotpError> {
+    let logged_in_username = user_info.into_inner().username;
+    if logged_in_username == username {
+        return Err(TotpError::NoResetOwn);
+    }
+    let mut transaction = pool.begin().await.context("failed to start transaction")?;
+    authentication::reset_totp(&username, &mut transaction).await?;
+    audit
+        .record(&mut transaction, AuditAction::TotpReset, &username, &())
+        .await?;
+    transaction
+        .commit()
+        .await
+        .context("failed to commit totp reset")?;
+    Ok(HttpResponse::Ok().finish())
+}
@@ -1,22 +1,27 @@
 #[cfg(feature = "mysql")]
 use crate::db_utils::db_int_to_bool;
-use crate::{authentication, db_utils::validate_one_row_affected};
+use crate::{
+    audit::{AuditChange, AuditContext},
+    authentication::{self, PasswordHashing, SessionGenerationCache},
+    db_utils::validate_one_row_affected,
+};
 use actix_web::{HttpResponse, web};
 use anyhow::Context;
-use argon2::{
-    PasswordHasher,
-    password_hash::{SaltString, rand_core},
-};
 use secrecy::ExposeSecret;
+use sqlx::{Acquire, Executor};
 use wykies_shared::{
-    db_types::DbPool,
+    audit::AuditAction,
+    db_types::{Db, DbPool},
     e400, e500,
     req_args::{
         RonWrapper,
-        api::user::{self, AssignReqArgs, NewUserReqArgs, PasswordResetReqArgs},
+        api::user::{
+            self, AssignReqArgs, NewUserReqArgs, PasswordResetReqArgs, RevokeSessionsReqArgs,
+        },
     },
     uac::{
-        ListUsersRoles, ResetPasswordError, RoleIdAndName, UserInfo, UserMetadata, UserMetadataDiff,
+        EmailAddress, ListUsersRoles, PasswordPolicy, ResetPasswordError, RoleIdAndName, SessionId,
+        UserInfo, UserMetadata, UserMetadataDiff, UserPermissions, Username,
     },
 };
 
@@ -26,105 +31,174 @@ pub async fn user(
     web::Query(user::LookupReqArgs { username }): web::Query<user::LookupReqArgs>,
 ) -> actix_web::Result<web::Json<UserMetadata>> {
     let pool: &DbPool = &pool;
+    Ok(web::Json(get_user_metadata(pool, &username).await?))
+}
+
+/// Shows which permissions come directly from the roles assigned to the user
+/// and which are inherited
+#[tracing::instrument(ret, err(Debug), skip(pool))]
+pub async fn user_permissions(
+    pool: web::Data<DbPool>,
+    web::Query(user::LookupReqArgs { username }): web::Query<user::LookupReqArgs>,
+) -> actix_web::Result<web::Json<UserPermissions>> {
+    Ok(web::Json(
+        authentication::get_user_permissions(username.as_ref(), &pool)
+            .await
+            .map_err(e500)?,
+    ))
+}
+
+async fn get_user_metadata(
+    db: impl Acquire<'_, Database = Db>,
+    username: &Username,
+) -> actix_web::Result<UserMetadata> {
+    let mut connection = db
+        .acquire()
+        .await
+        .context("failed to acquire connection")
+        .map_err(e500)?;
     #[cfg(feature = "mysql")]
     let query = sqlx::query!(
-        "SELECT `UserName`, `DisplayName`, `ForcePassChange`, `AssignedRole`, `Enabled`, `LockedOut`, `FailedAttempts`, `PassChangeDate`
+        "SELECT `UserName`, `DisplayName`, `ForcePassChange`, `Enabled`, `LockedOut`, `FailedAttempts`, `PassChangeDate`, `Email`
          FROM `user`
          WHERE UserName=?;",
     username);
     #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
     // TODO 5: Check why encode trait impl doesn't make converting not necessary
     let query = sqlx::query!(
-        "SELECT user_name, display_name, force_pass_change, assigned_role, is_enabled, locked_out, failed_attempts, pass_change_date
+        "SELECT user_name, display_name, force_pass_change, is_enabled, locked_out, failed_attempts, pass_change_date, email
          FROM users
          WHERE user_name=$1;",
         username.as_ref()
     );
     let Some(record) = query
-        .fetch_optional(pool)
+        .fetch_optional(&mut *connection)
         .await
         .context("failed to get user")
         .map_err(e500)?
     else {
         return Err(e400("no user found with that username"));
     };
+    let assigned_roles = authentication::get_assigned_roles(username.as_ref(), &mut *connection)
+        .await
+        .map_err(e500)?;
 
     #[cfg(feature = "mysql")]
     let result = UserMetadata {
         username: record.UserName.try_into()?,
         display_name: record.DisplayName.try_into()?,
         force_pass_change: db_int_to_bool(record.ForcePassChange),
-        assigned_role: record.AssignedRole.map(|x| x.try_into()).transpose()?,
+        assigned_roles,
         enabled: db_int_to_bool(record.Enabled),
         locked_out: db_int_to_bool(record.LockedOut),
         failed_attempts: record.FailedAttempts.try_into().map_err(e500)?,
         pass_change_date: record.PassChangeDate,
+        email: EmailAddress::try_from_opt(record.Email)?,
     };
     #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
     let result = UserMetadata {
         username: record.user_name.try_into()?,
         display_name: record.display_name.try_into()?,
         force_pass_change: record.force_pass_change,
-        assigned_role: record.assigned_role.map(|x| x.try_into()).transpose()?,
+        assigned_roles,
         enabled: record.is_enabled,
         locked_out: record.locked_out,
         failed_attempts: record.failed_attempts.try_into().map_err(e500)?,
         pass_change_date: record.pass_change_date,
+        email: EmailAddress::try_from_opt(record.email)?,
     };
 
-    Ok(web::Json(result))
+    Ok(result)
 }
 
-#[tracing::instrument(ret, err(Debug), skip(pool))]
+#[tracing::instrument(ret, err(Debug), skip(pool, password_hashing))]
 pub async fn user_new(
     pool: web::Data<DbPool>,
+    password_hashing: web::Data<PasswordHashing>,
     web::Json(args): web::Json<NewUserReqArgs>,
+    audit: AuditContext,
 ) -> actix_web::Result<HttpResponse> {
     let pool: &DbPool = &pool;
-    let salt = SaltString::generate(&mut rand_core::OsRng);
-    let password_hash = authentication::argon2_settings()
-        .hash_password(args.password.expose_secret().as_bytes(), &salt)
-        .unwrap()
-        .to_string();
+    // Requests made with an API key act as a user with the key's name
+    if authentication::is_name_used_by_api_key(&args.username, pool)
+        .await
+        .map_err(e500)?
+    {
+        return Err(e400("username is already used by an api key"));
+    }
+    let password_hash = password_hashing
+        .compute_password_hash(args.password.clone())
+        .map_err(e500)?;
+    let password_hash = password_hash.expose_secret();
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("failed to start transaction")
+        .map_err(e500)?;
     #[cfg(feature = "mysql")]
     let query  = sqlx::query!(
         "INSERT INTO `user`
-        (`UserName`, `Password`, `password_hash`, `salt`, `DisplayName`, `AssignedRole`, `PassChangeDate`, `Enabled`) 
-        VALUES (?, '', ?, '', ?, ?, CURRENT_DATE(), 1);",
+        (`UserName`, `Password`, `password_hash`, `salt`, `DisplayName`, `PassChangeDate`, `Enabled`, `Email`) 
+        VALUES (?, '', ?, '', ?, CURRENT_DATE(), 1, ?);",
         args.username,
         password_hash,
         args.display_name,
-        args.assigned_role
+        args.email
     );
     #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
     // TODO 5: Check why encode trait impl doesn't make converting not necessary
     let query = {
-        let assigned_role: Option<i32> = args.assigned_role.map(|x| x.try_into()).transpose()?;
+        let email = args.email.as_ref().map(|x| x.to_string());
         sqlx::query!(
             "INSERT INTO users
-        (user_name, password_hash, display_name, assigned_role, pass_change_date, is_enabled) 
-        VALUES ($1, $2, $3, $4, CURRENT_DATE, true);",
+        (user_name, password_hash, display_name, pass_change_date, is_enabled, email) 
+        VALUES ($1, $2, $3, CURRENT_DATE, true, $4);",
             args.username.as_ref(),
             password_hash,
             args.display_name.as_ref(),
-            assigned_role
+            email
         )
     };
     let sql_result = query
-        .execute(pool)
+        .execute(&mut *transaction)
         .await
         .context("failed to store user")
         .map_err(e500)?;
     validate_one_row_affected(&sql_result)
         .context("failed to save new user")
         .map_err(e500)?;
+    if !args.assigned_roles.is_empty() {
+        authentication::set_assigned_roles(&args.username, &args.assigned_roles, &mut transaction)
+            .await
+            .map_err(e500)?;
+    }
+    audit
+        .record(
+            &mut transaction,
+            AuditAction::UserCreated,
+            &args.username,
+            &serde_json::json!({
+                "display_name": args.display_name,
+                "assigned_roles": args.assigned_roles,
+                "email": args.email,
+            }),
+        )
+        .await
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("failed to commit new user")
+        .map_err(e500)?;
     Ok(HttpResponse::Ok().finish())
 }
 
-#[tracing::instrument(ret, err(Debug), skip(pool))]
+#[tracing::instrument(ret, err(Debug), skip(pool, generation_cache))]
 pub async fn user_update(
     pool: web::Data<DbPool>,
+    generation_cache: web::Data<SessionGenerationCache>,
     wrapped: web::Json<RonWrapper>,
+    audit: AuditContext,
 ) -> actix_web::Result<actix_web::HttpResponse> {
     // TODO 5: Ensure there is a test that assigns a role, changes a role and
     //          removes a role
@@ -134,73 +208,133 @@ pub async fn user_update(
         .context("convert from ron failed")
         .map_err(e400)?;
     diff.is_valid().map_err(e400)?;
+    // All parts of the change are saved together with the audit entry so a
+    // failure leaves the user unchanged
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("failed to start transaction")
+        .map_err(e500)?;
+    let before = get_user_metadata(&mut *transaction, &diff.username).await?;
+    if diff.has_user_row_changes() {
+        update_user_row(&mut *transaction, &diff).await?;
+    }
+    if let Some(assigned_roles) = &diff.assigned_roles {
+        authentication::set_assigned_roles(&diff.username, assigned_roles, &mut transaction)
+            .await
+            .context("failed to update roles assigned to user")
+            .map_err(e500)?;
+    }
+
+    // Disabled users should not be able to continue using existing sessions
+    let is_disabled = diff.enabled == Some(false);
+    if is_disabled {
+        authentication::invalidate_user_sessions(&diff.username, &mut *transaction)
+            .await
+            .context("failed to invalidate sessions of disabled user")
+            .map_err(e500)?;
+    }
+
+    // Record the full state after instead of the diff because a diff cannot
+    // distinguish between removing the role and not changing it in JSON
+    let after = get_user_metadata(&mut *transaction, &diff.username).await?;
+    audit
+        .record(
+            &mut transaction,
+            AuditAction::UserUpdated,
+            &diff.username,
+            &AuditChange {
+                before: &before,
+                after: &after,
+            },
+        )
+        .await
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("failed to commit user update")
+        .map_err(e500)?;
+    if is_disabled {
+        generation_cache.remove(&diff.username);
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Updates the fields of the diff that are stored in the user table
+async fn update_user_row(
+    executor: impl Executor<'_, Database = Db>,
+    diff: &UserMetadataDiff,
+) -> actix_web::Result<()> {
+    // Clearing `LockedOutAt` whenever `LockedOut` is set means lockouts set by an
+    // administrator do not expire
     #[cfg(feature = "mysql")]
     let query = sqlx::query!(
         "UPDATE `user` SET
         `DisplayName` = CASE WHEN ? IS NULL THEN `DisplayName` ELSE ? end,
         `ForcePassChange` = CASE WHEN ? IS NULL THEN `ForcePassChange` ELSE ? end,
-        `AssignedRole` = CASE WHEN ? <> 0 THEN `AssignedRole` ELSE ? end,
         `Enabled` = CASE WHEN ? IS NULL THEN `Enabled` ELSE ? end,
         `LockedOut` = CASE WHEN ? IS NULL THEN `LockedOut` ELSE ? end,
-        `FailedAttempts` = CASE WHEN ? IS NULL THEN `FailedAttempts` ELSE ? end
+        `LockedOutAt` = CASE WHEN ? IS NULL THEN `LockedOutAt` ELSE NULL end,
+        `FailedAttempts` = CASE WHEN ? IS NULL THEN `FailedAttempts` ELSE ? end,
+        `Email` = CASE WHEN ? <> 0 THEN `Email` ELSE ? end
         WHERE `UserName`=?;",
         diff.display_name,
         diff.display_name,
         diff.force_pass_change,
         diff.force_pass_change,
-        diff.assigned_role.is_none(),
-        diff.assigned_role,
         diff.enabled,
         diff.enabled,
         diff.locked_out,
         diff.locked_out,
+        diff.locked_out,
         diff.failed_attempts,
         diff.failed_attempts,
+        diff.email.is_none(),
+        diff.email,
         diff.username
     );
     #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
     // TODO 5: Check why encode trait impl doesn't make converting not necessary
     let query = {
-        let display_name = diff.display_name.map(|x| x.to_string());
-        let assigned_role: Option<i32> = match diff.assigned_role {
-            Some(Some(x)) => Some(x.try_into()?),
-            Some(None) | None => None,
-        };
+        let display_name = diff.display_name.as_ref().map(|x| x.to_string());
         let failed_attempts: Option<i16> = diff.failed_attempts.map(|x| x.into());
+        let email = diff.email.clone().flatten().map(|x| x.to_string());
         sqlx::query!(
             "UPDATE users SET
             display_name = CASE WHEN $1 THEN display_name ELSE $2 end,
             force_pass_change = CASE WHEN $3 THEN force_pass_change ELSE $4 end,
-            assigned_role = CASE WHEN $5 THEN assigned_role ELSE $6 end,
-            is_enabled = CASE WHEN $7 THEN is_enabled ELSE $8 end,
-            locked_out = CASE WHEN $9 THEN locked_out ELSE $10 end,
-            failed_attempts = CASE WHEN $11 THEN failed_attempts ELSE $12 end
+            is_enabled = CASE WHEN $5 THEN is_enabled ELSE $6 end,
+            locked_out = CASE WHEN $7 THEN locked_out ELSE $8 end,
+            locked_out_at = CASE WHEN $7 THEN locked_out_at ELSE NULL end,
+            failed_attempts = CASE WHEN $9 THEN failed_attempts ELSE $10 end,
+            email = CASE WHEN $11 THEN email ELSE $12 end
             WHERE user_name=$13",
             display_name.is_none(),
             display_name,
             diff.force_pass_change.is_none(),
             diff.force_pass_change,
-            diff.assigned_role.is_none(),
-            assigned_role,
             diff.enabled.is_none(),
             diff.enabled,
             diff.locked_out.is_none(),
             diff.locked_out,
             diff.failed_attempts.is_none(),
             failed_attempts,
+            diff.email.is_none(),
+            email,
             diff.username.as_ref()
         )
     };
     let sql_result = query
-        .execute(pool)
+        .execute(executor)
         .await
         .context("failed to update user")
         .map_err(e500)?;
     validate_one_row_affected(&sql_result)
         .context("wrong number of rows changed when updating user")
         .map_err(e500)?;
-
-    Ok(HttpResponse::Ok().finish())
+    Ok(())
 }
 
 #[tracing::instrument(ret, err(Debug), skip(pool))]
@@ -243,12 +377,15 @@ async fn role_list(pool: &DbPool) -> actix_web::Result<Vec<RoleIdAndName>> {
 async fn user_list(pool: &DbPool) -> actix_web::Result<Vec<UserMetadata>> {
     #[cfg(feature = "mysql")]
     let query = sqlx::query!(
-        "SELECT `UserName`, `DisplayName`, `ForcePassChange`, `AssignedRole`, `Enabled`, `LockedOut`, `FailedAttempts`, `PassChangeDate` FROM `user`",
+        "SELECT `UserName`, `DisplayName`, `ForcePassChange`, `Enabled`, `LockedOut`, `FailedAttempts`, `PassChangeDate`, `Email` FROM `user`",
     );
     #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
     let query = sqlx::query!(
-        "SELECT user_name, display_name, force_pass_change, assigned_role, is_enabled, locked_out, failed_attempts, pass_change_date FROM users",
+        "SELECT user_name, display_name, force_pass_change, is_enabled, locked_out, failed_attempts, pass_change_date, email FROM users",
     );
+    let mut assigned_roles = authentication::get_all_assigned_roles(pool)
+        .await
+        .map_err(e500)?;
     query
         .fetch_all(pool)
         .await
@@ -256,38 +393,48 @@ async fn user_list(pool: &DbPool) -> actix_web::Result<Vec<UserMetadata>> {
         .map_err(e500)?
         .into_iter()
         .map(|x| {
+            #[cfg(feature = "mysql")]
+            let assigned_roles = assigned_roles.remove(&x.UserName).unwrap_or_default();
+            #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
+            let assigned_roles = assigned_roles.remove(&x.user_name).unwrap_or_default();
             #[cfg(feature = "mysql")]
             return Ok(UserMetadata {
                 username: x.UserName.try_into()?,
                 display_name: x.DisplayName.try_into()?,
                 force_pass_change: db_int_to_bool(x.ForcePassChange),
-                assigned_role: x.AssignedRole.map(|x| x.try_into()).transpose()?,
+                assigned_roles,
                 enabled: db_int_to_bool(x.Enabled),
                 locked_out: db_int_to_bool(x.LockedOut),
                 failed_attempts: x.FailedAttempts.try_into()?,
                 pass_change_date: x.PassChangeDate,
+                email: EmailAddress::try_from_opt(x.Email)?,
             });
             #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
             Ok(UserMetadata {
                 username: x.user_name.try_into()?,
                 display_name: x.display_name.try_into()?,
                 force_pass_change: x.force_pass_change,
-                assigned_role: x.assigned_role.map(|x| x.try_into()).transpose()?,
+                assigned_roles,
                 enabled: x.is_enabled,
                 locked_out: x.locked_out,
                 failed_attempts: x.failed_attempts.try_into()?,
                 pass_change_date: x.pass_change_date,
+                email: EmailAddress::try_from_opt(x.email)?,
             })
         })
         .collect::<anyhow::Result<Vec<UserMetadata>>>()
         .map_err(e500)
 }
 
-#[tracing::instrument(skip(pool))]
+#[tracing::instrument(skip(pool, password_hashing, generation_cache))]
 pub async fn password_reset(
     pool: web::Data<DbPool>,
+    password_policy: web::Data<PasswordPolicy>,
+    password_hashing: web::Data<PasswordHashing>,
+    generation_cache: web::Data<SessionGenerationCache>,
     web::Json(args): web::Json<PasswordResetReqArgs>,
     user_info: web::ReqData<UserInfo>,
+    audit: AuditContext,
 ) -> Result<HttpResponse, ResetPasswordError> {
     let logged_in_username = user_info.into_inner().username;
     if logged_in_username == args.username {
@@ -295,49 +442,144 @@ pub async fn password_reset(
     }
 
     let should_force_pass_change = true;
+    let mut transaction = pool.begin().await.context("failed to start transaction")?;
     crate::authentication::change_password(
         &args.username,
         args.new_password,
         should_force_pass_change,
-        &pool,
+        password_policy.history_count,
+        &password_hashing,
+        &mut transaction,
     )
     .await
     .map_err(ResetPasswordError::UnexpectedError)?;
 
+    crate::authentication::invalidate_user_sessions(&args.username, &mut *transaction)
+        .await
+        .map_err(ResetPasswordError::UnexpectedError)?;
+
+    audit
+        .record(
+            &mut transaction,
+            AuditAction::PasswordReset,
+            &args.username,
+            &(),
+        )
+        .await?;
+    transaction
+        .commit()
+        .await
+        .context("failed to commit password reset")?;
+    generation_cache.remove(&args.username);
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Revokes a single session of the user or all of them ("log out everywhere")
+/// if no session is specified
+///
+/// Revoking all sessions works with every session store but single sessions
+/// can only be revoked when sessions are stored in the DB
+#[tracing::instrument(ret, err(Debug), skip(pool, generation_cache))]
+pub async fn user_sessions_revoke(
+    pool: web::Data<DbPool>,
+    generation_cache: web::Data<SessionGenerationCache>,
+    web::Json(args): web::Json<RevokeSessionsReqArgs>,
+    audit: AuditContext,
+) -> actix_web::Result<HttpResponse> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("failed to start transaction")
+        .map_err(e500)?;
+    match args.session_id {
+        Some(session_id) => {
+            revoke_single_session(&mut *transaction, &args.username, session_id).await?
+        }
+        None => authentication::invalidate_user_sessions(&args.username, &mut *transaction)
+            .await
+            .map_err(e500)?,
+    }
+    audit
+        .record(
+            &mut transaction,
+            AuditAction::SessionsRevoked,
+            &args.username,
+            &args.session_id,
+        )
+        .await
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("failed to commit session revocation")
+        .map_err(e500)?;
+    if args.session_id.is_none() {
+        generation_cache.remove(&args.username);
+    }
     Ok(HttpResponse::Ok().finish())
 }
 
+#[cfg(feature = "db-session")]
+async fn revoke_single_session(
+    executor: impl Executor<'_, Database = Db>,
+    username: &Username,
+    session_id: SessionId,
+) -> actix_web::Result<()> {
+    let qty_revoked = crate::db_session::revoke_user_sessions(executor, username, Some(session_id))
+        .await
+        .map_err(e500)?;
+    if qty_revoked == 0 {
+        return Err(e400("no session found with that ID for the user"));
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "db-session"))]
+async fn revoke_single_session(
+    _executor: impl Executor<'_, Database = Db>,
+    _username: &Username,
+    _session_id: SessionId,
+) -> actix_web::Result<()> {
+    Err(e400(
+        "single sessions can only be revoked when sessions are stored in the database",
+    ))
+}
+
 #[tracing::instrument(err(Debug), skip(pool))]
 pub async fn role_assign(
     pool: web::Data<DbPool>,
     web::Json(req_args): web::Json<AssignReqArgs>,
+    audit: AuditContext,
 ) -> actix_web::Result<HttpResponse> {
-    let pool: &DbPool = &pool;
-    #[cfg(feature = "mysql")]
-    let query = sqlx::query!(
-        "UPDATE `user`
-        SET `AssignedRole` = ? 
-        WHERE `user`.`UserName` = ?;",
-        req_args.role_id,
-        req_args.username
-    );
-    #[cfg(all(not(feature = "mysql"), feature = "postgres"))]
-    // TODO 5: Check why encode trait impl doesn't make converting not necessary
-    let query = {
-        let role_id: i32 = req_args.role_id.try_into()?;
-        sqlx::query!(
-            "UPDATE users
-        SET assigned_role = $1 
-        WHERE users.user_name = $2;",
-            role_id,
-            req_args.username.as_ref()
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("failed to start transaction")
+        .map_err(e500)?;
+    // Also ensures the user exists
+    let before = get_user_metadata(&mut *transaction, &req_args.username).await?;
+    authentication::add_assigned_role(&req_args.username, req_args.role_id, &mut *transaction)
+        .await
+        .map_err(e500)?;
+    let mut after = before.assigned_roles.clone();
+    after.insert(req_args.role_id);
+    audit
+        .record(
+            &mut transaction,
+            AuditAction::RoleAssigned,
+            &req_args.username,
+            &AuditChange {
+                before: &before.assigned_roles,
+                after: &after,
+            },
         )
-    };
-    let sql_result = query
-        .execute(pool)
         .await
-        .context("failed to set role for user")
         .map_err(e500)?;
-    validate_one_row_affected(&sql_result).map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("failed to commit role assignment")
+        .map_err(e500)?;
     Ok(HttpResponse::Ok().finish())
 }
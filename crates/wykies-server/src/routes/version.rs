@@ -0,0 +1,6 @@
+use actix_web::{HttpResponse, web};
+use wykies_shared::version::VersionInfo;
+
+pub async fn version(version_info: web::Data<VersionInfo>) -> HttpResponse {
+    HttpResponse::Ok().json(version_info.get_ref())
+}
@@ -1,12 +1,26 @@
 use actix_session::{Session, SessionExt, SessionGetError, SessionInsertError};
 use actix_web::{FromRequest, HttpRequest, dev::Payload};
 use std::future::{Ready, ready};
-use wykies_shared::uac::UserInfo;
+use wykies_shared::{host_branch::HostId, uac::UserInfo};
+use wykies_time::Timestamp;
 
 pub struct TypedSession(Session);
 
+/// Identifies the client that the session was created for
+///
+/// The branch resolved for the host at login is kept in the [`UserInfo`]
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+pub struct ClientBinding {
+    pub host_id: HostId,
+}
+
 impl TypedSession {
-    const USER_INFO_KEY: &'static str = "user_info";
+    pub(crate) const USER_INFO_KEY: &'static str = "user_info";
+    const LOGIN_TIMESTAMP_KEY: &'static str = "login_timestamp";
+    const LAST_ACTIVITY_KEY: &'static str = "last_activity";
+    pub(crate) const SESSION_GENERATION_KEY: &'static str = "session_generation";
+    const CLIENT_BINDING_KEY: &'static str = "client_binding";
+    const TOTP_ENROLMENT_REQUIRED_KEY: &'static str = "totp_enrolment_required";
 
     pub fn renew(&self) {
         self.0.renew();
@@ -20,6 +34,53 @@ impl TypedSession {
         self.0.get(Self::USER_INFO_KEY)
     }
 
+    /// Records the information needed to later check if the session is still
+    /// valid
+    pub fn insert_session_start(&self, session_generation: i32) -> Result<(), SessionInsertError> {
+        let now = Timestamp::now();
+        self.0.insert(Self::LOGIN_TIMESTAMP_KEY, now)?;
+        self.0.insert(Self::LAST_ACTIVITY_KEY, now)?;
+        self.0
+            .insert(Self::SESSION_GENERATION_KEY, session_generation)
+    }
+
+    pub fn get_login_timestamp(&self) -> Result<Option<Timestamp>, SessionGetError> {
+        self.0.get(Self::LOGIN_TIMESTAMP_KEY)
+    }
+
+    pub fn get_last_activity(&self) -> Result<Option<Timestamp>, SessionGetError> {
+        self.0.get(Self::LAST_ACTIVITY_KEY)
+    }
+
+    pub fn get_session_generation(&self) -> Result<Option<i32>, SessionGetError> {
+        self.0.get(Self::SESSION_GENERATION_KEY)
+    }
+
+    pub fn insert_client_binding(
+        &self,
+        client_binding: ClientBinding,
+    ) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::CLIENT_BINDING_KEY, client_binding)
+    }
+
+    pub fn get_client_binding(&self) -> Result<Option<ClientBinding>, SessionGetError> {
+        self.0.get(Self::CLIENT_BINDING_KEY)
+    }
+
+    /// Set when the user must enrol in two-factor authentication before they
+    /// are allowed to do anything else
+    pub fn insert_totp_enrolment_required(&self, value: bool) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::TOTP_ENROLMENT_REQUIRED_KEY, value)
+    }
+
+    pub fn get_totp_enrolment_required(&self) -> Result<Option<bool>, SessionGetError> {
+        self.0.get(Self::TOTP_ENROLMENT_REQUIRED_KEY)
+    }
+
+    pub fn update_last_activity(&self) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::LAST_ACTIVITY_KEY, Timestamp::now())
+    }
+
     pub fn log_out(self) {
         self.0.purge()
     }
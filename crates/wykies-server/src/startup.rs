@@ -1,22 +1,43 @@
+#[cfg(all(not(feature = "redis-session-rustls"), feature = "db-session"))]
+use crate::DbSessionStore;
+#[cfg(feature = "db-session")]
+use crate::routes::user_sessions;
 use crate::{
     Configuration, DatabaseSettings,
-    authentication::{LoginAttemptLimit, validate_user_access},
+    analytics::{AnalyticsRecorder, record_request_analytics},
+    authentication::{
+        HostLoginThrottle, LoginAttemptLimit, LoginThrottling, PasswordExpiry, PasswordHashing,
+        PasswordResetSettings, SessionGenerationCache, SessionHostBinding, SessionLifetimes,
+        TotpSettings, validate_user_access,
+    },
     configuration::ApplicationSettings,
     get_configuration,
+    health::{DbHealthCheck, HealthCheck, HealthChecks},
+    notifier::Notifier,
+    openapi::{ApiOperation, OpenApiDocument, server_operations},
+    plugin::ServerPlugin,
     routes::{
+        analytics_summary, api_key_list, api_key_new, api_key_revoke, api_key_rotate, audit_list,
         branch_list, branch_new, change_password, health_check, host_branch_pair_list,
-        host_branch_pair_lookup, host_branch_pair_set, log_out, login, password_reset, role,
-        role_assign, role_new, route_not_found, status, user, user_new, user_update,
-        users_and_roles_list,
+        host_branch_pair_lookup, host_branch_pair_set, log_out, login, openapi, password_policy,
+        password_reset, password_reset_redeem, password_reset_request, role, role_assign,
+        role_delete, role_list, role_new, role_parents_set, role_update, route_not_found, status,
+        status_json, totp_confirm, totp_enrol, user, user_new, user_permissions,
+        user_sessions_revoke, user_totp_reset, user_update, users_and_roles_list, version,
     },
 };
-use actix_session::SessionMiddleware;
-#[cfg(all(not(feature = "redis-session-rustls"), feature = "cookie-session"))]
+#[cfg(all(
+    not(feature = "redis-session-rustls"),
+    not(feature = "db-session"),
+    feature = "cookie-session"
+))]
 use actix_session::storage::CookieSessionStore;
 #[cfg(feature = "redis-session-rustls")]
 use actix_session::storage::RedisSessionStore;
+use actix_session::{SessionMiddleware, config::BrowserSession};
 use actix_web::{
-    App, HttpResponse, HttpServer,
+    App, HttpServer,
+    cookie::time::Duration,
     middleware::from_fn,
     web::{self, ServiceConfig},
 };
@@ -27,6 +48,7 @@ use std::{
     future::Future,
     net::{SocketAddr, TcpListener},
     str::FromStr,
+    sync::Arc,
 };
 use tracing::{info, instrument};
 use tracing_actix_web::TracingLogger;
@@ -35,7 +57,8 @@ use ws_auth::AuthTokenManager;
 use wykies_shared::{
     const_config,
     db_types::{DbPool, DbPoolOptions},
-    telemetry,
+    e400, telemetry,
+    version::VersionInfo,
 };
 
 pub trait ServerTask {
@@ -63,6 +86,10 @@ where
     pub db_pool: DbPool,
     pub api_server_init_bundle: ApiServerInitBundle<T>,
     pkg_version: &'static str,
+    analytics_recorder: Option<AnalyticsRecorder>,
+    health_checks: Vec<Arc<dyn HealthCheck>>,
+    plugin_ids: Vec<String>,
+    api_operations: Vec<ApiOperation>,
 }
 
 /// Initializes Tracing
@@ -121,9 +148,46 @@ impl<T: Clone + DeserializeOwned> ApiServerBuilder<T> {
             db_pool,
             api_server_init_bundle,
             pkg_version,
+            analytics_recorder: None,
+            health_checks: Default::default(),
+            plugin_ids: Default::default(),
+            api_operations: Default::default(),
         })
     }
 
+    /// Enables recording of endpoint usage (See [`crate::analytics`])
+    pub fn analytics_recorder(mut self, analytics_recorder: AnalyticsRecorder) -> Self {
+        self.analytics_recorder = Some(analytics_recorder);
+        self
+    }
+
+    /// Records that the plugin is in use so it is included in the version info
+    /// reported to clients
+    pub fn plugin<P: ServerPlugin>(mut self) -> Self {
+        self.plugin_ids.push(P::ID.to_string());
+        self
+    }
+
+    /// Adds checks to be included in the health report in addition to the ones
+    /// the server provides (See [`crate::health`])
+    pub fn health_checks(
+        mut self,
+        health_checks: impl IntoIterator<Item = Arc<dyn HealthCheck>>,
+    ) -> Self {
+        self.health_checks.extend(health_checks);
+        self
+    }
+
+    /// Describes routes added by plugins or the application so they are
+    /// included in the OpenAPI document (See [`crate::openapi`])
+    pub fn api_operations(
+        mut self,
+        api_operations: impl IntoIterator<Item = ApiOperation>,
+    ) -> Self {
+        self.api_operations.extend(api_operations);
+        self
+    }
+
     #[instrument(err(Debug), skip_all)]
     pub async fn build_runnable_api_server<FOpen, FProtected>(
         self,
@@ -135,16 +199,78 @@ impl<T: Clone + DeserializeOwned> ApiServerBuilder<T> {
         FOpen: Fn(&mut ServiceConfig) + Send + Clone + 'static,
         FProtected: Fn(&mut ServiceConfig) + Send + Clone + 'static,
     {
-        let db_pool = web::Data::new(self.db_pool);
         let ApiServerInitBundle {
             cancellation_tracker,
             configuration,
             ..
         } = self.api_server_init_bundle;
 
+        let mut health_checks = HealthChecks::default();
+        health_checks.push(Arc::new(DbHealthCheck(self.db_pool.clone())));
+        #[cfg(feature = "redis-session-rustls")]
+        health_checks.push(Arc::new(crate::health::RedisHealthCheck(
+            configuration.redis_uri.clone(),
+        )));
+        #[cfg(not(feature = "disable-tls"))]
+        health_checks.push(Arc::new(crate::health::TlsCertHealthCheck));
+        health_checks.extend(self.health_checks);
+        let health_checks = web::Data::new(health_checks);
+        let db_pool = web::Data::new(self.db_pool);
+
+        let analytics_recorder = self.analytics_recorder.map(web::Data::new);
         let login_attempt_limit = web::Data::new(LoginAttemptLimit(
             configuration.user_auth.login_attempt_limit,
         ));
+        let login_throttling = web::Data::new(LoginThrottling {
+            lockout_duration: configuration.user_auth.lockout_duration_secs,
+            failed_attempt_base_delay: configuration.user_auth.failed_attempt_base_delay_secs,
+            failed_attempt_max_delay: configuration.user_auth.failed_attempt_max_delay_secs,
+        });
+        let host_login_throttle = web::Data::new(HostLoginThrottle::new(
+            configuration.user_auth.host_login_attempt_limit,
+            configuration.user_auth.host_login_window_secs,
+        ));
+
+        let session_lifetimes = web::Data::new(SessionLifetimes {
+            max_lifetime: configuration.user_auth.session_max_lifetime_secs,
+            idle_timeout: configuration.user_auth.session_idle_timeout_secs,
+        });
+        let session_host_binding = web::Data::new(SessionHostBinding {
+            enforce: configuration.user_auth.enforce_session_host_binding,
+        });
+        let session_generation_cache = web::Data::new(SessionGenerationCache::new(
+            const_config::server::SESSION_GENERATION_CACHE_MAX_AGE,
+        ));
+        let totp_settings = web::Data::new(TotpSettings {
+            required_for_man_uac: configuration.user_auth.totp_required_for_man_uac,
+            issuer: configuration.user_auth.totp_issuer.clone(),
+        });
+        let password_expiry = web::Data::new(PasswordExpiry {
+            max_age_days: configuration.user_auth.password_max_age_days,
+            warning_days: configuration.user_auth.password_expiry_warning_days,
+        });
+        let password_hashing = web::Data::new(
+            PasswordHashing::new(configuration.user_auth.argon2)
+                .context("failed to setup password hashing")?,
+        );
+        let password_reset_settings = web::Data::new(PasswordResetSettings {
+            token_lifetime: configuration.user_auth.password_reset_token_lifetime_secs,
+        });
+        let notifier: web::Data<dyn Notifier> = web::Data::from(
+            configuration
+                .notifier
+                .build()
+                .context("failed to setup notifier")?,
+        );
+        let password_policy_config =
+            web::Data::new(configuration.user_auth.password_policy.clone());
+        let session_state_ttl = Duration::seconds(
+            configuration
+                .user_auth
+                .session_max_lifetime_secs
+                .try_into()
+                .context("session max lifetime too large")?,
+        );
 
         let websocket_auth_manager = web::Data::new(AuthTokenManager::new(
             configuration.websockets.token_lifetime_secs,
@@ -157,7 +283,14 @@ impl<T: Clone + DeserializeOwned> ApiServerBuilder<T> {
                 .expose_secret()
                 .as_bytes(),
         );
-        let pkg_version = self.pkg_version;
+        let version_info = web::Data::new(VersionInfo::new(self.pkg_version, self.plugin_ids));
+        let mut api_operations = server_operations();
+        api_operations.extend(self.api_operations);
+        let openapi_document = web::Data::new(OpenApiDocument::new(
+            "wykies-server",
+            self.pkg_version,
+            &api_operations,
+        ));
 
         #[cfg(feature = "redis-session-rustls")]
         let session_store = {
@@ -181,6 +314,10 @@ impl<T: Clone + DeserializeOwned> ApiServerBuilder<T> {
 
         let server = HttpServer::new(move || {
             let app = App::new();
+            let app = match analytics_recorder.as_ref() {
+                Some(analytics_recorder) => app.app_data(analytics_recorder.clone()),
+                None => app,
+            };
 
             // If both a debug build and disable-cors flag is set then set CORS to
             // permissive. This code runs once per thread so several of this will be
@@ -195,7 +332,11 @@ impl<T: Clone + DeserializeOwned> ApiServerBuilder<T> {
                 eprintln!("CORS set to permissive");
             }
 
-            #[cfg(all(not(feature = "redis-session-rustls"), feature = "cookie-session"))]
+            #[cfg(all(
+                not(feature = "redis-session-rustls"),
+                not(feature = "db-session"),
+                feature = "cookie-session"
+            ))]
             let session_store = {
                 info!(
                     // This info is repeated for each thread but less bad than duplicating the cfg
@@ -208,7 +349,21 @@ impl<T: Clone + DeserializeOwned> ApiServerBuilder<T> {
             #[cfg(feature = "redis-session-rustls")]
             let session_store = session_store.clone(); // When using redis we need to clone
 
-            // TODO 4: Look into session expiration https://docs.rs/actix-session/latest/actix_session/config/struct.SessionMiddlewareBuilder.html
+            #[cfg(all(not(feature = "redis-session-rustls"), feature = "db-session"))]
+            let session_store = {
+                info!(
+                    // This info is repeated for each thread but less bad than duplicating the cfg
+                    session_store = "DbSessionStore",
+                    "Using Database Session Storage"
+                );
+                DbSessionStore::new(db_pool.get_ref().clone())
+            };
+
+            // Server side checks in `validate_user_access` enforce the lifetimes, the TTL
+            // just ensures the storage backend does not keep sessions around for longer
+            let session_middleware = SessionMiddleware::builder(session_store, secret_key.clone())
+                .session_lifecycle(BrowserSession::default().state_ttl(session_state_ttl))
+                .build();
 
             let front_end_folder = if cfg!(feature = "running-from-workspace-root") {
                 "./crates/chat-app-server/app/"
@@ -216,16 +371,44 @@ impl<T: Clone + DeserializeOwned> ApiServerBuilder<T> {
                 "./app/"
             };
 
-            app.wrap(SessionMiddleware::new(session_store, secret_key.clone()))
-                // TODO 4: Spawn task to send relevant req info to DB to record endpoints used
-                // (Basic analytics)
+            let user_scope = web::scope("/user")
+                .route("/", web::get().to(user))
+                .route("/list", web::get().to(users_and_roles_list))
+                .route("/new", web::post().to(user_new))
+                .route("/password_reset", web::post().to(password_reset))
+                .route("/permissions", web::get().to(user_permissions))
+                .route("/role", web::post().to(role_assign))
+                .route("/sessions/revoke", web::post().to(user_sessions_revoke))
+                .route("/totp_reset", web::post().to(user_totp_reset))
+                .route("/update", web::patch().to(user_update));
+            #[cfg(feature = "db-session")]
+            let user_scope = user_scope.route("/sessions", web::get().to(user_sessions));
+
+            app.wrap(session_middleware)
+                .wrap(from_fn(record_request_analytics))
                 .wrap(TracingLogger::default())
+                // Registered before the "/api" scope so that it is matched first and does not
+                // require login
+                .route("/api/openapi.json", web::get().to(openapi))
                 .service(
                     web::scope("/api")
                         .wrap(from_fn(validate_user_access))
                         .configure(protected_resource.clone())
                         .route("/change_password", web::post().to(change_password))
                         .route("/logout", web::post().to(log_out))
+                        .route("/password_policy", web::get().to(password_policy))
+                        .service(
+                            web::scope("/analytics")
+                                .route("/summary", web::get().to(analytics_summary)),
+                        )
+                        .service(
+                            web::scope("/api_key")
+                                .route("/list", web::get().to(api_key_list))
+                                .route("/new", web::post().to(api_key_new))
+                                .route("/revoke", web::post().to(api_key_revoke))
+                                .route("/rotate", web::post().to(api_key_rotate)),
+                        )
+                        .service(web::scope("/audit").route("/list", web::get().to(audit_list)))
                         .service(web::scope("/branch").route("/new", web::post().to(branch_new)))
                         .service(
                             web::scope("/host_branch")
@@ -236,33 +419,54 @@ impl<T: Clone + DeserializeOwned> ApiServerBuilder<T> {
                         .service(
                             web::scope("/role")
                                 .route("/", web::get().to(role))
-                                .route("/new", web::post().to(role_new)),
+                                .route("/delete", web::post().to(role_delete))
+                                .route("/list", web::get().to(role_list))
+                                .route("/new", web::post().to(role_new))
+                                .route("/parents", web::post().to(role_parents_set))
+                                .route("/update", web::patch().to(role_update)),
                         )
                         .service(
-                            web::scope("/user")
-                                .route("/", web::get().to(user))
-                                .route("/list", web::get().to(users_and_roles_list))
-                                .route("/new", web::post().to(user_new))
-                                .route("/password_reset", web::post().to(password_reset))
-                                .route("/role", web::post().to(role_assign))
-                                .route("/update", web::patch().to(user_update)),
-                        ),
+                            web::scope("/totp")
+                                .route("/confirm", web::post().to(totp_confirm))
+                                .route("/enrol", web::post().to(totp_enrol)),
+                        )
+                        .service(user_scope),
                 )
                 .configure(open_resource.clone())
                 .route("/branch/list", web::get().to(branch_list))
                 .route("/health_check", web::get().to(health_check))
                 .route("/login", web::post().to(login))
-                .route("/status", web::get().to(status))
                 .route(
-                    // TODO 5: Use version endpoint in client to detect if compatible with server,
-                    // at least give warning if not
-                    "/version",
-                    web::get().to(|| async { HttpResponse::Ok().body(pkg_version.to_string()) }),
+                    "/password_reset/redeem",
+                    web::post().to(password_reset_redeem),
                 )
+                .route(
+                    "/password_reset/request",
+                    web::post().to(password_reset_request),
+                )
+                .route("/status", web::get().to(status))
+                .route("/status/json", web::get().to(status_json))
+                .route("/version", web::get().to(version))
                 .service(actix_files::Files::new("/", front_end_folder).index_file("index.html"))
                 .app_data(db_pool.clone())
+                .app_data(version_info.clone())
+                .app_data(openapi_document.clone())
+                .app_data(health_checks.clone())
                 .app_data(login_attempt_limit.clone())
+                .app_data(login_throttling.clone())
+                .app_data(host_login_throttle.clone())
+                .app_data(session_lifetimes.clone())
+                .app_data(session_host_binding.clone())
+                .app_data(session_generation_cache.clone())
+                .app_data(totp_settings.clone())
+                .app_data(password_policy_config.clone())
+                .app_data(password_expiry.clone())
+                .app_data(password_hashing.clone())
+                .app_data(password_reset_settings.clone())
+                .app_data(notifier.clone())
                 .app_data(websocket_auth_manager.clone())
+                .app_data(web::JsonConfig::default().error_handler(|err, _| e400(err)))
+                .app_data(web::QueryConfig::default().error_handler(|err, _| e400(err)))
                 .default_service(web::route().to(route_not_found))
         });
 
@@ -287,7 +491,7 @@ impl<T: Clone + DeserializeOwned> ApiServerBuilder<T> {
         let server = server.run();
         info!(
             version = env!("CARGO_PKG_VERSION"),
-            "API Server prepared to be run at version {}", pkg_version
+            "API Server prepared to be run at version {}", self.pkg_version
         );
         Ok((RunnableApiServer(server), cancellation_tracker, port))
     }
@@ -1,3 +1,6 @@
+#[cfg(not(feature = "disable-tls"))]
+const CERT_FILE: &str = "cert.pem";
+
 #[cfg(not(feature = "disable-tls"))]
 /// Patterned on example from https://github.com/actix/examples/tree/master/https-tls/rustls
 #[tracing::instrument(ret, err(Debug))]
@@ -12,7 +15,7 @@ pub fn load_rustls_config() -> anyhow::Result<rustls::ServerConfig> {
     let config = rustls::ServerConfig::builder().with_no_client_auth();
 
     // load TLS key/cert files
-    let cert_path = std::path::PathBuf::from("cert.pem")
+    let cert_path = std::path::PathBuf::from(CERT_FILE)
         .canonicalize()
         .context("failed to canonicalize path to cert.pem file")?;
     let key_path = std::path::PathBuf::from("key.pem")
@@ -35,3 +38,21 @@ pub fn load_rustls_config() -> anyhow::Result<rustls::ServerConfig> {
         .with_single_cert(cert_chain, key)
         .context("key_der invalid or private key does not match end-entity")
 }
+
+#[cfg(not(feature = "disable-tls"))]
+/// Returns when the end-entity certificate in the certificate file expires
+#[tracing::instrument(ret, err(Debug))]
+pub fn load_cert_expiry() -> anyhow::Result<wykies_time::Timestamp> {
+    use anyhow::Context as _;
+    use rustls_pki_types::{CertificateDer, pem::PemObject};
+
+    let cert = CertificateDer::from_pem_file(CERT_FILE)
+        .with_context(|| format!("failed to extract certificate from: {CERT_FILE:?}"))?;
+    let (_, cert) =
+        x509_parser::parse_x509_certificate(&cert).context("failed to parse certificate")?;
+    cert.validity()
+        .not_after
+        .timestamp()
+        .try_into()
+        .context("certificate expiry out of range")
+}
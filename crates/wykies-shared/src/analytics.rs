@@ -0,0 +1,12 @@
+//! Types for reporting on how the API endpoints are being used
+
+/// Usage of a single endpoint over the requested time window
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
+pub struct EndpointSummary {
+    pub path: String,
+    pub method: String,
+    pub count: u64,
+    pub p50_latency_ms: u32,
+    pub p95_latency_ms: u32,
+}
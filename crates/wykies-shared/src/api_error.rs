@@ -0,0 +1,275 @@
+//! The error body returned by the server for all failed requests
+//!
+//! The message is the same text the error type it was created from displays so
+//! it can be shown to the user as is, the other fields allow the client to act
+//! on the error without parsing the message.
+
+use crate::{
+    errors::{ConversionError, NotLoggedInError, PermissionConversionError},
+    uac::{
+        ApiKeyError, AuthError, ChangePasswordError, PasswordResetTokenError, Permission,
+        PermissionsError, ResetPasswordError, RoleError, SessionExpiredError, TotpError,
+    },
+};
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{message}")]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
+pub struct ApiError {
+    pub code: ApiErrorCode,
+    pub message: String,
+    /// Only set for [`ApiErrorCode::MissingPermissions`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing_permissions: Vec<Permission>,
+    /// Only set for [`ApiErrorCode::ValidationFailed`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub field_errors: Vec<FieldError>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
+pub enum ApiErrorCode {
+    NotLoggedIn,
+    SessionExpired(SessionExpiredError),
+    InvalidUserOrPassword,
+    LockedOut,
+    NotEnabled,
+    BranchNotSet,
+    InvalidTotpCode,
+    /// Login attempts are being throttled, retry after the time in the message
+    TooManyAttempts,
+    /// The user must enrol in two-factor authentication before they can use
+    /// any other endpoint
+    TotpEnrolmentRequired,
+    MissingPermissions,
+    /// The API key sent using the `Bearer` scheme was not accepted
+    InvalidApiKey,
+    /// See [`ApiError::field_errors`] for which fields failed
+    ValidationFailed,
+    /// The request could not be processed as sent (Not tied to a specific
+    /// field)
+    BadRequest,
+    NotFound,
+    WebSocketAuthFailed,
+    Internal,
+}
+
+/// Identifies which field of the request arguments failed validation
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(code: ApiErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            missing_permissions: Default::default(),
+            field_errors: Default::default(),
+        }
+    }
+
+    /// Creates a [`ApiErrorCode::ValidationFailed`] error for a single field
+    pub fn validation(field: impl Into<String>, message: impl Into<String>) -> Self {
+        let message = message.into();
+        Self {
+            field_errors: vec![FieldError {
+                field: field.into(),
+                message: message.clone(),
+            }],
+            ..Self::new(ApiErrorCode::ValidationFailed, message)
+        }
+    }
+
+    /// Returns the error for the field if there is one
+    pub fn field_error(&self, field: &str) -> Option<&FieldError> {
+        self.field_errors.iter().find(|x| x.field == field)
+    }
+
+    /// Returns `true` if the user needs to login (again) to continue
+    pub fn is_login_required(&self) -> bool {
+        matches!(
+            self.code,
+            ApiErrorCode::NotLoggedIn | ApiErrorCode::SessionExpired(_)
+        )
+    }
+}
+
+impl From<&NotLoggedInError> for ApiError {
+    fn from(value: &NotLoggedInError) -> Self {
+        Self::new(ApiErrorCode::NotLoggedIn, value.to_string())
+    }
+}
+
+impl From<&SessionExpiredError> for ApiError {
+    fn from(value: &SessionExpiredError) -> Self {
+        Self::new(ApiErrorCode::SessionExpired(*value), value.to_string())
+    }
+}
+
+impl From<&PermissionsError> for ApiError {
+    fn from(value: &PermissionsError) -> Self {
+        match value {
+            PermissionsError::MissingPermissions(permissions) => Self {
+                missing_permissions: permissions.clone(),
+                ..Self::new(ApiErrorCode::MissingPermissions, value.to_string())
+            },
+            PermissionsError::PathNotFound(_) => {
+                Self::new(ApiErrorCode::Internal, value.to_string())
+            }
+        }
+    }
+}
+
+impl From<&AuthError> for ApiError {
+    fn from(value: &AuthError) -> Self {
+        let code = match value {
+            AuthError::InvalidUserOrPassword => ApiErrorCode::InvalidUserOrPassword,
+            AuthError::LockedOut => ApiErrorCode::LockedOut,
+            AuthError::NotEnabled => ApiErrorCode::NotEnabled,
+            AuthError::BranchNotSetAndUnableToSet { .. } | AuthError::BranchNotSetResend { .. } => {
+                ApiErrorCode::BranchNotSet
+            }
+            AuthError::InvalidTotpCode => ApiErrorCode::InvalidTotpCode,
+            AuthError::TooManyAttempts { .. } => ApiErrorCode::TooManyAttempts,
+            AuthError::UnexpectedError(_) => ApiErrorCode::Internal,
+        };
+        Self::new(code, value.to_string())
+    }
+}
+
+impl From<&ApiKeyError> for ApiError {
+    fn from(value: &ApiKeyError) -> Self {
+        let code = match value {
+            ApiKeyError::Invalid | ApiKeyError::Expired => ApiErrorCode::InvalidApiKey,
+            ApiKeyError::SessionRequired => ApiErrorCode::BadRequest,
+            ApiKeyError::UnexpectedError(_) => ApiErrorCode::Internal,
+        };
+        Self::new(code, value.to_string())
+    }
+}
+
+impl From<&RoleError> for ApiError {
+    fn from(value: &RoleError) -> Self {
+        match value {
+            RoleError::ParentNotFound(_) | RoleError::Cycle { .. } => {
+                Self::validation("parent_roles", value.to_string())
+            }
+            RoleError::NotFound(_) => Self::new(ApiErrorCode::NotFound, value.to_string()),
+            RoleError::AssignedToUsers { .. } | RoleError::InheritedBy { .. } => {
+                Self::new(ApiErrorCode::BadRequest, value.to_string())
+            }
+            RoleError::UnexpectedError(_) => Self::new(ApiErrorCode::Internal, value.to_string()),
+        }
+    }
+}
+
+impl From<&TotpError> for ApiError {
+    fn from(value: &TotpError) -> Self {
+        let code = match value {
+            TotpError::AlreadyEnabled | TotpError::NoPendingEnrolment | TotpError::NoResetOwn => {
+                ApiErrorCode::BadRequest
+            }
+            TotpError::InvalidCode => return Self::validation("code", value.to_string()),
+            TotpError::EnrolmentRequired => ApiErrorCode::TotpEnrolmentRequired,
+            TotpError::UnexpectedError(_) => ApiErrorCode::Internal,
+        };
+        Self::new(code, value.to_string())
+    }
+}
+
+impl From<&ChangePasswordError> for ApiError {
+    fn from(value: &ChangePasswordError) -> Self {
+        let field = match value {
+            ChangePasswordError::Complexity(_) | ChangePasswordError::ReusedPassword(_) => {
+                "new_password"
+            }
+            ChangePasswordError::PasswordsDoNotMatch => "new_password_check",
+            ChangePasswordError::CurrentPasswordWrong(_) => "current_password",
+            ChangePasswordError::UnexpectedError(_) => {
+                return Self::new(ApiErrorCode::Internal, value.to_string());
+            }
+        };
+        Self::validation(field, value.to_string())
+    }
+}
+
+impl From<&PasswordResetTokenError> for ApiError {
+    fn from(value: &PasswordResetTokenError) -> Self {
+        let field = match value {
+            PasswordResetTokenError::InvalidToken => "token",
+            PasswordResetTokenError::Complexity(_) | PasswordResetTokenError::ReusedPassword(_) => {
+                "new_password"
+            }
+            PasswordResetTokenError::TooManyAttempts { .. } => {
+                return Self::new(ApiErrorCode::TooManyAttempts, value.to_string());
+            }
+            PasswordResetTokenError::UnexpectedError(_) => {
+                return Self::new(ApiErrorCode::Internal, value.to_string());
+            }
+        };
+        Self::validation(field, value.to_string())
+    }
+}
+
+impl From<&ResetPasswordError> for ApiError {
+    fn from(value: &ResetPasswordError) -> Self {
+        let code = match value {
+            ResetPasswordError::NoResetOwnPassword => ApiErrorCode::BadRequest,
+            ResetPasswordError::UnexpectedError(_) => ApiErrorCode::Internal,
+        };
+        Self::new(code, value.to_string())
+    }
+}
+
+impl From<&ConversionError> for ApiError {
+    fn from(value: &ConversionError) -> Self {
+        Self::new(ApiErrorCode::Internal, value.to_string())
+    }
+}
+
+impl From<&PermissionConversionError> for ApiError {
+    fn from(value: &PermissionConversionError) -> Self {
+        Self::new(ApiErrorCode::Internal, value.to_string())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod conversions {
+    use super::*;
+    use actix_web::{HttpResponse, http::StatusCode};
+
+    impl ApiError {
+        /// Used by the `ResponseError` impls so that all errors have the same
+        /// body format
+        pub fn to_response(&self, status: StatusCode) -> HttpResponse {
+            HttpResponse::build(status).json(self)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_keeps_missing_permissions() {
+        let expected: ApiError =
+            (&PermissionsError::MissingPermissions(vec![Permission::ManUAC])).into();
+        let json = serde_json::to_string(&expected).unwrap();
+        let actual: ApiError = serde_json::from_str(&json).unwrap();
+        assert_eq!(actual, expected);
+        assert_eq!(actual.missing_permissions, vec![Permission::ManUAC]);
+    }
+
+    #[test]
+    fn display_matches_source_error() {
+        let source = SessionExpiredError::IdleTimeout;
+        let actual = ApiError::from(&source);
+        assert_eq!(actual.to_string(), source.to_string());
+        assert!(actual.is_login_required());
+    }
+}
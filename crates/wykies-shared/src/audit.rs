@@ -0,0 +1,64 @@
+//! Types for the audit log of administrative actions (user, role and branch
+//! management)
+
+use crate::{host_branch::HostId, id_wrapper, uac::Username};
+use wykies_time::Timestamp;
+
+id_wrapper!(AuditEntryId, AuditEntryIdConversionError);
+
+/// The administrative actions that are recorded in the audit log
+///
+/// Stored in the database using the name of the variant so do not rename
+/// variants
+#[derive(
+    Debug,
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    strum::Display,
+    strum::EnumString,
+    strum::EnumIter,
+    strum::IntoStaticStr,
+)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
+pub enum AuditAction {
+    ApiKeyCreated,
+    ApiKeyRevoked,
+    ApiKeyRotated,
+    BranchCreated,
+    HostBranchPairSet,
+    PasswordChanged,
+    PasswordReset,
+    PasswordResetByToken,
+    RoleAssigned,
+    RoleCreated,
+    RoleDeleted,
+    RoleParentsSet,
+    RoleUpdated,
+    SessionsRevoked,
+    TotpEnabled,
+    TotpReset,
+    UserCreated,
+    UserUpdated,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
+pub struct AuditEntry {
+    pub id: AuditEntryId,
+    pub timestamp: Timestamp,
+    /// The user that performed the action
+    pub actor: Username,
+    pub action: AuditAction,
+    /// What the action was performed on (for example a username or branch id)
+    pub target: String,
+    /// JSON representation of what changed, for updates this includes the
+    /// values before the change
+    pub details: String,
+    /// The host the actor was using when the action was performed
+    pub host_id: HostId,
+}
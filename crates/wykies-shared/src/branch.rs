@@ -7,6 +7,7 @@ string_wrapper!(BranchName, 30, AlwaysCase::Any);
 char_array_wrapper!(BranchShortName, 2, AlwaysCase::Upper);
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
 pub struct Branch {
     pub id: BranchId,
     pub name: BranchName,
@@ -14,6 +15,7 @@ pub struct Branch {
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
 pub struct BranchDraft {
     pub name: BranchName,
     pub short_name: BranchShortName,
@@ -15,19 +15,47 @@ pub mod server {
     /// API Server after the API Server closes
     pub const SERVER_SHUTDOWN_TIMEOUT: Seconds = Seconds::new(20);
     pub const DB_ACQUIRE_TIMEOUT: Seconds = Seconds::new(2);
+    /// How often expired sessions are removed when using the DB session store
+    pub const SESSION_PURGE_INTERVAL: Seconds = Seconds::new(600);
+    /// How long a user's session generation read from the DB is reused before
+    /// it is read again. Invalidations made by other servers can take this long
+    /// to take effect
+    pub const SESSION_GENERATION_CACHE_MAX_AGE: Seconds = Seconds::new(30);
+    /// The last activity stored in a session is only updated once it is at
+    /// least this old so that the session is not saved on every request. The
+    /// server uses half the idle timeout instead if that is shorter
+    pub const SESSION_ACTIVITY_UPDATE_INTERVAL: Seconds = Seconds::new(60);
+    /// When an API key was last used is only saved once the stored time is at
+    /// least this old so that the key is not written on every request
+    pub const API_KEY_LAST_USED_UPDATE_INTERVAL: Seconds = Seconds::new(60);
+    /// Records are dropped instead of slowing down requests if the writer falls
+    /// this far behind
+    pub const ANALYTICS_BUFFER_SIZE: usize = 1000;
+    pub const ANALYTICS_MAX_TIME_BEFORE_SAVE: Seconds = Seconds::new(10);
+    pub const ANALYTICS_MAX_RECORDS_BEFORE_SAVE: u16 = 200;
+    /// The TLS certificate is reported as degraded when it expires in less than
+    /// this
+    pub const HEALTH_TLS_CERT_EXPIRY_WARNING: Seconds = Seconds::new(14 * 24 * 60 * 60);
+}
+
+pub mod audit {
+    pub const AUDIT_PAGE_SIZE_DEFAULT: u16 = 100;
+    pub const AUDIT_PAGE_SIZE_MAX: u16 = 500;
 }
 
 pub mod client {
     use super::*;
+    use std::time::Duration;
     pub const CLIENT_IDLE_TIMEOUT: Seconds = Seconds::new(300);
     /// Using the fact that updates only happen either once a second or when
     /// there is user activity we will use this as a measure to determine when
     /// there is user activity
     pub const CLIENT_TICKS_PER_SECOND_FOR_ACTIVE: usize = 5;
-    // TODO 5: Add a timeout for while awaiting a response. This happened a few
-    // times during remote testing. Didn't quite find a way to do timeout in WASM
-
-    // pub const AWAITING_RESPONSE_TIMEOUT: Seconds = Seconds::new(30);
+    /// Default for how long to wait for a response before giving up on a request
+    pub const AWAITING_RESPONSE_TIMEOUT: Seconds = Seconds::new(30);
+    pub const CLIENT_RETRY_MAX_ATTEMPTS: u8 = 3;
+    pub const CLIENT_RETRY_INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+    pub const CLIENT_RETRY_MAX_BACKOFF: Duration = Duration::from_secs(5);
 
     pub mod user_edit {
         use super::Seconds;
@@ -57,23 +85,45 @@ pub mod error {
 pub mod path {
     mod path_spec;
     pub use path_spec::PathSpec;
+    pub const PATH_API_ANALYTICS_SUMMARY: PathSpec = PathSpec::get("/api/analytics/summary");
+    pub const PATH_API_API_KEY_LIST: PathSpec = PathSpec::get("/api/api_key/list");
+    pub const PATH_API_API_KEY_NEW: PathSpec = PathSpec::post("/api/api_key/new");
+    pub const PATH_API_API_KEY_REVOKE: PathSpec = PathSpec::post("/api/api_key/revoke");
+    pub const PATH_API_API_KEY_ROTATE: PathSpec = PathSpec::post("/api/api_key/rotate");
+    pub const PATH_API_AUDIT_LIST: PathSpec = PathSpec::get("/api/audit/list");
     pub const PATH_API_BRANCH_NEW: PathSpec = PathSpec::post("/api/branch/new");
     pub const PATH_API_CHANGE_PASSWORD: PathSpec = PathSpec::post("/api/change_password");
     pub const PATH_API_HOSTBRANCH_LIST: PathSpec = PathSpec::get("/api/host_branch/list");
     pub const PATH_API_HOSTBRANCH_SET: PathSpec = PathSpec::post("/api/host_branch/set");
     pub const PATH_API_HOSTBRANCH: PathSpec = PathSpec::get("/api/host_branch/");
     pub const PATH_API_LOGOUT: PathSpec = PathSpec::post("/api/logout");
+    pub const PATH_API_OPENAPI: PathSpec = PathSpec::get("/api/openapi.json"); // Public, see route registration
+    pub const PATH_API_PASSWORD_POLICY: PathSpec = PathSpec::get("/api/password_policy");
+    pub const PATH_API_ROLE_DELETE: PathSpec = PathSpec::post("/api/role/delete");
+    pub const PATH_API_ROLE_LIST: PathSpec = PathSpec::get("/api/role/list");
     pub const PATH_API_ROLE_NEW: PathSpec = PathSpec::post("/api/role/new");
+    pub const PATH_API_ROLE_PARENTS: PathSpec = PathSpec::post("/api/role/parents");
+    pub const PATH_API_ROLE_UPDATE: PathSpec = PathSpec::patch("/api/role/update");
     pub const PATH_API_ROLE: PathSpec = PathSpec::get("/api/role/");
+    pub const PATH_API_TOTP_CONFIRM: PathSpec = PathSpec::post("/api/totp/confirm");
+    pub const PATH_API_TOTP_ENROL: PathSpec = PathSpec::post("/api/totp/enrol");
     pub const PATH_API_USER_NEW: PathSpec = PathSpec::post("/api/user/new");
     pub const PATH_API_USER_PASSWORD_RESET: PathSpec = PathSpec::post("/api/user/password_reset");
+    pub const PATH_API_USER_PERMISSIONS: PathSpec = PathSpec::get("/api/user/permissions");
     pub const PATH_API_USER_ROLE_SET: PathSpec = PathSpec::post("/api/user/role");
+    pub const PATH_API_USER_SESSIONS_REVOKE: PathSpec = PathSpec::post("/api/user/sessions/revoke");
+    pub const PATH_API_USER_SESSIONS: PathSpec = PathSpec::get("/api/user/sessions");
+    pub const PATH_API_USER_TOTP_RESET: PathSpec = PathSpec::post("/api/user/totp_reset");
     pub const PATH_API_USER_UPDATE: PathSpec = PathSpec::patch("/api/user/update");
     pub const PATH_API_USER: PathSpec = PathSpec::get("/api/user/");
     pub const PATH_API_USERS_LIST_AND_ROLES: PathSpec = PathSpec::get("/api/user/list");
     pub const PATH_BRANCH_LIST: PathSpec = PathSpec::get("/branch/list");
     pub const PATH_HEALTH_CHECK: PathSpec = PathSpec::get("/health_check");
     pub const PATH_LOGIN: PathSpec = PathSpec::post("/login");
+    pub const PATH_PASSWORD_RESET_REDEEM: PathSpec = PathSpec::post("/password_reset/redeem");
+    pub const PATH_PASSWORD_RESET_REQUEST: PathSpec = PathSpec::post("/password_reset/request");
+    pub const PATH_STATUS_JSON: PathSpec = PathSpec::get("/status/json");
+    pub const PATH_VERSION: PathSpec = PathSpec::get("/version");
     pub const PATH_WS_PREFIX: &str = "/api/ws_token"; // All websocket requests must start with this prefix
     pub const PATH_WS_TOKEN_CHAT: PathSpec = PathSpec::post("/api/ws_token/chat");
 }
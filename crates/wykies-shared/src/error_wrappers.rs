@@ -1,9 +1,12 @@
+use crate::api_error::{ApiError, ApiErrorCode};
+use actix_web::http::StatusCode;
+
 // Return an opaque 500 while preserving the error root's cause for logging.
 pub fn e500<T>(e: T) -> actix_web::Error
 where
     T: std::fmt::Debug + std::fmt::Display + 'static,
 {
-    actix_web::error::ErrorInternalServerError(e)
+    wrap(e, ApiErrorCode::Internal, StatusCode::INTERNAL_SERVER_ERROR)
 }
 
 // Return a 400 with the user-representation of the validation error as body.
@@ -12,5 +15,13 @@ pub fn e400<T>(e: T) -> actix_web::Error
 where
     T: std::fmt::Debug + std::fmt::Display + 'static,
 {
-    actix_web::error::ErrorBadRequest(e)
+    wrap(e, ApiErrorCode::BadRequest, StatusCode::BAD_REQUEST)
+}
+
+fn wrap<T>(e: T, code: ApiErrorCode, status: StatusCode) -> actix_web::Error
+where
+    T: std::fmt::Debug + std::fmt::Display + 'static,
+{
+    let response = ApiError::new(code, e.to_string()).to_response(status);
+    actix_web::error::InternalError::from_response(e, response).into()
 }
@@ -1,5 +1,3 @@
-use crate::uac::Permission;
-
 #[derive(Debug, thiserror::Error, PartialEq, Eq)]
 pub enum ConversionError {
     #[error("Empty not allowed for {type_name}")]
@@ -27,7 +25,9 @@ pub enum PermissionConversionError {
         value: String,
     },
     #[error("Invalid character found for {perm:?}. Only 0 or 1 expected but found {c}")]
-    InvalidCharacter { c: char, perm: Permission },
+    InvalidCharacter { c: char, perm: String },
+    #[error("Expected a list of permission names. Error: {message}. Value: {value:?}")]
+    InvalidList { message: String, value: String },
 }
 
 #[derive(Debug, thiserror::Error, PartialEq, Eq)]
@@ -41,23 +41,36 @@ pub enum HostIdConversionError {
 #[cfg(not(target_arch = "wasm32"))]
 pub mod conversions {
     use super::*;
-    use actix_web::http::StatusCode;
+    use crate::api_error::ApiError;
+    use actix_web::{HttpResponse, http::StatusCode};
 
     impl actix_web::error::ResponseError for NotLoggedInError {
         fn status_code(&self) -> StatusCode {
             StatusCode::UNAUTHORIZED
         }
+
+        fn error_response(&self) -> HttpResponse {
+            ApiError::from(self).to_response(self.status_code())
+        }
     }
 
     impl actix_web::error::ResponseError for ConversionError {
         fn status_code(&self) -> StatusCode {
             StatusCode::INTERNAL_SERVER_ERROR
         }
+
+        fn error_response(&self) -> HttpResponse {
+            ApiError::from(self).to_response(self.status_code())
+        }
     }
 
     impl actix_web::error::ResponseError for PermissionConversionError {
         fn status_code(&self) -> StatusCode {
             StatusCode::INTERNAL_SERVER_ERROR
         }
+
+        fn error_response(&self) -> HttpResponse {
+            ApiError::from(self).to_response(self.status_code())
+        }
     }
 }
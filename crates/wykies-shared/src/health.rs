@@ -0,0 +1,88 @@
+//! Types for reporting the health of the server and its plugins
+
+/// Ordered from best to worst so the overall status is the maximum of the
+/// individual checks
+#[derive(
+    Debug,
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Default,
+    strum::Display,
+)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
+pub enum HealthStatus {
+    #[default]
+    Ok,
+    /// Still working but needs attention
+    Degraded,
+    Error,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
+pub struct HealthCheckResult {
+    pub name: String,
+    pub status: HealthStatus,
+    pub message: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
+pub struct HealthReport {
+    /// The worst status of all the checks
+    pub status: HealthStatus,
+    pub checks: Vec<HealthCheckResult>,
+}
+
+impl HealthCheckResult {
+    pub fn new(name: impl Into<String>, status: HealthStatus, message: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            message: message.into(),
+        }
+    }
+
+    /// Uses [`HealthStatus::Ok`] with the message on success and
+    /// [`HealthStatus::Error`] with the error on failure
+    pub fn from_result(name: impl Into<String>, result: anyhow::Result<String>) -> Self {
+        match result {
+            Ok(message) => Self::new(name, HealthStatus::Ok, message),
+            Err(e) => Self::new(name, HealthStatus::Error, format!("{e:#}")),
+        }
+    }
+}
+
+impl HealthReport {
+    pub fn new(checks: Vec<HealthCheckResult>) -> Self {
+        let status = checks.iter().map(|x| x.status).max().unwrap_or_default();
+        Self { status, checks }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overall_status_is_worst_check() {
+        let report = HealthReport::new(vec![
+            HealthCheckResult::new("a", HealthStatus::Ok, ""),
+            HealthCheckResult::new("b", HealthStatus::Degraded, ""),
+            HealthCheckResult::new("c", HealthStatus::Ok, ""),
+        ]);
+        assert_eq!(report.status, HealthStatus::Degraded);
+    }
+
+    #[test]
+    fn no_checks_is_ok() {
+        let report = HealthReport::new(vec![]);
+        assert_eq!(report.status, HealthStatus::Ok);
+    }
+}
@@ -5,6 +5,7 @@ use crate::{AlwaysCase, branch::BranchId, errors::ConversionError, string_wrappe
 string_wrapper!(HostId, 50, AlwaysCase::Any);
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
 pub struct HostBranchPair {
     pub host_id: HostId,
     pub branch_id: BranchId,
@@ -5,12 +5,16 @@
 #[cfg(not(target_arch = "wasm32"))]
 use tokio_tungstenite as _; // Needed for our CA to work for WSS
 
+pub mod analytics;
+pub mod api_error;
+pub mod audit;
 pub mod branch;
 pub mod const_config;
 
 #[cfg(feature = "server_only")]
 mod error_wrappers;
 pub mod errors;
+pub mod health;
 pub mod host_branch;
 mod macros_debugging;
 mod macros_enums;
@@ -19,6 +23,7 @@ pub mod random;
 pub mod req_args;
 pub mod token;
 pub mod uac;
+pub mod version;
 pub mod websockets;
 
 #[cfg(feature = "server_only")]
@@ -30,6 +30,14 @@ macro_rules! enum_repr_u8_impls {
             fn status_code(&self) -> actix_web::http::StatusCode {
                 actix_web::http::StatusCode::INTERNAL_SERVER_ERROR
             }
+
+            fn error_response(&self) -> actix_web::HttpResponse {
+                $crate::api_error::ApiError::new(
+                    $crate::api_error::ApiErrorCode::Internal,
+                    self.to_string(),
+                )
+                .to_response(self.status_code())
+            }
         }
 
         impl From<&$name> for egui::WidgetText {
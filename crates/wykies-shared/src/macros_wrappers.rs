@@ -4,6 +4,7 @@ macro_rules! string_wrapper {
         #[derive(
             Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, Hash, PartialOrd, Ord,
         )]
+        #[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
         pub struct $name(String);
 
         impl TryFrom<String> for $name {
@@ -118,6 +119,7 @@ macro_rules! char_array_wrapper {
         #[derive(
             Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Copy
         )]
+        #[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
         pub struct $name([char; Self::LENGTH]);
 
         impl $name {
@@ -247,6 +249,7 @@ macro_rules! id_wrapper {
             Copy,
             Hash,
         )]
+        #[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
         pub struct $name(u64);
 
         impl std::fmt::Display for $name {
@@ -319,6 +322,14 @@ macro_rules! id_wrapper {
             fn status_code(&self) -> actix_web::http::StatusCode {
                 actix_web::http::StatusCode::INTERNAL_SERVER_ERROR
             }
+
+            fn error_response(&self) -> actix_web::HttpResponse {
+                $crate::api_error::ApiError::new(
+                    $crate::api_error::ApiErrorCode::Internal,
+                    self.to_string(),
+                )
+                .to_response(self.status_code())
+            }
         }
     };
 }
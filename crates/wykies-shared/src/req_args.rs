@@ -10,12 +10,14 @@ use secrecy::{ExposeSecret, SecretString};
 use std::fmt::Debug;
 
 pub mod api;
+pub mod password_reset;
 
 /// This struct exists because serde_json cannot round trip all types
 ///
 /// Specifically the problem the we ran into was not being able to do
 /// `Option<Option<T>>` <https://github.com/serde-rs/json/issues/1096>
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
 pub struct RonWrapper {
     data_as_ron_str: String,
 }
@@ -29,15 +31,22 @@ pub struct TopWrapper<T: Debug> {
 }
 
 #[derive(serde::Deserialize, Clone)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
 pub struct LoginReqArgs {
     // TODO 5: Is there a downside to making this a Username type instead of String
     pub username: String,
+    #[cfg_attr(feature = "server_only", schemars(with = "String"))]
     pub password: SecretString,
     /// Provides a way to choose what branch to set if no branch is already
     /// saved in the database. Will be ignored if the branch is already set
     /// in the database. Will also not be used even if it's needed and the
     /// user doesn't have the required permissions.
     pub branch_to_set: Option<BranchId>,
+    /// Required for users with two-factor authentication enabled. Either the
+    /// current code from their authenticator app or one of their recovery
+    /// codes
+    #[cfg_attr(feature = "server_only", schemars(with = "Option<String>"))]
+    pub totp_code: Option<SecretString>,
 }
 
 impl LoginReqArgs {
@@ -46,6 +55,7 @@ impl LoginReqArgs {
             username: username.into(),
             password,
             branch_to_set: None,
+            totp_code: None,
         }
     }
 
@@ -58,6 +68,7 @@ impl LoginReqArgs {
             username,
             password,
             branch_to_set: Some(branch_to_set),
+            totp_code: None,
         }
     }
 
@@ -75,6 +86,11 @@ impl LoginReqArgs {
         self.branch_to_set = branch_to_set;
         self
     }
+
+    pub fn totp_code(mut self, totp_code: Option<SecretString>) -> Self {
+        self.totp_code = totp_code;
+        self
+    }
 }
 
 impl Debug for LoginReqArgs {
@@ -83,6 +99,7 @@ impl Debug for LoginReqArgs {
             .field("username", &self.username)
             .field("has_password", &!self.password.expose_secret().is_empty())
             .field("branch_to_set", &self.branch_to_set)
+            .field("has_totp_code", &self.totp_code.is_some())
             .finish()
     }
 }
@@ -1,12 +1,20 @@
 use secrecy::SecretString;
 
+pub mod analytics;
+pub mod api_key;
+pub mod audit;
 pub mod host_branch;
 pub mod role;
+pub mod totp;
 pub mod user;
 
 #[derive(serde::Deserialize)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
 pub struct ChangePasswordReqArgs {
+    #[cfg_attr(feature = "server_only", schemars(with = "String"))]
     pub current_password: SecretString,
+    #[cfg_attr(feature = "server_only", schemars(with = "String"))]
     pub new_password: SecretString,
+    #[cfg_attr(feature = "server_only", schemars(with = "String"))]
     pub new_password_check: SecretString,
 }
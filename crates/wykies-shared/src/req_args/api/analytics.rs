@@ -0,0 +1,10 @@
+use wykies_time::Timestamp;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
+pub struct SummaryReqArgs {
+    /// Only include requests at or after this time
+    pub since: Timestamp,
+    /// Only include requests before this time, defaults to now
+    pub until: Option<Timestamp>,
+}
@@ -0,0 +1,29 @@
+use crate::{
+    branch::BranchId,
+    uac::{ApiKeyId, Permissions, Username},
+};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[cfg_attr(
+    feature = "server_only",
+    derive(schemars::JsonSchema),
+    schemars(rename = "ApiKeyNewReqArgs")
+)]
+pub struct NewReqArgs {
+    /// Name of the service account the key is for
+    pub name: Username,
+    pub permissions: Permissions,
+    pub branch_id: BranchId,
+    /// Number of days before the key expires. Never expires if not set
+    pub valid_for_days: Option<u16>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[cfg_attr(
+    feature = "server_only",
+    derive(schemars::JsonSchema),
+    schemars(rename = "ApiKeyLookupReqArgs")
+)]
+pub struct LookupReqArgs {
+    pub id: ApiKeyId,
+}
@@ -0,0 +1,24 @@
+use crate::{
+    audit::{AuditAction, AuditEntryId},
+    uac::Username,
+};
+use wykies_time::Timestamp;
+
+/// All filters are optional and combined using AND. Entries are returned
+/// newest first, to get the next page set `before_id` to the id of the last
+/// entry received
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
+pub struct ListReqArgs {
+    pub actor: Option<Username>,
+    pub action: Option<AuditAction>,
+    pub target: Option<String>,
+    /// Only include entries at or after this time
+    pub since: Option<Timestamp>,
+    /// Only include entries before this time
+    pub until: Option<Timestamp>,
+    pub before_id: Option<AuditEntryId>,
+    /// Defaults to [`crate::const_config::audit::AUDIT_PAGE_SIZE_DEFAULT`] and
+    /// is capped at [`crate::const_config::audit::AUDIT_PAGE_SIZE_MAX`]
+    pub page_size: Option<u16>,
+}
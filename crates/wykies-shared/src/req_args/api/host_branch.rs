@@ -1,6 +1,11 @@
 use crate::host_branch::HostId;
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[cfg_attr(
+    feature = "server_only",
+    derive(schemars::JsonSchema),
+    schemars(rename = "HostBranchLookupReqArgs")
+)]
 pub struct LookupReqArgs {
     pub host_id: HostId,
 }
@@ -1,6 +1,20 @@
 use crate::uac::RoleId;
+use std::collections::BTreeSet;
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[cfg_attr(
+    feature = "server_only",
+    derive(schemars::JsonSchema),
+    schemars(rename = "RoleLookupReqArgs")
+)]
 pub struct LookupReqArgs {
     pub role_id: RoleId,
 }
+
+/// Replaces the parents of the role. Rejected if it would create a cycle
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
+pub struct SetParentsReqArgs {
+    pub role_id: RoleId,
+    pub parent_roles: BTreeSet<RoleId>,
+}
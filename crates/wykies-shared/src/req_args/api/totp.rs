@@ -0,0 +1,9 @@
+use secrecy::SecretString;
+
+#[derive(serde::Deserialize)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
+pub struct ConfirmReqArgs {
+    /// Current code from the authenticator app the secret was added to
+    #[cfg_attr(feature = "server_only", schemars(with = "String"))]
+    pub code: SecretString,
+}
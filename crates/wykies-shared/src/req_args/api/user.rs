@@ -1,27 +1,50 @@
-use crate::uac::{DisplayName, RoleId, Username};
+use crate::uac::{DisplayName, EmailAddress, RoleId, SessionId, Username};
 use secrecy::SecretString;
+use std::collections::BTreeSet;
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[cfg_attr(
+    feature = "server_only",
+    derive(schemars::JsonSchema),
+    schemars(rename = "UserLookupReqArgs")
+)]
 pub struct LookupReqArgs {
     pub username: Username,
 }
 
 #[derive(Debug, serde::Deserialize, Clone)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
 pub struct NewUserReqArgs {
     pub username: Username,
     pub display_name: DisplayName,
+    #[cfg_attr(feature = "server_only", schemars(with = "String"))]
     pub password: SecretString,
-    pub assigned_role: Option<RoleId>,
+    #[serde(default)]
+    pub assigned_roles: BTreeSet<RoleId>,
+    #[serde(default)]
+    pub email: Option<EmailAddress>,
 }
 
 #[derive(Debug, serde::Deserialize, Clone)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
 pub struct PasswordResetReqArgs {
     pub username: Username,
+    #[cfg_attr(feature = "server_only", schemars(with = "String"))]
     pub new_password: SecretString,
 }
 
+/// Adds the role to the roles already assigned to the user
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
 pub struct AssignReqArgs {
     pub username: Username,
     pub role_id: RoleId,
 }
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
+pub struct RevokeSessionsReqArgs {
+    pub username: Username,
+    /// If `None` all sessions for the user are revoked
+    pub session_id: Option<SessionId>,
+}
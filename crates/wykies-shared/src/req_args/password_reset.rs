@@ -0,0 +1,30 @@
+//! Arguments for the `/password_reset` endpoints. These do not require a login
+//! because they are used by users that cannot login
+
+use crate::uac::Username;
+use secrecy::SecretString;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[cfg_attr(
+    feature = "server_only",
+    derive(schemars::JsonSchema),
+    schemars(rename = "PasswordResetRequestReqArgs")
+)]
+pub struct RequestReqArgs {
+    pub username: Username,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[cfg_attr(
+    feature = "server_only",
+    derive(schemars::JsonSchema),
+    schemars(rename = "PasswordResetRedeemReqArgs")
+)]
+pub struct RedeemReqArgs {
+    pub username: Username,
+    /// As received from the notification sent after the request
+    #[cfg_attr(feature = "server_only", schemars(with = "String"))]
+    pub token: SecretString,
+    #[cfg_attr(feature = "server_only", schemars(with = "String"))]
+    pub new_password: SecretString,
+}
@@ -1,6 +1,7 @@
 use crate::random_string_def_len;
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
 pub struct AuthToken(String);
 
 impl AuthToken {
@@ -1,18 +1,30 @@
 //! Shared items related to user account control
 
+mod api_key;
 mod errors;
 mod passwords;
 mod permissions;
 mod responses;
 mod role;
+mod session;
 mod user;
 
-pub use errors::{AuthError, ChangePasswordError, PermissionsError, ResetPasswordError};
-pub use passwords::{PasswordComplexity, PasswordComplexityError};
+pub use api_key::{ApiKeyId, ApiKeyIdConversionError, ApiKeyInfo, ApiKeySecret};
+pub use errors::{
+    ApiKeyError, AuthError, ChangePasswordError, PasswordResetTokenError, PermissionsError,
+    ResetPasswordError, RoleError, SessionExpiredError, TotpError,
+};
+pub use passwords::{PasswordComplexity, PasswordComplexityError, PasswordPolicy};
 pub use permissions::{
-    Permission, PermissionCheckOutcome, PermissionMap, Permissions, default_permissions,
-    get_required_permissions, init_permissions_to_defaults, try_set_permissions,
+    Permission, PermissionCategory, PermissionCheckOutcome, PermissionMap, Permissions,
+    default_permissions, get_required_permissions, init_permissions_to_defaults,
+    try_set_permissions,
+};
+pub use responses::{LoginResponse, TotpEnrolment, TotpRecoveryCodes};
+pub use role::{
+    Role, RoleDescription, RoleDiff, RoleDraft, RoleId, RoleIdAndName, RoleName, UserPermissions,
+};
+pub use session::{SessionId, SessionIdConversionError, SessionInfo};
+pub use user::{
+    DisplayName, EmailAddress, ListUsersRoles, UserInfo, UserMetadata, UserMetadataDiff, Username,
 };
-pub use responses::LoginResponse;
-pub use role::{Role, RoleDescription, RoleDraft, RoleId, RoleIdAndName, RoleName};
-pub use user::{DisplayName, ListUsersRoles, UserInfo, UserMetadata, UserMetadataDiff, Username};
@@ -0,0 +1,43 @@
+use super::{Permissions, Username};
+use crate::{branch::BranchId, id_wrapper};
+use wykies_time::Timestamp;
+
+id_wrapper!(ApiKeyId, ApiKeyIdConversionError);
+
+/// Information about an API key that is safe to show to an administrator
+/// (does not include the key)
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
+pub struct ApiKeyInfo {
+    pub id: ApiKeyId,
+    /// Name of the service account, used as the username for requests made
+    /// with the key
+    pub name: Username,
+    pub permissions: Permissions,
+    /// Branch that requests made with the key are treated as coming from
+    pub branch_id: BranchId,
+    pub created: Timestamp,
+    /// The key stops working after this time. Never expires if not set
+    pub expires: Option<Timestamp>,
+    pub last_used: Option<Timestamp>,
+    pub revoked: bool,
+}
+
+/// Returned when a key is created or rotated. This is the only time the key is
+/// available, only a hash of it is stored
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
+pub struct ApiKeySecret {
+    pub id: ApiKeyId,
+    /// Sent in the `Authorization` header using the `Bearer` scheme
+    pub key: String,
+}
+
+impl std::fmt::Debug for ApiKeySecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiKeySecret")
+            .field("id", &self.id)
+            .field("key", &"[REDACTED]")
+            .finish()
+    }
+}
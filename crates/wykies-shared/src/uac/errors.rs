@@ -1,6 +1,8 @@
+use wykies_time::Seconds;
+
 use crate::host_branch::HostId;
 
-use super::{PasswordComplexity, Permission};
+use super::{PasswordComplexity, Permission, RoleId};
 
 #[derive(thiserror::Error, Debug)]
 pub enum AuthError {
@@ -16,6 +18,10 @@ pub enum AuthError {
     BranchNotSetAndUnableToSet { client_identifier: HostId },
     #[error("Branch not set please resend specifying desired branch to set")]
     BranchNotSetResend { client_identifier: HostId },
+    #[error("Invalid two-factor authentication code")]
+    InvalidTotpCode,
+    #[error("Too many failed login attempts. Please try again in {retry_after} seconds")]
+    TooManyAttempts { retry_after: Seconds },
     #[error("Unexpected Error")]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -36,6 +42,8 @@ pub enum ChangePasswordError {
     Complexity(PasswordComplexity),
     #[error("You entered two different new passwords - the field values must match.")]
     PasswordsDoNotMatch,
+    #[error("New password must not be the same as any of your last {0} passwords")]
+    ReusedPassword(u16),
     #[error("Current password validation failed: {0}")]
     CurrentPasswordWrong(#[from] AuthError),
     #[error("Unexpected Error")]
@@ -50,6 +58,88 @@ pub enum ResetPasswordError {
     UnexpectedError(#[from] anyhow::Error),
 }
 
+#[derive(thiserror::Error, Debug)]
+pub enum PasswordResetTokenError {
+    #[error("Invalid or expired password reset token")]
+    InvalidToken,
+    #[error("Password complexity requirements not met: {0}")]
+    Complexity(PasswordComplexity),
+    #[error("New password must not be the same as any of your last {0} passwords")]
+    ReusedPassword(u16),
+    #[error("Too many failed attempts. Please try again in {retry_after} seconds")]
+    TooManyAttempts { retry_after: Seconds },
+    #[error("Unexpected Error")]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+/// Errors for requests that authenticate using an API key instead of a session
+#[derive(thiserror::Error, Debug)]
+pub enum ApiKeyError {
+    #[error("Invalid API key")]
+    Invalid,
+    #[error("API key has expired")]
+    Expired,
+    #[error("This endpoint requires a login session and cannot be used with an API key")]
+    SessionRequired,
+    #[error("Unexpected Error")]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum RoleError {
+    #[error("No role found with ID: {0}")]
+    NotFound(RoleId),
+    #[error("No parent role found with ID: {0}")]
+    ParentNotFound(RoleId),
+    #[error("Role {role_id} cannot inherit from role {parent_id} as it would create a cycle")]
+    Cycle { role_id: RoleId, parent_id: RoleId },
+    #[error("Role {role_id} is still assigned to these users: {}", usernames.join(", "))]
+    AssignedToUsers {
+        role_id: RoleId,
+        usernames: Vec<String>,
+    },
+    #[error("Role {role_id} is inherited by other roles: {child_roles:?}")]
+    InheritedBy {
+        role_id: RoleId,
+        child_roles: Vec<RoleId>,
+    },
+    #[error("Unexpected Error")]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum TotpError {
+    #[error("Two-factor authentication is already enabled. An administrator must reset it first")]
+    AlreadyEnabled,
+    #[error("No two-factor authentication enrolment in progress. Please start enrolment first")]
+    NoPendingEnrolment,
+    #[error("Invalid two-factor authentication code")]
+    InvalidCode,
+    #[error("Two-factor authentication must be set up before continuing")]
+    EnrolmentRequired,
+    #[error("You cannot reset your own two-factor authentication")]
+    NoResetOwn,
+    #[error("Unexpected Error")]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+/// Returned instead of [`crate::errors::NotLoggedInError`] when the user was
+/// logged in but the session is no longer valid
+#[derive(
+    thiserror::Error, Debug, PartialEq, Eq, Clone, Copy, serde::Serialize, serde::Deserialize,
+)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
+pub enum SessionExpiredError {
+    #[error("Session expired, maximum session lifetime reached. Please login again")]
+    MaxLifetimeReached,
+    #[error("Session expired, no activity for too long. Please login again")]
+    IdleTimeout,
+    #[error("Session has been invalidated. Please login again")]
+    Invalidated,
+    #[error("Session is not valid from this host. Please login again")]
+    HostMismatch,
+}
+
 #[derive(thiserror::Error, Debug, PartialEq, Eq)]
 pub enum PermissionsError {
     #[error("the following permissions are missing: {0:?}")]
@@ -63,7 +153,11 @@ pub enum PermissionsError {
 #[cfg(not(target_arch = "wasm32"))]
 pub mod conversions {
     use super::*;
-    use actix_web::http::StatusCode;
+    use crate::api_error::ApiError;
+    use actix_web::{
+        HttpResponse,
+        http::{StatusCode, header},
+    };
 
     impl actix_web::error::ResponseError for PermissionsError {
         fn status_code(&self) -> StatusCode {
@@ -72,31 +166,137 @@ pub mod conversions {
                 PermissionsError::PathNotFound(_) => StatusCode::SERVICE_UNAVAILABLE,
             }
         }
+
+        fn error_response(&self) -> HttpResponse {
+            ApiError::from(self).to_response(self.status_code())
+        }
     }
 
     impl actix_web::error::ResponseError for AuthError {
         fn status_code(&self) -> StatusCode {
             match self {
+                AuthError::TooManyAttempts { .. } => StatusCode::TOO_MANY_REQUESTS,
                 AuthError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
                 _ => StatusCode::UNAUTHORIZED,
             }
         }
+
+        fn error_response(&self) -> HttpResponse {
+            let mut response = ApiError::from(self).to_response(self.status_code());
+            if let AuthError::TooManyAttempts { retry_after } = self {
+                response.headers_mut().insert(
+                    header::RETRY_AFTER,
+                    header::HeaderValue::from(usize::from(*retry_after)),
+                );
+            }
+            response
+        }
+    }
+
+    impl actix_web::error::ResponseError for TotpError {
+        fn status_code(&self) -> StatusCode {
+            match self {
+                TotpError::AlreadyEnabled
+                | TotpError::NoPendingEnrolment
+                | TotpError::InvalidCode
+                | TotpError::NoResetOwn => StatusCode::BAD_REQUEST,
+                TotpError::EnrolmentRequired => StatusCode::FORBIDDEN,
+                TotpError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            }
+        }
+
+        fn error_response(&self) -> HttpResponse {
+            ApiError::from(self).to_response(self.status_code())
+        }
+    }
+
+    impl actix_web::error::ResponseError for RoleError {
+        fn status_code(&self) -> StatusCode {
+            match self {
+                RoleError::NotFound(_)
+                | RoleError::ParentNotFound(_)
+                | RoleError::Cycle { .. }
+                | RoleError::AssignedToUsers { .. }
+                | RoleError::InheritedBy { .. } => StatusCode::BAD_REQUEST,
+                RoleError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            }
+        }
+
+        fn error_response(&self) -> HttpResponse {
+            ApiError::from(self).to_response(self.status_code())
+        }
+    }
+
+    impl actix_web::error::ResponseError for SessionExpiredError {
+        fn status_code(&self) -> StatusCode {
+            StatusCode::UNAUTHORIZED
+        }
+
+        fn error_response(&self) -> HttpResponse {
+            ApiError::from(self).to_response(self.status_code())
+        }
     }
 
     impl actix_web::error::ResponseError for ChangePasswordError {
         fn status_code(&self) -> StatusCode {
             match self {
                 ChangePasswordError::PasswordsDoNotMatch
+                | ChangePasswordError::ReusedPassword(_)
                 | ChangePasswordError::CurrentPasswordWrong(_) => StatusCode::BAD_REQUEST,
                 ChangePasswordError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
                 ChangePasswordError::Complexity(_) => StatusCode::BAD_REQUEST,
             }
         }
+
+        fn error_response(&self) -> HttpResponse {
+            ApiError::from(self).to_response(self.status_code())
+        }
+    }
+
+    impl actix_web::error::ResponseError for PasswordResetTokenError {
+        fn status_code(&self) -> StatusCode {
+            match self {
+                PasswordResetTokenError::InvalidToken
+                | PasswordResetTokenError::Complexity(_)
+                | PasswordResetTokenError::ReusedPassword(_) => StatusCode::BAD_REQUEST,
+                PasswordResetTokenError::TooManyAttempts { .. } => StatusCode::TOO_MANY_REQUESTS,
+                PasswordResetTokenError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            }
+        }
+
+        fn error_response(&self) -> HttpResponse {
+            let mut response = ApiError::from(self).to_response(self.status_code());
+            if let PasswordResetTokenError::TooManyAttempts { retry_after } = self {
+                response.headers_mut().insert(
+                    header::RETRY_AFTER,
+                    header::HeaderValue::from(usize::from(*retry_after)),
+                );
+            }
+            response
+        }
+    }
+
+    impl actix_web::error::ResponseError for ApiKeyError {
+        fn status_code(&self) -> StatusCode {
+            match self {
+                ApiKeyError::Invalid | ApiKeyError::Expired => StatusCode::UNAUTHORIZED,
+                ApiKeyError::SessionRequired => StatusCode::FORBIDDEN,
+                ApiKeyError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            }
+        }
+
+        fn error_response(&self) -> HttpResponse {
+            ApiError::from(self).to_response(self.status_code())
+        }
     }
 
     impl actix_web::error::ResponseError for ResetPasswordError {
         fn status_code(&self) -> StatusCode {
             StatusCode::INTERNAL_SERVER_ERROR
         }
+
+        fn error_response(&self) -> HttpResponse {
+            ApiError::from(self).to_response(self.status_code())
+        }
     }
 }
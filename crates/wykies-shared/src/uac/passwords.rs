@@ -10,102 +10,129 @@ pub enum PasswordComplexityError {
     NeedsUpper,
     #[error("needs at least one lowercase character")]
     NeedsLower,
+    #[error("needs at least one digit")]
+    NeedsDigit,
+    #[error("needs at least one symbol")]
+    NeedsSymbol,
     #[error("needs at least one non-alphabetic character")]
     NeedsNonAlpha,
     #[error("your password may not contain your username")]
     HasUsername,
     #[error("consecutive duplicate characters are not allowed")]
     HasConsecutiveDuplicateChars,
-    #[error("minimum length of {} not met", PasswordComplexity::MIN_LENGTH)]
-    IsTooShort,
+    #[error("minimum length of {0} not met")]
+    IsTooShort(usize),
+    #[error("password is too common")]
+    IsBlocklisted,
 }
 
-#[derive(Debug, Default, Copy, Clone)]
-pub struct PasswordComplexity {
-    pub needs_upper: bool,
-    pub needs_lower: bool,
-    pub needs_non_alpha: bool,
-    pub has_username: bool,
-    pub has_consecutive_duplicate_chars: bool,
-    pub is_too_short: bool,
+/// The rules passwords must meet. Loaded from the server's configuration and
+/// served to the client so it can validate using the same rules
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
+#[serde(default)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    /// Longest run of the same character that is allowed. Zero disables the
+    /// check
+    pub max_consecutive_same_char: usize,
+    pub require_upper: bool,
+    pub require_lower: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    pub require_non_alpha: bool,
+    /// Passwords that are never allowed (compared ignoring case)
+    pub blocklist: Vec<String>,
+    /// Number of previous passwords (including the current one) that cannot
+    /// be reused. Zero disables the check. Only enforced by the server
+    pub history_count: u16,
 }
-impl PasswordComplexity {
-    const MIN_LENGTH: usize = 8;
-    const MAX_CONSECUTIVE_SAME_CHAR: usize = 3;
 
-    pub fn new(username: &Username, password: &SecretString) -> Self {
-        let needs_upper = password.expose_secret().chars().all(|c| !c.is_uppercase());
-        let needs_lower = password.expose_secret().chars().all(|c| !c.is_lowercase());
-        let needs_non_alpha = !password.expose_secret().chars().any(|c| !c.is_alphabetic());
-        let has_username = {
-            let lower_username = username.to_string().to_lowercase();
-            password
-                .expose_secret()
-                .to_lowercase()
-                .contains(&lower_username)
-        };
-        let has_consecutive_duplicate_chars = password
-            .expose_secret()
-            .as_bytes()
-            .windows(Self::MAX_CONSECUTIVE_SAME_CHAR + 1)
-            .any(|window| window.iter().all(|&c| c == window[0]));
-        let is_too_short = password.expose_secret().len() < Self::MIN_LENGTH;
+impl Default for PasswordPolicy {
+    fn default() -> Self {
         Self {
-            needs_upper,
-            needs_lower,
-            needs_non_alpha,
-            has_username,
-            has_consecutive_duplicate_chars,
-            is_too_short,
+            min_length: 8,
+            max_consecutive_same_char: 3,
+            require_upper: true,
+            require_lower: true,
+            require_digit: false,
+            require_symbol: false,
+            require_non_alpha: true,
+            blocklist: [
+                "Password1",
+                "Password1!",
+                "Password123",
+                "P@ssw0rd",
+                "Passw0rd",
+                "Qwerty123",
+                "Welcome1",
+                "Letmein1",
+                "Admin123",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+            history_count: 0,
         }
     }
+}
 
-    pub fn does_meet_requirements(&self) -> bool {
-        let Self {
-            needs_upper,
-            needs_lower,
-            needs_non_alpha,
-            has_username,
-            has_consecutive_duplicate_chars,
-            is_too_short,
-        } = self;
-        !(*needs_upper
-            || *needs_lower
-            || *needs_non_alpha
-            || *has_username
-            || *has_consecutive_duplicate_chars
-            || *is_too_short)
-    }
-
-    pub fn error_list(self) -> Vec<PasswordComplexityError> {
-        let mut result = vec![];
-        let Self {
-            needs_upper,
-            needs_lower,
-            needs_non_alpha,
-            has_username,
-            has_consecutive_duplicate_chars: has_consecutive_duplicates,
-            is_too_short,
-        } = self;
-        if needs_upper {
-            result.push(PasswordComplexityError::NeedsUpper);
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PasswordComplexity {
+    errors: Vec<PasswordComplexityError>,
+}
+impl PasswordComplexity {
+    pub fn new(policy: &PasswordPolicy, username: &Username, password: &SecretString) -> Self {
+        let password = password.expose_secret();
+        let mut errors = vec![];
+        if policy.require_upper && password.chars().all(|c| !c.is_uppercase()) {
+            errors.push(PasswordComplexityError::NeedsUpper);
+        }
+        if policy.require_lower && password.chars().all(|c| !c.is_lowercase()) {
+            errors.push(PasswordComplexityError::NeedsLower);
+        }
+        if policy.require_digit && !password.chars().any(|c| c.is_numeric()) {
+            errors.push(PasswordComplexityError::NeedsDigit);
         }
-        if needs_lower {
-            result.push(PasswordComplexityError::NeedsLower);
+        if policy.require_symbol && password.chars().all(|c| c.is_alphanumeric()) {
+            errors.push(PasswordComplexityError::NeedsSymbol);
         }
-        if needs_non_alpha {
-            result.push(PasswordComplexityError::NeedsNonAlpha);
+        if policy.require_non_alpha && password.chars().all(|c| c.is_alphabetic()) {
+            errors.push(PasswordComplexityError::NeedsNonAlpha);
         }
-        if has_username {
-            result.push(PasswordComplexityError::HasUsername);
+        if password
+            .to_lowercase()
+            .contains(&username.to_string().to_lowercase())
+        {
+            errors.push(PasswordComplexityError::HasUsername);
         }
-        if has_consecutive_duplicates {
-            result.push(PasswordComplexityError::HasConsecutiveDuplicateChars);
+        if policy.max_consecutive_same_char > 0
+            && password
+                .as_bytes()
+                .windows(policy.max_consecutive_same_char + 1)
+                .any(|window| window.iter().all(|&c| c == window[0]))
+        {
+            errors.push(PasswordComplexityError::HasConsecutiveDuplicateChars);
         }
-        if is_too_short {
-            result.push(PasswordComplexityError::IsTooShort);
+        if password.chars().count() < policy.min_length {
+            errors.push(PasswordComplexityError::IsTooShort(policy.min_length));
         }
-        result
+        if policy
+            .blocklist
+            .iter()
+            .any(|blocked| blocked.eq_ignore_ascii_case(password))
+        {
+            errors.push(PasswordComplexityError::IsBlocklisted);
+        }
+        Self { errors }
+    }
+
+    pub fn does_meet_requirements(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn error_list(&self) -> &[PasswordComplexityError] {
+        &self.errors
     }
 
     pub fn generate_random_password() -> SecretString {
@@ -127,7 +154,7 @@ impl Display for PasswordComplexity {
             write!(f, "All password complexity criteria met")
         } else {
             let err_list = err_list
-                .into_iter()
+                .iter()
                 .map(|x| x.to_string())
                 .collect::<Vec<_>>()
                 .join(", ");
@@ -145,7 +172,7 @@ mod tests {
     fn random_passwords_pass() {
         let password = PasswordComplexity::generate_random_password();
         let username = Username::try_from("bob".to_string()).unwrap();
-        let actual = PasswordComplexity::new(&username, &password);
+        let actual = PasswordComplexity::new(&PasswordPolicy::default(), &username, &password);
         assert!(actual.does_meet_requirements());
     }
 
@@ -158,12 +185,50 @@ mod tests {
         "aa7BBBBBBB",
         PasswordComplexityError::HasConsecutiveDuplicateChars
     )]
-    #[case::is_too_short("jUst2sh", PasswordComplexityError::IsTooShort)]
+    #[case::is_too_short("jUst2sh", PasswordComplexityError::IsTooShort(8))]
+    #[case::is_blocklisted("pASSWORD123", PasswordComplexityError::IsBlocklisted)]
     fn constraints_work(#[case] password: String, #[case] expected: PasswordComplexityError) {
         let username = Username::try_from("bob".to_string()).unwrap();
-        let password_complexity = PasswordComplexity::new(&username, &password.into());
+        let password_complexity =
+            PasswordComplexity::new(&PasswordPolicy::default(), &username, &password.into());
         let actual = password_complexity.error_list();
         assert_eq!(actual, vec![expected]);
         assert!(!password_complexity.does_meet_requirements());
     }
+
+    #[rstest]
+    #[case::needs_digit("No-Digits-Here", 12, PasswordComplexityError::NeedsDigit)]
+    #[case::needs_symbol("NoSymbols4You", 12, PasswordComplexityError::NeedsSymbol)]
+    #[case::is_too_short("Short-4-Policy", 20, PasswordComplexityError::IsTooShort(20))]
+    fn configured_policy_is_used(
+        #[case] password: String,
+        #[case] min_length: usize,
+        #[case] expected: PasswordComplexityError,
+    ) {
+        let policy = PasswordPolicy {
+            min_length,
+            require_digit: true,
+            require_symbol: true,
+            ..Default::default()
+        };
+        let username = Username::try_from("bob".to_string()).unwrap();
+        let actual = PasswordComplexity::new(&policy, &username, &password.into());
+        assert_eq!(actual.error_list(), [expected]);
+    }
+
+    #[test]
+    fn disabled_rules_are_not_checked() {
+        let policy = PasswordPolicy {
+            min_length: 4,
+            max_consecutive_same_char: 0,
+            require_upper: false,
+            require_non_alpha: false,
+            blocklist: vec![],
+            ..Default::default()
+        };
+        let username = Username::try_from("bob".to_string()).unwrap();
+        let actual =
+            PasswordComplexity::new(&policy, &username, &"lowercaaaase".to_string().into());
+        assert!(actual.does_meet_requirements());
+    }
 }
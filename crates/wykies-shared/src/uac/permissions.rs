@@ -7,13 +7,15 @@ use std::{
     fmt::{Debug, Display},
     sync::OnceLock,
 };
-use strum::{EnumCount, IntoEnumIterator};
-use tracing::instrument;
+use strum::IntoEnumIterator;
+use tracing::{instrument, warn};
 
 use crate::{const_config::path::*, errors::PermissionConversionError};
 
 use super::PermissionsError;
 
+/// Stored in the database using the name of the variant so do not rename
+/// variants. Variants can be added, reordered or removed
 #[derive(
     Debug,
     serde::Serialize,
@@ -26,7 +28,10 @@ use super::PermissionsError;
     Clone,
     strum::EnumCount,
     strum::EnumIter,
+    strum::EnumString,
+    strum::IntoStaticStr,
 )]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
 pub enum Permission {
     // Record Transactions
     RecordManualTransaction,
@@ -59,6 +64,8 @@ pub enum Permission {
 
     // Management
     ManBranches,
+    /// Moderate chat rooms (edit and delete other users' messages)
+    ManChat,
     ManClasses,
     ManHostBranchAssignment,
     ManLines,
@@ -76,10 +83,79 @@ pub enum Permission {
     ImportTamsSync,
     ImportSales,
     ImportReceiving,
+
+    // Monitoring
+    ViewAnalytics,
 }
 
 pub type PermissionMap = HashMap<&'static str, Vec<Permission>>;
 
+/// Groups [`Permission`]s for display (matches the sections of the enum)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumIter)]
+pub enum PermissionCategory {
+    #[strum(to_string = "Record Transactions")]
+    RecordTransactions,
+    Transfers,
+    #[strum(to_string = "Miscellaneous")]
+    Misc,
+    Overrides,
+    Management,
+    Import,
+    Monitoring,
+}
+
+impl Permission {
+    pub fn category(&self) -> PermissionCategory {
+        use PermissionCategory as cat;
+        match self {
+            Permission::RecordManualTransaction | Permission::RecordDiscrepancy => {
+                cat::RecordTransactions
+            }
+            Permission::TransferRequest
+            | Permission::TransferTo
+            | Permission::TransferFrom
+            | Permission::TransferView
+            | Permission::TransferAny
+            | Permission::TransferRemove => cat::Transfers,
+            Permission::CustomsEntries
+            | Permission::ViewShipmentManifest
+            | Permission::ChangePass
+            | Permission::ImportData
+            | Permission::ViewLog
+            | Permission::ViewStockInfo
+            | Permission::RunReports
+            | Permission::Settings
+            | Permission::NonCurrentDate => cat::Misc,
+            Permission::GrantOverrideLocal | Permission::GrantOverrideRemote => cat::Overrides,
+            Permission::ManBranches
+            | Permission::ManChat
+            | Permission::ManClasses
+            | Permission::ManHostBranchAssignment
+            | Permission::ManLines
+            | Permission::ManMenu
+            | Permission::ManMinMax
+            | Permission::ManResetLocks
+            | Permission::ManRoles
+            | Permission::ManSpareParts
+            | Permission::ManSuppliers
+            | Permission::ManSupplierInvoices
+            | Permission::ManUAC => cat::Management,
+            Permission::ImportStockLevelCountBatch
+            | Permission::ImportTamsSync
+            | Permission::ImportSales
+            | Permission::ImportReceiving => cat::Import,
+            Permission::ViewAnalytics => cat::Monitoring,
+        }
+    }
+}
+
+impl PermissionCategory {
+    /// The permissions in this category in the order they are declared
+    pub fn permissions(&self) -> impl Iterator<Item = Permission> {
+        Permission::iter().filter(move |x| x.category() == *self)
+    }
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub enum PermissionCheckOutcome {
     HasRequiredPermissions,
@@ -99,15 +175,32 @@ pub fn default_permissions() -> PermissionMap {
         PATH_API_HOSTBRANCH_SET.path,
         vec![perm::ManHostBranchAssignment],
     );
+    result.insert(PATH_API_ANALYTICS_SUMMARY.path, vec![perm::ViewAnalytics]);
+    result.insert(PATH_API_API_KEY_LIST.path, vec![perm::ManUAC]);
+    result.insert(PATH_API_API_KEY_NEW.path, vec![perm::ManUAC]);
+    result.insert(PATH_API_API_KEY_REVOKE.path, vec![perm::ManUAC]);
+    result.insert(PATH_API_API_KEY_ROTATE.path, vec![perm::ManUAC]);
+    result.insert(PATH_API_AUDIT_LIST.path, vec![perm::ViewLog]);
     result.insert(PATH_API_BRANCH_NEW.path, vec![perm::ManBranches]);
     result.insert(PATH_API_CHANGE_PASSWORD.path, vec![]);
     result.insert(PATH_API_HOSTBRANCH.path, vec![]);
     result.insert(PATH_API_LOGOUT.path, vec![]);
+    result.insert(PATH_API_PASSWORD_POLICY.path, vec![]);
     result.insert(PATH_API_USER_ROLE_SET.path, vec![perm::ManUAC]);
+    result.insert(PATH_API_ROLE_DELETE.path, vec![perm::ManRoles]);
+    result.insert(PATH_API_ROLE_LIST.path, vec![perm::ManRoles]);
     result.insert(PATH_API_ROLE_NEW.path, vec![perm::ManRoles]);
+    result.insert(PATH_API_ROLE_PARENTS.path, vec![perm::ManRoles]);
+    result.insert(PATH_API_ROLE_UPDATE.path, vec![perm::ManRoles]);
     result.insert(PATH_API_ROLE.path, vec![perm::ManRoles]);
+    result.insert(PATH_API_TOTP_CONFIRM.path, vec![]);
+    result.insert(PATH_API_TOTP_ENROL.path, vec![]);
     result.insert(PATH_API_USER_NEW.path, vec![perm::ManUAC]);
     result.insert(PATH_API_USER_PASSWORD_RESET.path, vec![perm::ManUAC]);
+    result.insert(PATH_API_USER_SESSIONS_REVOKE.path, vec![perm::ManUAC]);
+    result.insert(PATH_API_USER_SESSIONS.path, vec![perm::ManUAC]);
+    result.insert(PATH_API_USER_TOTP_RESET.path, vec![perm::ManUAC]);
+    result.insert(PATH_API_USER_PERMISSIONS.path, vec![perm::ManUAC]);
     result.insert(PATH_API_USER_UPDATE.path, vec![perm::ManUAC]);
     result.insert(PATH_API_USER.path, vec![perm::ManUAC]);
     result.insert(PATH_API_USERS_LIST_AND_ROLES.path, vec![perm::ManUAC]);
@@ -141,6 +234,11 @@ pub fn get_required_permissions(path: &str) -> Option<&'static [Permission]> {
 }
 
 impl Permissions {
+    /// Every permission that exists
+    pub fn all() -> Self {
+        Self(Permission::iter().collect())
+    }
+
     pub fn includes(&self, perms: &[Permission]) -> PermissionCheckOutcome {
         if perms.iter().all(|x| self.0.contains(x)) {
             PermissionCheckOutcome::HasRequiredPermissions
@@ -172,6 +270,7 @@ impl Permissions {
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
 pub struct Permissions(pub BTreeSet<Permission>);
 
 impl From<Vec<Permission>> for Permissions {
@@ -184,36 +283,109 @@ impl From<Vec<Permission>> for Permissions {
     }
 }
 
-impl TryFrom<String> for Permissions {
-    type Error = PermissionConversionError;
-
-    fn try_from(value: String) -> Result<Self, Self::Error> {
-        if value.is_empty() {
-            return Ok(Self::default());
-        }
+/// Names of the permissions in the order used by the legacy format where each
+/// permission was stored as a '0' or '1' at its position in the enum. Frozen so
+/// that the enum can change without breaking values not yet converted
+const LEGACY_ORDER: [&str; 36] = [
+    "RecordManualTransaction",
+    "RecordDiscrepancy",
+    "TransferRequest",
+    "TransferTo",
+    "TransferFrom",
+    "TransferView",
+    "TransferAny",
+    "TransferRemove",
+    "CustomsEntries",
+    "ViewShipmentManifest",
+    "ChangePass",
+    "ImportData",
+    "ViewLog",
+    "ViewStockInfo",
+    "RunReports",
+    "Settings",
+    "NonCurrentDate",
+    "GrantOverrideLocal",
+    "GrantOverrideRemote",
+    "ManBranches",
+    "ManClasses",
+    "ManHostBranchAssignment",
+    "ManLines",
+    "ManMenu",
+    "ManMinMax",
+    "ManResetLocks",
+    "ManRoles",
+    "ManSpareParts",
+    "ManSuppliers",
+    "ManSupplierInvoices",
+    "ManUAC",
+    "ImportStockLevelCountBatch",
+    "ImportTamsSync",
+    "ImportSales",
+    "ImportReceiving",
+    "ViewAnalytics",
+];
 
-        if Permission::COUNT != value.len() {
+impl Permissions {
+    /// Reads the legacy positional format (See [`LEGACY_ORDER`])
+    fn try_from_legacy(value: String) -> Result<Self, PermissionConversionError> {
+        if LEGACY_ORDER.len() != value.len() {
             return Err(PermissionConversionError::WrongLength {
-                allowed_length: Permission::COUNT,
+                allowed_length: LEGACY_ORDER.len(),
                 actual_length: value.len(),
                 value,
             });
         }
-        let mut result = Permissions::default();
-        for (c, p) in value.chars().zip(Permission::iter()) {
+        let mut names = Vec::new();
+        for (c, name) in value.chars().zip(LEGACY_ORDER) {
             match c {
                 '0' => (), // Do nothing this one is not included
-                '1' => {
-                    let did_not_exist = result.0.insert(p);
-                    debug_assert!(
-                        did_not_exist,
-                        "Since we should always get a new Permission we should never already have the value inserted"
-                    );
+                '1' => names.push(name),
+                _ => {
+                    return Err(PermissionConversionError::InvalidCharacter {
+                        c,
+                        perm: name.to_string(),
+                    });
+                }
+            }
+        }
+        Ok(Self::from_names(names))
+    }
+
+    /// Names that no longer match a [`Permission`] are skipped so that removed
+    /// permissions do not prevent loading stored values
+    fn from_names<'a>(names: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut result = Self::default();
+        for name in names {
+            match name.parse::<Permission>() {
+                Ok(permission) => {
+                    result.0.insert(permission);
                 }
-                _ => return Err(PermissionConversionError::InvalidCharacter { c, perm: p }),
+                Err(_) => warn!(?name, "skipping unknown permission"),
             }
         }
-        Ok(result)
+        result
+    }
+}
+
+/// Reads the stored format which is a JSON list of the names of the
+/// permissions. Also accepts the legacy positional format
+impl TryFrom<String> for Permissions {
+    type Error = PermissionConversionError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if value.is_empty() {
+            return Ok(Self::default());
+        }
+        if !value.starts_with('[') {
+            return Self::try_from_legacy(value);
+        }
+        match serde_json::from_str::<Vec<String>>(&value) {
+            Ok(names) => Ok(Self::from_names(names.iter().map(String::as_str))),
+            Err(e) => Err(PermissionConversionError::InvalidList {
+                message: e.to_string(),
+                value,
+            }),
+        }
     }
 }
 
@@ -225,28 +397,10 @@ impl TryFrom<Option<String>> for Permissions {
     }
 }
 
+/// Converts into the stored format (See [`TryFrom<String>`] for [`Permissions`])
 impl From<&Permissions> for String {
     fn from(value: &Permissions) -> Self {
-        let mut iter = value.0.iter();
-        let mut next = iter.next();
-        let mut result = String::with_capacity(Permission::COUNT);
-        for permission in Permission::iter() {
-            let ch = match next.as_ref() {
-                Some(&x) if x == &permission => {
-                    next = iter.next();
-                    debug_assert!(
-                        next.is_none() || next.as_ref().is_some_and(|&x| x > &permission),
-                        "Implementation assumes sorted values from iterator but assumption violated. Next: {next:?} Current: {permission}"
-                    );
-                    '1'
-                }
-                _ => '0',
-            };
-            result.push(ch);
-        }
-        debug_assert_eq!(result.len(), Permission::COUNT);
-        debug_assert!(next.is_none());
-        result
+        serde_json::to_string(&value.0).expect("serializing a list of unit variants cannot fail")
     }
 }
 
@@ -279,6 +433,7 @@ impl Display for Permission {
             Permission::GrantOverrideLocal => "Grant Override Local",
             Permission::GrantOverrideRemote => "Grant Override Remote",
             Permission::ManBranches => "Manage Branches",
+            Permission::ManChat => "Moderate Chat",
             Permission::ManClasses => "Manage Classes",
             Permission::ManHostBranchAssignment => "Manage Host Branch Assignment",
             Permission::ManLines => "Manage Lines",
@@ -294,6 +449,7 @@ impl Display for Permission {
             Permission::ImportTamsSync => "Import - Tams Sync",
             Permission::ImportSales => "Import - Sales",
             Permission::ImportReceiving => "Import - Receiving",
+            Permission::ViewAnalytics => "View Analytics",
         };
         write!(f, "{display_text}")
     }
@@ -352,71 +508,101 @@ mod tests {
     use rstest::rstest;
 
     #[rstest]
-    #[case::empty("00000000000000000000000000000000000", vec![])]
-    #[case::administrator("11111101111111111111111111111111111", vec![p::RecordManualTransaction, p::RecordDiscrepancy, p::TransferRequest, p::TransferTo, p::TransferFrom, p::TransferView, p::TransferRemove, p::CustomsEntries, p::ViewShipmentManifest, p::ChangePass, p::ImportData, p::ViewLog, p::ViewStockInfo, p::RunReports, p::Settings, p::NonCurrentDate, p::GrantOverrideLocal, p::GrantOverrideRemote, p::ManBranches, p::ManClasses, p::ManHostBranchAssignment, p::ManLines, p::ManMenu, p::ManMinMax, p::ManResetLocks, p::ManRoles, p::ManSpareParts, p::ManSuppliers, p::ManSupplierInvoices, p::ManUAC, p::ImportStockLevelCountBatch, p::ImportTamsSync, p::ImportSales, p::ImportReceiving])]
-    #[case::view_only("00000100001001000000000000000000000", vec![p::TransferView, p::ChangePass, p::ViewStockInfo])]
-    #[case::request_transfer("00100100001001000000000000000000000", vec![p::TransferRequest, p::TransferView, p::ChangePass, p::ViewStockInfo])]
-    #[case::prepare_transfer("00010100001001000000000000000000000", vec![p::TransferTo, p::TransferView, p::ChangePass, p::ViewStockInfo])]
-    #[case::receive_transfer("00001100001001000000000000000000000", vec![p::TransferFrom, p::TransferView, p::ChangePass, p::ViewStockInfo])]
-    #[case::transfer_admin("00111101001001100000000000000000000", vec![p::TransferRequest, p::TransferTo, p::TransferFrom, p::TransferView, p::ChangePass, p::TransferRemove, p::ViewStockInfo, p::RunReports])]
-    #[case::transfers_all("01111100001101100000000000000001000", vec![p::RecordDiscrepancy, p::TransferRequest, p::TransferTo, p::TransferFrom, p::TransferView, p::ChangePass, p::ImportData, p::ViewStockInfo, p::RunReports, p::ImportStockLevelCountBatch])]
-    fn string_to_permissions(#[case] s: String, #[case] permission_list: Vec<Permission>) {
+    #[case::empty("000000000000000000000000000000000000", vec![])]
+    #[case::administrator("111111011111111111111111111111111111", vec![p::RecordManualTransaction, p::RecordDiscrepancy, p::TransferRequest, p::TransferTo, p::TransferFrom, p::TransferView, p::TransferRemove, p::CustomsEntries, p::ViewShipmentManifest, p::ChangePass, p::ImportData, p::ViewLog, p::ViewStockInfo, p::RunReports, p::Settings, p::NonCurrentDate, p::GrantOverrideLocal, p::GrantOverrideRemote, p::ManBranches, p::ManClasses, p::ManHostBranchAssignment, p::ManLines, p::ManMenu, p::ManMinMax, p::ManResetLocks, p::ManRoles, p::ManSpareParts, p::ManSuppliers, p::ManSupplierInvoices, p::ManUAC, p::ImportStockLevelCountBatch, p::ImportTamsSync, p::ImportSales, p::ImportReceiving, p::ViewAnalytics])]
+    #[case::view_only("000001000010010000000000000000000000", vec![p::TransferView, p::ChangePass, p::ViewStockInfo])]
+    #[case::request_transfer("001001000010010000000000000000000000", vec![p::TransferRequest, p::TransferView, p::ChangePass, p::ViewStockInfo])]
+    #[case::prepare_transfer("000101000010010000000000000000000000", vec![p::TransferTo, p::TransferView, p::ChangePass, p::ViewStockInfo])]
+    #[case::receive_transfer("000011000010010000000000000000000000", vec![p::TransferFrom, p::TransferView, p::ChangePass, p::ViewStockInfo])]
+    #[case::transfer_admin("001111010010011000000000000000000000", vec![p::TransferRequest, p::TransferTo, p::TransferFrom, p::TransferView, p::ChangePass, p::TransferRemove, p::ViewStockInfo, p::RunReports])]
+    #[case::transfers_all("011111000011011000000000000000010000", vec![p::RecordDiscrepancy, p::TransferRequest, p::TransferTo, p::TransferFrom, p::TransferView, p::ChangePass, p::ImportData, p::ViewStockInfo, p::RunReports, p::ImportStockLevelCountBatch])]
+    fn legacy_string_to_permissions(#[case] s: String, #[case] permission_list: Vec<Permission>) {
         // Arrange
         let expected: Permissions = permission_list.into();
 
         // Act
-        let actual: Permissions = s.clone().try_into().unwrap();
+        let actual: Permissions = s.try_into().unwrap();
 
         // Assert
         assert_eq!(actual, expected);
 
-        // Arrange - Test reverse
-        let expected = s;
+        // Arrange - Test reverse goes through the current format
         let input = actual;
 
         // Act
-        let actual: String = input.into();
+        let stored: String = input.clone().into();
+        let actual: Permissions = stored.try_into().unwrap();
+
+        // Assert
+        assert_eq!(actual, input);
+    }
+
+    #[rstest]
+    #[case::empty_list("[]", vec![])]
+    #[case::single(r#"["ViewLog"]"#, vec![p::ViewLog])]
+    #[case::multiple(r#"["ManRoles","ViewLog"]"#, vec![p::ManRoles, p::ViewLog])]
+    #[case::order_ignored(r#"["ViewLog","ManRoles"]"#, vec![p::ManRoles, p::ViewLog])]
+    #[case::unknown_skipped(r#"["Removed","ViewLog"]"#, vec![p::ViewLog])]
+    fn names_to_permissions(#[case] s: &str, #[case] permission_list: Vec<Permission>) {
+        // Arrange
+        let expected: Permissions = permission_list.into();
+
+        // Act
+        let actual: Permissions = s.to_string().try_into().unwrap();
 
         // Assert
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn permissions_stored_by_name() {
+        // Arrange
+        let input: Permissions = vec![p::ViewLog, p::ManRoles].into();
+
+        // Act
+        let actual: String = input.into();
+
+        // Assert
+        assert_eq!(actual, r#"["ViewLog","ManRoles"]"#);
+    }
+
     #[rstest]
-    #[case("10000000000000000000000000000000000", p::RecordManualTransaction)]
-    #[case("01000000000000000000000000000000000", p::RecordDiscrepancy)]
-    #[case("00100000000000000000000000000000000", p::TransferRequest)]
-    #[case("00010000000000000000000000000000000", p::TransferTo)]
-    #[case("00001000000000000000000000000000000", p::TransferFrom)]
-    #[case("00000100000000000000000000000000000", p::TransferView)]
-    #[case("00000010000000000000000000000000000", p::TransferAny)]
-    #[case("00000001000000000000000000000000000", p::TransferRemove)]
-    #[case("00000000100000000000000000000000000", p::CustomsEntries)]
-    #[case("00000000010000000000000000000000000", p::ViewShipmentManifest)]
-    #[case("00000000001000000000000000000000000", p::ChangePass)]
-    #[case("00000000000100000000000000000000000", p::ImportData)]
-    #[case("00000000000010000000000000000000000", p::ViewLog)]
-    #[case("00000000000001000000000000000000000", p::ViewStockInfo)]
-    #[case("00000000000000100000000000000000000", p::RunReports)]
-    #[case("00000000000000010000000000000000000", p::Settings)]
-    #[case("00000000000000001000000000000000000", p::NonCurrentDate)]
-    #[case("00000000000000000100000000000000000", p::GrantOverrideLocal)]
-    #[case("00000000000000000010000000000000000", p::GrantOverrideRemote)]
-    #[case("00000000000000000001000000000000000", p::ManBranches)]
-    #[case("00000000000000000000100000000000000", p::ManClasses)]
-    #[case("00000000000000000000010000000000000", p::ManHostBranchAssignment)]
-    #[case("00000000000000000000001000000000000", p::ManLines)]
-    #[case("00000000000000000000000100000000000", p::ManMenu)]
-    #[case("00000000000000000000000010000000000", p::ManMinMax)]
-    #[case("00000000000000000000000001000000000", p::ManResetLocks)]
-    #[case("00000000000000000000000000100000000", p::ManRoles)]
-    #[case("00000000000000000000000000010000000", p::ManSpareParts)]
-    #[case("00000000000000000000000000001000000", p::ManSuppliers)]
-    #[case("00000000000000000000000000000100000", p::ManSupplierInvoices)]
-    #[case("00000000000000000000000000000010000", p::ManUAC)]
-    #[case("00000000000000000000000000000001000", p::ImportStockLevelCountBatch)]
-    #[case("00000000000000000000000000000000100", p::ImportTamsSync)]
-    #[case("00000000000000000000000000000000010", p::ImportSales)]
-    #[case("00000000000000000000000000000000001", p::ImportReceiving)]
+    #[case("100000000000000000000000000000000000", p::RecordManualTransaction)]
+    #[case("010000000000000000000000000000000000", p::RecordDiscrepancy)]
+    #[case("001000000000000000000000000000000000", p::TransferRequest)]
+    #[case("000100000000000000000000000000000000", p::TransferTo)]
+    #[case("000010000000000000000000000000000000", p::TransferFrom)]
+    #[case("000001000000000000000000000000000000", p::TransferView)]
+    #[case("000000100000000000000000000000000000", p::TransferAny)]
+    #[case("000000010000000000000000000000000000", p::TransferRemove)]
+    #[case("000000001000000000000000000000000000", p::CustomsEntries)]
+    #[case("000000000100000000000000000000000000", p::ViewShipmentManifest)]
+    #[case("000000000010000000000000000000000000", p::ChangePass)]
+    #[case("000000000001000000000000000000000000", p::ImportData)]
+    #[case("000000000000100000000000000000000000", p::ViewLog)]
+    #[case("000000000000010000000000000000000000", p::ViewStockInfo)]
+    #[case("000000000000001000000000000000000000", p::RunReports)]
+    #[case("000000000000000100000000000000000000", p::Settings)]
+    #[case("000000000000000010000000000000000000", p::NonCurrentDate)]
+    #[case("000000000000000001000000000000000000", p::GrantOverrideLocal)]
+    #[case("000000000000000000100000000000000000", p::GrantOverrideRemote)]
+    #[case("000000000000000000010000000000000000", p::ManBranches)]
+    #[case("000000000000000000001000000000000000", p::ManClasses)]
+    #[case("000000000000000000000100000000000000", p::ManHostBranchAssignment)]
+    #[case("000000000000000000000010000000000000", p::ManLines)]
+    #[case("000000000000000000000001000000000000", p::ManMenu)]
+    #[case("000000000000000000000000100000000000", p::ManMinMax)]
+    #[case("000000000000000000000000010000000000", p::ManResetLocks)]
+    #[case("000000000000000000000000001000000000", p::ManRoles)]
+    #[case("000000000000000000000000000100000000", p::ManSpareParts)]
+    #[case("000000000000000000000000000010000000", p::ManSuppliers)]
+    #[case("000000000000000000000000000001000000", p::ManSupplierInvoices)]
+    #[case("000000000000000000000000000000100000", p::ManUAC)]
+    #[case("000000000000000000000000000000010000", p::ImportStockLevelCountBatch)]
+    #[case("000000000000000000000000000000001000", p::ImportTamsSync)]
+    #[case("000000000000000000000000000000000100", p::ImportSales)]
+    #[case("000000000000000000000000000000000010", p::ImportReceiving)]
+    #[case("000000000000000000000000000000000001", p::ViewAnalytics)]
     fn string_to_permission(#[case] s: String, #[case] permission: Permission) {
         println!("Inputs are String: {s} and permission: {permission:?} ({permission})"); // This print is included to make it easier to identify which test
         let mut expected: Permissions = Permissions::default();
@@ -432,7 +618,9 @@ mod tests {
 
     #[rstest]
     #[case::too_short("111")]
-    #[case::invalid_char("a0000100001001000000000000000000000")]
+    #[case::invalid_char("a00001000010010000000000000000000000")]
+    #[case::invalid_list(r#"["ViewLog""#)]
+    #[case::not_names("[1, 2]")]
     fn invalid_inputs(#[case] s: String) {
         let actual: Result<Permissions, PermissionConversionError> = s.try_into();
         match actual {
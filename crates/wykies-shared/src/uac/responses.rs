@@ -1,7 +1,37 @@
 use super::UserInfo;
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
 pub enum LoginResponse {
-    Success(UserInfo),
+    Success {
+        user_info: UserInfo,
+        /// Set when the password expires soon so the user can be warned
+        password_expires_in_days: Option<u16>,
+    },
     SuccessForcePassChange(UserInfo),
+    /// Logged in but only able to enrol in two-factor authentication until it
+    /// has been confirmed. Required for users that can manage user account
+    /// control
+    SuccessTotpEnrolmentRequired(UserInfo),
+    /// Password was correct but the user has two-factor authentication enabled.
+    /// No session was created, resend the login including the code
+    TotpRequired,
+}
+
+/// Returned when starting enrolment in two-factor authentication
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
+pub struct TotpEnrolment {
+    /// `otpauth://` URI to be shown as a QR code for authenticator apps
+    pub provisioning_uri: String,
+    /// Base32 encoded secret for authenticator apps that need it typed in
+    pub secret: String,
+}
+
+/// Single use codes that can be used instead of a TOTP code. Only returned
+/// once, when enrolment is confirmed
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
+pub struct TotpRecoveryCodes {
+    pub codes: Vec<String>,
 }
@@ -2,8 +2,9 @@ use super::Permissions;
 #[cfg(feature = "server_only")]
 use crate::db_types::Db;
 use crate::{AlwaysCase, errors::ConversionError, id_wrapper, string_wrapper};
+use anyhow::bail;
 use serde::{Deserialize, Serialize};
-use std::sync::LazyLock;
+use std::{collections::BTreeSet, sync::LazyLock};
 
 id_wrapper!(RoleId, RoleIdConversionError);
 string_wrapper!(RoleName, 16, AlwaysCase::Any);
@@ -19,29 +20,177 @@ impl RoleName {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
 pub struct Role {
     pub id: RoleId,
     pub name: RoleName,
     pub description: RoleDescription,
+    /// Permissions granted directly by this role (excludes inherited ones)
     pub permissions: Permissions,
+    /// Roles whose permissions are also granted by this role
+    #[serde(default)]
+    pub parent_roles: BTreeSet<RoleId>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
 pub struct RoleIdAndName {
     pub id: RoleId,
     pub name: RoleName,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
 pub struct RoleDraft {
     pub name: RoleName,
     pub description: RoleDescription,
     pub permissions: Permissions,
+    #[serde(default)]
+    pub parent_roles: BTreeSet<RoleId>,
+}
+
+/// Stores the changes to a role for updating in the DB.
+///
+/// `Some` are the ones changed
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
+pub struct RoleDiff {
+    /// Stores the role to be updated
+    pub id: RoleId,
+    pub name: Option<RoleName>,
+    pub description: Option<RoleDescription>,
+    pub permissions: Option<Permissions>,
+    pub parent_roles: Option<BTreeSet<RoleId>>,
+}
+
+/// The permissions of a user split by where they come from
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
+pub struct UserPermissions {
+    /// Granted directly by the roles assigned to the user
+    pub direct: Permissions,
+    /// Union of `direct` and the permissions inherited from parent roles. These
+    /// are the permissions the user has after logging in
+    pub effective: Permissions,
+}
+
+impl Role {
+    pub fn same_id(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl RoleDiff {
+    /// Returns an error if the IDs do not match
+    ///
+    /// Returns None if there are no differences otherwise
+    /// sets the changed fields to `Some`
+    pub fn from_diff(from: &Role, to: &Role) -> anyhow::Result<Option<Self>> {
+        if !from.same_id(to) {
+            bail!("role ids do not match");
+        }
+        let name = if from.name == to.name {
+            None
+        } else {
+            Some(to.name.clone())
+        };
+        let description = if from.description == to.description {
+            None
+        } else {
+            Some(to.description.clone())
+        };
+        let permissions = if from.permissions == to.permissions {
+            None
+        } else {
+            Some(to.permissions.clone())
+        };
+        let parent_roles = if from.parent_roles == to.parent_roles {
+            None
+        } else {
+            Some(to.parent_roles.clone())
+        };
+        Ok(
+            if name.is_none()
+                && description.is_none()
+                && permissions.is_none()
+                && parent_roles.is_none()
+            {
+                None
+            } else {
+                Some(Self {
+                    id: from.id,
+                    name,
+                    description,
+                    permissions,
+                    parent_roles,
+                })
+            },
+        )
+    }
+
+    /// Returns `true` if any of the fields stored on the role itself changed.
+    /// The parents are stored separately
+    pub fn has_role_row_changes(&self) -> bool {
+        self.name.is_some() || self.description.is_some() || self.permissions.is_some()
+    }
+
+    pub fn is_valid(&self) -> anyhow::Result<()> {
+        if self.has_role_row_changes() || self.parent_roles.is_some() {
+            Ok(())
+        } else {
+            bail!("No change is being requested")
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::uac::Permission;
+
+    fn role() -> Role {
+        Role {
+            id: 1.into(),
+            name: "Role".to_string().try_into().unwrap(),
+            description: "Description".to_string().try_into().unwrap(),
+            permissions: vec![Permission::ViewLog].into(),
+            parent_roles: BTreeSet::new(),
+        }
+    }
+
+    #[test]
+    fn diff_of_same_role_is_none() {
+        let actual = RoleDiff::from_diff(&role(), &role()).unwrap();
+        assert_eq!(actual, None);
+    }
+
+    #[test]
+    fn diff_only_includes_changes() {
+        // Arrange
+        let mut edited = role();
+        edited.permissions = vec![Permission::ViewLog, Permission::ManRoles].into();
+
+        // Act
+        let actual = RoleDiff::from_diff(&role(), &edited).unwrap().unwrap();
+
+        // Assert
+        let expected = RoleDiff {
+            id: 1.into(),
+            name: None,
+            description: None,
+            permissions: Some(edited.permissions),
+            parent_roles: None,
+        };
+        assert_eq!(actual, expected);
+        assert!(actual.is_valid().is_ok());
+    }
+
+    #[test]
+    fn diff_of_different_roles_is_error() {
+        let mut other = role();
+        other.id = 2.into();
+        assert!(RoleDiff::from_diff(&role(), &other).is_err());
+    }
 
     #[test]
     fn no_role_set_is_valid_value() {
@@ -0,0 +1,16 @@
+use super::Username;
+use crate::id_wrapper;
+use wykies_time::Timestamp;
+
+id_wrapper!(SessionId, SessionIdConversionError);
+
+/// Information about a server side session that is safe to show to an
+/// administrator (does not include the session key)
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
+pub struct SessionInfo {
+    pub id: SessionId,
+    pub username: Username,
+    pub created: Timestamp,
+    pub expires: Timestamp,
+}
@@ -4,12 +4,15 @@ use crate::db_types::Db;
 use crate::{AlwaysCase, branch::BranchId, errors::ConversionError, string_wrapper, uac::RoleId};
 use anyhow::bail;
 use chrono::NaiveDate;
+use std::collections::BTreeSet;
 
 string_wrapper!(Username, 16, AlwaysCase::Any);
 string_wrapper!(DisplayName, 30, AlwaysCase::Any);
+string_wrapper!(EmailAddress, 254, AlwaysCase::Any);
 
 /// Stores the user info that is returned on login
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
 pub struct UserInfo {
     pub username: Username,
     pub permissions: Permissions,
@@ -18,15 +21,22 @@ pub struct UserInfo {
 
 /// Stores metadata about a user for representation on management screens
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
 pub struct UserMetadata {
     pub username: Username,
     pub display_name: DisplayName,
     pub force_pass_change: bool,
-    pub assigned_role: Option<RoleId>,
+    /// The user's permissions are the union of the permissions of these roles
+    /// (including the ones they inherit)
+    pub assigned_roles: BTreeSet<RoleId>,
     pub enabled: bool,
     pub locked_out: bool,
     pub failed_attempts: u8,
     pub pass_change_date: NaiveDate,
+    /// Where password reset tokens are sent. Users without one can only have
+    /// their password reset by an administrator
+    #[serde(default)]
+    pub email: Option<EmailAddress>,
 }
 // TODO 6: We elected to not use user locking which means there is the
 //          possibility of race conditions if multiple admins are editing the
@@ -43,10 +53,12 @@ pub struct UserMetadataDiff {
     pub username: Username,
     pub display_name: Option<DisplayName>,
     pub force_pass_change: Option<bool>,
-    pub assigned_role: Option<Option<RoleId>>,
+    pub assigned_roles: Option<BTreeSet<RoleId>>,
     pub enabled: Option<bool>,
     pub locked_out: Option<bool>,
     pub failed_attempts: Option<u8>,
+    #[serde(default)]
+    pub email: Option<Option<EmailAddress>>,
 }
 
 /// A list of users and roles
@@ -54,6 +66,7 @@ pub struct UserMetadataDiff {
 /// Warning: Assumes that each assigned role ID has a corresponding role in the
 /// list)
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
 pub struct ListUsersRoles {
     pub users: Vec<UserMetadata>,
     pub roles: Vec<RoleIdAndName>,
@@ -96,10 +109,10 @@ impl UserMetadataDiff {
         } else {
             Some(to.force_pass_change)
         };
-        let assigned_role = if from.assigned_role == to.assigned_role {
+        let assigned_roles = if from.assigned_roles == to.assigned_roles {
             None
         } else {
-            Some(to.assigned_role)
+            Some(to.assigned_roles.clone())
         };
         let enabled = if from.enabled == to.enabled {
             None
@@ -116,13 +129,19 @@ impl UserMetadataDiff {
         } else {
             Some(to.failed_attempts)
         };
+        let email = if from.email == to.email {
+            None
+        } else {
+            Some(to.email.clone())
+        };
         Ok(
             if display_name.is_none()
                 && force_pass_change.is_none()
-                && assigned_role.is_none()
+                && assigned_roles.is_none()
                 && enabled.is_none()
                 && locked_out.is_none()
                 && failed_attempts.is_none()
+                && email.is_none()
             {
                 None
             } else {
@@ -130,23 +149,29 @@ impl UserMetadataDiff {
                     username,
                     display_name,
                     force_pass_change,
-                    assigned_role,
+                    assigned_roles,
                     enabled,
                     locked_out,
                     failed_attempts,
+                    email,
                 })
             },
         )
     }
 
-    pub fn is_valid(&self) -> anyhow::Result<()> {
-        if self.display_name.is_some()
+    /// Returns `true` if any of the fields stored on the user itself changed.
+    /// The assigned roles are stored separately
+    pub fn has_user_row_changes(&self) -> bool {
+        self.display_name.is_some()
             || self.force_pass_change.is_some()
-            || self.assigned_role.is_some()
             || self.enabled.is_some()
             || self.locked_out.is_some()
             || self.failed_attempts.is_some()
-        {
+            || self.email.is_some()
+    }
+
+    pub fn is_valid(&self) -> anyhow::Result<()> {
+        if self.has_user_row_changes() || self.assigned_roles.is_some() {
             Ok(())
         } else {
             bail!("No change is being requested")
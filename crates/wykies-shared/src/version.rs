@@ -0,0 +1,144 @@
+//! Used by the client to detect if it is able to work with the server
+
+/// Incremented whenever a change is made to the API that breaks compatibility
+/// with older clients (or servers). Clients refuse to connect to servers with a
+/// different protocol version
+pub const PROTOCOL_VERSION: u16 = 1;
+
+/// Returned by the server's version endpoint
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "server_only", derive(schemars::JsonSchema))]
+pub struct VersionInfo {
+    /// Semver of the server application
+    pub pkg_version: String,
+    pub protocol_version: u16,
+    /// IDs of the plugins the server is running
+    pub plugins: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompatibilityOutcome {
+    Compatible,
+    /// Expected to work but the user should be told that they may experience
+    /// problems
+    Warn(String),
+    /// Client should not attempt to use the server
+    Refuse(String),
+}
+
+impl VersionInfo {
+    pub fn new(pkg_version: impl Into<String>, plugins: Vec<String>) -> Self {
+        Self {
+            pkg_version: pkg_version.into(),
+            protocol_version: PROTOCOL_VERSION,
+            plugins,
+        }
+    }
+
+    /// Checks if a client at `client_pkg_version` that uses `required_plugins`
+    /// can work with this server. Different protocol versions or missing
+    /// plugins are refused and a semver incompatible package version is a
+    /// warning
+    pub fn compatibility(
+        &self,
+        client_pkg_version: &str,
+        required_plugins: &[&str],
+    ) -> CompatibilityOutcome {
+        if self.protocol_version != PROTOCOL_VERSION {
+            return CompatibilityOutcome::Refuse(format!(
+                "server protocol version is {} but client requires {PROTOCOL_VERSION}",
+                self.protocol_version
+            ));
+        }
+
+        let missing_plugins: Vec<&str> = required_plugins
+            .iter()
+            .filter(|x| !self.plugins.iter().any(|plugin| plugin == *x))
+            .copied()
+            .collect();
+        if !missing_plugins.is_empty() {
+            return CompatibilityOutcome::Refuse(format!(
+                "server is missing required plugins: {}",
+                missing_plugins.join(", ")
+            ));
+        }
+
+        let (server, client) = match (
+            semver::Version::parse(&self.pkg_version),
+            semver::Version::parse(client_pkg_version),
+        ) {
+            (Ok(server), Ok(client)) => (server, client),
+            _ => {
+                return CompatibilityOutcome::Warn(format!(
+                    "unable to compare versions. Server: {:?} Client: {client_pkg_version:?}",
+                    self.pkg_version
+                ));
+            }
+        };
+
+        // Follows the same rules as cargo for which versions are compatible
+        let is_semver_compatible = server.major == client.major
+            && (server.major > 0 || server.minor == client.minor)
+            && (server.major > 0 || server.minor > 0 || server.patch == client.patch);
+        if is_semver_compatible {
+            CompatibilityOutcome::Compatible
+        } else {
+            CompatibilityOutcome::Warn(format!(
+                "server version {server} may not be compatible with client version {client}"
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn server(pkg_version: &str) -> VersionInfo {
+        VersionInfo::new(pkg_version, vec!["chat".to_string()])
+    }
+
+    #[rstest]
+    #[case::same("0.1.5", "0.1.5")]
+    #[case::pre_release("0.1.5-dev", "0.1.5")]
+    #[case::patch_after_1_0("1.2.3", "1.2.0")]
+    #[case::minor_after_1_0("1.2.3", "1.5.0")]
+    fn compatible(#[case] server_version: &str, #[case] client_version: &str) {
+        let actual = server(server_version).compatibility(client_version, &["chat"]);
+        assert_eq!(actual, CompatibilityOutcome::Compatible);
+    }
+
+    #[rstest]
+    #[case::minor_before_1_0("0.1.5", "0.2.0")]
+    #[case::major("1.0.0", "2.0.0")]
+    #[case::patch_before_0_1("0.0.1", "0.0.2")]
+    #[case::invalid("not a version", "0.1.5")]
+    fn warn(#[case] server_version: &str, #[case] client_version: &str) {
+        let actual = server(server_version).compatibility(client_version, &["chat"]);
+        assert!(
+            matches!(actual, CompatibilityOutcome::Warn(_)),
+            "{actual:?}"
+        );
+    }
+
+    #[test]
+    fn refuse_different_protocol() {
+        let mut server = server("0.1.5");
+        server.protocol_version = PROTOCOL_VERSION + 1;
+        let actual = server.compatibility("0.1.5", &["chat"]);
+        assert!(
+            matches!(actual, CompatibilityOutcome::Refuse(_)),
+            "{actual:?}"
+        );
+    }
+
+    #[test]
+    fn refuse_missing_plugin() {
+        let actual = server("0.1.5").compatibility("0.1.5", &["chat", "other"]);
+        assert_eq!(
+            actual,
+            CompatibilityOutcome::Refuse("server is missing required plugins: other".to_string())
+        );
+    }
+}
@@ -9,6 +9,7 @@ use std::{fmt::Display, time::Duration};
 #[derive(
     Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, PartialOrd, Ord,
 )]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Seconds(u64);
 
 /// Intended to be similar to Instant but keeps on ticking if the computer is
@@ -16,12 +17,15 @@ pub struct Seconds(u64);
 #[derive(
     Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, PartialOrd, Ord,
 )]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Timestamp(u64);
 
 #[derive(Debug, thiserror::Error, PartialEq, Eq)]
 pub enum TimestampConversionError {
     #[error("Timestamps do not support negative numbers. Value: {0}")]
     NegativeI64(i64),
+    #[error("Expected a date in the format YYYY-MM-DD but got: {0:?}")]
+    InvalidDate(String),
 }
 #[derive(Debug, thiserror::Error, PartialEq, Eq)]
 pub enum SecondsConversionError {
@@ -104,6 +108,16 @@ impl Timestamp {
     pub fn elapsed(self) -> Option<Seconds> {
         Self::now().seconds_since(self)
     }
+
+    /// Parses a date in the format YYYY-MM-DD as the start of that day in UTC
+    pub fn from_utc_date_str(value: &str) -> Result<Self, TimestampConversionError> {
+        let date = chrono::NaiveDate::parse_from_str(value.trim(), "%F")
+            .map_err(|_| TimestampConversionError::InvalidDate(value.to_string()))?;
+        date.and_time(chrono::NaiveTime::MIN)
+            .and_utc()
+            .timestamp()
+            .try_into()
+    }
 }
 
 impl TryFrom<i64> for Timestamp {